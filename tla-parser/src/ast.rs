@@ -0,0 +1,318 @@
+use crate::lexer::Pos;
+
+
+/// Region of the source code covered by a syntax node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: Pos,
+    pub end: Pos,
+}
+
+impl Span {
+    pub fn new(start: Pos, end: Pos) -> Self {
+        Span { start, end }
+    }
+
+    /// Smallest span containing both `self` and `other`.
+    pub fn to(&self, other: &Span) -> Span {
+        Span { start: self.start, end: other.end }
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start.byte_offset <= offset && offset < self.end.byte_offset
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub name: Ident,
+    pub extends: Vec<Ident>,
+    pub units: Vec<Unit>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unit {
+    Constants(Vec<OpDecl>),
+    Variables(Vec<Ident>),
+    Recursive(Vec<OpDecl>),
+    Definition(Definition),
+    Instance(Instance),
+    Assume(Assumption),
+    Theorem(Assumption),
+    Module(Module),
+}
+
+/// How an operator is applied: `F(a, b)`, `-a`, `a + b` or `a'`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Ordinary,
+    Prefix,
+    Infix,
+    Postfix,
+}
+
+/// Operator declaration: `F`, `F(_, _)`, `-. _`, `_ + _`, `_ ^+`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpDecl {
+    pub name: Ident,
+    pub arity: usize,
+    pub shape: Shape,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub name: Ident,
+    pub shape: Shape,
+    pub params: Vec<OpDecl>,
+    pub local: bool,
+    pub body: DefBody,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefBody {
+    /// `F(x) == e`
+    Expr(Expr),
+    /// `f[x \in S] == e`
+    Function(Vec<Bound>, Expr),
+    /// `I == INSTANCE M WITH ...`
+    Instance(Instance),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instance {
+    pub module: Ident,
+    pub substitutions: Vec<(Ident, Expr)>,
+    pub local: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assumption {
+    pub name: Option<Ident>,
+    pub expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quantifier {
+    Forall,
+    Exists,
+    TemporalForall,
+    TemporalExists,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Junction {
+    And,
+    Or,
+}
+
+/// Bound variables: `x, y \in S`, `<<x, y>> \in S` or unbounded `x`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bound {
+    pub vars: Vec<Ident>,
+    pub tuple: bool,
+    pub set: Option<Box<Expr>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExceptKey {
+    Field(Ident),
+    Index(Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExceptUpdate {
+    pub path: Vec<ExceptKey>,
+    pub value: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Num(String),
+    Str(String),
+    /// Identifier or operator application `I!F(a, b)`.
+    Apply { path: Vec<Ident>, name: Ident, args: Vec<Expr> },
+    /// Prefix, infix and postfix operators. Synonyms are normalized,
+    /// e.g. `\land` becomes `/\` and unary minus becomes `-.`.
+    OpApply { op: Ident, args: Vec<Expr> },
+    /// Both infix `a /\ b` and bulleted lists.
+    Junction { kind: Junction, items: Vec<Expr> },
+    FnApply(Box<Expr>, Vec<Expr>),
+    Field(Box<Expr>, Ident),
+    Quant { kind: Quantifier, bounds: Vec<Bound>, body: Box<Expr> },
+    Choose { bound: Bound, body: Box<Expr> },
+    SetEnum(Vec<Expr>),
+    SetFilter { bound: Bound, pred: Box<Expr> },
+    SetMap { expr: Box<Expr>, bounds: Vec<Bound> },
+    FnCons { bounds: Vec<Bound>, body: Box<Expr> },
+    FnSet(Box<Expr>, Box<Expr>),
+    Record(Vec<(Ident, Expr)>),
+    RecordSet(Vec<(Ident, Expr)>),
+    Except { base: Box<Expr>, updates: Vec<ExceptUpdate> },
+    /// `@` inside of EXCEPT.
+    At,
+    Tuple(Vec<Expr>),
+    If { cond: Box<Expr>, then: Box<Expr>, other: Box<Expr> },
+    Case { arms: Vec<(Expr, Expr)>, other: Option<Box<Expr>> },
+    Let { defs: Vec<Definition>, body: Box<Expr> },
+    Lambda { params: Vec<Ident>, body: Box<Expr> },
+    /// `[A]_v`
+    BoxAction { action: Box<Expr>, sub: Box<Expr> },
+    /// `<<A>>_v`
+    AngleAction { action: Box<Expr>, sub: Box<Expr> },
+    /// `WF_v(A)` and `SF_v(A)`
+    Fairness { strong: bool, sub: Box<Expr>, action: Box<Expr> },
+}
+
+impl Expr {
+    /// Immediate subexpressions, in source order.
+    pub fn children(&self) -> Vec<&Expr> {
+        fn bounds<'a>(res: &mut Vec<&'a Expr>, bs: &'a [Bound]) {
+            res.extend(bs.iter().filter_map(|b| b.set.as_deref()));
+        }
+        let mut res = Vec::new();
+        match &self.kind {
+            ExprKind::Num(_) | ExprKind::Str(_) | ExprKind::At => {}
+            ExprKind::Apply { args, .. } => res.extend(args),
+            ExprKind::OpApply { args, .. } => res.extend(args),
+            ExprKind::Junction { items, .. } => res.extend(items),
+            ExprKind::FnApply(f, args) => {
+                res.push(&**f);
+                res.extend(args);
+            }
+            ExprKind::Field(e, _) => res.push(&**e),
+            ExprKind::Quant { bounds: bs, body, .. } => {
+                bounds(&mut res, bs);
+                res.push(&**body);
+            }
+            ExprKind::Choose { bound, body } => {
+                res.extend(bound.set.as_deref());
+                res.push(&**body);
+            }
+            ExprKind::SetEnum(items) | ExprKind::Tuple(items) => res.extend(items),
+            ExprKind::SetFilter { bound, pred } => {
+                res.extend(bound.set.as_deref());
+                res.push(&**pred);
+            }
+            ExprKind::SetMap { expr, bounds: bs } => {
+                res.push(&**expr);
+                bounds(&mut res, bs);
+            }
+            ExprKind::FnCons { bounds: bs, body } => {
+                bounds(&mut res, bs);
+                res.push(&**body);
+            }
+            ExprKind::FnSet(a, b) => {
+                res.push(&**a);
+                res.push(&**b);
+            }
+            ExprKind::Record(fields) | ExprKind::RecordSet(fields) => {
+                res.extend(fields.iter().map(|f| &f.1));
+            }
+            ExprKind::Except { base, updates } => {
+                res.push(&**base);
+                for u in updates {
+                    for key in &u.path {
+                        if let ExceptKey::Index(ix) = key {
+                            res.extend(ix);
+                        }
+                    }
+                    res.push(&u.value);
+                }
+            }
+            ExprKind::If { cond, then, other } => {
+                res.push(&**cond);
+                res.push(&**then);
+                res.push(&**other);
+            }
+            ExprKind::Case { arms, other } => {
+                for (guard, e) in arms {
+                    res.push(guard);
+                    res.push(e);
+                }
+                res.extend(other.as_ref().map(|e| &**e));
+            }
+            ExprKind::Let { defs, body } => {
+                for def in defs {
+                    match &def.body {
+                        DefBody::Expr(e) => res.push(e),
+                        DefBody::Function(bs, e) => {
+                            bounds(&mut res, bs);
+                            res.push(e);
+                        }
+                        DefBody::Instance(inst) => {
+                            res.extend(inst.substitutions.iter().map(|s| &s.1));
+                        }
+                    }
+                }
+                res.push(&**body);
+            }
+            ExprKind::Lambda { body, .. } => res.push(&**body),
+            ExprKind::BoxAction { action, sub } | ExprKind::AngleAction { action, sub } => {
+                res.push(&**action);
+                res.push(&**sub);
+            }
+            ExprKind::Fairness { sub, action, .. } => {
+                res.push(&**sub);
+                res.push(&**action);
+            }
+        }
+        res
+    }
+
+    /// Visits the expression and all its subexpressions in pre-order.
+    pub fn walk<'a>(&'a self, f: &mut dyn FnMut(&'a Expr)) {
+        f(self);
+        for e in self.children() {
+            e.walk(f);
+        }
+    }
+}
+
+impl Definition {
+    pub fn arity(&self) -> usize {
+        self.params.len()
+    }
+}
+
+impl Module {
+    /// Top level definitions in the order of appearance.
+    pub fn definitions(&self) -> impl Iterator<Item = &Definition> {
+        self.units.iter().filter_map(|u| match u {
+            Unit::Definition(def) => Some(def),
+            _ => None,
+        })
+    }
+
+    pub fn definition(&self, name: &str) -> Option<&Definition> {
+        self.definitions().find(|d| d.name.name == name)
+    }
+
+    pub fn constants(&self) -> impl Iterator<Item = &OpDecl> {
+        self.units.iter().flat_map(|u| match u {
+            Unit::Constants(decls) => decls.as_slice(),
+            _ => &[],
+        })
+    }
+
+    pub fn variables(&self) -> impl Iterator<Item = &Ident> {
+        self.units.iter().flat_map(|u| match u {
+            Unit::Variables(vars) => vars.as_slice(),
+            _ => &[],
+        })
+    }
+}
@@ -0,0 +1,260 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, fs, process};
+
+use tla_parser::cfg;
+use tla_parser::check::{check, check_with, simulate, Model, Simulation, Stats};
+use tla_parser::eval::Evaluator;
+use tla_parser::resolve::{resolve_with, Modules};
+use tla_parser::workspace::Workspace;
+
+const USAGE: &str = "\
+Usage: tla-check [OPTION]... FILE
+
+Checks the module in FILE by exploring its states, or random behaviors,
+like TLC. Modules that it extends or instantiates are loaded from the same
+directory.
+  --config FILE      TLC model config: the values of its CONSTANTS, and the
+                     names in its SPECIFICATION, INIT, NEXT, INVARIANTS,
+                     PROPERTIES, CONSTRAINTS, SYMMETRY, VIEW and
+                     CHECK_DEADLOCK sections, which take precedence over
+                     the options
+  --constant NAME=VALUE
+                     value of a constant as in a config, e.g. N=3 or
+                     Procs={p1,p2}, or NAME<-DEF for the value of a definition
+  --init NAME        initial predicate, Init by default
+  --next NAME        next-state action, Next by default
+  --invariant NAME   state predicate that must hold in every state
+  --property NAME    temporal formula that every behavior must satisfy
+  --fairness NAME    fairness condition that behaviors are assumed to meet
+  --constraint NAME  state predicate that limits the explored states
+  --symmetry NAME    set of permutations of model values, states that are
+                     equal up to them are explored once
+  --view NAME        state function, states with the same value of it are
+                     explored once
+  --max-states N     stop after N distinct states
+  --no-deadlock      don't report states without successors
+  --workers N        explore the states with N threads
+  --fingerprint-memory N
+                     keep at most N fingerprints of states in memory
+  --spill-dir DIR    directory for the fingerprints that don't fit in memory
+  --progress SECS    report the progress every SECS seconds
+  --graph FILE       write the explored state graph to FILE, in Graphviz DOT,
+                     GraphML or JSON format by its extension
+  --simulate N       check N random behaviors instead of all states
+  --depth N          states in a random behavior, 100 by default
+  --seed N           seed of the random behaviors
+  --weight NAME=N    relative chance of the subaction NAME in the random
+                     behaviors, 1 by default";
+
+fn main() {
+    let mut model = Model::default();
+    let mut progress = None;
+    let mut graph = None;
+    let mut traces = None;
+    let mut sim = Simulation::default();
+    let mut constants = Vec::new();
+    let mut configs = Vec::new();
+    let mut file = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| match args.next() {
+            Some(v) => v,
+            None => {
+                eprintln!("{} requires a value\n{}", name, USAGE);
+                process::exit(2);
+            }
+        };
+        let mut number = |name: &str| match value(name).parse() {
+            Ok(n) => n,
+            Err(_) => {
+                eprintln!("{} requires a number\n{}", name, USAGE);
+                process::exit(2);
+            }
+        };
+        match arg.as_str() {
+            "--config" => {
+                let path = value(&arg);
+                let config = fs::read_to_string(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| cfg::parse(&text).map_err(|e| e.to_string()));
+                match config {
+                    Ok(c) => {
+                        constants.extend(c.constants().cloned());
+                        configs.push((path, c));
+                    }
+                    Err(err) => {
+                        eprintln!("{}: {}", path, err);
+                        process::exit(2);
+                    }
+                }
+            }
+            "--constant" => {
+                let constant = value(&arg);
+                match cfg::parse(&format!("CONSTANT {}", constant)) {
+                    Ok(c) if c.sections.len() == 1 => constants.extend(c.constants().cloned()),
+                    _ => {
+                        eprintln!("--constant requires NAME=VALUE\n{}", USAGE);
+                        process::exit(2);
+                    }
+                }
+            }
+            "--init" => model.init = value(&arg),
+            "--next" => model.next = value(&arg),
+            "--invariant" => model.invariants.push(value(&arg)),
+            "--property" => model.properties.push(value(&arg)),
+            "--fairness" => model.fairness.push(value(&arg)),
+            "--constraint" => model.constraints.push(value(&arg)),
+            "--symmetry" => model.symmetry = Some(value(&arg)),
+            "--view" => model.view = Some(value(&arg)),
+            "--max-states" => model.max_states = Some(number(&arg)),
+            "--no-deadlock" => model.deadlock = false,
+            "--workers" => model.workers = number(&arg),
+            "--fingerprint-memory" => model.fingerprint_memory = Some(number(&arg)),
+            "--spill-dir" => model.spill_dir = Some(PathBuf::from(value(&arg))),
+            "--progress" => progress = Some(Duration::from_secs(number(&arg) as u64)),
+            "--graph" => graph = Some(value(&arg)),
+            "--simulate" => traces = Some(number(&arg)),
+            "--depth" => sim.depth = number(&arg),
+            "--seed" => sim.seed = number(&arg) as u64,
+            "--weight" => {
+                let weight = value(&arg);
+                match weight.split_once('=').map(|(name, n)| (name, n.parse())) {
+                    Some((name, Ok(n))) => sim.weights.push((name.to_string(), n)),
+                    _ => {
+                        eprintln!("--weight requires NAME=N\n{}", USAGE);
+                        process::exit(2);
+                    }
+                }
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with('-') => {
+                eprintln!("unknown option {}\n{}", arg, USAGE);
+                process::exit(2);
+            }
+            _ if file.is_none() => file = Some(arg),
+            _ => {
+                eprintln!("only one file can be checked\n{}", USAGE);
+                process::exit(2);
+            }
+        }
+    }
+    let file = match file {
+        Some(file) => file,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let format = graph.as_ref().map(|g| Path::new(g).extension().and_then(|e| e.to_str()).unwrap_or_default());
+    match format {
+        None | Some("dot") | Some("gv") | Some("graphml") | Some("json") => model.graph = graph.is_some(),
+        Some(_) => {
+            eprintln!("--graph requires a .dot, .graphml or .json file\n{}", USAGE);
+            process::exit(2);
+        }
+    }
+
+    let path = Path::new(&file);
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+    let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let ws = match Workspace::load_dir(dir) {
+        Ok(ws) => ws,
+        Err(err) => {
+            eprintln!("{}: {}", dir.display(), err);
+            process::exit(2);
+        }
+    };
+    let module = match (ws.module(name), ws.parse_error(name)) {
+        (Some(module), _) => module,
+        (None, Some(err)) => {
+            eprintln!("{}: {}", file, err);
+            process::exit(2);
+        }
+        (None, None) => {
+            eprintln!("{}: no such module", file);
+            process::exit(2);
+        }
+    };
+    let mut res = resolve_with(module, &ws);
+    res.errors.splice(0..0, ws.cycle_errors(name));
+    if !res.errors.is_empty() {
+        for err in &res.errors {
+            eprintln!("{}:{}", file, err);
+        }
+        process::exit(2);
+    }
+
+    for (path, config) in &configs {
+        if let Err(err) = model.configure(config, module, &ws) {
+            eprintln!("{}: {}", path, err);
+            process::exit(2);
+        }
+    }
+    let mut ev = Evaluator::with_modules(module, &res, &ws);
+    for c in &constants {
+        if let Err(err) = c.assign(&mut ev) {
+            eprintln!("{}: constant `{}`: {}", file, c.name().text, err);
+            process::exit(2);
+        }
+    }
+    let outcome = match (traces, progress) {
+        (Some(traces), _) => simulate(&ev, &model, &Simulation { traces, ..sim }),
+        (None, Some(every)) => check_with(&ev, &model, every, &|stats: &Stats| {
+            eprintln!(
+                "Progress({}): {} states generated, {} distinct states found, {} states left on queue.",
+                stats.depth, stats.generated, stats.distinct, stats.queue,
+            )
+        }),
+        (None, None) => check(&ev, &model),
+    };
+    for line in ev.take_output() {
+        println!("{}", line);
+    }
+    if let (Some(path), Some(g)) = (&graph, &outcome.graph) {
+        let text = match format {
+            Some("graphml") => g.to_graphml(),
+            Some("json") => g.to_json(),
+            _ => g.to_dot(),
+        };
+        if let Err(err) = fs::write(path, text) {
+            eprintln!("{}: {}", path, err);
+            process::exit(2);
+        }
+    }
+    if let Some(v) = &outcome.violation {
+        println!("{}:{}", file, v);
+        if let Some(err) = &outcome.error {
+            eprintln!("{}: {}", file, err);
+        }
+    } else if let Some(err) = &outcome.error {
+        println!("{}: {}", file, err);
+    } else if !outcome.complete {
+        println!("Stopped after {} distinct states.", outcome.stats.distinct);
+    } else if let Some(traces) = traces {
+        println!("Simulation of {} behaviors completed. No error has been found.", traces);
+    } else {
+        println!("Model checking completed. No error has been found.");
+    }
+    if traces.is_some() {
+        println!(
+            "{} states generated, {} distinct states found. The longest behavior has {} states.",
+            outcome.stats.generated, outcome.stats.distinct, outcome.stats.depth,
+        );
+    } else {
+        println!(
+            "{} states generated, {} distinct states found. The depth of the state graph is {}.",
+            outcome.stats.generated, outcome.stats.distinct, outcome.stats.depth,
+        );
+    }
+    if outcome.violation.is_some() {
+        process::exit(1);
+    }
+    if outcome.error.is_some() {
+        process::exit(2);
+    }
+}
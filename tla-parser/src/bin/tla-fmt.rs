@@ -0,0 +1,70 @@
+use std::io::{self, Read};
+use std::{env, fs, process};
+
+use tla_parser::fmt::{format, Config};
+
+const USAGE: &str = "\
+Usage: tla-fmt [--check] [--unicode] [FILE]...
+
+Formats TLA+ modules in place, or stdin to stdout if no files are given.
+  --check    don't write anything, fail if some files are not formatted
+  --unicode  use Unicode symbols for the operators";
+
+fn main() {
+    let mut config = Config::default();
+    let mut check = false;
+    let mut files = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--unicode" => config.unicode = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with('-') => {
+                eprintln!("unknown option {}\n{}", arg, USAGE);
+                process::exit(2);
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    let mut unformatted = false;
+    if files.is_empty() {
+        let mut source = String::new();
+        if let Err(err) = io::stdin().read_to_string(&mut source) {
+            eprintln!("stdin: {}", err);
+            process::exit(2);
+        }
+        match format(&source, &config) {
+            Ok(out) if check => unformatted = out != source,
+            Ok(out) => print!("{}", out),
+            Err(err) => {
+                eprintln!("stdin: {}", err);
+                process::exit(2);
+            }
+        }
+    }
+    for file in &files {
+        let result = fs::read_to_string(file).map_err(|e| e.to_string()).and_then(|source| {
+            let out = format(&source, &config).map_err(|e| e.to_string())?;
+            if out == source {
+                return Ok(());
+            }
+            if check {
+                println!("{}: not formatted", file);
+                unformatted = true;
+                return Ok(());
+            }
+            fs::write(file, out).map_err(|e| e.to_string())
+        });
+        if let Err(err) = result {
+            eprintln!("{}: {}", file, err);
+            process::exit(2);
+        }
+    }
+    if unformatted {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,56 @@
+use std::io::{self, Read};
+use std::{env, fs, process};
+
+use tla_parser::highlight;
+
+const USAGE: &str = "\
+Usage: tla-highlight [--html] [FILE]...
+
+Prints TLA+ modules with syntax highlighting for the terminal, or stdin
+if no files are given. Use `less -R` to page the output.
+  --html  print HTML, classes are described by `highlight::CSS`";
+
+fn main() {
+    let mut html = false;
+    let mut files = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--html" => html = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with('-') => {
+                eprintln!("unknown option {}\n{}", arg, USAGE);
+                process::exit(2);
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    let mut sources = Vec::new();
+    if files.is_empty() {
+        let mut source = String::new();
+        if let Err(err) = io::stdin().read_to_string(&mut source) {
+            eprintln!("stdin: {}", err);
+            process::exit(2);
+        }
+        sources.push(source);
+    }
+    for file in &files {
+        match fs::read_to_string(file) {
+            Ok(source) => sources.push(source),
+            Err(err) => {
+                eprintln!("{}: {}", file, err);
+                process::exit(2);
+            }
+        }
+    }
+    for source in sources {
+        if html {
+            print!("{}", highlight::html(&source));
+        } else {
+            print!("{}", highlight::ansi(&source));
+        }
+    }
+}
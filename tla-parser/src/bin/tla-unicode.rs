@@ -0,0 +1,52 @@
+use std::io::{self, Read};
+use std::{env, fs, process};
+
+use tla_parser::unicode::{convert, Form};
+
+const USAGE: &str = "\
+Usage: tla-unicode [--ascii] [FILE]...
+
+Rewrites operators of TLA+ modules with Unicode symbols, in place, or
+stdin to stdout if no files are given.
+  --ascii  convert Unicode symbols back to ASCII";
+
+fn main() {
+    let mut form = Form::Unicode;
+    let mut files = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--ascii" => form = Form::Ascii,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with('-') => {
+                eprintln!("unknown option {}\n{}", arg, USAGE);
+                process::exit(2);
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    if files.is_empty() {
+        let mut source = String::new();
+        if let Err(err) = io::stdin().read_to_string(&mut source) {
+            eprintln!("stdin: {}", err);
+            process::exit(2);
+        }
+        print!("{}", convert(&source, form));
+    }
+    for file in &files {
+        let result = fs::read_to_string(file).and_then(|source| {
+            let out = convert(&source, form);
+            if out == source {
+                return Ok(());
+            }
+            fs::write(file, out)
+        });
+        if let Err(err) = result {
+            eprintln!("{}: {}", file, err);
+            process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,795 @@
+mod validate;
+
+use std::fmt;
+
+use crate::ast::Span;
+use crate::eval::{self, Evaluator, Int};
+use crate::lexer::Pos;
+
+pub use self::validate::validate;
+pub(crate) use self::validate::behavior;
+
+
+/// Model configuration of TLC, the contents of a `.cfg` file.
+///
+/// Every word keeps the whitespace and comments before it, so that a parsed
+/// config is written back exactly as it was.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub sections: Vec<Section>,
+    /// Whitespace and comments after the last section.
+    pub trailing: String,
+}
+
+/// Keyword, name or punctuation of a config with the text before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Word {
+    /// Whitespace and comments before the word.
+    pub leading: String,
+    pub text: String,
+    /// `None` for the words that were not parsed.
+    pub span: Option<Span>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    /// Keyword as written, like `CONSTANT` or `CONSTANTS`.
+    pub keyword: Word,
+    pub body: Body,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Body {
+    Specification(Word),
+    Init(Word),
+    Next(Word),
+    Constants(Vec<Constant>),
+    Invariants(Vec<Word>),
+    Properties(Vec<Word>),
+    Symmetry(Word),
+    View(Word),
+    Constraints(Vec<Word>),
+    ActionConstraints(Vec<Word>),
+    /// `TRUE` or `FALSE`.
+    CheckDeadlock(Word),
+    Alias(Word),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    /// `N = 3`, or `p = p` for a model value.
+    Value { name: Word, eq: Word, value: Value },
+    /// `Op <- Def`, or `Op <- [M] Def` to replace `Op` in the module `M`
+    /// only.
+    Replace { name: Word, arrow: Word, module: Option<[Word; 3]>, by: Word },
+}
+
+/// Value assigned to a constant.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// Integer, string, `TRUE` or `FALSE`, or the name of a model value.
+    Atom(Word),
+    /// `{a, b}`, with the commas between the elements.
+    Set { open: Word, elements: Vec<Value>, commas: Vec<Word>, close: Word },
+    /// `<<a, b>>`
+    Tuple { open: Word, elements: Vec<Value>, commas: Vec<Word>, close: Word },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Lexer { span: Span },
+    Unexpected { span: Span, found: String, expected: &'static str },
+    /// At the end of the input, after the trailing whitespace and comments.
+    UnexpectedEnd { span: Span, expected: &'static str },
+}
+
+impl Error {
+    pub fn span(&self) -> Span {
+        match self {
+            Error::Lexer { span } | Error::Unexpected { span, .. } | Error::UnexpectedEnd { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Lexer { span } => write!(
+                f, "{}:{}: unrecognized token", span.start.line, span.start.col),
+            Error::Unexpected { span, found, expected } => write!(
+                f, "{}:{}: unexpected `{}`, expected {}",
+                span.start.line, span.start.col, found, expected),
+            Error::UnexpectedEnd { span, expected } => write!(
+                f, "{}:{}: unexpected end of input, expected {}",
+                span.start.line, span.start.col, expected),
+        }
+    }
+}
+
+const KEYWORDS: &[&str] = &[
+    "SPECIFICATION",
+    "INIT",
+    "NEXT",
+    "CONSTANT",
+    "CONSTANTS",
+    "INVARIANT",
+    "INVARIANTS",
+    "PROPERTY",
+    "PROPERTIES",
+    "SYMMETRY",
+    "VIEW",
+    "CONSTRAINT",
+    "CONSTRAINTS",
+    "ACTION_CONSTRAINT",
+    "ACTION_CONSTRAINTS",
+    "CHECK_DEADLOCK",
+    "ALIAS",
+];
+
+
+/// Parses a TLC model configuration.
+pub fn parse(source: &str) -> Result<Config, Error> {
+    let (tokens, trailing, end) = tokenize(source)?;
+    let mut p = Parser { tokens, i: 0, end };
+    let mut sections = Vec::new();
+    while p.i < p.tokens.len() {
+        sections.push(p.section()?);
+    }
+    Ok(Config { sections, trailing: trailing.to_string() })
+}
+
+impl Config {
+    /// Adds a section, on a line of its own.
+    pub fn push(&mut self, body: Body) {
+        let mut keyword = Word::new(body.keyword());
+        keyword.leading = if self.sections.is_empty() { String::new() } else { "\n".to_string() };
+        self.sections.push(Section { keyword, body });
+    }
+
+    pub fn specification(&self) -> Option<&str> {
+        self.single(|b| if let Body::Specification(w) = b { Some(w) } else { None })
+    }
+
+    pub fn init(&self) -> Option<&str> {
+        self.single(|b| if let Body::Init(w) = b { Some(w) } else { None })
+    }
+
+    pub fn next(&self) -> Option<&str> {
+        self.single(|b| if let Body::Next(w) = b { Some(w) } else { None })
+    }
+
+    pub fn constants(&self) -> impl Iterator<Item = &Constant> {
+        self.sections
+            .iter()
+            .flat_map(|s| if let Body::Constants(c) = &s.body { c.as_slice() } else { &[] })
+    }
+
+    pub fn invariants(&self) -> Vec<&str> {
+        self.names(|b| if let Body::Invariants(w) = b { Some(w) } else { None })
+    }
+
+    pub fn properties(&self) -> Vec<&str> {
+        self.names(|b| if let Body::Properties(w) = b { Some(w) } else { None })
+    }
+
+    pub fn symmetry(&self) -> Option<&str> {
+        self.single(|b| if let Body::Symmetry(w) = b { Some(w) } else { None })
+    }
+
+    pub fn view(&self) -> Option<&str> {
+        self.single(|b| if let Body::View(w) = b { Some(w) } else { None })
+    }
+
+    pub fn constraints(&self) -> Vec<&str> {
+        self.names(|b| if let Body::Constraints(w) = b { Some(w) } else { None })
+    }
+
+    pub fn action_constraints(&self) -> Vec<&str> {
+        self.names(|b| if let Body::ActionConstraints(w) = b { Some(w) } else { None })
+    }
+
+    pub fn check_deadlock(&self) -> Option<bool> {
+        self.single(|b| if let Body::CheckDeadlock(w) = b { Some(w) } else { None }).map(|w| w == "TRUE")
+    }
+
+    pub fn alias(&self) -> Option<&str> {
+        self.single(|b| if let Body::Alias(w) = b { Some(w) } else { None })
+    }
+
+    // Name in the last section of a kind, which overrides the others.
+    fn single(&self, f: fn(&Body) -> Option<&Word>) -> Option<&str> {
+        self.sections.iter().rev().find_map(|s| f(&s.body)).map(|w| w.text.as_str())
+    }
+
+    // Names in all the sections of a kind.
+    fn names(&self, f: fn(&Body) -> Option<&Vec<Word>>) -> Vec<&str> {
+        self.sections.iter().filter_map(|s| f(&s.body)).flatten().map(|w| w.text.as_str()).collect()
+    }
+}
+
+impl Word {
+    /// Word that is preceded by a space.
+    pub fn new(text: &str) -> Self {
+        Word { leading: " ".to_string(), text: text.to_string(), span: None }
+    }
+}
+
+impl Body {
+    /// Keyword of the sections with this body.
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Body::Specification(_) => "SPECIFICATION",
+            Body::Init(_) => "INIT",
+            Body::Next(_) => "NEXT",
+            Body::Constants(_) => "CONSTANTS",
+            Body::Invariants(_) => "INVARIANTS",
+            Body::Properties(_) => "PROPERTIES",
+            Body::Symmetry(_) => "SYMMETRY",
+            Body::View(_) => "VIEW",
+            Body::Constraints(_) => "CONSTRAINTS",
+            Body::ActionConstraints(_) => "ACTION_CONSTRAINTS",
+            Body::CheckDeadlock(_) => "CHECK_DEADLOCK",
+            Body::Alias(_) => "ALIAS",
+        }
+    }
+}
+
+impl Constant {
+    pub fn name(&self) -> &Word {
+        match self {
+            Constant::Value { name, .. } | Constant::Replace { name, .. } => name,
+        }
+    }
+
+    /// Gives the constant its value in the evaluator, or the value of the
+    /// definition that replaces it. Definitions with parameters and
+    /// replacements in other modules are not supported.
+    pub fn assign(&self, ev: &mut Evaluator) -> Result<(), String> {
+        match self {
+            Constant::Value { name, value, .. } => ev.set_constant(&name.text, value.to_value()),
+            Constant::Replace { name, module: None, by, .. } => {
+                let value = ev.definition(&by.text).map_err(|e| e.to_string())?;
+                ev.set_constant(&name.text, value);
+            }
+            Constant::Replace { name, .. } => {
+                return Err(format!("`{}` cannot be replaced in another module", name.text));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Value {
+    /// Value that TLC gives to the constant: atoms that are not integers,
+    /// strings or booleans are model values.
+    pub fn to_value(&self) -> eval::Value {
+        match self {
+            Value::Atom(w) => match w.text.as_str() {
+                "TRUE" => eval::Value::Bool(true),
+                "FALSE" => eval::Value::Bool(false),
+                s if s.len() > 1 && s.starts_with('"') && s.ends_with('"') => {
+                    eval::Value::str(&unescape(&s[1..s.len() - 1]))
+                }
+                s => match Int::parse(s.strip_prefix('-').unwrap_or(s), 10) {
+                    Some(n) if s.starts_with('-') => eval::Value::Int(-&n),
+                    Some(n) => eval::Value::Int(n),
+                    None => eval::Value::model(s),
+                },
+            },
+            Value::Set { elements, .. } => eval::Value::set(elements.iter().map(Value::to_value)),
+            Value::Tuple { elements, .. } => eval::Value::seq(elements.iter().map(Value::to_value)),
+        }
+    }
+
+    /// Names of the model values in the value.
+    pub fn model_values(&self) -> Vec<&Word> {
+        match self {
+            Value::Atom(w) => match self.to_value() {
+                eval::Value::Model(_) => vec![w],
+                _ => Vec::new(),
+            },
+            Value::Set { elements, .. } | Value::Tuple { elements, .. } => {
+                elements.iter().flat_map(Value::model_values).collect()
+            }
+        }
+    }
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        out.push(match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some(c) => c,
+                None => break,
+            },
+            (c, false) => c,
+        });
+    }
+    out
+}
+
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for s in &self.sections {
+            write!(f, "{}{}", s.keyword, s.body)?;
+        }
+        f.write_str(&self.trailing)
+    }
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.leading, self.text)
+    }
+}
+
+impl fmt::Display for Body {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Body::Specification(w)
+            | Body::Init(w)
+            | Body::Next(w)
+            | Body::Symmetry(w)
+            | Body::View(w)
+            | Body::CheckDeadlock(w)
+            | Body::Alias(w) => write!(f, "{}", w),
+            Body::Invariants(ws) | Body::Properties(ws) | Body::Constraints(ws) | Body::ActionConstraints(ws) => {
+                ws.iter().try_for_each(|w| write!(f, "{}", w))
+            }
+            Body::Constants(cs) => cs.iter().try_for_each(|c| write!(f, "{}", c)),
+        }
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constant::Value { name, eq, value } => write!(f, "{}{}{}", name, eq, value),
+            Constant::Replace { name, arrow, module, by } => {
+                write!(f, "{}{}", name, arrow)?;
+                if let Some([open, m, close]) = module {
+                    write!(f, "{}{}{}", open, m, close)?;
+                }
+                write!(f, "{}", by)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Atom(w) => write!(f, "{}", w),
+            Value::Set { open, elements, commas, close } | Value::Tuple { open, elements, commas, close } => {
+                write!(f, "{}", open)?;
+                for (i, e) in elements.iter().enumerate() {
+                    if i > 0 {
+                        // Elements that were added without a comma.
+                        match commas.get(i - 1) {
+                            Some(c) => write!(f, "{}", c)?,
+                            None => f.write_str(",")?,
+                        }
+                    }
+                    write!(f, "{}", e)?;
+                }
+                write!(f, "{}", close)
+            }
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Name,
+    Number,
+    Str,
+    Punct,
+}
+
+#[derive(Debug, Clone)]
+struct Token<'a> {
+    kind: Kind,
+    leading: &'a str,
+    text: &'a str,
+    span: Span,
+}
+
+struct Scanner<'a> {
+    source: &'a str,
+    pos: Pos,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.pos.byte_offset..].chars().next()
+    }
+
+    fn at(&self, s: &str) -> bool {
+        self.source[self.pos.byte_offset..].starts_with(s)
+    }
+
+    fn bump(&mut self) {
+        match self.peek() {
+            Some('\n') => {
+                self.pos.line += 1;
+                self.pos.col = 1;
+            }
+            Some('\t') => self.pos.col += 4,
+            Some(_) => self.pos.col += 1,
+            None => return,
+        }
+        self.pos.byte_offset += self.pos.char_size;
+        self.pos.char_size = self.peek().map_or(0, char::len_utf8);
+    }
+
+    fn bump_str(&mut self, s: &str) {
+        for _ in s.chars() {
+            self.bump();
+        }
+    }
+
+    // Skips whitespace and comments: `\*` to the end of the line and
+    // `(* *)`, which may be nested.
+    fn trivia(&mut self) -> Result<(), Error> {
+        loop {
+            if self.peek().is_some_and(char::is_whitespace) {
+                self.bump();
+            } else if self.at("\\*") {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.bump();
+                }
+            } else if self.at("(*") {
+                let start = self.pos;
+                let mut depth = 0;
+                loop {
+                    if self.at("(*") {
+                        depth += 1;
+                        self.bump_str("(*");
+                    } else if self.at("*)") {
+                        depth -= 1;
+                        self.bump_str("*)");
+                        if depth == 0 {
+                            break;
+                        }
+                    } else if self.peek().is_some() {
+                        self.bump();
+                    } else {
+                        return Err(Error::Lexer { span: Span::new(start, self.pos) });
+                    }
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn token(&mut self) -> Result<Kind, Error> {
+        let start = self.pos;
+        let c = self.peek().unwrap_or(' ');
+        let word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let minus = c == '-' && self.source[start.byte_offset + 1..].starts_with(|c: char| c.is_ascii_digit());
+        if c.is_ascii_digit() || minus {
+            self.bump();
+            while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                self.bump();
+            }
+            // Names may start with digits, like `1a`.
+            if !minus && self.peek().is_some_and(word) {
+                while self.peek().is_some_and(word) {
+                    self.bump();
+                }
+                return Ok(Kind::Name);
+            }
+            return Ok(Kind::Number);
+        }
+        if word(c) {
+            while self.peek().is_some_and(word) {
+                self.bump();
+            }
+            return Ok(Kind::Name);
+        }
+        if c == '"' {
+            self.bump();
+            loop {
+                match self.peek() {
+                    Some('"') => break,
+                    Some('\\') => {
+                        self.bump();
+                        self.bump();
+                    }
+                    Some('\n') | None => return Err(Error::Lexer { span: Span::new(start, self.pos) }),
+                    Some(_) => self.bump(),
+                }
+            }
+            self.bump();
+            return Ok(Kind::Str);
+        }
+        for p in &["<-", "<<", ">>", "=", "{", "}", ",", "[", "]"] {
+            if self.at(p) {
+                self.bump_str(p);
+                return Ok(Kind::Punct);
+            }
+        }
+        self.bump();
+        Err(Error::Lexer { span: Span::new(start, self.pos) })
+    }
+}
+
+// Tokens, the text after the last one, and the end of the source.
+fn tokenize(source: &str) -> Result<(Vec<Token<'_>>, &str, Pos), Error> {
+    let mut s = Scanner {
+        source,
+        pos: Pos { line: 1, col: 1, byte_offset: 0, char_size: source.chars().next().map_or(0, char::len_utf8) },
+    };
+    let mut tokens = Vec::new();
+    loop {
+        let before = s.pos.byte_offset;
+        s.trivia()?;
+        if s.peek().is_none() {
+            return Ok((tokens, &source[before..], s.pos));
+        }
+        let start = s.pos;
+        let kind = s.token()?;
+        tokens.push(Token {
+            kind,
+            leading: &source[before..start.byte_offset],
+            text: &source[start.byte_offset..s.pos.byte_offset],
+            span: Span::new(start, s.pos),
+        });
+    }
+}
+
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    i: usize,
+    end: Pos,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.i)
+    }
+
+    fn at(&self, text: &str) -> bool {
+        self.peek().is_some_and(|t| t.kind == Kind::Punct && t.text == text)
+    }
+
+    // Whether the next token is a name that does not start a section.
+    fn at_name(&self) -> bool {
+        self.peek().is_some_and(|t| t.kind == Kind::Name && !KEYWORDS.contains(&t.text))
+    }
+
+    fn next(&mut self, expected: &'static str) -> Result<Token<'a>, Error> {
+        let span = Span::new(self.end, self.end);
+        let t = self.peek().cloned().ok_or(Error::UnexpectedEnd { span, expected })?;
+        self.i += 1;
+        Ok(t)
+    }
+
+    fn expect(&mut self, ok: impl Fn(&Token) -> bool, expected: &'static str) -> Result<Word, Error> {
+        let t = self.next(expected)?;
+        if !ok(&t) {
+            return Err(Error::Unexpected { span: t.span, found: t.text.to_string(), expected });
+        }
+        Ok(word(&t))
+    }
+
+    fn name(&mut self) -> Result<Word, Error> {
+        self.expect(|t| t.kind == Kind::Name && !KEYWORDS.contains(&t.text), "name")
+    }
+
+    fn punct(&mut self, text: &'static str) -> Result<Word, Error> {
+        self.expect(|t| t.kind == Kind::Punct && t.text == text, text)
+    }
+
+    fn names(&mut self) -> Vec<Word> {
+        let mut names = Vec::new();
+        while self.at_name() {
+            names.push(word(&self.tokens[self.i]));
+            self.i += 1;
+        }
+        names
+    }
+
+    fn section(&mut self) -> Result<Section, Error> {
+        let keyword = self.expect(|t| KEYWORDS.contains(&t.text), "section keyword")?;
+        let body = match keyword.text.as_str() {
+            "SPECIFICATION" => Body::Specification(self.name()?),
+            "INIT" => Body::Init(self.name()?),
+            "NEXT" => Body::Next(self.name()?),
+            "CONSTANT" | "CONSTANTS" => {
+                let mut constants = Vec::new();
+                while self.at_name() {
+                    constants.push(self.constant()?);
+                }
+                Body::Constants(constants)
+            }
+            "INVARIANT" | "INVARIANTS" => Body::Invariants(self.names()),
+            "PROPERTY" | "PROPERTIES" => Body::Properties(self.names()),
+            "SYMMETRY" => Body::Symmetry(self.name()?),
+            "VIEW" => Body::View(self.name()?),
+            "CONSTRAINT" | "CONSTRAINTS" => Body::Constraints(self.names()),
+            "ACTION_CONSTRAINT" | "ACTION_CONSTRAINTS" => Body::ActionConstraints(self.names()),
+            "CHECK_DEADLOCK" => {
+                Body::CheckDeadlock(self.expect(|t| t.text == "TRUE" || t.text == "FALSE", "`TRUE` or `FALSE`")?)
+            }
+            _ => Body::Alias(self.name()?),
+        };
+        Ok(Section { keyword, body })
+    }
+
+    fn constant(&mut self) -> Result<Constant, Error> {
+        let name = self.name()?;
+        let op = self.expect(|t| t.kind == Kind::Punct && (t.text == "=" || t.text == "<-"), "`=` or `<-`")?;
+        if op.text == "=" {
+            return Ok(Constant::Value { name, eq: op, value: self.value()? });
+        }
+        let module = if self.at("[") {
+            Some([self.punct("[")?, self.name()?, self.punct("]")?])
+        } else {
+            None
+        };
+        Ok(Constant::Replace { name, arrow: op, module, by: self.name()? })
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        let (close, tuple) = if self.at("{") {
+            ("}", false)
+        } else if self.at("<<") {
+            (">>", true)
+        } else {
+            return Ok(Value::Atom(self.expect(|t| t.kind != Kind::Punct && !KEYWORDS.contains(&t.text), "value")?));
+        };
+        let open = self.next("value")?;
+        let mut elements = Vec::new();
+        let mut commas = Vec::new();
+        if !self.at(close) {
+            elements.push(self.value()?);
+            while self.at(",") {
+                commas.push(self.punct(",")?);
+                elements.push(self.value()?);
+            }
+        }
+        let (open, close) = (word(&open), self.punct(close)?);
+        Ok(if tuple {
+            Value::Tuple { open, elements, commas, close }
+        } else {
+            Value::Set { open, elements, commas, close }
+        })
+    }
+}
+
+fn word(t: &Token) -> Word {
+    Word { leading: t.leading.to_string(), text: t.text.to_string(), span: Some(t.span) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\\* Model of the spec\n\
+        SPECIFICATION Spec\n\
+        CONSTANTS\n    \
+            N = 3\n    \
+            Procs = {p1, p2,p3}   (* model values *)\n    \
+            Msgs = << \"a\\\"b\", -2, TRUE >>\n    \
+            Empty = {}\n    \
+            p1 = p1\n    \
+            Nat <- MCNat\n    \
+            Max <-[Bounds] MCMax\n\
+        INVARIANT TypeOK\n    Safe\n\
+        INVARIANTS (* nested (* comment *) *) Other\n\
+        PROPERTY Live\n\
+        SYMMETRY Perms VIEW View\n\
+        CONSTRAINT Small\n\
+        ACTION_CONSTRAINT Step\n\
+        CHECK_DEADLOCK FALSE\n\
+        ALIAS Alias\n\n";
+
+    #[test]
+    fn round_trip() {
+        let config = parse(CONFIG).unwrap();
+        assert_eq!(config.to_string(), CONFIG);
+        assert_eq!(config.sections.len(), 11);
+        assert_eq!(parse("").unwrap(), Config::default());
+        assert_eq!(parse("  \\* only a comment").unwrap().to_string(), "  \\* only a comment");
+    }
+
+    #[test]
+    fn sections() {
+        let config = parse(CONFIG).unwrap();
+        assert_eq!(config.specification(), Some("Spec"));
+        assert_eq!(config.init(), None);
+        assert_eq!(config.invariants(), vec!["TypeOK", "Safe", "Other"]);
+        assert_eq!(config.properties(), vec!["Live"]);
+        assert_eq!(config.symmetry(), Some("Perms"));
+        assert_eq!(config.view(), Some("View"));
+        assert_eq!(config.constraints(), vec!["Small"]);
+        assert_eq!(config.action_constraints(), vec!["Step"]);
+        assert_eq!(config.check_deadlock(), Some(false));
+        assert_eq!(config.alias(), Some("Alias"));
+
+        let constants: Vec<&Constant> = config.constants().collect();
+        assert_eq!(constants.len(), 7);
+        let value = |i: usize| match constants[i] {
+            Constant::Value { value, .. } => value.to_value(),
+            c => panic!("{:?}", c),
+        };
+        assert_eq!(value(0), eval::Value::int(3));
+        assert_eq!(value(1), eval::Value::set(vec![eval::Value::model("p1"), eval::Value::model("p2"), eval::Value::model("p3")]));
+        assert_eq!(
+            value(2),
+            eval::Value::seq(vec![eval::Value::str("a\"b"), eval::Value::int(-2), eval::Value::Bool(true)])
+        );
+        assert_eq!(value(3), eval::Value::set(vec![]));
+        for text in ["", "-", "\"", "-x"].iter() {
+            let atom = Value::Atom(Word::new(text));
+            assert_eq!(atom.to_value(), eval::Value::model(text));
+            assert_eq!(atom.model_values().len(), 1);
+        }
+        match constants[1] {
+            Constant::Value { value, .. } => {
+                let names: Vec<&str> = value.model_values().iter().map(|w| w.text.as_str()).collect();
+                assert_eq!(names, vec!["p1", "p2", "p3"]);
+            }
+            c => panic!("{:?}", c),
+        }
+        match constants[6] {
+            Constant::Replace { name, module: Some([_, m, _]), by, .. } => {
+                assert_eq!((name.text.as_str(), m.text.as_str(), by.text.as_str()), ("Max", "Bounds", "MCMax"));
+                let span = name.span.unwrap();
+                assert_eq!((span.start.line, span.start.col), (10, 5));
+            }
+            c => panic!("{:?}", c),
+        }
+    }
+
+    #[test]
+    fn write() {
+        let mut config = Config::default();
+        config.push(Body::Init(Word::new("Init")));
+        config.push(Body::Next(Word::new("Next")));
+        config.push(Body::Constants(vec![Constant::Value {
+            name: Word::new("S"),
+            eq: Word::new("="),
+            value: Value::Set {
+                open: Word::new("{"),
+                elements: vec![Value::Atom(Word::new("a")), Value::Atom(Word::new("b"))],
+                commas: Vec::new(),
+                close: Word::new("}"),
+            },
+        }]));
+        config.push(Body::Invariants(vec![Word::new("Inv1"), Word::new("Inv2")]));
+        assert_eq!(config.to_string(), "INIT Init\nNEXT Next\nCONSTANTS S = { a, b }\nINVARIANTS Inv1 Inv2");
+        assert_eq!(parse(&config.to_string()).unwrap().invariants(), vec!["Inv1", "Inv2"]);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |s: &str| parse(s).unwrap_err().to_string();
+        assert_eq!(error("INIT Init\nNEXT"), "2:5: unexpected end of input, expected name");
+        assert_eq!(error("INIT\n"), "2:1: unexpected end of input, expected name");
+        assert_eq!(error("SYMMETRY"), "1:9: unexpected end of input, expected name");
+        assert_eq!(error("CONSTANT N = \\* value\n"), "2:1: unexpected end of input, expected value");
+        assert_eq!(error("INIT Init\nNEXT INIT"), "2:6: unexpected `INIT`, expected name");
+        assert_eq!(error("Init"), "1:1: unexpected `Init`, expected section keyword");
+        assert_eq!(error("CONSTANT N 3"), "1:12: unexpected `3`, expected `=` or `<-`");
+        assert_eq!(error("CONSTANT S = {a, b"), "1:19: unexpected end of input, expected }");
+        assert_eq!(error("CONSTANT S = {a b}"), "1:17: unexpected `b`, expected }");
+        assert_eq!(error("CHECK_DEADLOCK no"), "1:16: unexpected `no`, expected `TRUE` or `FALSE`");
+        assert_eq!(error("INIT Init\n  (* open"), "2:3: unrecognized token");
+        assert_eq!(error("CONSTANT S = \"abc\nNEXT Next"), "1:14: unrecognized token");
+        assert_eq!(error("INIT Init ;"), "1:11: unrecognized token");
+        assert_eq!(parse("INIT Init ;").unwrap_err().span().start.byte_offset, 10);
+        assert_eq!(parse("INIT Init\nNEXT ").unwrap_err().span().start.byte_offset, 15);
+    }
+}
@@ -0,0 +1,491 @@
+use crate::ast::*;
+use crate::level::{self, Level, Levels};
+use crate::lexer::Pos;
+use crate::lint::{Diagnostic, Severity};
+use crate::resolve::{resolve_with, Modules};
+
+use super::{Body, Config, Constant, Value, Word};
+
+
+/// Checks the config against the module it is for, like TLC does when it
+/// starts: every constant has a value, the names in the sections are
+/// defined with the right levels, the specification has the form
+/// `Init /\ [][Next]_vars`, and model values are not named like
+/// definitions. The diagnostics have positions in the config.
+pub fn validate(config: &Config, module: &Module, modules: &dyn Modules) -> Vec<Diagnostic> {
+    let mut v = Validator { config, scope: scope(module, modules), out: Vec::new() };
+    v.constants();
+    v.behavior();
+    for s in &config.sections {
+        v.section(&s.body);
+    }
+    v.out
+}
+
+// The module and the modules it extends, transitively, with the levels of
+// their definitions.
+fn scope<'a>(module: &'a Module, modules: &'a dyn Modules) -> Vec<(&'a Module, Levels)> {
+    let mut scope: Vec<(&Module, Levels)> = Vec::new();
+    let mut pending = vec![module];
+    while let Some(m) = pending.pop() {
+        if scope.iter().any(|(s, _)| s.name.name == m.name.name) {
+            continue;
+        }
+        let res = resolve_with(m, modules);
+        scope.push((m, level::check_with(m, &res, modules)));
+        pending.extend(m.extends.iter().filter_map(|e| modules.module(&e.name)));
+    }
+    scope
+}
+
+struct Validator<'a> {
+    config: &'a Config,
+    scope: Vec<(&'a Module, Levels)>,
+    out: Vec<Diagnostic>,
+}
+
+impl<'a> Validator<'a> {
+    fn report(&mut self, rule: &'static str, severity: Severity, span: Span, message: String) {
+        self.out.push(Diagnostic { rule, severity, span, message });
+    }
+
+    fn definition(&self, name: &str) -> Option<(&'a Definition, &Levels)> {
+        self.scope.iter().find_map(|(m, levels)| Some((m.definition(name)?, levels)))
+    }
+
+    fn is_variable(&self, name: &str) -> bool {
+        self.scope.iter().any(|(m, _)| m.variables().any(|v| v.name == name))
+    }
+
+    // Level of the definition of a name in the config, `None` if it is
+    // reported as undefined or its level is unknown.
+    fn level(&mut self, w: &Word, what: &str) -> Option<Level> {
+        match self.definition(&w.text) {
+            Some((def, _)) if !def.params.is_empty() => {
+                let message = format!("{} `{}` must not have parameters", what, w.text);
+                self.report("parameterized-name", Severity::Error, span(w), message);
+                None
+            }
+            Some((_, levels)) => levels.definitions.get(&w.text).copied(),
+            None => {
+                let message = format!("{} `{}` is not defined in the module", what, w.text);
+                self.report("undefined-name", Severity::Error, span(w), message);
+                None
+            }
+        }
+    }
+
+    // Reports a definition whose level is above `max`.
+    fn expect_level(&mut self, w: &Word, what: &str, max: Level, expected: &str) {
+        match self.level(w, what) {
+            Some(level) if level > max => {
+                let message = format!("{} `{}` has {} level, expected {}", what, w.text, level, expected);
+                self.report("wrong-level", Severity::Error, span(w), message);
+            }
+            _ => {}
+        }
+    }
+
+    fn constants(&mut self) {
+        let config = self.config;
+        // Missing values are reported at the last CONSTANTS section.
+        let anchor = config
+            .sections
+            .iter()
+            .rev()
+            .find(|s| matches!(s.body, Body::Constants(_)))
+            .map_or_else(start, |s| span(&s.keyword));
+        let mut unassigned = Vec::new();
+        for (m, _) in &self.scope {
+            for decl in m.constants() {
+                let assigns = |c: &Constant| c.name().text == decl.name.name && !matches!(c.module(), Some(n) if n != m.name.name);
+                if !config.constants().any(assigns) {
+                    unassigned.push(format!("constant `{}` of module `{}` has no value", decl.name.name, m.name.name));
+                }
+            }
+        }
+        for message in unassigned {
+            self.report("unassigned-constant", Severity::Error, anchor, message);
+        }
+
+        for c in config.constants() {
+            let name = c.name();
+            let declared = self.scope.iter().any(|(m, _)| m.constants().any(|d| d.name.name == name.text));
+            if c.module().is_none() && !declared && self.definition(&name.text).is_none() {
+                let message = format!("`{}` is not a constant or a definition of the module", name.text);
+                self.report("unknown-constant", Severity::Error, span(name), message);
+            }
+            match c {
+                Constant::Value { value, .. } => self.model_values(value),
+                Constant::Replace { by, .. } => {
+                    if self.definition(&by.text).is_none() {
+                        let message = format!("`{}` is not defined in the module", by.text);
+                        self.report("undefined-name", Severity::Error, span(by), message);
+                    }
+                }
+            }
+        }
+    }
+
+    fn model_values(&mut self, value: &Value) {
+        for w in value.model_values() {
+            let what = if self.definition(&w.text).is_some() {
+                "definition"
+            } else if self.is_variable(&w.text) {
+                "variable"
+            } else {
+                continue;
+            };
+            let message = format!("model value `{}` has the same name as a {}", w.text, what);
+            self.report("model-value-clash", Severity::Error, span(w), message);
+        }
+    }
+
+    // Either SPECIFICATION, or INIT and NEXT.
+    fn behavior(&mut self) {
+        let find = |f: fn(&Body) -> bool| self.config.sections.iter().find(|s| f(&s.body)).map(|s| span(&s.keyword));
+        let spec = find(|b| matches!(b, Body::Specification(_)));
+        let init = find(|b| matches!(b, Body::Init(_)));
+        let next = find(|b| matches!(b, Body::Next(_)));
+        let message = match (spec, init, next) {
+            (Some(_), Some(at), _) | (Some(_), None, Some(at)) => {
+                Some((at, "SPECIFICATION cannot be used together with INIT or NEXT"))
+            }
+            (None, None, None) => Some((start(), "the config has neither SPECIFICATION nor INIT and NEXT")),
+            (None, Some(at), None) => Some((at, "INIT is given without NEXT")),
+            (None, None, Some(at)) => Some((at, "NEXT is given without INIT")),
+            _ => None,
+        };
+        if let Some((at, message)) = message {
+            self.report("specification-form", Severity::Error, at, message.to_string());
+        }
+    }
+
+    fn section(&mut self, body: &Body) {
+        match body {
+            Body::Specification(w) => self.specification(w),
+            Body::Init(w) => self.expect_level(w, "initial predicate", Level::State, "a state predicate"),
+            Body::Next(w) => self.expect_level(w, "next-state action", Level::Action, "an action"),
+            Body::Invariants(ws) => {
+                for w in ws {
+                    self.expect_level(w, "invariant", Level::State, "a state predicate");
+                }
+            }
+            Body::Properties(ws) => {
+                for w in ws {
+                    self.property(w);
+                }
+            }
+            Body::Symmetry(w) => self.expect_level(w, "symmetry set", Level::Constant, "a constant set of permutations"),
+            Body::View(w) => self.expect_level(w, "view", Level::State, "a state function"),
+            Body::Constraints(ws) => {
+                for w in ws {
+                    self.expect_level(w, "constraint", Level::State, "a state predicate");
+                }
+            }
+            Body::ActionConstraints(ws) => {
+                for w in ws {
+                    self.expect_level(w, "action constraint", Level::Action, "an action");
+                }
+            }
+            Body::Alias(w) => self.expect_level(w, "alias", Level::State, "a state function"),
+            Body::Constants(_) | Body::CheckDeadlock(_) => {}
+        }
+    }
+
+    fn property(&mut self, w: &Word) {
+        match self.level(w, "property") {
+            Some(Level::Action) => {
+                let message = format!("property `{}` has action level, expected a temporal formula", w.text);
+                self.report("wrong-level", Severity::Error, span(w), message);
+            }
+            Some(Level::Constant) | Some(Level::State) => {
+                let message = format!(
+                    "property `{}` is not temporal, it is checked in the initial states only; `[]{}` checks it in every state",
+                    w.text, w.text,
+                );
+                self.report("wrong-level", Severity::Warning, span(w), message);
+            }
+            _ => {}
+        }
+    }
+
+    // Conjunction of initial predicates, one `[][Next]_vars`, and fairness
+    // conditions.
+    fn specification(&mut self, w: &Word) {
+        let (def, levels) = match self.definition(&w.text) {
+            Some((def, levels)) if def.params.is_empty() => (def, levels),
+            _ => {
+                self.level(w, "specification");
+                return;
+            }
+        };
+        let reason = match &def.body {
+            DefBody::Expr(body) => self.parts(&w.text, body, levels).err(),
+            _ => Some("it is not a formula"),
+        };
+        if let Some(reason) = reason {
+            let message = format!("specification `{}` does not have the form `Init /\\ [][Next]_vars`: {}", w.text, reason);
+            self.report("specification-form", Severity::Error, span(w), message);
+        }
+    }
+
+    // Initial predicates, `[][Next]_vars` and the other conjuncts of the
+    // specification `name`, or why it does not have that form.
+    fn parts<'s>(&'s self, name: &'s str, body: &'s Expr, levels: &'s Levels) -> Result<Parts<'s>, &'static str> {
+        let mut items = Vec::new();
+        self.conjuncts(body, levels, &mut vec![name], &mut items);
+        let (next, rest): (Vec<_>, Vec<_>) = items.into_iter().partition(|(e, _)| is_always_action(e));
+        let (init, rest): (Vec<_>, Vec<_>) =
+            rest.into_iter().partition(|(e, l)| l.level(e).is_some_and(|l| l <= Level::State));
+        if next.is_empty() {
+            Err("it has no conjunct `[][Next]_vars`")
+        } else if next.len() > 1 {
+            Err("it has more than one conjunct `[][Next]_vars`")
+        } else if init.is_empty() {
+            Err("it has no initial predicate")
+        } else if rest.iter().any(|(e, l)| l.level(e) == Some(Level::Action)) {
+            Err("it has a conjunct of action level outside of `[][Next]_vars`")
+        } else {
+            let init = init.into_iter().map(|(e, _)| e).collect();
+            Ok(Parts { init, next: next[0].0, fairness: rest.into_iter().map(|(e, _)| e).collect() })
+        }
+    }
+
+    // Conjuncts of the formula with the levels of their modules. Names of
+    // temporal definitions without parameters are expanded, as in
+    // `FairSpec == Spec /\ WF_vars(Next)`, except for the ones in `seen`
+    // that are being expanded.
+    fn conjuncts<'s>(
+        &'s self,
+        e: &'s Expr,
+        levels: &'s Levels,
+        seen: &mut Vec<&'s str>,
+        out: &mut Vec<(&'s Expr, &'s Levels)>,
+    ) {
+        match &e.kind {
+            ExprKind::Junction { kind: Junction::And, items } => {
+                for i in items {
+                    self.conjuncts(i, levels, seen, out);
+                }
+            }
+            ExprKind::Apply { path, name, args } if path.is_empty() && args.is_empty() && !seen.contains(&&*name.name) => {
+                match self.definition(&name.name) {
+                    Some((Definition { params, body: DefBody::Expr(body), .. }, levels))
+                        if params.is_empty() && levels.definitions.get(&name.name) == Some(&Level::Temporal) =>
+                    {
+                        seen.push(&name.name);
+                        self.conjuncts(body, levels, seen, out);
+                        seen.pop();
+                    }
+                    _ => out.push((e, levels)),
+                }
+            }
+            _ => out.push((e, levels)),
+        }
+    }
+}
+
+// Conjuncts of a specification `Init /\ [][Next]_vars /\ Fairness`.
+struct Parts<'s> {
+    init: Vec<&'s Expr>,
+    // `[][Next]_vars`
+    next: &'s Expr,
+    fairness: Vec<&'s Expr>,
+}
+
+/// Names of the initial predicate and of the next-state action of the
+/// specification `name` of a config, which has the form
+/// `Init /\ [][Next]_vars`, and the fairness conditions that it assumes.
+/// These are the specification itself if it has conjuncts besides `Init`
+/// and `[][Next]_vars`: the behaviors explored with them satisfy those
+/// two anyway.
+pub(crate) fn behavior(name: &str, module: &Module, modules: &dyn Modules) -> Result<(String, String, Vec<String>), String> {
+    let config = Config::default();
+    let v = Validator { config: &config, scope: scope(module, modules), out: Vec::new() };
+    let form = |reason: &str| format!("specification `{}` does not have the form `Init /\\ [][Next]_vars`: {}", name, reason);
+    let parts = match v.definition(name) {
+        Some((def, levels)) if def.params.is_empty() => match &def.body {
+            DefBody::Expr(body) => v.parts(name, body, levels).map_err(form)?,
+            _ => return Err(form("it is not a formula")),
+        },
+        Some(_) => return Err(format!("specification `{}` must not have parameters", name)),
+        None => return Err(format!("specification `{}` is not defined in the module", name)),
+    };
+    let next = match &parts.next.kind {
+        ExprKind::OpApply { args, .. } => match &args[0].kind {
+            ExprKind::BoxAction { action, .. } => definition_name(action),
+            _ => None,
+        },
+        _ => None,
+    };
+    let init = match &parts.init[..] {
+        [init] => definition_name(init),
+        _ => None,
+    };
+    match (init, next) {
+        (Some(init), Some(next)) => {
+            let fairness = if parts.fairness.is_empty() { Vec::new() } else { vec![name.to_string()] };
+            Ok((init, next, fairness))
+        }
+        _ => Err(form("`Init` and `Next` are not names of definitions")),
+    }
+}
+
+// `Name` of a definition without arguments.
+fn definition_name(e: &Expr) -> Option<String> {
+    match &e.kind {
+        ExprKind::Apply { path, name, args } if path.is_empty() && args.is_empty() => Some(name.name.clone()),
+        _ => None,
+    }
+}
+
+// `[][A]_v`
+fn is_always_action(e: &Expr) -> bool {
+    match &e.kind {
+        ExprKind::OpApply { op, args } if op.name == "[]" && args.len() == 1 => {
+            matches!(args[0].kind, ExprKind::BoxAction { .. })
+        }
+        _ => false,
+    }
+}
+
+impl Constant {
+    // Module of `Op <- [M] Def`.
+    fn module(&self) -> Option<&str> {
+        match self {
+            Constant::Replace { module: Some([_, m, _]), .. } => Some(&m.text),
+            _ => None,
+        }
+    }
+}
+
+// Words that were not parsed are reported at the start of the config.
+fn span(w: &Word) -> Span {
+    w.span.unwrap_or_else(start)
+}
+
+fn start() -> Span {
+    let pos = Pos { line: 1, col: 1, byte_offset: 0, char_size: 0 };
+    Span::new(pos, pos)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::cfg::parse;
+
+    fn validate(config: &str) -> Vec<String> {
+        let base = crate::parser::parse(
+            "---- MODULE Base ----\n\
+             CONSTANT Procs\n\
+             Helper == 1\n\
+             ====",
+        )
+        .unwrap();
+        let module = crate::parser::parse(
+            "---- MODULE M ----\n\
+             EXTENDS Base, Naturals\n\
+             CONSTANTS N, Max\n\
+             VARIABLE x\n\
+             vars == <<x>>\n\
+             Init == x = 0\n\
+             Next == x' = (x + 1) % N\n\
+             Spec == Init /\\ [][Next]_vars /\\ WF_vars(Next)\n\
+             Safe == Init /\\ [][Next]_vars\n\
+             FairSpec == Safe /\\ WF_vars(Next)\n\
+             NoInit == [][Next]_vars\n\
+             Bad == Init /\\ Next /\\ [][Next]_vars\n\
+             TypeOK == x \\in 0..N\n\
+             Live == []<>(x = 0)\n\
+             Op(a) == a\n\
+             MCMax == 3\n\
+             ====",
+        )
+        .unwrap();
+        let config = parse(config).unwrap();
+        super::validate(&config, &module, &vec![base]).iter().map(|d| d.to_string()).collect::<Vec<_>>()
+    }
+
+    #[test]
+    fn valid() {
+        let config = "SPECIFICATION Spec\n\
+            CONSTANTS N = 3 Max <- MCMax Procs = {p1, p2}\n\
+            INVARIANT TypeOK\n\
+            PROPERTY Live\n";
+        assert_eq!(validate(config), Vec::<String>::new());
+        let config = "INIT Init NEXT Next CONSTANTS N = 3 Max = 2 Procs = {}";
+        assert_eq!(validate(config), Vec::<String>::new());
+        let config = "SPECIFICATION FairSpec CONSTANTS N = 3 Max = 2 Procs = {}";
+        assert_eq!(validate(config), Vec::<String>::new());
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!(
+            validate("INIT Init NEXT Next\nCONSTANTS N = 3 Limit = 2 Max <- Unknown\nCONSTANT Helper = 2"),
+            vec![
+                "3:1: error: constant `Procs` of module `Base` has no value [unassigned-constant]",
+                "2:17: error: `Limit` is not a constant or a definition of the module [unknown-constant]",
+                "2:34: error: `Unknown` is not defined in the module [undefined-name]",
+            ],
+        );
+        assert_eq!(
+            validate("SPECIFICATION Spec\nCONSTANTS N = 3 Max = 2 Procs = {x, Init, p}"),
+            vec![
+                "2:34: error: model value `x` has the same name as a variable [model-value-clash]",
+                "2:37: error: model value `Init` has the same name as a definition [model-value-clash]",
+            ],
+        );
+    }
+
+    #[test]
+    fn levels() {
+        let constants = "\nCONSTANTS N = 3 Max = 2 Procs = {}";
+        assert_eq!(
+            validate(&format!("INIT Next NEXT Init\nINVARIANT TypeOK Live Missing Op{}", constants)),
+            vec![
+                "1:6: error: initial predicate `Next` has action level, expected a state predicate [wrong-level]",
+                "2:18: error: invariant `Live` has temporal level, expected a state predicate [wrong-level]",
+                "2:23: error: invariant `Missing` is not defined in the module [undefined-name]",
+                "2:31: error: invariant `Op` must not have parameters [parameterized-name]",
+            ],
+        );
+        assert_eq!(
+            validate(&format!("SPECIFICATION Spec\nPROPERTIES Next TypeOK{}", constants)),
+            vec![
+                "2:12: error: property `Next` has action level, expected a temporal formula [wrong-level]",
+                "2:17: warning: property `TypeOK` is not temporal, it is checked in the initial states only; \
+                 `[]TypeOK` checks it in every state [wrong-level]",
+            ],
+        );
+    }
+
+    #[test]
+    fn specification() {
+        let constants = "\nCONSTANTS N = 3 Max = 2 Procs = {}";
+        assert_eq!(
+            validate(&format!("SPECIFICATION NoInit{}", constants)),
+            vec!["1:15: error: specification `NoInit` does not have the form `Init /\\ [][Next]_vars`: \
+                  it has no initial predicate [specification-form]"],
+        );
+        assert_eq!(
+            validate(&format!("SPECIFICATION Bad{}", constants)),
+            vec!["1:15: error: specification `Bad` does not have the form `Init /\\ [][Next]_vars`: \
+                  it has a conjunct of action level outside of `[][Next]_vars` [specification-form]"],
+        );
+        assert_eq!(
+            validate(&format!("SPECIFICATION TypeOK{}", constants)),
+            vec!["1:15: error: specification `TypeOK` does not have the form `Init /\\ [][Next]_vars`: \
+                  it has no conjunct `[][Next]_vars` [specification-form]"],
+        );
+        assert_eq!(
+            validate(&format!("SPECIFICATION Spec\nNEXT Next{}", constants)),
+            vec!["2:1: error: SPECIFICATION cannot be used together with INIT or NEXT [specification-form]"],
+        );
+        assert_eq!(
+            validate(&format!("INVARIANT TypeOK{}", constants)),
+            vec!["1:1: error: the config has neither SPECIFICATION nor INIT and NEXT [specification-form]"],
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::{env, iter, process};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// Fingerprints in a page of a file, the unit that is read by a lookup.
+const PAGE: usize = 512;
+
+// Numbers the files of the sets in the process.
+static SETS: AtomicUsize = AtomicUsize::new(0);
+
+/// Set of the fingerprints of the explored states, split into shards that
+/// are locked separately. Shards that outgrow their part of the memory
+/// budget move their fingerprints to sorted files on disk.
+pub(super) struct Fingerprints {
+    shards: Vec<Mutex<Shard>>,
+    // Fingerprints that a shard keeps in memory.
+    limit: usize,
+    dir: Option<PathBuf>,
+    id: usize,
+}
+
+struct Shard {
+    memory: HashSet<u64>,
+    // Runs from the oldest, each more than twice as long as the next one.
+    disk: Vec<Run>,
+    // Files created, which number them.
+    files: usize,
+    // Fingerprints written to the files.
+    written: usize,
+}
+
+// Sorted fingerprints in a file, with the first one of every page.
+struct Run {
+    file: File,
+    path: PathBuf,
+    len: usize,
+    index: Vec<u64>,
+}
+
+impl Fingerprints {
+    /// Set of `shards` shards that keeps at most about `memory`
+    /// fingerprints in memory, the rest go to files in `dir`, or in the
+    /// temporary directory.
+    pub(super) fn new(shards: usize, memory: Option<usize>, dir: Option<&Path>) -> Self {
+        let shards = shards.max(1);
+        Fingerprints {
+            shards: (0..shards)
+                .map(|_| Mutex::new(Shard { memory: HashSet::new(), disk: Vec::new(), files: 0, written: 0 }))
+                .collect(),
+            limit: memory.map_or(usize::MAX, |m| (m / shards).max(1)),
+            dir: dir.map(Path::to_path_buf),
+            id: SETS.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Adds the fingerprint, the result is false if it is in the set.
+    pub(super) fn insert(&self, fp: u64) -> io::Result<bool> {
+        let i = (fp >> 32) as usize % self.shards.len();
+        let mut shard = self.shards[i].lock().unwrap();
+        if shard.memory.contains(&fp) {
+            return Ok(false);
+        }
+        for run in &mut shard.disk {
+            if run.contains(fp)? {
+                return Ok(false);
+            }
+        }
+        shard.memory.insert(fp);
+        if shard.memory.len() > self.limit {
+            let dir = self.dir.clone().unwrap_or_else(env::temp_dir);
+            let prefix = dir.join(format!("fingerprints-{}-{}-{}", process::id(), self.id, i));
+            shard.spill(&prefix)?;
+        }
+        Ok(true)
+    }
+}
+
+impl Shard {
+    // Moves the fingerprints in memory to a new run. Then the last run is
+    // merged into the one before it while that one is not more than twice
+    // as long, so that there are few runs and every fingerprint is written
+    // a logarithmic number of times.
+    fn spill(&mut self, prefix: &Path) -> io::Result<()> {
+        let mut memory: Vec<u64> = self.memory.iter().copied().collect();
+        memory.sort_unstable();
+        let run = self.write(prefix, memory.into_iter().map(Ok))?;
+        self.disk.push(run);
+        self.memory.clear();
+        while let [.., older, newer] = self.disk.as_slice() {
+            if older.len > 2 * newer.len {
+                break;
+            }
+            let (newer, older) = (self.disk.pop().unwrap(), self.disk.pop().unwrap());
+            let run = self.write(prefix, merge(older.iter()?, newer.iter()?))?;
+            self.disk.push(run);
+        }
+        Ok(())
+    }
+
+    // Writes the sorted fingerprints to a new file.
+    fn write(&mut self, prefix: &Path, fps: impl Iterator<Item = io::Result<u64>>) -> io::Result<Run> {
+        let path = PathBuf::from(format!("{}-{}", prefix.display(), self.files));
+        self.files += 1;
+        let mut out = BufWriter::new(File::create(&path)?);
+        let mut len = 0;
+        let mut index = Vec::new();
+        for fp in fps {
+            let fp = fp?;
+            if len == index.len() * PAGE {
+                index.push(fp);
+            }
+            len += 1;
+            out.write_all(&fp.to_le_bytes())?;
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        self.written += len;
+        Ok(Run { file: File::open(&path)?, path, len, index })
+    }
+}
+
+// Fingerprints of two sorted runs, in order.
+fn merge(
+    a: impl Iterator<Item = io::Result<u64>>,
+    b: impl Iterator<Item = io::Result<u64>>,
+) -> impl Iterator<Item = io::Result<u64>> {
+    let (mut a, mut b) = (a.peekable(), b.peekable());
+    iter::from_fn(move || match (a.peek(), b.peek()) {
+        (Some(Ok(x)), Some(Ok(y))) if y < x => b.next(),
+        (Some(_), _) => a.next(),
+        (None, _) => b.next(),
+    })
+}
+
+impl Run {
+    // Fingerprints of the run in order, read from the start of the file.
+    fn iter(&self) -> io::Result<impl Iterator<Item = io::Result<u64>>> {
+        let mut file = BufReader::new(File::open(&self.path)?);
+        Ok((0..self.len).map(move |_| {
+            let mut buf = [0; 8];
+            file.read_exact(&mut buf)?;
+            Ok(u64::from_le_bytes(buf))
+        }))
+    }
+
+    fn contains(&mut self, fp: u64) -> io::Result<bool> {
+        // The page that would hold the fingerprint.
+        let page = match self.index.partition_point(|&first| first <= fp) {
+            0 => return Ok(false),
+            n => n - 1,
+        };
+        let n = PAGE.min(self.len - page * PAGE);
+        let mut buf = vec![0; n * 8];
+        self.file.seek(SeekFrom::Start((page * PAGE * 8) as u64))?;
+        self.file.read_exact(&mut buf)?;
+        let found = buf
+            .chunks(8)
+            .map(|c| {
+                let mut b = [0; 8];
+                b.copy_from_slice(c);
+                u64::from_le_bytes(b)
+            })
+            .collect::<Vec<_>>()
+            .binary_search(&fp)
+            .is_ok();
+        Ok(found)
+    }
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spill() {
+        let set = Fingerprints::new(4, Some(100), None);
+        let fps: Vec<u64> = (0..5000u64).map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15)).collect();
+        for &fp in &fps {
+            assert!(set.insert(fp).unwrap());
+        }
+        for &fp in &fps {
+            assert!(!set.insert(fp).unwrap());
+        }
+        assert!(set.insert(1).unwrap());
+        for s in &set.shards {
+            let s = s.lock().unwrap();
+            assert!(s.memory.len() <= 25);
+            assert!(s.disk.iter().map(|r| r.len).sum::<usize>() > 1000);
+        }
+    }
+
+    #[test]
+    fn spill_writes() {
+        let set = Fingerprints::new(1, Some(100), None);
+        for i in 0..20_000u64 {
+            assert!(set.insert(i.wrapping_mul(0x9e37_79b9_7f4a_7c15)).unwrap());
+        }
+        // About log2(20000 / 100) runs, and as many writes of each
+        // fingerprint, instead of a rewrite of all of them on every spill.
+        let s = set.shards[0].lock().unwrap();
+        assert!(s.disk.len() <= 8, "{} runs", s.disk.len());
+        assert!(s.written <= 20_000 * 9, "{} fingerprints written", s.written);
+        assert!(s.disk.windows(2).all(|w| w[0].len > 2 * w[1].len));
+    }
+}
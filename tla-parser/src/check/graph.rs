@@ -0,0 +1,201 @@
+use std::fmt::Write;
+
+use crate::eval::Value;
+
+
+/// Explored states of a model and the steps between them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateGraph {
+    pub variables: Vec<String>,
+    /// Values of the variables in each state.
+    pub states: Vec<Vec<Value>>,
+    /// Indices of the initial states.
+    pub initial: Vec<usize>,
+    pub edges: Vec<Edge>,
+}
+
+/// Step from a state to another by a subaction of the next-state action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub action: String,
+}
+
+impl StateGraph {
+    /// Graphviz graph, with the states labelled by the values of the
+    /// variables as in TLC traces and the initial states filled.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph StateGraph {\n");
+        for (i, state) in self.states.iter().enumerate() {
+            let label: Vec<String> = self.assignments(state).map(|(name, v)| format!("/\\ {} = {}", name, v)).collect();
+            let style = if self.initial.contains(&i) { ", style = filled" } else { "" };
+            writeln!(out, "  {} [label = \"{}\"{}];", i, dot_escape(&label.join("\n")), style).unwrap();
+        }
+        for e in &self.edges {
+            writeln!(out, "  {} -> {} [label = \"{}\"];", e.from, e.to, dot_escape(&e.action)).unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// GraphML document with the values of the variables and the actions as
+    /// data of the nodes and the edges.
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n  \
+             <key id=\"initial\" for=\"node\" attr.name=\"initial\" attr.type=\"boolean\"/>\n",
+        );
+        for (i, name) in self.variables.iter().enumerate() {
+            writeln!(out, "  <key id=\"v{}\" for=\"node\" attr.name=\"{}\" attr.type=\"string\"/>", i, xml_escape(name))
+                .unwrap();
+        }
+        out.push_str("  <key id=\"action\" for=\"edge\" attr.name=\"action\" attr.type=\"string\"/>\n");
+        out.push_str("  <graph id=\"StateGraph\" edgedefault=\"directed\">\n");
+        for (i, state) in self.states.iter().enumerate() {
+            writeln!(out, "    <node id=\"s{}\">", i).unwrap();
+            writeln!(out, "      <data key=\"initial\">{}</data>", self.initial.contains(&i)).unwrap();
+            for (j, v) in state.iter().enumerate() {
+                writeln!(out, "      <data key=\"v{}\">{}</data>", j, xml_escape(&v.to_string())).unwrap();
+            }
+            out.push_str("    </node>\n");
+        }
+        for e in &self.edges {
+            writeln!(
+                out,
+                "    <edge source=\"s{}\" target=\"s{}\"><data key=\"action\">{}</data></edge>",
+                e.from,
+                e.to,
+                xml_escape(&e.action),
+            )
+            .unwrap();
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+
+    /// JSON object with the variables, the states with the values of the
+    /// variables as TLA+ expressions, and the edges.
+    pub fn to_json(&self) -> String {
+        let variables: Vec<String> = self.variables.iter().map(|s| json_string(s)).collect();
+        let mut out = String::from("{\n");
+        writeln!(out, "  \"variables\": [{}],", variables.join(", ")).unwrap();
+        out.push_str("  \"states\": [");
+        for (i, state) in self.states.iter().enumerate() {
+            let values: Vec<String> = self
+                .assignments(state)
+                .map(|(name, v)| format!("{}: {}", json_string(name), json_string(&v.to_string())))
+                .collect();
+            out.push_str(if i == 0 { "\n" } else { ",\n" });
+            write!(
+                out,
+                "    {{\"id\": {}, \"initial\": {}, \"values\": {{{}}}}}",
+                i,
+                self.initial.contains(&i),
+                values.join(", "),
+            )
+            .unwrap();
+        }
+        out.push_str(if self.states.is_empty() { "],\n" } else { "\n  ],\n" });
+        out.push_str("  \"edges\": [");
+        for (i, e) in self.edges.iter().enumerate() {
+            out.push_str(if i == 0 { "\n" } else { ",\n" });
+            write!(out, "    {{\"from\": {}, \"to\": {}, \"action\": {}}}", e.from, e.to, json_string(&e.action)).unwrap();
+        }
+        out.push_str(if self.edges.is_empty() { "]\n}\n" } else { "\n  ]\n}\n" });
+        out
+    }
+
+    fn assignments<'s>(&'s self, state: &'s [Value]) -> impl Iterator<Item = (&'s str, &'s Value)> {
+        self.variables.iter().map(|s| s.as_str()).zip(state)
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> StateGraph {
+        StateGraph {
+            variables: vec!["x".to_string(), "s".to_string()],
+            states: vec![vec![Value::int(0), Value::str("a<b")], vec![Value::int(1), Value::str("a<b")]],
+            initial: vec![0],
+            edges: vec![
+                Edge { from: 0, to: 1, action: "Inc".to_string() },
+                Edge { from: 1, to: 0, action: "Reset".to_string() },
+            ],
+        }
+    }
+
+    #[test]
+    fn dot() {
+        assert_eq!(
+            graph().to_dot(),
+            "digraph StateGraph {\n  \
+             0 [label = \"/\\\\ x = 0\\n/\\\\ s = \\\"a<b\\\"\", style = filled];\n  \
+             1 [label = \"/\\\\ x = 1\\n/\\\\ s = \\\"a<b\\\"\"];\n  \
+             0 -> 1 [label = \"Inc\"];\n  \
+             1 -> 0 [label = \"Reset\"];\n\
+             }\n",
+        );
+    }
+
+    #[test]
+    fn graphml() {
+        let out = graph().to_graphml();
+        assert!(out.contains("<key id=\"v1\" for=\"node\" attr.name=\"s\" attr.type=\"string\"/>"), "{}", out);
+        assert!(out.contains(
+            "    <node id=\"s0\">\n      \
+             <data key=\"initial\">true</data>\n      \
+             <data key=\"v0\">0</data>\n      \
+             <data key=\"v1\">&quot;a&lt;b&quot;</data>\n    \
+             </node>\n"
+        ), "{}", out);
+        assert!(out.contains("<edge source=\"s1\" target=\"s0\"><data key=\"action\">Reset</data></edge>"), "{}", out);
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            graph().to_json(),
+            "{\n  \
+             \"variables\": [\"x\", \"s\"],\n  \
+             \"states\": [\n    \
+             {\"id\": 0, \"initial\": true, \"values\": {\"x\": \"0\", \"s\": \"\\\"a<b\\\"\"}},\n    \
+             {\"id\": 1, \"initial\": false, \"values\": {\"x\": \"1\", \"s\": \"\\\"a<b\\\"\"}}\n  \
+             ],\n  \
+             \"edges\": [\n    \
+             {\"from\": 0, \"to\": 1, \"action\": \"Inc\"},\n    \
+             {\"from\": 1, \"to\": 0, \"action\": \"Reset\"}\n  \
+             ]\n}\n",
+        );
+        let empty = StateGraph { variables: Vec::new(), states: Vec::new(), initial: Vec::new(), edges: Vec::new() };
+        assert_eq!(empty.to_json(), "{\n  \"variables\": [],\n  \"states\": [],\n  \"edges\": []\n}\n");
+    }
+}
@@ -0,0 +1,580 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use crate::eval::{Atom, Error, Evaluator, Temporal, Value};
+
+use super::{Loop, Step, Trace};
+
+
+// Formula in negation normal form over the atoms, `Lit(i, false)` is the
+// negation of atom `i`. `[]p` is `False R p` and `<>p` is `True U p`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Ltl {
+    True,
+    False,
+    Lit(usize, bool),
+    And(Box<Ltl>, Box<Ltl>),
+    Or(Box<Ltl>, Box<Ltl>),
+    Until(Box<Ltl>, Box<Ltl>),
+    Release(Box<Ltl>, Box<Ltl>),
+}
+
+fn nnf<'a>(t: &Temporal<'a>, negate: bool, atoms: &mut Vec<Atom<'a>>) -> Ltl {
+    let binary = |items: Vec<Ltl>, and: bool| {
+        let unit = if and { Ltl::True } else { Ltl::False };
+        items.into_iter().rev().fold(None, |acc, item| match acc {
+            None => Some(item),
+            Some(acc) if and => Some(Ltl::And(Box::new(item), Box::new(acc))),
+            Some(acc) => Some(Ltl::Or(Box::new(item), Box::new(acc))),
+        }).unwrap_or(unit)
+    };
+    match t {
+        Temporal::Atom(a) => {
+            atoms.push(a.clone());
+            Ltl::Lit(atoms.len() - 1, !negate)
+        }
+        Temporal::Not(t) => nnf(t, !negate, atoms),
+        Temporal::And(items) => binary(items.iter().map(|t| nnf(t, negate, atoms)).collect(), !negate),
+        Temporal::Or(items) => binary(items.iter().map(|t| nnf(t, negate, atoms)).collect(), negate),
+        Temporal::Always(t) if negate => Ltl::Until(Box::new(Ltl::True), Box::new(nnf(t, true, atoms))),
+        Temporal::Always(t) => Ltl::Release(Box::new(Ltl::False), Box::new(nnf(t, false, atoms))),
+        Temporal::Eventually(t) if negate => Ltl::Release(Box::new(Ltl::False), Box::new(nnf(t, true, atoms))),
+        Temporal::Eventually(t) => Ltl::Until(Box::new(Ltl::True), Box::new(nnf(t, false, atoms))),
+        // [](a => <>b)
+        Temporal::LeadsTo(a, b) => {
+            let t = Temporal::Always(Box::new(Temporal::Or(vec![
+                Temporal::Not(a.clone()),
+                Temporal::Eventually(b.clone()),
+            ])));
+            nnf(&t, negate, atoms)
+        }
+        // WF: []<>~enabled \/ []<>step, SF: <>[]~enabled \/ []<>step.
+        Temporal::Fairness { strong, enabled, step } => {
+            let disabled = Box::new(Temporal::Not(Box::new(Temporal::Atom(enabled.clone()))));
+            let disabled = if *strong {
+                Temporal::Eventually(Box::new(Temporal::Always(disabled)))
+            } else {
+                Temporal::Always(Box::new(Temporal::Eventually(disabled)))
+            };
+            let step = Box::new(Temporal::Atom(step.clone()));
+            let t = Temporal::Or(vec![disabled, Temporal::Always(Box::new(Temporal::Eventually(step)))]);
+            nnf(&t, negate, atoms)
+        }
+    }
+}
+
+// Generalized Büchi automaton of a formula, built by the tableau method of
+// Gerth, Peled, Vardi and Wolper. The literals of a node hold in the step
+// from the state that the node is paired with.
+struct Automaton {
+    literals: Vec<Vec<(usize, bool)>>,
+    initial: Vec<usize>,
+    succ: Vec<Vec<usize>>,
+    // For each `a U b`, the nodes that don't wait for `b`.
+    accepting: Vec<Vec<bool>>,
+}
+
+struct TableauNode {
+    incoming: BTreeSet<usize>,
+    new: BTreeSet<Ltl>,
+    old: BTreeSet<Ltl>,
+    next: BTreeSet<Ltl>,
+}
+
+const INIT: usize = usize::MAX;
+
+fn tableau(f: Ltl) -> Automaton {
+    let mut done: Vec<TableauNode> = Vec::new();
+    let mut stack = vec![TableauNode {
+        incoming: vec![INIT].into_iter().collect(),
+        new: vec![f].into_iter().collect(),
+        old: BTreeSet::new(),
+        next: BTreeSet::new(),
+    }];
+    while let Some(mut n) = stack.pop() {
+        let eta = match n.new.iter().next() {
+            Some(eta) => eta.clone(),
+            None => {
+                if let Some(d) = done.iter_mut().find(|d| d.old == n.old && d.next == n.next) {
+                    d.incoming.extend(n.incoming);
+                    continue;
+                }
+                let next = n.next.clone();
+                done.push(n);
+                stack.push(TableauNode {
+                    incoming: vec![done.len() - 1].into_iter().collect(),
+                    new: next,
+                    old: BTreeSet::new(),
+                    next: BTreeSet::new(),
+                });
+                continue;
+            }
+        };
+        n.new.remove(&eta);
+        n.old.insert(eta.clone());
+        let add = |n: &mut TableauNode, f: &Ltl| {
+            if !n.old.contains(f) {
+                n.new.insert(f.clone());
+            }
+        };
+        let split = |n: &TableauNode| TableauNode {
+            incoming: n.incoming.clone(),
+            new: n.new.clone(),
+            old: n.old.clone(),
+            next: n.next.clone(),
+        };
+        match &eta {
+            Ltl::False => {}
+            Ltl::Lit(i, pos) if n.old.contains(&Ltl::Lit(*i, !pos)) => {}
+            Ltl::True | Ltl::Lit(..) => stack.push(n),
+            Ltl::And(a, b) => {
+                add(&mut n, a);
+                add(&mut n, b);
+                stack.push(n);
+            }
+            Ltl::Or(a, b) => {
+                let mut other = split(&n);
+                add(&mut n, a);
+                add(&mut other, b);
+                stack.push(n);
+                stack.push(other);
+            }
+            Ltl::Until(a, b) => {
+                let mut other = split(&n);
+                add(&mut n, a);
+                n.next.insert(eta.clone());
+                add(&mut other, b);
+                stack.push(n);
+                stack.push(other);
+            }
+            Ltl::Release(a, b) => {
+                let mut other = split(&n);
+                add(&mut n, b);
+                n.next.insert(eta.clone());
+                add(&mut other, a);
+                add(&mut other, b);
+                stack.push(n);
+                stack.push(other);
+            }
+        }
+    }
+
+    let mut untils = BTreeSet::new();
+    for d in &done {
+        untils.extend(d.old.iter().filter(|f| matches!(f, Ltl::Until(..))));
+    }
+    let accepting = untils
+        .iter()
+        .map(|u| match u {
+            Ltl::Until(_, b) => done.iter().map(|d| !d.old.contains(u) || d.old.contains(b)).collect(),
+            _ => unreachable!(),
+        })
+        .collect();
+    let mut succ = vec![Vec::new(); done.len()];
+    let mut initial = Vec::new();
+    for (j, d) in done.iter().enumerate() {
+        for &i in &d.incoming {
+            if i == INIT {
+                initial.push(j);
+            } else {
+                succ[i].push(j);
+            }
+        }
+    }
+    let literals = done
+        .iter()
+        .map(|d| d.old.iter().filter_map(|f| match f {
+            Ltl::Lit(i, pos) => Some((*i, *pos)),
+            _ => None,
+        }).collect())
+        .collect();
+    Automaton { literals, initial, succ, accepting }
+}
+
+// `WF_v(A)` or `SF_v(A)` of the spec, checked on the components instead of
+// being part of the automaton.
+struct Fairness<'a> {
+    strong: bool,
+    enabled: Atom<'a>,
+    step: Atom<'a>,
+}
+
+/// Explored state graph: the states, and the successors of each state with
+/// the subaction that produced them.
+pub(super) struct Graph<'g, 'a> {
+    pub states: &'g [&'g [Value]],
+    pub initial: &'g [usize],
+    pub edges: &'g [Vec<(usize, Option<&'a str>)>],
+    // Name of the next-state action, for the steps without a subaction.
+    pub next: &'g str,
+}
+
+/// Searches for a behavior that satisfies the fairness formulas but not the
+/// property. The result is a lasso: the trace loops back to an earlier
+/// state. `Err` carries the state in which the evaluation failed.
+pub(super) fn check<'a>(
+    ev: &Evaluator<'a>,
+    graph: &Graph<'_, 'a>,
+    property: Temporal<'a>,
+    fairness: Vec<Temporal<'a>>,
+) -> Result<Option<Trace>, (Error, usize)> {
+    let mut conjuncts = Vec::new();
+    let mut fair = Vec::new();
+    let mut todo = fairness;
+    while let Some(t) = todo.pop() {
+        match t {
+            Temporal::And(items) => todo.extend(items),
+            Temporal::Fairness { strong, enabled, step } => fair.push(Fairness { strong, enabled, step }),
+            t => conjuncts.push(t),
+        }
+    }
+    conjuncts.push(Temporal::Not(Box::new(property)));
+    let mut atoms = Vec::new();
+    let formula = nnf(&Temporal::And(conjuncts), false, &mut atoms);
+    let aut = tableau(formula);
+    let mut search = Search {
+        ev,
+        graph,
+        aut: &aut,
+        atoms: &atoms,
+        fair: &fair,
+        nodes: Vec::new(),
+        ids: HashMap::new(),
+        succ: Vec::new(),
+        parent: Vec::new(),
+        cache: HashMap::new(),
+    };
+    search.product()?;
+    let all: Vec<usize> = (0..search.nodes.len()).collect();
+    let member = vec![true; all.len()];
+    let mut components = components(&search.succ, &all, &member);
+    // Nodes are numbered breadth-first, the components that are reached
+    // first give the shortest counterexamples.
+    components.sort_by_key(|c| c[0]);
+    for c in components {
+        if let Some(cycle) = search.accepting(c)? {
+            return Ok(Some(search.lasso(&cycle)));
+        }
+    }
+    Ok(None)
+}
+
+struct Search<'s, 'a> {
+    ev: &'s Evaluator<'a>,
+    graph: &'s Graph<'s, 'a>,
+    aut: &'s Automaton,
+    atoms: &'s [Atom<'a>],
+    fair: &'s [Fairness<'a>],
+    // Nodes of the product of the state graph and the automaton.
+    nodes: Vec<(usize, usize)>,
+    ids: HashMap<(usize, usize), usize>,
+    succ: Vec<Vec<usize>>,
+    // Node from which each node was reached first, for the shortest prefix.
+    parent: Vec<Option<usize>>,
+    // Values of the predicates by the predicate and the step.
+    cache: HashMap<(Pred, usize, usize), bool>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Pred {
+    Atom(usize),
+    Enabled(usize),
+    Step(usize),
+}
+
+impl<'s, 'a> Search<'s, 'a> {
+    fn holds(&mut self, pred: Pred, s: usize, t: usize) -> Result<bool, (Error, usize)> {
+        if let Some(&b) = self.cache.get(&(pred, s, t)) {
+            return Ok(b);
+        }
+        let atom = match pred {
+            Pred::Atom(i) => &self.atoms[i],
+            Pred::Enabled(i) => &self.fair[i].enabled,
+            Pred::Step(i) => &self.fair[i].step,
+        };
+        let states = self.graph.states;
+        let b = self.ev.holds(atom, states[s], states[t]).map_err(|e| (e, s))?;
+        self.cache.insert((pred, s, t), b);
+        Ok(b)
+    }
+
+    fn node(&mut self, s: usize, q: usize, parent: Option<usize>, queue: &mut VecDeque<usize>) -> usize {
+        if let Some(&id) = self.ids.get(&(s, q)) {
+            return id;
+        }
+        let id = self.nodes.len();
+        self.nodes.push((s, q));
+        self.ids.insert((s, q), id);
+        self.succ.push(Vec::new());
+        self.parent.push(parent);
+        queue.push_back(id);
+        id
+    }
+
+    // Explores the product breadth-first. Every state can stutter.
+    fn product(&mut self) -> Result<(), (Error, usize)> {
+        let mut queue = VecDeque::new();
+        for &s in self.graph.initial {
+            for &q in &self.aut.initial {
+                self.node(s, q, None, &mut queue);
+            }
+        }
+        while let Some(id) = queue.pop_front() {
+            let (s, q) = self.nodes[id];
+            let mut targets: Vec<usize> = self.graph.edges[s].iter().map(|e| e.0).collect();
+            targets.push(s);
+            targets.sort_unstable();
+            targets.dedup();
+            for t in targets {
+                let mut ok = true;
+                for &(i, pos) in &self.aut.literals[q] {
+                    if self.holds(Pred::Atom(i), s, t)? != pos {
+                        ok = false;
+                        break;
+                    }
+                }
+                if !ok {
+                    continue;
+                }
+                for k in 0..self.aut.succ[q].len() {
+                    let next = self.node(t, self.aut.succ[q][k], Some(id), &mut queue);
+                    self.succ[id].push(next);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // A cycle through the component that visits every acceptance set and
+    // satisfies the fairness conditions. Strong fairness that fails is
+    // retried without the states where the action is enabled.
+    fn accepting(&mut self, component: Vec<usize>) -> Result<Option<Vec<usize>>, (Error, usize)> {
+        let mut member = vec![false; self.nodes.len()];
+        for &u in &component {
+            member[u] = true;
+        }
+        let cyclic = component.len() > 1 || self.succ[component[0]].contains(&component[0]);
+        if !cyclic {
+            return Ok(None);
+        }
+        // Nodes and edges that the cycle has to pass.
+        let mut waypoints: Vec<(usize, Option<usize>)> = Vec::new();
+        for acc in &self.aut.accepting {
+            match component.iter().find(|&&u| acc[self.nodes[u].1]) {
+                Some(&u) => waypoints.push((u, None)),
+                None => return Ok(None),
+            }
+        }
+        let mut remove = Vec::new();
+        for k in 0..self.fair.len() {
+            let step = self.step_edge(&component, &member, k)?;
+            if let Some((u, v)) = step {
+                waypoints.push((u, Some(v)));
+                continue;
+            }
+            let mut enabled = Vec::new();
+            let mut disabled = None;
+            for &u in &component {
+                let s = self.nodes[u].0;
+                if self.holds(Pred::Enabled(k), s, s)? {
+                    enabled.push(u);
+                } else if disabled.is_none() {
+                    disabled = Some(u);
+                }
+            }
+            match disabled {
+                Some(u) if !self.fair[k].strong => waypoints.push((u, None)),
+                _ if enabled.is_empty() => {}
+                _ if self.fair[k].strong => remove.extend(enabled),
+                _ => return Ok(None),
+            }
+        }
+        if !remove.is_empty() {
+            for u in remove {
+                member[u] = false;
+            }
+            let rest: Vec<usize> = component.into_iter().filter(|&u| member[u]).collect();
+            for c in components(&self.succ, &rest, &member) {
+                if let Some(cycle) = self.accepting(c)? {
+                    return Ok(Some(cycle));
+                }
+            }
+            return Ok(None);
+        }
+        Ok(Some(self.cycle(&member, component[0], &waypoints)))
+    }
+
+    fn step_edge(&mut self, component: &[usize], member: &[bool], k: usize) -> Result<Option<(usize, usize)>, (Error, usize)> {
+        for &u in component {
+            for j in 0..self.succ[u].len() {
+                let v = self.succ[u][j];
+                if member[v] && self.holds(Pred::Step(k), self.nodes[u].0, self.nodes[v].0)? {
+                    return Ok(Some((u, v)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // Cycle from `start` through the waypoints back to `start`, inside of
+    // the component. Begins with `start` and doesn't repeat it at the end.
+    fn cycle(&self, member: &[bool], start: usize, waypoints: &[(usize, Option<usize>)]) -> Vec<usize> {
+        let mut cycle = vec![start];
+        for &(u, v) in waypoints {
+            let from = *cycle.last().unwrap();
+            cycle.extend(self.path(member, from, u).into_iter().skip(1));
+            if let Some(v) = v {
+                cycle.push(v);
+            }
+        }
+        // At least one step, so that the cycle is not empty.
+        let from = *cycle.last().unwrap();
+        let back = self.succ[from]
+            .iter()
+            .filter(|&&w| member[w])
+            .map(|&w| self.path(member, w, start))
+            .min_by_key(|p| p.len())
+            .unwrap_or_default();
+        cycle.extend(back);
+        cycle.pop();
+        cycle
+    }
+
+    // Shortest path inside of the component, including both ends.
+    fn path(&self, member: &[bool], from: usize, to: usize) -> Vec<usize> {
+        let mut prev = HashMap::new();
+        let mut queue = VecDeque::new();
+        prev.insert(from, from);
+        queue.push_back(from);
+        while let Some(u) = queue.pop_front() {
+            if u == to {
+                break;
+            }
+            for &w in &self.succ[u] {
+                if member[w] && !prev.contains_key(&w) {
+                    prev.insert(w, u);
+                    queue.push_back(w);
+                }
+            }
+        }
+        let mut path = vec![to];
+        let mut u = to;
+        while u != from {
+            u = prev[&u];
+            path.push(u);
+        }
+        path.reverse();
+        path
+    }
+
+    fn lasso(&self, cycle: &[usize]) -> Trace {
+        let mut nodes = vec![cycle[0]];
+        while let Some(p) = self.parent[*nodes.last().unwrap()] {
+            nodes.push(p);
+        }
+        nodes.reverse();
+        let start = nodes.len() - 1;
+        nodes.extend(&cycle[1..]);
+        // Steps of the tableau that stutter don't show in the behavior.
+        let mut states: Vec<usize> = Vec::new();
+        let mut loop_start = 0;
+        for (i, &u) in nodes.iter().enumerate() {
+            let s = self.nodes[u].0;
+            if states.last() != Some(&s) {
+                states.push(s);
+            }
+            if i == start {
+                loop_start = states.len() - 1;
+            }
+        }
+        if states.len() > loop_start + 1 && states.last() == Some(&states[loop_start]) {
+            states.pop();
+        }
+        // The loop may go around the same states several times, and end
+        // the way the prefix does.
+        let looped = &states[loop_start..];
+        if let Some(period) = (1..looped.len()).find(|&p| looped.chunks(p).all(|c| c == &looped[..p])) {
+            states.truncate(loop_start + period);
+        }
+        while loop_start > 0 && states[loop_start - 1] == *states.last().unwrap() {
+            states.pop();
+            loop_start -= 1;
+        }
+        let mut steps = Vec::new();
+        for (i, &s) in states.iter().enumerate() {
+            let action = if i == 0 { None } else { Some(self.action(states[i - 1], s)) };
+            steps.push(Step { action, state: self.graph.states[s].to_vec() });
+        }
+        let last = *states.last().unwrap();
+        let back = Loop { state: loop_start, action: self.action(last, states[loop_start]) };
+        Trace { variables: self.ev.variables().to_vec(), steps, back: Some(back) }
+    }
+
+    // Subaction of the step, "Stuttering" if the state is unchanged.
+    fn action(&self, s: usize, t: usize) -> String {
+        match self.graph.edges[s].iter().find(|e| e.0 == t) {
+            Some((_, Some(action))) => action.to_string(),
+            Some((_, None)) | None if s == t => "Stuttering".to_string(),
+            _ => self.graph.next.to_string(),
+        }
+    }
+}
+
+// Strongly connected components of the subgraph of `nodes`, by Tarjan's
+// algorithm without recursion. Nodes of each component are sorted.
+fn components(succ: &[Vec<usize>], nodes: &[usize], member: &[bool]) -> Vec<Vec<usize>> {
+    const NONE: usize = usize::MAX;
+    let mut index = vec![NONE; succ.len()];
+    let mut low = vec![0; succ.len()];
+    let mut on_stack = vec![false; succ.len()];
+    let mut stack = Vec::new();
+    let mut res = Vec::new();
+    let mut counter = 0;
+    for &root in nodes {
+        if index[root] != NONE {
+            continue;
+        }
+        let mut work = vec![(root, 0)];
+        index[root] = counter;
+        low[root] = counter;
+        counter += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        while let Some(&(v, i)) = work.last() {
+            if i < succ[v].len() {
+                work.last_mut().unwrap().1 += 1;
+                let w = succ[v][i];
+                if !member[w] {
+                    continue;
+                }
+                if index[w] == NONE {
+                    index[w] = counter;
+                    low[w] = counter;
+                    counter += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    work.push((w, 0));
+                } else if on_stack[w] {
+                    low[v] = low[v].min(index[w]);
+                }
+                continue;
+            }
+            work.pop();
+            if let Some(&(u, _)) = work.last() {
+                low[u] = low[u].min(low[v]);
+            }
+            if low[v] == index[v] {
+                let mut c = Vec::new();
+                while let Some(w) = stack.pop() {
+                    on_stack[w] = false;
+                    c.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                c.sort_unstable();
+                res.push(c);
+            }
+        }
+    }
+    res
+}
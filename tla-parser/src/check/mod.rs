@@ -0,0 +1,1123 @@
+mod fingerprints;
+mod graph;
+mod liveness;
+mod simulate;
+
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::{fmt, io, panic, thread};
+
+use self::fingerprints::Fingerprints;
+use crate::ast::{Module, Span};
+use crate::cfg::{self, Config};
+use crate::eval::{Error, Evaluator, Value};
+use crate::resolve::Modules;
+
+pub(crate) use self::graph::json_string;
+pub use self::graph::{Edge, StateGraph};
+pub use self::simulate::{simulate, Simulation};
+
+
+/// What to check, by the names of the definitions of the module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Model {
+    pub init: String,
+    pub next: String,
+    pub invariants: Vec<String>,
+    /// Temporal properties that every behavior must satisfy.
+    pub properties: Vec<String>,
+    /// Temporal formulas that the behaviors are assumed to satisfy, like
+    /// the fairness conditions of the spec.
+    pub fairness: Vec<String>,
+    /// State constraints: successors of the states that violate them are
+    /// not explored.
+    pub constraints: Vec<String>,
+    pub deadlock: bool,
+    /// SYMMETRY: set of permutations of model values. States that are
+    /// equal up to one of them are explored once.
+    pub symmetry: Option<String>,
+    /// VIEW: state function, the states with the same value of it are
+    /// explored once.
+    pub view: Option<String>,
+    /// Stops the search after this many distinct states.
+    pub max_states: Option<usize>,
+    /// Records the explored states and steps in `Outcome::graph`.
+    pub graph: bool,
+    /// Threads that explore the states.
+    pub workers: usize,
+    /// Fingerprints of the explored states that are kept in memory, the
+    /// rest are moved to files in `spill_dir`. All are kept if `None`.
+    pub fingerprint_memory: Option<usize>,
+    /// Directory for the fingerprint files, the temporary one if `None`.
+    pub spill_dir: Option<PathBuf>,
+}
+
+impl Default for Model {
+    fn default() -> Self {
+        Model {
+            init: "Init".to_string(),
+            next: "Next".to_string(),
+            invariants: Vec::new(),
+            properties: Vec::new(),
+            fairness: Vec::new(),
+            constraints: Vec::new(),
+            deadlock: true,
+            symmetry: None,
+            view: None,
+            max_states: None,
+            graph: false,
+            workers: 1,
+            fingerprint_memory: None,
+            spill_dir: None,
+        }
+    }
+}
+
+impl Model {
+    /// Takes the names in the sections of a TLC config: SPECIFICATION,
+    /// INIT, NEXT, INVARIANTS, PROPERTIES, CONSTRAINTS, SYMMETRY, VIEW and
+    /// CHECK_DEADLOCK. A SPECIFICATION `Init /\ [][Next]_vars /\ Fairness`
+    /// of the module gives the initial predicate, the next-state action
+    /// and the fairness. The values of the constants are given to the
+    /// evaluator by `Constant::assign`, ALIAS is ignored, and
+    /// ACTION_CONSTRAINTS are not supported.
+    pub fn configure(&mut self, config: &Config, module: &Module, modules: &dyn Modules) -> Result<(), String> {
+        if !config.action_constraints().is_empty() {
+            return Err("ACTION_CONSTRAINTS are not supported".to_string());
+        }
+        if let Some(spec) = config.specification() {
+            let (init, next, fairness) = cfg::behavior(spec, module, modules)?;
+            self.init = init;
+            self.next = next;
+            self.fairness.extend(fairness);
+        }
+        if let Some(init) = config.init() {
+            self.init = init.to_string();
+        }
+        if let Some(next) = config.next() {
+            self.next = next.to_string();
+        }
+        self.invariants.extend(config.invariants().into_iter().map(str::to_string));
+        self.properties.extend(config.properties().into_iter().map(str::to_string));
+        self.constraints.extend(config.constraints().into_iter().map(str::to_string));
+        if let Some(symmetry) = config.symmetry() {
+            self.symmetry = Some(symmetry.to_string());
+        }
+        if let Some(view) = config.view() {
+            self.view = Some(view.to_string());
+        }
+        if let Some(deadlock) = config.check_deadlock() {
+            self.deadlock = deadlock;
+        }
+        Ok(())
+    }
+}
+
+/// Behavior that leads to a violation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub variables: Vec<String>,
+    pub steps: Vec<Step>,
+    /// Step from the last state back to an earlier one, for the infinite
+    /// behaviors that violate temporal properties.
+    pub back: Option<Loop>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loop {
+    /// Index of the state in `Trace::steps`.
+    pub state: usize,
+    pub action: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    /// Subaction that produced the state, `None` for the initial state.
+    pub action: Option<String>,
+    /// Values of the variables.
+    pub state: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// ASSUME that is false, or fails to evaluate.
+    Assumption { span: Span, error: Option<Error> },
+    Invariant { name: String, trace: Trace },
+    /// Temporal property that is violated by the lasso-shaped trace.
+    Property { name: String, trace: Trace },
+    /// State without successors.
+    Deadlock { trace: Trace },
+    /// Evaluation failed in the last state of the trace, or before the
+    /// initial states are known if the trace is empty.
+    Error { error: Error, trace: Trace },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    /// States produced by the initial predicate and the next-state action.
+    pub generated: usize,
+    pub distinct: usize,
+    /// States that wait to be explored.
+    pub queue: usize,
+    /// Length of the longest shortest behavior to a state.
+    pub depth: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub stats: Stats,
+    /// The first violation found.
+    pub violation: Option<Violation>,
+    /// False if the search stopped at `Model::max_states`, or on an error.
+    pub complete: bool,
+    /// Failure of the fingerprint files that stopped the search, or of the
+    /// replay of the trace of the violation.
+    pub error: Option<String>,
+    /// The explored part of the state graph, if `Model::graph` is set.
+    pub graph: Option<StateGraph>,
+}
+
+// Explored state, with the one it was first reached from. The states
+// themselves are not kept, traces are found again by replaying the
+// actions.
+struct Node<'a> {
+    fp: u64,
+    parent: Option<usize>,
+    action: Option<&'a str>,
+}
+
+/// Explores the reachable states breadth-first and checks the ASSUME
+/// statements, the invariants and, if enabled, the absence of deadlocks.
+/// The counterexamples are the shortest ones. Temporal properties are
+/// checked on the state graph once it is complete.
+///
+/// The states of each depth are explored by `Model::workers` threads,
+/// which take the states from each other when they run out. With a
+/// SYMMETRY or a VIEW, the states that are the same for them are explored
+/// once, and the temporal properties are checked on the reduced graph.
+pub fn check(ev: &Evaluator, model: &Model) -> Outcome {
+    search(ev, model, None)
+}
+
+/// Same as `check`, and calls `progress` with the statistics of the search
+/// at most once in `every`, from the thread that notices it is time.
+pub fn check_with(ev: &Evaluator, model: &Model, every: Duration, progress: &(dyn Fn(&Stats) + Sync)) -> Outcome {
+    search(ev, model, Some(Progress { every, report: progress, last: Mutex::new(Instant::now()) }))
+}
+
+fn search<'e, 'a>(ev: &'e Evaluator<'a>, model: &'e Model, progress: Option<Progress<'e>>) -> Outcome {
+    let shards = if model.workers > 1 { 64 } else { 1 };
+    let mut search = Search {
+        ev,
+        model,
+        nodes: Vec::new(),
+        seen: Fingerprints::new(shards, model.fingerprint_memory, model.spill_dir.as_deref()),
+        symmetry: Vec::new(),
+        initial: Vec::new(),
+        edges: Vec::new(),
+        states: Vec::new(),
+        counters: Counters::default(),
+        progress,
+        stop: AtomicBool::new(false),
+        limited: AtomicBool::new(false),
+        untraced: AtomicBool::new(false),
+        error: None,
+    };
+    let mut violation = search.run();
+    let complete = !search.limited.load(Ordering::Relaxed) && search.error.is_none();
+    if violation.is_none() && complete {
+        violation = search.properties();
+    }
+    if search.untraced.load(Ordering::Relaxed) {
+        search.error = Some("could not reconstruct the trace by replaying the actions, it ends early".to_string());
+    }
+    let graph = if model.graph { Some(search.state_graph()) } else { None };
+    Outcome { stats: search.stats(), violation, complete, error: search.error, graph }
+}
+
+struct Search<'e, 'a> {
+    ev: &'e Evaluator<'a>,
+    model: &'e Model,
+    nodes: Vec<Node<'a>>,
+    seen: Fingerprints,
+    symmetry: Vec<BTreeMap<Value, Value>>,
+    // The state graph, recorded if there are temporal properties or it is
+    // requested. Edges
+    // lead to the fingerprints of the states, as the new states of a depth
+    // are numbered after all of them are found.
+    initial: Vec<u64>,
+    edges: Vec<(usize, u64, Option<&'a str>)>,
+    states: Vec<Vec<Value>>,
+    counters: Counters,
+    progress: Option<Progress<'e>>,
+    // Set when the workers have to stop, and when they stopped at
+    // `Model::max_states`.
+    stop: AtomicBool,
+    limited: AtomicBool,
+    // Set when the replay of a trace fails, and it ends early.
+    untraced: AtomicBool,
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct Counters {
+    generated: AtomicUsize,
+    distinct: AtomicUsize,
+    queue: AtomicUsize,
+    depth: AtomicUsize,
+}
+
+struct Progress<'p> {
+    every: Duration,
+    report: &'p (dyn Fn(&Stats) + Sync),
+    last: Mutex<Instant>,
+}
+
+// State to explore, with its position among the states of its depth and
+// its node.
+type Item = (usize, usize, Vec<Value>);
+
+// Order of the successors in a sequential search: the position of the
+// explored state, and the successor, 0 for the state itself.
+type Key = (usize, usize);
+
+// Edges from a node of the graph, with their subactions.
+type Edges<'a> = Vec<(usize, Option<&'a str>)>;
+
+// New states and graph edges found by a worker.
+#[derive(Default)]
+struct Output<'a> {
+    new: Vec<(Key, Successor<'a>)>,
+    edges: Vec<(Key, usize, u64, Option<&'a str>)>,
+}
+
+struct Successor<'a> {
+    // None for the initial states.
+    parent: Option<usize>,
+    action: Option<&'a str>,
+    state: Vec<Value>,
+    fp: u64,
+    // False if the state violates a constraint.
+    explore: bool,
+}
+
+enum Found<'a> {
+    // Violation in the state of the node, or in its successors.
+    Node(usize, Fault),
+    // Violation in a new state, or in its fingerprint.
+    Successor(Successor<'a>, Fault),
+    Io(io::Error),
+}
+
+enum Fault {
+    Deadlock,
+    Invariant(String),
+    Error(Error),
+}
+
+impl<'e, 'a> Search<'e, 'a> {
+    fn run(&mut self) -> Option<Violation> {
+        if let Some(v) = assumptions(self.ev) {
+            return Some(v);
+        }
+        if let Some(name) = &self.model.symmetry {
+            match self.ev.permutations(name) {
+                Ok(perms) => self.symmetry = perms,
+                Err(error) => return Some(Violation::Error { error, trace: self.trace(None) }),
+            }
+        }
+        let mut initial = Vec::new();
+        if let Err(error) = self.ev.initial_states(&self.model.init, &mut |s| {
+            initial.push(s);
+            true
+        }) {
+            return Some(Violation::Error { error, trace: self.trace(None) });
+        }
+        let graph = self.model.graph || !self.model.properties.is_empty();
+        let mut frontier = Vec::new();
+        for state in initial {
+            self.counters.generated.fetch_add(1, Ordering::Relaxed);
+            let fp = match self.fingerprint(&state) {
+                Ok(fp) => fp,
+                Err(error) => {
+                    let s = Successor { parent: None, action: None, state, fp: 0, explore: false };
+                    return self.violation(Found::Successor(s, Fault::Error(error)));
+                }
+            };
+            if graph {
+                self.initial.push(fp);
+            }
+            match self.seen.insert(fp) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(err) => return self.violation(Found::Io(err)),
+            }
+            let i = self.nodes.len();
+            self.nodes.push(Node { fp, parent: None, action: None });
+            self.counters.distinct.fetch_add(1, Ordering::Relaxed);
+            self.counters.depth.store(1, Ordering::Relaxed);
+            if graph {
+                self.states.push(state.clone());
+            }
+            match check_state(self.ev, self.model, &state) {
+                Ok(true) => frontier.push((frontier.len(), i, state)),
+                Ok(false) => {}
+                Err(fault) => return self.violation(Found::Node(i, fault)),
+            }
+        }
+        while !frontier.is_empty() {
+            let (mut output, found) = self.level(frontier);
+            if let Some(found) = found {
+                return self.violation(found);
+            }
+            if self.limited.load(Ordering::Relaxed) {
+                return None;
+            }
+            output.new.sort_by_key(|s| s.0);
+            output.edges.sort_by_key(|e| e.0);
+            frontier = Vec::new();
+            for (_, s) in output.new {
+                let i = self.nodes.len();
+                self.nodes.push(Node { fp: s.fp, parent: s.parent, action: s.action });
+                if graph {
+                    self.states.push(s.state.clone());
+                }
+                if s.explore {
+                    frontier.push((frontier.len(), i, s.state));
+                }
+            }
+            self.edges.extend(output.edges.into_iter().map(|(_, i, fp, action)| (i, fp, action)));
+            if !frontier.is_empty() {
+                self.counters.depth.fetch_add(1, Ordering::Relaxed);
+            }
+            self.tick();
+        }
+        None
+    }
+
+    // Explores the states of a depth, the result is the union of the
+    // outputs of the workers and the first violation.
+    fn level(&self, frontier: Vec<Item>) -> (Output<'a>, Option<Found<'a>>) {
+        let workers = self.model.workers.max(1).min(frontier.len());
+        self.counters.queue.store(frontier.len(), Ordering::Relaxed);
+        let mut queues: Vec<Mutex<VecDeque<Item>>> = (0..workers).map(|_| Mutex::new(VecDeque::new())).collect();
+        for (k, item) in frontier.into_iter().enumerate() {
+            queues[k % workers].get_mut().unwrap().push_back(item);
+        }
+        let found = Mutex::new(None);
+        let outputs = if workers == 1 {
+            vec![self.work(0, &queues, &found)]
+        } else {
+            let (queues, found) = (&queues, &found);
+            thread::scope(|s| {
+                let handles: Vec<_> = (0..workers).map(|w| s.spawn(move || self.work(w, queues, found))).collect();
+                handles
+                    .into_iter()
+                    .map(|h| h.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+                    .collect::<Vec<_>>()
+            })
+        };
+        let mut output = Output::default();
+        for o in outputs {
+            output.new.extend(o.new);
+            output.edges.extend(o.edges);
+        }
+        (output, found.into_inner().unwrap().map(|(_, f)| f))
+    }
+
+    // Explores the states from the queue of the worker, and from the other
+    // queues when it is empty.
+    fn work(&self, w: usize, queues: &[Mutex<VecDeque<Item>>], found: &Mutex<Option<(Key, Found<'a>)>>) -> Output<'a> {
+        let graph = self.model.graph || !self.model.properties.is_empty();
+        let report = |key: Key, f: Found<'a>| {
+            let mut found = found.lock().unwrap();
+            if !matches!(&*found, Some((k, _)) if *k < key) {
+                *found = Some((key, f));
+            }
+            self.stop.store(true, Ordering::Relaxed);
+        };
+        let mut output = Output::default();
+        'items: while let Some((pos, i, state)) = take(w, queues) {
+            self.counters.queue.fetch_sub(1, Ordering::Relaxed);
+            if self.stop.load(Ordering::Relaxed) {
+                break;
+            }
+            if let Some(max) = self.model.max_states {
+                if self.counters.distinct.load(Ordering::Relaxed) >= max {
+                    self.limited.store(true, Ordering::Relaxed);
+                    self.stop.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+            let mut successors = Vec::new();
+            if let Err(error) = self.ev.successors(&self.model.next, &state, &mut |s, action| {
+                successors.push((s, action));
+                true
+            }) {
+                report((pos, 0), Found::Node(i, Fault::Error(error)));
+                break;
+            }
+            if self.model.deadlock && successors.is_empty() {
+                report((pos, 0), Found::Node(i, Fault::Deadlock));
+                break;
+            }
+            for (j, (state, action)) in successors.into_iter().enumerate() {
+                let key = (pos, j + 1);
+                self.counters.generated.fetch_add(1, Ordering::Relaxed);
+                let mut s = Successor { parent: Some(i), action, state, fp: 0, explore: false };
+                let fp = match self.fingerprint(&s.state) {
+                    Ok(fp) => fp,
+                    Err(error) => {
+                        report(key, Found::Successor(s, Fault::Error(error)));
+                        break 'items;
+                    }
+                };
+                s.fp = fp;
+                if graph {
+                    output.edges.push((key, i, fp, action));
+                }
+                match self.seen.insert(fp) {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(err) => {
+                        report(key, Found::Io(err));
+                        break 'items;
+                    }
+                }
+                self.counters.distinct.fetch_add(1, Ordering::Relaxed);
+                match check_state(self.ev, self.model, &s.state) {
+                    Ok(explore) => {
+                        if explore {
+                            self.counters.queue.fetch_add(1, Ordering::Relaxed);
+                        }
+                        s.explore = explore;
+                        output.new.push((key, s));
+                    }
+                    Err(fault) => {
+                        report(key, Found::Successor(s, fault));
+                        break 'items;
+                    }
+                }
+            }
+            self.tick();
+        }
+        output
+    }
+
+    fn violation(&mut self, found: Found<'a>) -> Option<Violation> {
+        let (trace, fault) = match found {
+            Found::Node(i, fault) => (self.trace(Some(i)), fault),
+            Found::Successor(s, fault) => {
+                let mut trace = self.trace(s.parent);
+                let action = s.parent.map(|_| s.action.unwrap_or(&self.model.next).to_string());
+                trace.steps.push(Step { action, state: s.state });
+                (trace, fault)
+            }
+            Found::Io(err) => {
+                self.error = Some(format!("fingerprint files: {}", err));
+                return None;
+            }
+        };
+        Some(fault.violation(trace))
+    }
+
+    fn properties(&self) -> Option<Violation> {
+        if self.model.properties.is_empty() {
+            return None;
+        }
+        let (initial, edges) = self.graph();
+        let states: Vec<&[Value]> = self.states.iter().map(|s| s.as_slice()).collect();
+        let graph = liveness::Graph { states: &states, initial: &initial, edges: &edges, next: &self.model.next };
+        let mut fairness = Vec::new();
+        for name in &self.model.fairness {
+            match self.ev.temporal(name) {
+                Ok(t) => fairness.push(t),
+                Err(error) => return Some(Violation::Error { error, trace: self.trace(None) }),
+            }
+        }
+        for name in &self.model.properties {
+            let property = match self.ev.temporal(name) {
+                Ok(t) => t,
+                Err(error) => return Some(Violation::Error { error, trace: self.trace(None) }),
+            };
+            match liveness::check(self.ev, &graph, property, fairness.clone()) {
+                Ok(None) => {}
+                Ok(Some(trace)) => return Some(Violation::Property { name: name.clone(), trace }),
+                Err((error, i)) => return Some(Violation::Error { error, trace: self.trace(Some(i)) }),
+            }
+        }
+        None
+    }
+
+    // Initial nodes and the edges from each node of the recorded graph.
+    fn graph(&self) -> (Vec<usize>, Vec<Edges<'a>>) {
+        let index: HashMap<u64, usize> = self.nodes.iter().enumerate().map(|(i, n)| (n.fp, i)).collect();
+        let initial = self.initial.iter().filter_map(|fp| index.get(fp).copied()).collect();
+        let mut edges = vec![Vec::new(); self.nodes.len()];
+        for &(i, fp, action) in &self.edges {
+            if let Some(&j) = index.get(&fp) {
+                edges[i].push((j, action));
+            }
+        }
+        (initial, edges)
+    }
+
+    fn state_graph(&self) -> StateGraph {
+        let (mut initial, edges) = self.graph();
+        let mut seen = HashSet::new();
+        initial.retain(|&i| seen.insert(i));
+        let mut seen = HashSet::new();
+        let edges = edges
+            .into_iter()
+            .enumerate()
+            .flat_map(|(from, out)| out.into_iter().map(move |(to, action)| (from, to, action)))
+            .filter(|&e| seen.insert(e))
+            .map(|(from, to, action)| Edge { from, to, action: action.unwrap_or(&self.model.next).to_string() })
+            .collect();
+        StateGraph { variables: self.ev.variables().to_vec(), states: self.states.clone(), initial, edges }
+    }
+
+    // Behavior from an initial state to the node: the recorded states, or
+    // else found again by replaying the actions along the fingerprints of
+    // its states. With a VIEW or a SYMMETRY several states have the same
+    // fingerprint and only some of them lead on to the node, so the replay
+    // backtracks. If it fails anyway the trace ends early and
+    // `Search::untraced` is set.
+    fn trace(&self, mut node: Option<usize>) -> Trace {
+        let mut path = Vec::new();
+        while let Some(i) = node {
+            path.push(i);
+            node = self.nodes[i].parent;
+        }
+        path.reverse();
+        let action = |k: usize| match k {
+            0 => None,
+            _ => Some(self.nodes[path[k]].action.unwrap_or(&self.model.next).to_string()),
+        };
+        let variables = self.ev.variables().to_vec();
+        if path.iter().all(|&i| i < self.states.len()) {
+            let steps = path.iter().enumerate().map(|(k, &i)| Step { action: action(k), state: self.states[i].clone() });
+            return Trace { variables, steps: steps.collect(), back: None };
+        }
+        let mut steps: Vec<Step> = Vec::new();
+        // States with the fingerprints of the nodes of the path that are
+        // not tried yet, for each of the steps and the next one.
+        let mut pending: Vec<Vec<Vec<Value>>> = Vec::new();
+        let mut longest = Vec::new();
+        while steps.len() < path.len() {
+            if pending.len() == steps.len() {
+                pending.push(self.replay(path[steps.len()], steps.last()));
+            }
+            match pending.last_mut().and_then(Vec::pop) {
+                Some(state) => steps.push(Step { action: action(steps.len()), state }),
+                None => {
+                    if steps.len() > longest.len() {
+                        longest = steps.clone();
+                    }
+                    pending.pop();
+                    if steps.pop().is_none() {
+                        self.untraced.store(true, Ordering::Relaxed);
+                        steps = longest;
+                        break;
+                    }
+                }
+            }
+        }
+        Trace { variables, steps, back: None }
+    }
+
+    // States with the fingerprint of the node, among the initial states or
+    // the successors of the state, in the reverse order they are produced.
+    fn replay(&self, node: usize, state: Option<&Step>) -> Vec<Vec<Value>> {
+        let fp = self.nodes[node].fp;
+        // Without a VIEW and a SYMMETRY only the state itself matches.
+        let unique = self.model.view.is_none() && self.symmetry.is_empty();
+        let mut found: Vec<Vec<Value>> = Vec::new();
+        let mut matches = |s: Vec<Value>| {
+            if self.fingerprint(&s).ok() == Some(fp) && !found.contains(&s) {
+                found.push(s);
+            }
+            !unique || found.is_empty()
+        };
+        let res = match state {
+            None => self.ev.initial_states(&self.model.init, &mut |s| matches(s)),
+            Some(last) => self.ev.successors(&self.model.next, &last.state, &mut |s, _| matches(s)),
+        };
+        if res.is_err() {
+            return Vec::new();
+        }
+        found.reverse();
+        found
+    }
+
+    // Fingerprint of the state, or of its view, that is the same for the
+    // states that are equal up to the symmetry: the least of the permuted
+    // values is hashed.
+    fn fingerprint(&self, state: &[Value]) -> Result<u64, Error> {
+        let view = match &self.model.view {
+            Some(name) => Cow::Owned(vec![self.ev.state_function(name, state)?]),
+            None => Cow::Borrowed(state),
+        };
+        let canonical = self.symmetry.iter().map(|p| view.iter().map(|v| permute(v, p)).collect::<Vec<_>>()).min();
+        Ok(match canonical {
+            Some(c) if c.as_slice() < view.as_ref() => fingerprint(&c),
+            _ => fingerprint(&view),
+        })
+    }
+
+    fn stats(&self) -> Stats {
+        Stats {
+            generated: self.counters.generated.load(Ordering::Relaxed),
+            distinct: self.counters.distinct.load(Ordering::Relaxed),
+            queue: self.counters.queue.load(Ordering::Relaxed),
+            depth: self.counters.depth.load(Ordering::Relaxed),
+        }
+    }
+
+    // Reports the progress if it is time.
+    fn tick(&self) {
+        if let Some(p) = &self.progress {
+            if let Ok(mut last) = p.last.try_lock() {
+                if last.elapsed() >= p.every {
+                    *last = Instant::now();
+                    (p.report)(&self.stats());
+                }
+            }
+        }
+    }
+}
+
+// The first ASSUME that is false, or fails to evaluate.
+fn assumptions(ev: &Evaluator) -> Option<Violation> {
+    for (span, res) in ev.assumptions() {
+        match res {
+            Ok(true) => {}
+            Ok(false) => return Some(Violation::Assumption { span, error: None }),
+            Err(error) => return Some(Violation::Assumption { span, error: Some(error) }),
+        }
+    }
+    None
+}
+
+// Checks a new state, the result is false if its successors are not
+// explored.
+fn check_state(ev: &Evaluator, model: &Model, state: &[Value]) -> Result<bool, Fault> {
+    for name in &model.invariants {
+        match ev.predicate(name, state) {
+            Ok(true) => {}
+            Ok(false) => return Err(Fault::Invariant(name.clone())),
+            Err(error) => return Err(Fault::Error(error)),
+        }
+    }
+    for name in &model.constraints {
+        match ev.predicate(name, state) {
+            Ok(true) => {}
+            Ok(false) => return Ok(false),
+            Err(error) => return Err(Fault::Error(error)),
+        }
+    }
+    Ok(true)
+}
+
+impl Fault {
+    fn violation(self, trace: Trace) -> Violation {
+        match self {
+            Fault::Deadlock => Violation::Deadlock { trace },
+            Fault::Invariant(name) => Violation::Invariant { name, trace },
+            Fault::Error(error) => Violation::Error { error, trace },
+        }
+    }
+}
+
+// Next state for the worker, from the front of its queue, or from the back
+// half of another one.
+fn take(w: usize, queues: &[Mutex<VecDeque<Item>>]) -> Option<Item> {
+    if let Some(item) = queues[w].lock().unwrap().pop_front() {
+        return Some(item);
+    }
+    for k in 1..queues.len() {
+        let mut stolen = {
+            let mut other = queues[(w + k) % queues.len()].lock().unwrap();
+            let n = other.len();
+            other.split_off(n / 2)
+        };
+        if let Some(item) = stolen.pop_front() {
+            queues[w].lock().unwrap().extend(stolen);
+            return Some(item);
+        }
+    }
+    None
+}
+
+// Value with the model values replaced by the permutation.
+fn permute(v: &Value, p: &BTreeMap<Value, Value>) -> Value {
+    match v {
+        Value::Model(_) => p.get(v).unwrap_or(v).clone(),
+        Value::Set(s) => Value::set(s.iter().map(|x| permute(x, p))),
+        Value::Seq(s) => Value::seq(s.iter().map(|x| permute(x, p))),
+        Value::Record(r) => Value::record(r.iter().map(|(k, x)| (k.clone(), permute(x, p)))),
+        Value::Fun(f) => Value::function(f.iter().map(|(k, x)| (permute(k, p), permute(x, p)))),
+        _ => v.clone(),
+    }
+}
+
+/// Hash of the state that identifies it during the search.
+pub fn fingerprint(state: &[Value]) -> u64 {
+    let mut h = DefaultHasher::new();
+    state.hash(&mut h);
+    h.finish()
+}
+
+impl fmt::Display for Trace {
+    /// Formats the trace as TLC does.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let action = step.action.as_deref().unwrap_or("Initial predicate");
+            writeln!(f, "State {}: <{}>", i + 1, action)?;
+            for (name, v) in self.variables.iter().zip(&step.state) {
+                writeln!(f, "/\\ {} = {}", name, v)?;
+            }
+        }
+        match &self.back {
+            Some(back) if back.state + 1 == self.steps.len() && back.action == "Stuttering" => {
+                write!(f, "\nState {}: Stuttering\n", self.steps.len() + 1)
+            }
+            Some(back) => write!(
+                f,
+                "\nState {}: Back to state {}: <{}>\n",
+                self.steps.len() + 1,
+                back.state + 1,
+                back.action,
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::Assumption { span, error: None } => {
+                write!(f, "{}:{}: assumption is false", span.start.line, span.start.col)
+            }
+            Violation::Assumption { error: Some(error), .. } => write!(f, "{}", error),
+            Violation::Invariant { name, trace } => write!(f, "invariant {} is violated\n{}", name, trace),
+            Violation::Property { name, trace } => write!(f, "temporal property {} is violated\n{}", name, trace),
+            Violation::Deadlock { trace } => write!(f, "deadlock reached\n{}", trace),
+            Violation::Error { error, trace } => write!(f, "{}\n{}", error, trace),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::resolve::resolve;
+
+    fn run(spec: &str, model: &Model) -> Outcome {
+        let code = format!("---- MODULE M ----\nEXTENDS Naturals, Sequences\n{}\n====", spec);
+        let module = parse(&code).unwrap();
+        let res = resolve(&module);
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let ev = Evaluator::new(&module, &res);
+        check(&ev, model)
+    }
+
+    const DIE_HARD: &str = "\
+VARIABLES big, small
+Init == big = 0 /\\ small = 0
+FillSmall == small' = 3 /\\ big' = big
+FillBig == big' = 5 /\\ small' = small
+EmptySmall == small' = 0 /\\ big' = big
+EmptyBig == big' = 0 /\\ small' = small
+SmallToBig == IF big + small =< 5
+                THEN big' = big + small /\\ small' = 0
+                ELSE big' = 5 /\\ small' = small - (5 - big)
+BigToSmall == IF big + small =< 3
+                THEN big' = 0 /\\ small' = big + small
+                ELSE big' = big - (3 - small) /\\ small' = 3
+Next == FillSmall \\/ FillBig \\/ EmptySmall \\/ EmptyBig \\/ SmallToBig \\/ BigToSmall
+TypeOK == big \\in 0..5 /\\ small \\in 0..3
+NotSolved == big /= 4";
+
+    #[test]
+    fn invariants() {
+        let model = Model { invariants: vec!["TypeOK".to_string()], ..Model::default() };
+        let outcome = run(DIE_HARD, &model);
+        assert_eq!(outcome.violation, None);
+        assert_eq!(outcome.stats.distinct, 16);
+        assert!(outcome.complete);
+
+        let model = Model { invariants: vec!["TypeOK".to_string(), "NotSolved".to_string()], ..Model::default() };
+        let outcome = run(DIE_HARD, &model);
+        let trace = match outcome.violation {
+            Some(Violation::Invariant { name, trace }) if name == "NotSolved" => trace,
+            v => panic!("{:?}", v),
+        };
+        assert_eq!(trace.steps.len(), 7);
+        assert_eq!(trace.steps[1].action.as_deref(), Some("FillBig"));
+        assert!(trace.to_string().ends_with("State 7: <BigToSmall>\n/\\ big = 4\n/\\ small = 3\n"));
+    }
+
+    #[test]
+    fn workers() {
+        let model = Model {
+            invariants: vec!["TypeOK".to_string(), "NotSolved".to_string()],
+            workers: 4,
+            fingerprint_memory: Some(4),
+            ..Model::default()
+        };
+        let outcome = run(DIE_HARD, &model);
+        match outcome.violation {
+            Some(Violation::Invariant { trace, .. }) => assert_eq!(trace.steps.len(), 7),
+            v => panic!("{:?}", v),
+        }
+
+        let spec = "\
+            VARIABLES x, y\n\
+            Init == x = 0 /\\ y = 0\n\
+            Next == (x < 30 /\\ x' = x + 1 /\\ y' = y) \\/ (y < 30 /\\ y' = y + 1 /\\ x' = x)\n\
+            Small == x + y < 60";
+        let code = format!("---- MODULE M ----\nEXTENDS Naturals\n{}\n====", spec);
+        let module = parse(&code).unwrap();
+        let res = resolve(&module);
+        let ev = Evaluator::new(&module, &res);
+        let model = Model { workers: 4, fingerprint_memory: Some(64), deadlock: false, ..Model::default() };
+        let reports = Mutex::new(Vec::new());
+        let outcome = check_with(&ev, &model, Duration::from_secs(0), &|stats| reports.lock().unwrap().push(stats.clone()));
+        assert_eq!(outcome.violation, None);
+        assert_eq!(outcome.stats, Stats { generated: 1861, distinct: 961, queue: 0, depth: 61 });
+        assert!(!reports.into_inner().unwrap().is_empty());
+
+        let model = Model { invariants: vec!["Small".to_string()], ..model };
+        match check(&ev, &model).violation {
+            Some(Violation::Invariant { trace, .. }) => assert_eq!(trace.steps.len(), 61),
+            v => panic!("{:?}", v),
+        }
+    }
+
+    #[test]
+    fn symmetry() {
+        let code = "---- MODULE M ----\n\
+            EXTENDS Naturals, FiniteSets, TLC\n\
+            CONSTANT Procs\n\
+            VARIABLE done\n\
+            Init == done = {}\n\
+            Next == \\E p \\in Procs \\ done : done' = done \\cup {p}\n\
+            Sym == Permutations(Procs)\n\
+            Size == Cardinality(done)\n\
+            Some == Size < 3\n\
+            Bad == {Procs}\n\
+            ====";
+        let module = parse(code).unwrap();
+        let res = resolve(&module);
+        let mut ev = Evaluator::new(&module, &res);
+        ev.set_constant("Procs", Value::set(vec![Value::model("p1"), Value::model("p2"), Value::model("p3")]));
+        let model = Model { deadlock: false, ..Model::default() };
+        assert_eq!(check(&ev, &model).stats.distinct, 8);
+        let model = Model { symmetry: Some("Sym".to_string()), ..model };
+        assert_eq!(check(&ev, &model).stats.distinct, 4);
+        let model = Model { symmetry: None, view: Some("Size".to_string()), ..model };
+        assert_eq!(check(&ev, &model).stats.distinct, 4);
+
+        let model = Model { symmetry: Some("Sym".to_string()), invariants: vec!["Some".to_string()], ..model };
+        let trace = match check(&ev, &model).violation {
+            Some(Violation::Invariant { trace, .. }) => trace,
+            v => panic!("{:?}", v),
+        };
+        let states: Vec<_> = trace.steps.iter().map(|s| s.state[0].to_string()).collect();
+        assert_eq!(states, vec!["{}", "{p1}", "{p1, p2}", "{p1, p2, p3}"]);
+
+        let model = Model { symmetry: None, view: Some("Size".to_string()), ..model };
+        for graph in [false, true].iter() {
+            let outcome = check(&ev, &Model { graph: *graph, ..model.clone() });
+            match outcome.violation {
+                Some(Violation::Invariant { trace, .. }) => assert_eq!(trace.steps.len(), 4),
+                v => panic!("{:?}", v),
+            }
+            assert_eq!(outcome.error, None);
+        }
+
+        let config = crate::cfg::parse("CONSTANTS Procs = {p1, p2, p3} SYMMETRY Sym CHECK_DEADLOCK FALSE").unwrap();
+        let mut ev = Evaluator::new(&module, &res);
+        let mut model = Model::default();
+        model.configure(&config, &module, &Vec::new()).unwrap();
+        for c in config.constants() {
+            c.assign(&mut ev).unwrap();
+        }
+        assert_eq!(check(&ev, &model).stats.distinct, 4);
+        let config = crate::cfg::parse("CONSTANT Procs <- Bad SPECIFICATION Spec").unwrap();
+        assert!(model.configure(&config, &module, &Vec::new()).is_err());
+        config.constants().next().unwrap().assign(&mut ev).unwrap();
+        let procs = Value::set(vec![Value::model("p1"), Value::model("p2"), Value::model("p3")]);
+        assert_eq!(ev.constant("Procs"), Some(&Value::set(vec![procs])));
+
+        let model = Model { symmetry: Some("Bad".to_string()), ..Model::default() };
+        match check(&ev, &model).violation {
+            Some(Violation::Error { error: Error::Type { .. }, .. }) => {}
+            v => panic!("{:?}", v),
+        }
+    }
+
+    #[test]
+    fn graph() {
+        let spec = "\
+            VARIABLE x\n\
+            Init == x \\in {0, 1}\n\
+            Up == x < 2 /\\ x' = x + 1\n\
+            Down == x > 0 /\\ x' = x - 1\n\
+            Next == Up \\/ Down";
+        let model = Model { graph: true, ..Model::default() };
+        let graph = run(spec, &model).graph.unwrap();
+        let states: Vec<_> = graph.states.iter().map(|s| s[0].to_string()).collect();
+        assert_eq!(states, vec!["0", "1", "2"]);
+        assert_eq!(graph.initial, vec![0, 1]);
+        let edges: Vec<_> = graph.edges.iter().map(|e| (e.from, e.to, e.action.as_str())).collect();
+        assert_eq!(edges, vec![(0, 1, "Up"), (1, 2, "Up"), (1, 0, "Down"), (2, 1, "Down")]);
+        assert_eq!(run(spec, &Model::default()).graph, None);
+    }
+
+    #[test]
+    fn deadlock() {
+        let spec = "\
+            VARIABLE x\n\
+            Init == x \\in {0, 1}\n\
+            Next == \\E d \\in {1, 2} : x + d < 4 /\\ x' = x + d";
+        let outcome = run(spec, &Model::default());
+        let trace = match outcome.violation {
+            Some(Violation::Deadlock { trace }) => trace,
+            v => panic!("{:?}", v),
+        };
+        let states: Vec<_> = trace.steps.iter().map(|s| s.state[0].to_string()).collect();
+        assert_eq!(states, vec!["1", "3"]);
+
+        let model = Model { deadlock: false, ..Model::default() };
+        assert_eq!(run(spec, &model).violation, None);
+    }
+
+    #[test]
+    fn actions() {
+        let spec = "\
+            VARIABLES q, n\n\
+            vars == <<q, n>>\n\
+            Init == q = <<>> /\\ n \\in 0..1\n\
+            Send == n < 3 /\\ n' = n + 1 /\\ q' = Append(q, n)\n\
+            Recv == q /= <<>> /\\ q' = Tail(q) /\\ UNCHANGED n\n\
+            Next == [Send \\/ (ENABLED Recv /\\ Recv)]_vars\n\
+            Short == Len(q) <= 3";
+        let model = Model { invariants: vec!["Short".to_string()], deadlock: false, ..Model::default() };
+        let outcome = run(spec, &model);
+        assert_eq!(outcome.violation, None);
+        assert_eq!(outcome.stats.distinct, 10);
+
+        let outcome = run("VARIABLES x, y\nInit == x = 0 /\\ y = 0\nNext == x' = 1", &Model::default());
+        match outcome.violation {
+            Some(Violation::Error { error, trace }) => {
+                assert_eq!(error.to_string(), "5:1: variable `y'` has no value");
+                assert_eq!(trace.steps.len(), 1);
+            }
+            v => panic!("{:?}", v),
+        }
+    }
+
+    #[test]
+    fn liveness() {
+        let spec = "\
+            VARIABLE x\n\
+            Init == x = 0\n\
+            Up == x' = (x + 1) % 3\n\
+            Next == Up\n\
+            Fair == WF_x(Up)\n\
+            Often == []<>(x = 2)\n\
+            Leads == x = 1 ~> x = 0\n\
+            Settles == <>[](x = 0)";
+        let model = |property: &str, fair: bool| Model {
+            properties: vec![property.to_string()],
+            fairness: if fair { vec!["Fair".to_string()] } else { vec![] },
+            ..Model::default()
+        };
+        let trace = match run(spec, &model("Often", false)).violation {
+            Some(Violation::Property { trace, .. }) => trace,
+            v => panic!("{:?}", v),
+        };
+        assert_eq!(trace.to_string(), "State 1: <Initial predicate>\n/\\ x = 0\n\nState 2: Stuttering\n");
+        assert_eq!(run(spec, &model("Often", true)).violation, None);
+        assert_eq!(run(spec, &model("Leads", true)).violation, None);
+        let trace = match run(spec, &model("Settles", true)).violation {
+            Some(Violation::Property { trace, .. }) => trace,
+            v => panic!("{:?}", v),
+        };
+        assert_eq!(trace.steps.len(), 3);
+        assert_eq!(trace.back, Some(Loop { state: 0, action: "Up".to_string() }));
+        assert!(trace.to_string().ends_with("State 4: Back to state 1: <Up>\n"));
+
+        let code = format!(
+            "---- MODULE M ----\nEXTENDS Naturals\n{}\n\
+             Spec == Init /\\ [][Next]_x\n\
+             FairSpec == Spec /\\ Fair\n\
+             Inline == x = 0 /\\ [][Next]_x\n\
+             ====",
+            spec,
+        );
+        let module = parse(&code).unwrap();
+        let res = resolve(&module);
+        let ev = Evaluator::new(&module, &res);
+        let configured = |config: &str| {
+            let mut model = Model::default();
+            model.configure(&crate::cfg::parse(config).unwrap(), &module, &Vec::new()).map(|_| model)
+        };
+        let model = configured("SPECIFICATION FairSpec PROPERTY Often").unwrap();
+        assert_eq!((model.init.as_str(), model.next.as_str()), ("Init", "Next"));
+        assert_eq!(model.fairness, vec!["FairSpec".to_string()]);
+        assert_eq!(check(&ev, &model).violation, None);
+        let model = configured("SPECIFICATION Spec PROPERTY Often").unwrap();
+        assert_eq!(model.fairness, Vec::<String>::new());
+        assert!(matches!(check(&ev, &model).violation, Some(Violation::Property { .. })));
+        assert_eq!(
+            configured("SPECIFICATION Inline"),
+            Err("specification `Inline` does not have the form `Init /\\ [][Next]_vars`: \
+                 `Init` and `Next` are not names of definitions"
+                .to_string()),
+        );
+        assert!(configured("SPECIFICATION Often").is_err());
+    }
+
+    #[test]
+    fn fairness() {
+        let spec = "\
+            VARIABLES x, y\n\
+            vars == <<x, y>>\n\
+            Init == x = 0 /\\ y = FALSE\n\
+            Flip == x' = 1 - x /\\ UNCHANGED y\n\
+            Set == x = 1 /\\ y' = TRUE /\\ UNCHANGED x\n\
+            Next == Flip \\/ Set\n\
+            Done == <>y\n\
+            Weak == WF_vars(Flip) /\\ WF_vars(Set)\n\
+            Strong == \\A a \\in {\"Flip\"} : WF_vars(Flip) /\\ SF_vars(Set)";
+        let model = |fairness: &str| Model {
+            properties: vec!["Done".to_string()],
+            fairness: vec![fairness.to_string()],
+            deadlock: false,
+            ..Model::default()
+        };
+        match run(spec, &model("Weak")).violation {
+            Some(Violation::Property { trace, .. }) => {
+                let ys: Vec<_> = trace.steps.iter().map(|s| s.state[1].to_string()).collect();
+                assert!(ys.iter().all(|y| y == "FALSE"), "{}", trace);
+            }
+            v => panic!("{:?}", v),
+        }
+        assert_eq!(run(spec, &model("Strong")).violation, None);
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::eval::{Evaluator, Temporal, Value};
+
+use super::liveness::{self, Graph};
+use super::{assumptions, check_state, fingerprint, Fault, Model, Outcome, Stats, Step, Trace, Violation};
+
+
+/// Options of the random simulation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Simulation {
+    /// Behaviors to generate.
+    pub traces: usize,
+    /// States in a behavior, at most.
+    pub depth: usize,
+    /// Seed of the random choices, the same seed gives the same behaviors.
+    pub seed: u64,
+    /// Relative weights of the subactions by name, 1 for the others.
+    /// Subactions of weight 0 are never taken.
+    pub weights: Vec<(String, u32)>,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Simulation { traces: 1000, depth: 100, seed: 0, weights: Vec::new() }
+    }
+}
+
+// State of a behavior, with the subaction that produced it.
+type Path<'a> = Vec<(Vec<Value>, Option<&'a str>)>;
+
+/// Generates random behaviors from the initial states by the next-state
+/// action, as TLC does with `-simulate`. The invariants are checked in
+/// their states and the temporal properties on each behavior, that ends at
+/// the depth, in a state without successors, or in a state that violates a
+/// constraint. The options of the state space search, and `Model::graph`,
+/// are ignored.
+pub fn simulate(ev: &Evaluator, model: &Model, sim: &Simulation) -> Outcome {
+    let mut stats = Stats::default();
+    let violation = behaviors(ev, model, sim, &mut stats);
+    Outcome { stats, violation, complete: true, error: None, graph: None }
+}
+
+fn behaviors<'a>(ev: &Evaluator<'a>, model: &Model, sim: &Simulation, stats: &mut Stats) -> Option<Violation> {
+    if let Some(v) = assumptions(ev) {
+        return Some(v);
+    }
+    let empty = Trace { variables: ev.variables().to_vec(), steps: Vec::new(), back: None };
+    let temporal = |names: &[String]| names.iter().map(|name| ev.temporal(name)).collect::<Result<Vec<_>, _>>();
+    let (properties, fairness) = match (temporal(&model.properties), temporal(&model.fairness)) {
+        (Ok(p), Ok(f)) => (p, f),
+        (Err(error), _) | (_, Err(error)) => return Some(Violation::Error { error, trace: empty }),
+    };
+    let mut initial = Vec::new();
+    if let Err(error) = ev.initial_states(&model.init, &mut |s| {
+        initial.push(s);
+        true
+    }) {
+        return Some(Violation::Error { error, trace: empty });
+    }
+    stats.generated += initial.len();
+
+    let mut rng = Rng(sim.seed);
+    let mut seen = HashSet::new();
+    for _ in 0..sim.traces {
+        if initial.is_empty() {
+            break;
+        }
+        let mut path: Path<'a> = vec![(initial[rng.below(initial.len())].clone(), None)];
+        loop {
+            let state = &path[path.len() - 1].0;
+            if seen.insert(fingerprint(state)) {
+                stats.distinct += 1;
+            }
+            stats.depth = stats.depth.max(path.len());
+            match check_state(ev, model, state) {
+                Ok(true) if path.len() < sim.depth => {}
+                Ok(_) => break,
+                Err(fault) => return Some(fault.violation(trace(ev, model, path))),
+            }
+            let mut successors = Vec::new();
+            if let Err(error) = ev.successors(&model.next, state, &mut |s, action| {
+                successors.push((s, action));
+                true
+            }) {
+                return Some(Violation::Error { error, trace: trace(ev, model, path) });
+            }
+            stats.generated += successors.len();
+            if successors.is_empty() && model.deadlock {
+                return Some(Fault::Deadlock.violation(trace(ev, model, path)));
+            }
+            match pick(&mut rng, &successors, model, sim) {
+                Some(i) => path.push(successors.swap_remove(i)),
+                None => break,
+            }
+        }
+        if !properties.is_empty() {
+            if let Some(v) = check_properties(ev, model, &path, &properties, &fairness) {
+                return Some(v);
+            }
+        }
+    }
+    None
+}
+
+// Successor for the next step: the subaction is chosen by the weights,
+// and the state among its successors with equal chances.
+fn pick(rng: &mut Rng, successors: &[(Vec<Value>, Option<&str>)], model: &Model, sim: &Simulation) -> Option<usize> {
+    let name = |action: Option<&str>| action.unwrap_or(&model.next).to_string();
+    let mut actions: Vec<(String, u64)> = Vec::new();
+    for (_, action) in successors {
+        let action = name(*action);
+        if actions.iter().all(|(a, _)| *a != action) {
+            let weight = sim.weights.iter().find(|(a, _)| *a == action).map_or(1, |(_, w)| *w);
+            actions.push((action, u64::from(weight)));
+        }
+    }
+    let total: u64 = actions.iter().map(|(_, w)| w).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut r = rng.next() % total;
+    let action = actions.iter().find(|(_, w)| {
+        let found = r < *w;
+        r = r.saturating_sub(*w);
+        found
+    })?;
+    let choices: Vec<usize> = (0..successors.len()).filter(|&i| name(successors[i].1) == action.0).collect();
+    Some(choices[rng.below(choices.len())])
+}
+
+// Checks the temporal properties on the graph of the behavior, where each
+// state may also stutter forever.
+fn check_properties<'a>(
+    ev: &Evaluator<'a>,
+    model: &Model,
+    path: &Path<'a>,
+    properties: &[Temporal<'a>],
+    fairness: &[Temporal<'a>],
+) -> Option<Violation> {
+    let mut index = HashMap::new();
+    let mut states: Vec<&[Value]> = Vec::new();
+    let mut edges: Vec<Vec<(usize, Option<&'a str>)>> = Vec::new();
+    let mut last: Option<usize> = None;
+    for (state, action) in path {
+        let i = *index.entry(fingerprint(state)).or_insert_with(|| {
+            states.push(state);
+            edges.push(Vec::new());
+            states.len() - 1
+        });
+        if let Some(last) = last {
+            edges[last].push((i, *action));
+        }
+        last = Some(i);
+    }
+    let graph = Graph { states: &states, initial: &[0], edges: &edges, next: &model.next };
+    for (name, property) in model.properties.iter().zip(properties) {
+        match liveness::check(ev, &graph, property.clone(), fairness.to_vec()) {
+            Ok(None) => {}
+            Ok(Some(trace)) => return Some(Violation::Property { name: name.clone(), trace }),
+            Err((error, _)) => return Some(Violation::Error { error, trace: trace(ev, model, path.clone()) }),
+        }
+    }
+    None
+}
+
+fn trace(ev: &Evaluator, model: &Model, path: Path) -> Trace {
+    let steps = path
+        .into_iter()
+        .enumerate()
+        .map(|(i, (state, action))| Step {
+            action: if i == 0 { None } else { Some(action.unwrap_or(&model.next).to_string()) },
+            state,
+        })
+        .collect();
+    Trace { variables: ev.variables().to_vec(), steps, back: None }
+}
+
+// Random numbers by SplitMix64, which are the same for a seed on all
+// platforms.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::resolve::resolve;
+
+    #[test]
+    fn simulate() {
+        let code = "---- MODULE M ----\n\
+            EXTENDS Naturals\n\
+            CONSTANT Max\n\
+            VARIABLES x, y\n\
+            Init == x = 0 /\\ y = 0\n\
+            IncX == x < Max /\\ x' = x + 1 /\\ y' = y\n\
+            IncY == y' = y + 1 /\\ x' = x\n\
+            Next == IncX \\/ IncY\n\
+            Small == y < 10\n\
+            Done == <>(x = Max)\n\
+            Fair == WF_<<x, y>>(IncX)\n\
+            ====";
+        let module = parse(code).unwrap();
+        let res = resolve(&module);
+        let mut ev = Evaluator::new(&module, &res);
+        ev.set_constant("Max", Value::int(5));
+        let model = Model { deadlock: false, ..Model::default() };
+        let sim = Simulation { traces: 20, depth: 8, seed: 7, weights: Vec::new() };
+        let outcome = super::simulate(&ev, &model, &sim);
+        assert_eq!(outcome.violation, None);
+        assert_eq!(outcome.stats.depth, 8);
+        assert_eq!(outcome, super::simulate(&ev, &model, &sim));
+
+        // Only IncX is taken until it is disabled.
+        let weights = vec![("IncY".to_string(), 0)];
+        let sim = Simulation { weights, ..sim };
+        assert_eq!(super::simulate(&ev, &model, &sim).stats.depth, 6);
+
+        let model = Model { invariants: vec!["Small".to_string()], ..model };
+        let sim = Simulation { depth: 20, weights: vec![("IncX".to_string(), 0)], ..sim };
+        let trace = match super::simulate(&ev, &model, &sim).violation {
+            Some(Violation::Invariant { trace, .. }) => trace,
+            v => panic!("{:?}", v),
+        };
+        assert_eq!(trace.steps.len(), 11);
+        assert_eq!(trace.steps[10].action.as_deref(), Some("IncY"));
+
+        let model = Model { invariants: Vec::new(), properties: vec!["Done".to_string()], ..model };
+        match super::simulate(&ev, &model, &sim).violation {
+            Some(Violation::Property { trace, .. }) => assert!(trace.back.is_some()),
+            v => panic!("{:?}", v),
+        }
+        // The behaviors only stutter while IncX is enabled, which is unfair.
+        let model = Model { fairness: vec!["Fair".to_string()], ..model };
+        assert_eq!(super::simulate(&ev, &model, &sim).violation, None);
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::lexer::{lex, Keyword, Pos, TokenType};
+
+
+/// Token together with the text that precedes it, so that the source can be
+/// reproduced exactly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Token<'a> {
+    /// `Unknown` for the text that the lexer could not recognize.
+    pub kind: TokenType,
+    pub text: &'a str,
+    /// Whitespace and line breaks before the token.
+    pub leading: &'a str,
+    pub start: Pos,
+    pub end: Pos,
+}
+
+impl<'a> Token<'a> {
+    /// Whether the token is the first one on its line.
+    pub fn starts_line(&self) -> bool {
+        self.leading.contains('\n')
+    }
+
+    /// Number of line breaks before the token.
+    pub fn line_breaks(&self) -> usize {
+        self.leading.matches('\n').count()
+    }
+}
+
+
+/// Lossless concrete syntax of a source file: all the tokens including
+/// comments, with the whitespace between them.
+#[derive(Debug, Clone)]
+pub struct Cst<'a> {
+    pub source: &'a str,
+    pub tokens: Vec<Token<'a>>,
+    /// Text after the last token.
+    pub trailing: &'a str,
+}
+
+impl<'a> Cst<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let mut tokens = Vec::new();
+        let mut offset = 0;
+        for lexeme in lex(source) {
+            let kind = match lexeme.value {
+                Ok(TokenType::Indent) => continue,
+                Ok(kind) => kind,
+                Err(_) => TokenType::Unknown,
+            };
+            let (start, end) = (lexeme.start.byte_offset, lexeme.end.byte_offset);
+            tokens.push(Token {
+                kind,
+                text: &source[start..end],
+                leading: &source[offset..start],
+                start: lexeme.start,
+                end: lexeme.end,
+            });
+            offset = end;
+        }
+        Cst { source, tokens, trailing: &source[offset..] }
+    }
+
+    /// Ranges of the tokens that start on the same line. Tokens after
+    /// a multiline comment belong to the line where the comment starts.
+    pub fn lines(&self) -> Vec<Range<usize>> {
+        let mut res: Vec<Range<usize>> = Vec::new();
+        for (i, t) in self.tokens.iter().enumerate() {
+            match res.last_mut() {
+                Some(line) if !t.starts_line() => line.end = i + 1,
+                _ => res.push(i..i + 1),
+            }
+        }
+        res
+    }
+
+    /// Tokens from the header of the first module up to the end of the
+    /// last one. Text outside of this range is not part of the module.
+    pub fn module_range(&self) -> Option<Range<usize>> {
+        let start = self.tokens.windows(2).position(|w| {
+            w[0].kind == TokenType::Separator && w[1].kind == TokenType::Keyword(Keyword::Module)
+        })?;
+        let end = self.tokens.iter().rposition(|t| t.kind == TokenType::ModuleEnd)?;
+        if end < start {
+            return None;
+        }
+        Some(start..end + 1)
+    }
+}
+
+impl<'a> fmt::Display for Cst<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for t in &self.tokens {
+            f.write_str(t.leading)?;
+            f.write_str(t.text)?;
+        }
+        f.write_str(self.trailing)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lossless() {
+        let code = "text\r\n---- MODULE M ----\nx ==  1 \\* c\n\n  (* a\n b *) y ? \"s\"\n====\nmore";
+        let cst = Cst::new(code);
+        assert_eq!(cst.to_string(), code);
+        assert_eq!(cst.lines().len(), 6);
+        let range = cst.module_range().unwrap();
+        assert_eq!(cst.tokens[range.start].text, "----");
+        assert_eq!(cst.tokens[range.end - 1].text, "====");
+        assert!(cst.tokens.iter().any(|t| t.kind == TokenType::Unknown));
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::ast::*;
+use crate::resolve::DeclKind;
+
+use super::{flatten, type_error, Ctx, Error, Evaluator, Value};
+
+
+// Assignment of the variables that is built while an initial predicate or
+// an action is enumerated, as TLC does: `x = e` and `x \in S` assign the
+// unassigned variables, `\/` and `\E` branch and the other formulas filter.
+#[derive(Clone)]
+pub(super) struct Partial<'a> {
+    values: Arc<Vec<Option<Value>>>,
+    // Assigns the next state, `x'` instead of `x`.
+    primed: bool,
+    // Subaction that made the first assignment.
+    action: Option<&'a str>,
+}
+
+impl<'a> Partial<'a> {
+    pub(super) fn new(n: usize, primed: bool) -> Self {
+        Partial { values: Arc::new(vec![None; n]), primed, action: None }
+    }
+
+    fn assign(&self, i: usize, v: Value, action: Option<&'a str>) -> Self {
+        let mut values = (*self.values).clone();
+        values[i] = Some(v.normalize());
+        Partial { values: Arc::new(values), primed: self.primed, action: self.action.or(action) }
+    }
+}
+
+// Continuation of the enumeration, returns false to stop it.
+type Cont<'a, 'f> = dyn FnMut(&Partial<'a>) -> Result<bool, Error> + 'f;
+
+impl<'a> Evaluator<'a> {
+    /// Variables of the module and the modules it extends. States are
+    /// given as their values in this order.
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    /// Calls `f` with each state that satisfies the initial predicate
+    /// `init`, until it returns false.
+    pub fn initial_states(&self, init: &str, f: &mut dyn FnMut(Vec<Value>) -> bool) -> Result<(), Error> {
+        let (cx, def, body) = self.formula(init)?;
+        let p = Partial::new(self.variables.len(), false);
+        self.act(&cx, body, &p, None, &mut |p| Ok(f(self.complete(p, def)?)))?;
+        Ok(())
+    }
+
+    /// Calls `f` with each successor of the state by the action `next` and
+    /// the name of the subaction that produced it, until `f` returns false.
+    /// The subaction is the last definition on the path through `\/` and
+    /// `\E` to the first assignment, if any.
+    pub fn successors(
+        &self,
+        next: &str,
+        state: &[Value],
+        f: &mut dyn FnMut(Vec<Value>, Option<&'a str>) -> bool,
+    ) -> Result<(), Error> {
+        let (mut cx, def, body) = self.formula(next)?;
+        cx.state = Some(Arc::new(state.iter().cloned().map(Some).collect()));
+        let p = Partial::new(self.variables.len(), true);
+        let action = self.subaction(&cx, body);
+        self.act(&cx, body, &p, action, &mut |p| Ok(f(self.complete(p, def)?, p.action)))?;
+        Ok(())
+    }
+
+    /// Subactions of `next` that take `state` to `next_state`, named as by
+    /// `successors`, with `None` for a path through `next` without one.
+    /// Empty if `next` does not allow the step. The states are the values
+    /// of `names`, which may order the variables differently, like the
+    /// states of a TLC trace.
+    pub fn step_actions(
+        &self,
+        next: &str,
+        names: &[String],
+        state: &[Value],
+        next_state: &[Value],
+    ) -> Result<Vec<Option<&'a str>>, Error> {
+        let reorder = |values: &[Value]| -> Result<Vec<Value>, Error> {
+            self.variables
+                .iter()
+                .map(|var| match names.iter().position(|n| n == var) {
+                    Some(i) if i < values.len() => Ok(values[i].clone()),
+                    _ => Err(Error::Unspecified { name: var.clone(), span: self.module.name.span }),
+                })
+                .collect()
+        };
+        let (state, next_state) = (reorder(state)?, reorder(next_state)?);
+        let mut actions = Vec::new();
+        self.successors(next, &state, &mut |s, action| {
+            if s == next_state && !actions.contains(&action) {
+                actions.push(action);
+            }
+            true
+        })?;
+        Ok(actions)
+    }
+
+    /// Evaluates a state predicate in the state.
+    pub fn predicate(&self, name: &str, state: &[Value]) -> Result<bool, Error> {
+        let (mut cx, _, body) = self.formula(name)?;
+        cx.state = Some(Arc::new(state.iter().cloned().map(Some).collect()));
+        self.bool(&cx, body)
+    }
+
+    /// Evaluates a state function, like a VIEW, in the state.
+    pub fn state_function(&self, name: &str, state: &[Value]) -> Result<Value, Error> {
+        let (mut cx, _, body) = self.formula(name)?;
+        cx.state = Some(Arc::new(state.iter().cloned().map(Some).collect()));
+        self.expr(&cx, body)
+    }
+
+    /// Permutations of model values of a SYMMETRY definition, like
+    /// `Permutations(Procs)` or a union of such sets.
+    pub fn permutations(&self, name: &str) -> Result<Vec<BTreeMap<Value, Value>>, Error> {
+        let (cx, _, body) = self.formula(name)?;
+        let set = self.expr(&cx, body)?;
+        let expected = "set of permutations of model values";
+        let elems = match set.elements() {
+            Some(elems) => elems,
+            None => return Err(type_error(expected, &set, body.span)),
+        };
+        let mut res = Vec::new();
+        for f in elems.iter() {
+            match f.pairs() {
+                Some(pairs) if pairs.iter().all(|(a, b)| matches!((a, b), (Value::Model(_), Value::Model(_)))) => {
+                    res.push(pairs.into_iter().collect());
+                }
+                _ => return Err(type_error(expected, &set, body.span)),
+            }
+        }
+        Ok(res)
+    }
+
+    // Definition without parameters in the module or the modules it
+    // extends.
+    pub(super) fn formula(&self, name: &str) -> Result<(Ctx<'a>, &'a Definition, &'a Expr), Error> {
+        let found = self.scope.iter().find_map(|m| Some((m, m.definition(name)?)));
+        let (module, def) = match found {
+            Some(found) => found,
+            None => return Err(Error::Undefined { name: name.to_string(), span: self.module.name.span }),
+        };
+        match &def.body {
+            DefBody::Expr(body) if def.params.is_empty() => {
+                Ok((Ctx::new(self.file_of[&module.name.name]), def, body))
+            }
+            _ => Err(Error::Unsupported {
+                span: def.name.span,
+                what: format!("`{}` as a formula without parameters", name),
+            }),
+        }
+    }
+
+    // Values of the complete assignment.
+    fn complete(&self, p: &Partial<'a>, def: &Definition) -> Result<Vec<Value>, Error> {
+        p.values
+            .iter()
+            .zip(&self.variables)
+            .map(|(v, name)| v.clone().ok_or_else(|| Error::Unspecified {
+                name: if p.primed { format!("{}'", name) } else { name.clone() },
+                span: def.name.span,
+            }))
+            .collect()
+    }
+
+    pub(super) fn unchanged(&self, cx: &Ctx<'a>, e: &'a Expr) -> Result<bool, Error> {
+        let mut next = cx.clone();
+        next.primed = true;
+        Ok(self.expr(cx, e)?.equals(&self.expr(&next, e)?))
+    }
+
+    pub(super) fn enabled(&self, cx: &Ctx<'a>, a: &'a Expr) -> Result<bool, Error> {
+        let mut cx = cx.clone();
+        cx.primed = false;
+        let p = Partial::new(self.variables.len(), true);
+        // Variables left unassigned can take any value.
+        let stopped = !self.act(&cx, a, &p, None, &mut |_| Ok(false))?;
+        Ok(stopped)
+    }
+
+    pub(super) fn act(
+        &self,
+        cx: &Ctx<'a>,
+        e: &'a Expr,
+        p: &Partial<'a>,
+        action: Option<&'a str>,
+        k: &mut Cont<'a, '_>,
+    ) -> Result<bool, Error> {
+        let cx = &self.assigned(cx, p);
+        match &e.kind {
+            ExprKind::Junction { kind: Junction::And, items } => self.conjuncts(cx, items, p, action, k),
+            ExprKind::Junction { kind: Junction::Or, items } => {
+                for item in items {
+                    if !self.act(cx, item, p, self.subaction(cx, item).or(action), k)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            ExprKind::Quant { kind: Quantifier::Exists, bounds, body } => {
+                let action = self.subaction(cx, body).or(action);
+                self.bind(cx, &flatten(bounds), &mut vec![], &mut |cx, _| self.act(cx, body, p, action, k))
+            }
+            ExprKind::If { cond, then, other } => {
+                let branch = if self.bool(cx, cond)? { then } else { other };
+                self.act(cx, branch, p, action, k)
+            }
+            ExprKind::Case { arms, other } => {
+                for (guard, arm) in arms {
+                    if self.bool(cx, guard)? {
+                        return self.act(cx, arm, p, action, k);
+                    }
+                }
+                match other {
+                    Some(arm) => self.act(cx, arm, p, action, k),
+                    None => Err(Error::NoValue { span: e.span }),
+                }
+            }
+            ExprKind::Let { body, .. } => self.act(cx, body, p, action, k),
+            ExprKind::BoxAction { action: a, sub } if p.primed => {
+                Ok(self.act(cx, a, p, action, k)? && self.keep(cx, sub, p, action, k)?)
+            }
+            ExprKind::AngleAction { action: a, sub } if p.primed => self.act(cx, a, p, action, &mut |p| {
+                let cx = self.assigned(cx, p);
+                if self.unchanged(&cx, sub)? { Ok(true) } else { k(p) }
+            }),
+            ExprKind::OpApply { op, args } if self.is_builtin(cx, op) => match (op.name.as_str(), args.as_slice()) {
+                ("=", [lhs, rhs]) => match self.target(cx, lhs, p) {
+                    Some(i) => {
+                        let v = self.expr(cx, rhs)?;
+                        k(&p.assign(i, v, action))
+                    }
+                    None => self.filter(cx, e, p, k),
+                },
+                ("\\in", [lhs, set]) => match self.target(cx, lhs, p) {
+                    Some(i) => {
+                        let s = self.set(cx, set)?;
+                        let elems = s.elements().ok_or(Error::Infinite { span: set.span })?;
+                        for x in elems.iter() {
+                            if !k(&p.assign(i, x.clone(), action))? {
+                                return Ok(false);
+                            }
+                        }
+                        Ok(true)
+                    }
+                    None => self.filter(cx, e, p, k),
+                },
+                ("UNCHANGED", [a]) if p.primed => self.keep(cx, a, p, action, k),
+                _ => self.filter(cx, e, p, k),
+            },
+            ExprKind::Apply { path, name, args } => match self.expand(cx, path, name, args)? {
+                Some((cx, body)) => self.act(&cx, body, p, action, k),
+                None => self.filter(cx, e, p, k),
+            },
+            ExprKind::OpApply { op, args } => match self.expand(cx, &[], op, args)? {
+                Some((cx, body)) => self.act(&cx, body, p, action, k),
+                None => self.filter(cx, e, p, k),
+            },
+            _ => self.filter(cx, e, p, k),
+        }
+    }
+
+    fn conjuncts(
+        &self,
+        cx: &Ctx<'a>,
+        items: &'a [Expr],
+        p: &Partial<'a>,
+        action: Option<&'a str>,
+        k: &mut Cont<'a, '_>,
+    ) -> Result<bool, Error> {
+        match items.split_first() {
+            None => k(p),
+            Some((first, rest)) => self.act(cx, first, p, action, &mut |p| self.conjuncts(cx, rest, p, action, k)),
+        }
+    }
+
+    // `UNCHANGED e`: assigns the variables of `e` to their current values.
+    fn keep(
+        &self,
+        cx: &Ctx<'a>,
+        e: &'a Expr,
+        p: &Partial<'a>,
+        action: Option<&'a str>,
+        k: &mut Cont<'a, '_>,
+    ) -> Result<bool, Error> {
+        let cx = &self.assigned(cx, p);
+        if let Some(i) = self.variable_slot(cx, e) {
+            if p.values[i].is_none() {
+                let v = self.expr(cx, e)?;
+                return k(&p.assign(i, v, action));
+            }
+        }
+        let expanded = match &e.kind {
+            ExprKind::Tuple(items) => return self.keep_all(cx, items, p, action, k),
+            ExprKind::Apply { path, name, args } => self.expand(cx, path, name, args)?,
+            _ => None,
+        };
+        match expanded {
+            Some((cx, body)) => self.keep(&cx, body, p, action, k),
+            None if self.unchanged(cx, e)? => k(p),
+            None => Ok(true),
+        }
+    }
+
+    fn keep_all(
+        &self,
+        cx: &Ctx<'a>,
+        items: &'a [Expr],
+        p: &Partial<'a>,
+        action: Option<&'a str>,
+        k: &mut Cont<'a, '_>,
+    ) -> Result<bool, Error> {
+        match items.split_first() {
+            None => k(p),
+            Some((first, rest)) => self.keep(cx, first, p, action, &mut |p| self.keep_all(cx, rest, p, action, k)),
+        }
+    }
+
+    // Formula that doesn't assign: continues if it is true.
+    fn filter(&self, cx: &Ctx<'a>, e: &'a Expr, p: &Partial<'a>, k: &mut Cont<'a, '_>) -> Result<bool, Error> {
+        if self.bool(cx, e)? {
+            k(p)
+        } else {
+            Ok(true)
+        }
+    }
+
+    // Context in which the variables have the assigned values.
+    pub(super) fn assigned(&self, cx: &Ctx<'a>, p: &Partial<'a>) -> Ctx<'a> {
+        let mut cx = cx.clone();
+        if p.primed {
+            cx.next = Some(p.values.clone());
+        } else {
+            cx.state = Some(p.values.clone());
+        }
+        cx
+    }
+
+    // Body of a user-defined operator with the arguments bound.
+    pub(super) fn expand(
+        &self,
+        cx: &Ctx<'a>,
+        path: &'a [Ident],
+        name: &'a Ident,
+        args: &'a [Expr],
+    ) -> Result<Option<(Ctx<'a>, &'a Expr)>, Error> {
+        let file = &self.files[cx.file];
+        let id = match file.ref_at.get(&name.span.start.byte_offset) {
+            Some(&id) => id,
+            None => return Ok(None),
+        };
+        let decl = &file.decls[id];
+        let bound = path.is_empty() && cx.lookup(id).is_some();
+        if bound || decl.kind != DeclKind::Operator || decl.span.is_none() {
+            return Ok(None);
+        }
+        let (mut body_cx, def) = self.locate(cx, path, decl, name.span)?;
+        let body = match &def.body {
+            DefBody::Expr(body) => body,
+            _ => return Ok(None),
+        };
+        let arities: Vec<usize> = def.params.iter().map(|p| p.arity).collect();
+        for (param, arg) in def.params.iter().zip(self.arguments(cx, &arities, args)?) {
+            let id = self.declared(body_cx.file, &param.name)?;
+            body_cx = body_cx.with(id, arg);
+        }
+        Ok(Some((body_cx, body)))
+    }
+
+    // Name of the operator that `e` applies, as the label of a subaction.
+    fn subaction(&self, cx: &Ctx<'a>, e: &'a Expr) -> Option<&'a str> {
+        let name = match &e.kind {
+            ExprKind::Apply { name, .. } => name,
+            _ => return None,
+        };
+        let id = *self.files[cx.file].ref_at.get(&name.span.start.byte_offset)?;
+        let decl = &self.files[cx.file].decls[id];
+        let user = decl.kind == DeclKind::Operator && decl.span.is_some();
+        if user && cx.lookup(id).is_none() {
+            Some(&name.name)
+        } else {
+            None
+        }
+    }
+
+    pub(super) fn is_builtin(&self, cx: &Ctx<'a>, op: &Ident) -> bool {
+        let file = &self.files[cx.file];
+        matches!(file.ref_at.get(&op.span.start.byte_offset), Some(&id) if file.decls[id].kind == DeclKind::Builtin)
+    }
+
+    // Unassigned variable that `x' = e` assigns in an action, or `x = e`
+    // in an initial predicate.
+    fn target(&self, cx: &Ctx<'a>, lhs: &'a Expr, p: &Partial<'a>) -> Option<usize> {
+        let var = match &lhs.kind {
+            ExprKind::OpApply { op, args } if p.primed && op.name == "'" && args.len() == 1 => &args[0],
+            _ if !p.primed => lhs,
+            _ => return None,
+        };
+        self.variable_slot(cx, var).filter(|&i| p.values[i].is_none())
+    }
+
+    // Index of the variable in states, if `e` is a variable.
+    fn variable_slot(&self, cx: &Ctx<'a>, e: &'a Expr) -> Option<usize> {
+        let name = match &e.kind {
+            ExprKind::Apply { path, name, args } if path.is_empty() && args.is_empty() => name,
+            _ => return None,
+        };
+        let file = &self.files[cx.file];
+        let id = *file.ref_at.get(&name.span.start.byte_offset)?;
+        let decl = &file.decls[id];
+        if decl.kind != DeclKind::Variable || cx.lookup(id).is_some() {
+            return None;
+        }
+        self.variable_index(cx, &decl.name)
+    }
+
+    fn variable_index(&self, cx: &Ctx<'a>, name: &str) -> Option<usize> {
+        match &cx.subst {
+            Some(s) => match s.inst.substitutions.iter().find(|(p, _)| p.name == name) {
+                Some((_, e)) => self.variable_slot(&s.outer, e),
+                None => self.variable_index(&s.outer, name),
+            },
+            None => self.variables.iter().position(|v| v == name),
+        }
+    }
+}
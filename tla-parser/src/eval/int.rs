@@ -0,0 +1,373 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+
+/// Integer of arbitrary precision. Numbers that fit into `i64` are stored
+/// inline, larger ones as sign and magnitude, so that every number has
+/// exactly one representation and can be compared and hashed structurally.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Int(Repr);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Repr {
+    Small(i64),
+    // Digits in base 2^32, least significant first, without leading zeros.
+    // The number is always outside of the range of `i64`.
+    Big { negative: bool, digits: Vec<u32> },
+}
+
+impl Int {
+    pub fn to_i64(&self) -> Option<i64> {
+        match self.0 {
+            Repr::Small(n) => Some(n),
+            Repr::Big { .. } => None,
+        }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        match &self.0 {
+            Repr::Small(n) => *n < 0,
+            Repr::Big { negative, .. } => *negative,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == Repr::Small(0)
+    }
+
+    /// Parses digits in the given radix, without sign or prefix.
+    pub fn parse(s: &str, radix: u32) -> Option<Int> {
+        if s.is_empty() {
+            return None;
+        }
+        let base = Int::from(radix as i64);
+        let mut res = Int::from(0);
+        for c in s.chars() {
+            let d = c.to_digit(radix)?;
+            res = &(&res * &base) + &Int::from(d as i64);
+        }
+        Some(res)
+    }
+
+    /// Quotient rounded towards negative infinity, as `\div` of TLA+.
+    /// `None` if `other` is zero.
+    pub fn div_floor(&self, other: &Int) -> Option<Int> {
+        self.div_mod_floor(other).map(|(q, _)| q)
+    }
+
+    /// Remainder with the sign of `other`, as `%` of TLA+.
+    /// `None` if `other` is zero.
+    pub fn mod_floor(&self, other: &Int) -> Option<Int> {
+        self.div_mod_floor(other).map(|(_, r)| r)
+    }
+
+    fn div_mod_floor(&self, other: &Int) -> Option<(Int, Int)> {
+        if other.is_zero() {
+            return None;
+        }
+        if let (Repr::Small(a), Repr::Small(b)) = (&self.0, &other.0) {
+            if let (Some(mut q), Some(mut r)) = (a.checked_div(*b), a.checked_rem(*b)) {
+                if r != 0 && (r < 0) != (*b < 0) {
+                    q -= 1;
+                    r += b;
+                }
+                return Some((Int::from(q), Int::from(r)));
+            }
+        }
+        let (an, a) = self.parts();
+        let (bn, b) = other.parts();
+        let (q, r) = div_mod_mag(&a, &b);
+        let q = Int::from_parts(an != bn, q);
+        let r = Int::from_parts(an, r);
+        if !r.is_zero() && an != bn {
+            Some((&q - &Int::from(1), &r + other))
+        } else {
+            Some((q, r))
+        }
+    }
+
+    pub fn pow(&self, mut exp: u32) -> Int {
+        let mut base = self.clone();
+        let mut res = Int::from(1);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                res = &res * &base;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = &base * &base;
+            }
+        }
+        res
+    }
+
+    fn parts(&self) -> (bool, Vec<u32>) {
+        match &self.0 {
+            Repr::Small(n) => (*n < 0, mag(n.unsigned_abs())),
+            Repr::Big { negative, digits } => (*negative, digits.clone()),
+        }
+    }
+
+    fn from_parts(negative: bool, digits: Vec<u32>) -> Int {
+        if digits.len() <= 2 {
+            let m = digits.iter().rev().fold(0i128, |acc, &d| (acc << 32) | d as i128);
+            let n = if negative { -m } else { m };
+            if let Ok(n) = i64::try_from(n) {
+                return Int(Repr::Small(n));
+            }
+        }
+        Int(Repr::Big { negative, digits })
+    }
+}
+
+impl From<i64> for Int {
+    fn from(n: i64) -> Int {
+        Int(Repr::Small(n))
+    }
+}
+
+impl Add for &Int {
+    type Output = Int;
+
+    fn add(self, other: &Int) -> Int {
+        if let (Repr::Small(a), Repr::Small(b)) = (&self.0, &other.0) {
+            if let Some(n) = a.checked_add(*b) {
+                return Int::from(n);
+            }
+        }
+        let (an, a) = self.parts();
+        let (bn, b) = other.parts();
+        if an == bn {
+            return Int::from_parts(an, add_mag(&a, &b));
+        }
+        match cmp_mag(&a, &b) {
+            Ordering::Less => Int::from_parts(bn, sub_mag(&b, &a)),
+            _ => Int::from_parts(an, sub_mag(&a, &b)),
+        }
+    }
+}
+
+impl Sub for &Int {
+    type Output = Int;
+
+    fn sub(self, other: &Int) -> Int {
+        self + &-other
+    }
+}
+
+impl Mul for &Int {
+    type Output = Int;
+
+    fn mul(self, other: &Int) -> Int {
+        if let (Repr::Small(a), Repr::Small(b)) = (&self.0, &other.0) {
+            if let Some(n) = a.checked_mul(*b) {
+                return Int::from(n);
+            }
+        }
+        let (an, a) = self.parts();
+        let (bn, b) = other.parts();
+        Int::from_parts(an != bn, mul_mag(&a, &b))
+    }
+}
+
+impl Neg for &Int {
+    type Output = Int;
+
+    fn neg(self) -> Int {
+        match &self.0 {
+            Repr::Small(n) => match n.checked_neg() {
+                Some(n) => Int::from(n),
+                None => Int::from_parts(false, mag(n.unsigned_abs())),
+            },
+            Repr::Big { negative, digits } => Int::from_parts(!negative, digits.clone()),
+        }
+    }
+}
+
+impl Ord for Int {
+    fn cmp(&self, other: &Int) -> Ordering {
+        if let (Repr::Small(a), Repr::Small(b)) = (&self.0, &other.0) {
+            return a.cmp(b);
+        }
+        let (an, a) = self.parts();
+        let (bn, b) = other.parts();
+        match (an, bn) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&a, &b),
+            (true, true) => cmp_mag(&b, &a),
+        }
+    }
+}
+
+impl PartialOrd for Int {
+    fn partial_cmp(&self, other: &Int) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Int {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (negative, mut digits) = match &self.0 {
+            Repr::Small(n) => return write!(f, "{}", n),
+            Repr::Big { negative, digits } => (*negative, digits.clone()),
+        };
+        // Groups of 9 decimal digits, least significant first.
+        let mut groups = Vec::new();
+        while !digits.is_empty() {
+            let (q, r) = div_mod_small(&digits, 1_000_000_000);
+            groups.push(r);
+            digits = q;
+        }
+        if negative {
+            f.write_str("-")?;
+        }
+        write!(f, "{}", groups.pop().unwrap_or(0))?;
+        for g in groups.iter().rev() {
+            write!(f, "{:09}", g)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Int {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+
+fn mag(n: u64) -> Vec<u32> {
+    trim(vec![n as u32, (n >> 32) as u32])
+}
+
+fn trim(mut digits: Vec<u32>) -> Vec<u32> {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    digits
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        res.push(sum as u32);
+        carry = sum >> 32;
+    }
+    res.push(carry as u32);
+    trim(res)
+}
+
+// Requires `a >= b`.
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &x) in a.iter().enumerate() {
+        let mut d = x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if d < 0 {
+            d += 1 << 32;
+            borrow = 1;
+        }
+        res.push(d as u32);
+    }
+    trim(res)
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let t = x as u64 * y as u64 + res[i + j] as u64 + carry;
+            res[i + j] = t as u32;
+            carry = t >> 32;
+        }
+        res[i + b.len()] = carry as u32;
+    }
+    trim(res)
+}
+
+fn div_mod_small(a: &[u32], d: u32) -> (Vec<u32>, u32) {
+    let mut q = vec![0u32; a.len()];
+    let mut r = 0u64;
+    for i in (0..a.len()).rev() {
+        let cur = (r << 32) | a[i] as u64;
+        q[i] = (cur / d as u64) as u32;
+        r = cur % d as u64;
+    }
+    (trim(q), r as u32)
+}
+
+// Truncated division of magnitudes, one bit at a time.
+fn div_mod_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if b.len() == 1 {
+        let (q, r) = div_mod_small(a, b[0]);
+        return (q, mag(r as u64));
+    }
+    let mut q = vec![0u32; a.len()];
+    let mut r: Vec<u32> = Vec::new();
+    for i in (0..a.len() * 32).rev() {
+        let mut carry = (a[i / 32] >> (i % 32)) & 1;
+        for d in r.iter_mut() {
+            let top = *d >> 31;
+            *d = (*d << 1) | carry;
+            carry = top;
+        }
+        if carry != 0 {
+            r.push(carry);
+        }
+        if cmp_mag(&r, b) != Ordering::Less {
+            r = sub_mag(&r, b);
+            q[i / 32] |= 1 << (i % 32);
+        }
+    }
+    (trim(q), r)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(s: &str) -> Int {
+        match s.strip_prefix('-') {
+            Some(s) => -&Int::parse(s, 10).unwrap(),
+            None => Int::parse(s, 10).unwrap(),
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        let big = int("2").pow(100);
+        assert_eq!(big.to_string(), "1267650600228229401496703205376");
+        assert_eq!((&(&big + &int("1")) - &big).to_i64(), Some(1));
+        assert_eq!((&big * &int("-3")).to_string(), "-3802951800684688204490109616128");
+        assert_eq!(&int("9223372036854775807") + &int("1"), int("9223372036854775808"));
+        assert_eq!((-&int("-9223372036854775808")).to_string(), "9223372036854775808");
+        assert!(int("-99999999999999999999") < int("-1"));
+        assert!(int("99999999999999999999") > int("9223372036854775807"));
+        assert_eq!(Int::parse("ff", 16), Some(int("255")));
+    }
+
+    #[test]
+    fn division() {
+        let cases = [("7", "2", "3", "1"), ("-7", "2", "-4", "1"), ("7", "-2", "-4", "-1")];
+        for (a, b, q, r) in cases.iter() {
+            assert_eq!(int(a).div_floor(&int(b)), Some(int(q)));
+            assert_eq!(int(a).mod_floor(&int(b)), Some(int(r)));
+        }
+        let big = int("1267650600228229401496703205377");
+        assert_eq!(big.div_floor(&int("4294967296")), Some(int("295147905179352825856")));
+        assert_eq!(big.mod_floor(&int("-10000000000000000000")), Some(int("-1770598503296794623")));
+        assert_eq!(big.div_floor(&int("0")), None);
+    }
+}
@@ -0,0 +1,1474 @@
+mod action;
+mod int;
+mod temporal;
+mod value;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::ast::*;
+use crate::level::{check_with, Level};
+use crate::resolve::{resolve_with, Decl, DeclKind, Modules, Resolution};
+use crate::stdlib;
+use crate::workspace::dependencies;
+
+pub use self::int::Int;
+pub use self::temporal::{Atom, Temporal};
+pub use self::value::{LazySet, Value};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// Name without a declaration, the module has unresolved references.
+    Undefined { name: String, span: Span },
+    /// Operand of a wrong kind, e.g. `1 + TRUE`.
+    Type { span: Span, expected: &'static str, found: String },
+    /// Function applied to an argument outside of its domain.
+    Domain { span: Span, arg: String },
+    /// Enumeration of an infinite set, or an unbounded quantifier.
+    Infinite { span: Span },
+    /// CHOOSE without a satisfying element, or CASE without a true guard.
+    NoValue { span: Span },
+    /// Constant that has not been given a value.
+    Unassigned { name: String, span: Span },
+    /// Variable without a value in the state, e.g. `x'` before an action
+    /// assigns it.
+    Unspecified { name: String, span: Span },
+    /// Invalid argument of a standard operator, e.g. division by zero.
+    Invalid { span: Span, message: String },
+    /// Failed `Assert` of the TLC module.
+    Assert { span: Span, message: String },
+    /// Expression that has no constant value, like a variable or a temporal
+    /// formula, or an operator that the evaluator does not implement.
+    Unsupported { span: Span, what: String },
+}
+
+impl Error {
+    pub fn span(&self) -> Span {
+        match self {
+            Error::Undefined { span, .. }
+            | Error::Type { span, .. }
+            | Error::Domain { span, .. }
+            | Error::Infinite { span }
+            | Error::NoValue { span }
+            | Error::Unassigned { span, .. }
+            | Error::Unspecified { span, .. }
+            | Error::Invalid { span, .. }
+            | Error::Assert { span, .. }
+            | Error::Unsupported { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pos = self.span().start;
+        write!(f, "{}:{}: ", pos.line, pos.col)?;
+        match self {
+            Error::Undefined { name, .. } => write!(f, "unknown identifier `{}`", name),
+            Error::Type { expected, found, .. } => write!(f, "expected {}, found {}", expected, found),
+            Error::Domain { arg, .. } => write!(f, "{} is not in the domain of the function", arg),
+            Error::Infinite { .. } => write!(f, "cannot enumerate an infinite set"),
+            Error::NoValue { .. } => write!(f, "no value satisfies the condition"),
+            Error::Unassigned { name, .. } => write!(f, "constant `{}` has no value", name),
+            Error::Unspecified { name, .. } => write!(f, "variable `{}` has no value", name),
+            Error::Invalid { message, .. } => f.write_str(message),
+            Error::Assert { message, .. } => write!(f, "assertion failed: {}", message),
+            Error::Unsupported { what, .. } => write!(f, "cannot evaluate {}", what),
+        }
+    }
+}
+
+
+/// Evaluator of constant expressions of a module. Definitions of the
+/// extended and instantiated modules are evaluated from their source,
+/// operators of the standard modules are built in.
+pub struct Evaluator<'a> {
+    module: &'a Module,
+    // The module and the modules it extends, transitively.
+    scope: Vec<&'a Module>,
+    // Variables of the modules in scope, states hold their values in
+    // this order.
+    variables: Vec<String>,
+    files: Vec<File<'a>>,
+    // Files by module name, including the modules nested in them.
+    file_of: HashMap<String, usize>,
+    constants: HashMap<String, Value>,
+    // Values of constant definitions without parameters, by file and
+    // the byte offset of the name.
+    cache: Mutex<HashMap<(usize, usize), Value>>,
+    output: Mutex<Vec<String>>,
+}
+
+// Module file together with its resolution.
+struct File<'a> {
+    decls: Vec<Decl>,
+    // Referenced declarations by the byte offset of the reference.
+    ref_at: HashMap<usize, usize>,
+    // Declarations by the byte offset of the declared name.
+    decl_at: HashMap<usize, usize>,
+    // Definitions by the byte offset of the name, with a flag that is set
+    // for the top level ones. LET definitions are included.
+    defs: HashMap<usize, (&'a Definition, bool)>,
+    // Unnamed `INSTANCE M` of the modules in the file.
+    instances: Vec<&'a Instance>,
+    // Top level definitions of constant level, their values are cached.
+    constant: HashSet<usize>,
+}
+
+static NO_MODULES: Vec<Module> = Vec::new();
+
+impl<'a> Evaluator<'a> {
+    pub fn new(module: &'a Module, res: &Resolution) -> Self {
+        Evaluator::with_modules(module, res, &NO_MODULES)
+    }
+
+    /// Evaluator for a module that uses the user-defined modules from
+    /// `modules`.
+    pub fn with_modules(module: &'a Module, res: &Resolution, modules: &'a dyn Modules) -> Self {
+        let mut ev = Evaluator {
+            module,
+            scope: Vec::new(),
+            variables: Vec::new(),
+            files: Vec::new(),
+            file_of: HashMap::new(),
+            constants: HashMap::new(),
+            cache: Mutex::new(HashMap::new()),
+            output: Mutex::new(Vec::new()),
+        };
+        ev.add_file(module, res, modules);
+        let mut queue: Vec<String> = dependencies(module).into_iter().collect();
+        while let Some(name) = queue.pop() {
+            if ev.file_of.contains_key(&name) {
+                continue;
+            }
+            // Standard modules are not loaded.
+            if let Some(m) = modules.module(&name) {
+                let res = resolve_with(m, modules);
+                ev.add_file(m, &res, modules);
+                queue.extend(dependencies(m));
+            }
+        }
+        ev.extend(module, modules);
+        ev
+    }
+
+    fn extend(&mut self, module: &'a Module, modules: &'a dyn Modules) {
+        if self.scope.iter().any(|m| m.name.name == module.name.name) {
+            return;
+        }
+        self.scope.push(module);
+        for name in &module.extends {
+            if let Some(m) = modules.module(&name.name) {
+                self.extend(m, modules);
+            }
+        }
+        for v in module.variables() {
+            if !self.variables.contains(&v.name) {
+                self.variables.push(v.name.clone());
+            }
+        }
+    }
+
+    fn add_file(&mut self, module: &'a Module, res: &Resolution, modules: &dyn Modules) {
+        fn collect<'a>(m: &'a Module, file: &mut File<'a>, names: &mut Vec<String>) {
+            names.push(m.name.name.clone());
+            let let_defs = |e: &'a Expr, file: &mut File<'a>| e.walk(&mut |e| {
+                if let ExprKind::Let { defs, .. } = &e.kind {
+                    for d in defs {
+                        file.defs.insert(d.name.span.start.byte_offset, (d, false));
+                    }
+                }
+            });
+            for u in &m.units {
+                match u {
+                    Unit::Definition(def) => {
+                        file.defs.insert(def.name.span.start.byte_offset, (def, true));
+                        match &def.body {
+                            DefBody::Expr(e) | DefBody::Function(_, e) => let_defs(e, file),
+                            DefBody::Instance(_) => {}
+                        }
+                    }
+                    Unit::Instance(inst) => file.instances.push(inst),
+                    Unit::Assume(a) => let_defs(&a.expr, file),
+                    Unit::Module(m) => collect(m, file, names),
+                    _ => {}
+                }
+            }
+        }
+        let mut file = File {
+            decls: res.decls.clone(),
+            ref_at: res.references.iter().map(|r| (r.span.start.byte_offset, r.decl)).collect(),
+            decl_at: HashMap::new(),
+            defs: HashMap::new(),
+            instances: Vec::new(),
+            constant: HashSet::new(),
+        };
+        let mut names = Vec::new();
+        collect(module, &mut file, &mut names);
+        for (i, d) in res.decls.iter().enumerate() {
+            let local = d.module.iter().all(|m| names.contains(m));
+            if let (true, Some(span)) = (local, d.span) {
+                file.decl_at.insert(span.start.byte_offset, i);
+            }
+        }
+        let levels = check_with(module, res, modules);
+        for def in module.definitions() {
+            let constant = levels.definitions.get(&def.name.name) == Some(&Level::Constant);
+            if constant && def.params.is_empty() {
+                file.constant.insert(def.name.span.start.byte_offset);
+            }
+        }
+        for name in names {
+            self.file_of.insert(name, self.files.len());
+        }
+        self.files.push(file);
+    }
+
+    /// Sets the value of a constant of the module.
+    pub fn set_constant(&mut self, name: &str, value: Value) {
+        self.constants.insert(name.to_string(), value);
+        self.cache.lock().unwrap().clear();
+    }
+
+    pub fn constant(&self, name: &str) -> Option<&Value> {
+        self.constants.get(name)
+    }
+
+    /// Lines printed by `Print` and `PrintT` since the last call.
+    pub fn take_output(&self) -> Vec<String> {
+        std::mem::take(&mut *self.output.lock().unwrap())
+    }
+
+    /// Evaluates an expression of the module.
+    pub fn eval(&self, expr: &'a Expr) -> Result<Value, Error> {
+        self.expr(&Ctx::new(0), expr)
+    }
+
+    /// Evaluates a top level definition without parameters.
+    pub fn definition(&self, name: &str) -> Result<Value, Error> {
+        match self.module.definition(name) {
+            Some(def) if def.params.is_empty() => self.definition_value(&Ctx::new(0), def, true),
+            Some(def) => Err(Error::Unsupported {
+                span: def.name.span,
+                what: format!("operator `{}` without arguments", name),
+            }),
+            None => Err(Error::Undefined { name: name.to_string(), span: self.module.name.span }),
+        }
+    }
+
+    /// Checks the ASSUME statements of the module, in the order of appearance.
+    pub fn assumptions(&self) -> Vec<(Span, Result<bool, Error>)> {
+        self.module.units
+            .iter()
+            .filter_map(|u| match u {
+                Unit::Assume(a) => Some((a.expr.span, self.bool(&Ctx::new(0), &a.expr))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn expr(&self, cx: &Ctx<'a>, e: &'a Expr) -> Result<Value, Error> {
+        match &e.kind {
+            ExprKind::Num(n) => match Int::parse(n, 10) {
+                Some(i) => Ok(Value::Int(i)),
+                None => Err(Error::Unsupported { span: e.span, what: "real numbers".to_string() }),
+            },
+            ExprKind::Str(s) => Ok(Value::str(s)),
+            ExprKind::Apply { path, name, args } => self.apply(cx, path, name, args, e.span),
+            ExprKind::OpApply { op, args } => self.apply(cx, &[], op, args, e.span),
+            ExprKind::Junction { kind, items } => {
+                let stop = *kind == Junction::Or;
+                for item in items {
+                    if self.bool(cx, item)? == stop {
+                        return Ok(Value::Bool(stop));
+                    }
+                }
+                Ok(Value::Bool(!stop))
+            }
+            ExprKind::FnApply(f, args) => self.fn_apply(cx, f, args, e.span),
+            ExprKind::Field(r, field) => {
+                let r = self.expr(cx, r)?;
+                if !r.is_function() {
+                    return Err(type_error("a record", &r, e.span));
+                }
+                r.apply(&Value::str(&field.name)).ok_or_else(|| Error::Domain {
+                    span: field.span,
+                    arg: format!("\"{}\"", field.name),
+                })
+            }
+            ExprKind::Quant { kind: Quantifier::Forall, bounds, body } => {
+                let all = self.bind(cx, &flatten(bounds), &mut vec![], &mut |cx, _| {
+                    self.bool(cx, body)
+                })?;
+                Ok(Value::Bool(all))
+            }
+            ExprKind::Quant { kind: Quantifier::Exists, bounds, body } => {
+                let none = self.bind(cx, &flatten(bounds), &mut vec![], &mut |cx, _| {
+                    Ok(!self.bool(cx, body)?)
+                })?;
+                Ok(Value::Bool(!none))
+            }
+            ExprKind::Quant { .. } => Err(temporal(e.span)),
+            ExprKind::Choose { bound, body } => {
+                let mut found = None;
+                self.bind(cx, &flatten(std::slice::from_ref(bound)), &mut vec![], &mut |cx, key| {
+                    if self.bool(cx, body)? {
+                        found = Some(key[0].clone());
+                        return Ok(false);
+                    }
+                    Ok(true)
+                })?;
+                found.ok_or(Error::NoValue { span: e.span })
+            }
+            ExprKind::SetEnum(items) => {
+                let items = items.iter().map(|e| self.expr(cx, e)).collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::set(items))
+            }
+            ExprKind::SetFilter { bound, pred } => {
+                let mut res = Vec::new();
+                self.bind(cx, &flatten(std::slice::from_ref(bound)), &mut vec![], &mut |cx, key| {
+                    if self.bool(cx, pred)? {
+                        res.push(key[0].clone());
+                    }
+                    Ok(true)
+                })?;
+                Ok(Value::set(res))
+            }
+            ExprKind::SetMap { expr, bounds } => {
+                let mut res = Vec::new();
+                self.bind(cx, &flatten(bounds), &mut vec![], &mut |cx, _| {
+                    res.push(self.expr(cx, expr)?);
+                    Ok(true)
+                })?;
+                Ok(Value::set(res))
+            }
+            ExprKind::FnCons { bounds, body } => self.function(cx, bounds, body),
+            ExprKind::FnSet(s, t) => {
+                let s = self.set(cx, s)?;
+                let t = self.set(cx, t)?;
+                Ok(Value::lazy(LazySet::Functions(s, t)))
+            }
+            ExprKind::Record(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(k, v)| Ok((k.name.clone(), self.expr(cx, v)?)))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                Ok(Value::record(fields))
+            }
+            ExprKind::RecordSet(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(k, s)| Ok((k.name.clone(), self.set(cx, s)?)))
+                    .collect::<Result<BTreeMap<_, _>, Error>>()?;
+                Ok(Value::lazy(LazySet::Records(fields)))
+            }
+            ExprKind::Except { base, updates } => {
+                let mut f = self.expr(cx, base)?;
+                for u in updates {
+                    let path = u.path
+                        .iter()
+                        .map(|k| match k {
+                            ExceptKey::Field(name) => Ok(Value::str(&name.name)),
+                            ExceptKey::Index(args) => self.argument(cx, args),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    f = self.except(cx, &f, &path, &u.value, base.span)?;
+                }
+                Ok(f)
+            }
+            ExprKind::At => cx.at.clone().ok_or_else(|| Error::Unsupported {
+                span: e.span,
+                what: "`@` outside of EXCEPT".to_string(),
+            }),
+            ExprKind::Tuple(items) => {
+                let items = items.iter().map(|e| self.expr(cx, e)).collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::seq(items))
+            }
+            ExprKind::If { cond, then, other } => {
+                if self.bool(cx, cond)? {
+                    self.expr(cx, then)
+                } else {
+                    self.expr(cx, other)
+                }
+            }
+            ExprKind::Case { arms, other } => {
+                for (guard, e) in arms {
+                    if self.bool(cx, guard)? {
+                        return self.expr(cx, e);
+                    }
+                }
+                match other {
+                    Some(e) => self.expr(cx, e),
+                    None => Err(Error::NoValue { span: e.span }),
+                }
+            }
+            // LET definitions are found by their references.
+            ExprKind::Let { body, .. } => self.expr(cx, body),
+            ExprKind::Lambda { .. } => Err(Error::Unsupported {
+                span: e.span,
+                what: "LAMBDA outside of an operator argument".to_string(),
+            }),
+            ExprKind::BoxAction { action, sub } if cx.next.is_some() => {
+                Ok(Value::Bool(self.bool(cx, action)? || self.unchanged(cx, sub)?))
+            }
+            ExprKind::AngleAction { action, sub } if cx.next.is_some() => {
+                Ok(Value::Bool(self.bool(cx, action)? && !self.unchanged(cx, sub)?))
+            }
+            ExprKind::BoxAction { .. } | ExprKind::AngleAction { .. } | ExprKind::Fairness { .. } => {
+                Err(temporal(e.span))
+            }
+        }
+    }
+
+    fn bool(&self, cx: &Ctx<'a>, e: &'a Expr) -> Result<bool, Error> {
+        match self.expr(cx, e)? {
+            Value::Bool(b) => Ok(b),
+            v => Err(type_error("a Boolean", &v, e.span)),
+        }
+    }
+
+    fn set(&self, cx: &Ctx<'a>, e: &'a Expr) -> Result<Value, Error> {
+        match self.expr(cx, e)? {
+            v if v.is_set() => Ok(v),
+            v => Err(type_error("a set", &v, e.span)),
+        }
+    }
+
+    // Argument of a function: `f[a]` or the tuple in `f[a, b]`.
+    fn argument(&self, cx: &Ctx<'a>, args: &'a [Expr]) -> Result<Value, Error> {
+        match args {
+            [a] => self.expr(cx, a),
+            _ => Ok(Value::seq(args.iter().map(|a| self.expr(cx, a)).collect::<Result<Vec<_>, _>>()?)),
+        }
+    }
+
+    // Calls `f` for each assignment of values to the bound variables, until
+    // it returns false. The result is false if the iteration was stopped.
+    // `key` holds the value of each component of the bounds.
+    fn bind(
+        &self,
+        cx: &Ctx<'a>,
+        bounds: &[Component<'a>],
+        key: &mut Vec<Value>,
+        f: &mut Visit<'a, '_>,
+    ) -> Result<bool, Error> {
+        let (c, rest) = match bounds.split_first() {
+            Some(split) => split,
+            None => return f(cx, key),
+        };
+        let set = match c.set {
+            Some(set) => set,
+            None => return Err(Error::Infinite { span: c.vars[0].span }),
+        };
+        let s = self.set(cx, set)?;
+        let elems = s.elements().ok_or(Error::Infinite { span: set.span })?;
+        for x in elems.iter() {
+            let cx = self.destructure(cx, c, x, set.span)?;
+            key.push(x.clone());
+            let go = self.bind(&cx, rest, key, f)?;
+            key.pop();
+            if !go {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Binds the variables of the component to the value.
+    fn destructure(&self, cx: &Ctx<'a>, c: &Component<'a>, x: &Value, span: Span) -> Result<Ctx<'a>, Error> {
+        let mut cx = cx.clone();
+        if !c.tuple {
+            let id = self.declared(cx.file, &c.vars[0])?;
+            return Ok(cx.with(id, Binding::Value(x.clone())));
+        }
+        match x {
+            Value::Seq(items) if items.len() == c.vars.len() => {
+                for (v, item) in c.vars.iter().zip(items.iter()) {
+                    let id = self.declared(cx.file, v)?;
+                    cx = cx.with(id, Binding::Value(item.clone()));
+                }
+                Ok(cx)
+            }
+            _ => Err(type_error("a tuple", x, span)),
+        }
+    }
+
+    fn declared(&self, file: usize, name: &Ident) -> Result<usize, Error> {
+        self.files[file]
+            .decl_at
+            .get(&name.span.start.byte_offset)
+            .copied()
+            .ok_or_else(|| Error::Undefined { name: name.name.clone(), span: name.span })
+    }
+
+    fn function(&self, cx: &Ctx<'a>, bounds: &'a [Bound], body: &'a Expr) -> Result<Value, Error> {
+        let components = flatten(bounds);
+        let mut pairs = Vec::new();
+        self.bind(cx, &components, &mut vec![], &mut |cx, key| {
+            let arg = match key {
+                [k] => k.clone(),
+                _ => Value::seq(key.to_vec()),
+            };
+            pairs.push((arg, self.expr(cx, body)?));
+            Ok(true)
+        })?;
+        Ok(Value::function(pairs))
+    }
+
+    fn fn_apply(&self, cx: &Ctx<'a>, f: &'a Expr, args: &'a [Expr], span: Span) -> Result<Value, Error> {
+        // Functions defined with `f[x \in S] == e` are applied without
+        // computing the whole function, so they can be recursive and have
+        // infinite domains.
+        if let ExprKind::Apply { path, name, args: fn_args } = &f.kind {
+            let id = self.files[cx.file].ref_at.get(&name.span.start.byte_offset);
+            if let (true, Some(&id)) = (fn_args.is_empty(), id) {
+                let decl = &self.files[cx.file].decls[id];
+                if decl.kind == DeclKind::Function && cx.lookup(id).is_none() {
+                    let (body_cx, def) = self.locate(cx, path, decl, name.span)?;
+                    if let DefBody::Function(bounds, body) = &def.body {
+                        let arg = self.argument(cx, args)?;
+                        return self.apply_definition(&body_cx, bounds, body, arg, span);
+                    }
+                }
+            }
+        }
+        let fv = self.expr(cx, f)?;
+        if !fv.is_function() {
+            return Err(type_error("a function", &fv, f.span));
+        }
+        let arg = self.argument(cx, args)?;
+        fv.apply(&arg).ok_or_else(|| Error::Domain { span, arg: arg.to_string() })
+    }
+
+    fn apply_definition(
+        &self,
+        cx: &Ctx<'a>,
+        bounds: &'a [Bound],
+        body: &'a Expr,
+        arg: Value,
+        span: Span,
+    ) -> Result<Value, Error> {
+        let components = flatten(bounds);
+        let parts = match (&arg, components.len()) {
+            (_, 1) => vec![arg.clone()],
+            (Value::Seq(items), n) if items.len() == n => items.to_vec(),
+            _ => return Err(Error::Domain { span, arg: arg.to_string() }),
+        };
+        let mut cx = cx.clone();
+        for (c, x) in components.iter().zip(&parts) {
+            if let Some(set) = c.set {
+                if self.set(&cx, set)?.contains(x) != Some(true) {
+                    return Err(Error::Domain { span, arg: arg.to_string() });
+                }
+            }
+            cx = self.destructure(&cx, c, x, span)?;
+        }
+        self.expr(&cx, body)
+    }
+
+    fn except(&self, cx: &Ctx<'a>, f: &Value, path: &[Value], e: &'a Expr, span: Span) -> Result<Value, Error> {
+        if !f.is_function() {
+            return Err(type_error("a function", f, span));
+        }
+        // Arguments outside of the domain leave the function unchanged.
+        let old = match f.apply(&path[0]) {
+            Some(old) => old,
+            None => return Ok(f.clone()),
+        };
+        let new = if path.len() == 1 {
+            let mut cx = cx.clone();
+            cx.at = Some(old);
+            self.expr(&cx, e)?
+        } else {
+            self.except(cx, &old, &path[1..], e, span)?
+        };
+        Ok(f.except(&path[0], new))
+    }
+
+    fn apply(
+        &self,
+        cx: &Ctx<'a>,
+        path: &'a [Ident],
+        name: &'a Ident,
+        args: &'a [Expr],
+        span: Span,
+    ) -> Result<Value, Error> {
+        let file = &self.files[cx.file];
+        let id = match file.ref_at.get(&name.span.start.byte_offset) {
+            Some(&id) => id,
+            None => return Err(Error::Undefined { name: name.name.clone(), span: name.span }),
+        };
+        if path.is_empty() {
+            match cx.lookup(id) {
+                Some(Binding::Value(v)) => return Ok(v.clone()),
+                Some(Binding::Op(op)) => {
+                    let args = self.arguments(cx, &op.arities(), args)?;
+                    return self.call(cx, op, args, span);
+                }
+                None => {}
+            }
+        }
+        let decl = &file.decls[id];
+        match decl.kind {
+            DeclKind::Builtin => self.builtin(cx, &decl.name, args, span),
+            DeclKind::Constant if args.is_empty() => self.parameter(cx, &decl.name, name.span),
+            DeclKind::Constant => Err(Error::Unsupported {
+                span,
+                what: format!("constant operator `{}`", decl.name),
+            }),
+            DeclKind::Variable if args.is_empty() => self.variable(cx, &decl.name, name.span),
+            DeclKind::Variable => Err(Error::Unsupported {
+                span,
+                what: format!("variable `{}` with arguments", decl.name),
+            }),
+            DeclKind::Operator | DeclKind::Function if decl.span.is_none() => {
+                self.standard(cx, &decl.name, args, span)
+            }
+            DeclKind::Operator | DeclKind::Function => {
+                let (body_cx, def) = self.locate(cx, path, decl, name.span)?;
+                let top = self.files[body_cx.file].defs[&def.name.span.start.byte_offset].1;
+                let arities: Vec<usize> = def.params.iter().map(|p| p.arity).collect();
+                let args = self.arguments(cx, &arities, args)?;
+                if args.is_empty() {
+                    return self.definition_value(&body_cx, def, top);
+                }
+                self.call(cx, &Closure::Def { cx: body_cx, def }, args, span)
+            }
+            DeclKind::Instance | DeclKind::Parameter | DeclKind::Bound => Err(Error::Unsupported {
+                span,
+                what: format!("`{}` as a value", decl.name),
+            }),
+        }
+    }
+
+    // Value of a definition without parameters, cached for the top level
+    // constant ones.
+    fn definition_value(&self, cx: &Ctx<'a>, def: &'a Definition, top: bool) -> Result<Value, Error> {
+        let key = (cx.file, def.name.span.start.byte_offset);
+        let cached = top && cx.subst.is_none() && self.files[cx.file].constant.contains(&key.1);
+        if cached {
+            if let Some(v) = self.cache.lock().unwrap().get(&key) {
+                return Ok(v.clone());
+            }
+        }
+        let v = match &def.body {
+            DefBody::Expr(e) => self.expr(cx, e)?,
+            DefBody::Function(bounds, e) => self.function(cx, bounds, e)?,
+            DefBody::Instance(_) => return Err(Error::Unsupported {
+                span: def.name.span,
+                what: format!("instance `{}` as a value", def.name.name),
+            }),
+        };
+        if cached {
+            self.cache.lock().unwrap().insert(key, v.clone());
+        }
+        Ok(v)
+    }
+
+    // Finds the definition of a declaration and the context to evaluate
+    // its body in: top level definitions don't see the bound variables of
+    // the caller, definitions of instantiated modules see the substitutions.
+    fn locate(
+        &self,
+        cx: &Ctx<'a>,
+        path: &'a [Ident],
+        decl: &Decl,
+        span: Span,
+    ) -> Result<(Ctx<'a>, &'a Definition), Error> {
+        let mut subst = cx.subst.clone();
+        for step in path {
+            let id = self.files[cx.file].ref_at.get(&step.span.start.byte_offset);
+            let step_decl = match id {
+                Some(&id) => &self.files[cx.file].decls[id],
+                None => return Err(Error::Undefined { name: step.name.clone(), span: step.span }),
+            };
+            let (file, def, _) = self.find_definition(cx.file, step_decl, step.span)?;
+            let inst = match &def.body {
+                DefBody::Instance(inst) => inst,
+                _ => return Err(Error::Undefined { name: step.name.clone(), span: step.span }),
+            };
+            let outer = cx.moved(file, None, subst);
+            subst = Some(Arc::new(Subst { inst, outer }));
+        }
+        let (file, def, top) = self.find_definition(cx.file, decl, span)?;
+        if path.is_empty() {
+            let inst = self.files[cx.file]
+                .instances
+                .iter()
+                .find(|inst| decl.module.as_ref() == Some(&inst.module.name));
+            if let Some(inst) = inst {
+                let outer = cx.moved(cx.file, None, subst.clone());
+                subst = Some(Arc::new(Subst { inst, outer }));
+            }
+        }
+        let env = if top { None } else { cx.env.clone() };
+        Ok((cx.moved(file, env, subst), def))
+    }
+
+    fn find_definition(&self, file: usize, decl: &Decl, span: Span) -> Result<(usize, &'a Definition, bool), Error> {
+        let file = match &decl.module {
+            Some(m) => self.file_of.get(m).copied(),
+            None => Some(file),
+        };
+        let found = file.and_then(|f| {
+            let offset = decl.span?.start.byte_offset;
+            self.files[f].defs.get(&offset).map(|&(def, top)| (f, def, top))
+        });
+        found.ok_or_else(|| Error::Undefined { name: decl.name.clone(), span })
+    }
+
+    // Values of the arguments, operators for the parameters of higher-order
+    // operators.
+    fn arguments(&self, cx: &Ctx<'a>, arities: &[usize], args: &'a [Expr]) -> Result<Vec<Binding<'a>>, Error> {
+        args.iter()
+            .enumerate()
+            .map(|(i, a)| match arities.get(i) {
+                Some(&n) if n > 0 => Ok(Binding::Op(self.closure(cx, a)?)),
+                _ => Ok(Binding::Value(self.expr(cx, a)?)),
+            })
+            .collect()
+    }
+
+    // Operator passed as an argument: a name or a LAMBDA.
+    fn closure(&self, cx: &Ctx<'a>, e: &'a Expr) -> Result<Arc<Closure<'a>>, Error> {
+        let (path, name) = match &e.kind {
+            ExprKind::Lambda { params, body } => {
+                return Ok(Arc::new(Closure::Lambda { cx: cx.clone(), params, body }));
+            }
+            ExprKind::Apply { path, name, args } if args.is_empty() => (path.as_slice(), name),
+            ExprKind::OpApply { op, args } if args.is_empty() => (&[][..], op),
+            _ => return Err(type_error("an operator", &self.expr(cx, e)?, e.span)),
+        };
+        let file = &self.files[cx.file];
+        let id = match file.ref_at.get(&name.span.start.byte_offset) {
+            Some(&id) => id,
+            None => return Err(Error::Undefined { name: name.name.clone(), span: name.span }),
+        };
+        if let Some(Binding::Op(op)) = cx.lookup(id) {
+            return Ok(op.clone());
+        }
+        let decl = &file.decls[id];
+        if decl.span.is_none() {
+            return Ok(Arc::new(Closure::Builtin(decl.name.clone())));
+        }
+        let (cx, def) = self.locate(cx, path, decl, name.span)?;
+        Ok(Arc::new(Closure::Def { cx, def }))
+    }
+
+    // Calls the operator in the states of the caller `cx`.
+    fn call(&self, cx: &Ctx<'a>, op: &Closure<'a>, args: Vec<Binding<'a>>, span: Span) -> Result<Value, Error> {
+        match op {
+            Closure::Def { cx: def_cx, def } => {
+                let mut cx = cx.moved(def_cx.file, def_cx.env.clone(), def_cx.subst.clone());
+                for (p, arg) in def.params.iter().zip(args) {
+                    let id = self.declared(cx.file, &p.name)?;
+                    cx = cx.with(id, arg);
+                }
+                self.definition_value(&cx, def, false)
+            }
+            Closure::Lambda { cx: lambda_cx, params, body } => {
+                let mut cx = cx.moved(lambda_cx.file, lambda_cx.env.clone(), lambda_cx.subst.clone());
+                for (p, arg) in params.iter().zip(args) {
+                    let id = self.declared(cx.file, p)?;
+                    cx = cx.with(id, arg);
+                }
+                self.expr(&cx, body)
+            }
+            Closure::Builtin(name) => {
+                let args = args
+                    .into_iter()
+                    .map(|a| match a {
+                        Binding::Value(v) => Ok(v),
+                        Binding::Op(_) => Err(Error::Unsupported {
+                            span,
+                            what: format!("`{}` with operator arguments", name),
+                        }),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.operator(name, &args, span)
+            }
+        }
+    }
+
+    // Value of a constant, which can be substituted in an instance.
+    fn parameter(&self, cx: &Ctx<'a>, name: &str, span: Span) -> Result<Value, Error> {
+        if let Some(s) = &cx.subst {
+            let outer = s.outer(cx);
+            return match s.inst.substitutions.iter().find(|(p, _)| p.name == name) {
+                Some((_, e)) => self.expr(&outer, e),
+                None => self.parameter(&outer, name, span),
+            };
+        }
+        self.constants
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::Unassigned { name: name.to_string(), span })
+    }
+
+    // Value of a variable in the current state, or in the next one under
+    // a prime. Variables can be substituted in an instance, like constants.
+    fn variable(&self, cx: &Ctx<'a>, name: &str, span: Span) -> Result<Value, Error> {
+        if let Some(s) = &cx.subst {
+            let outer = s.outer(cx);
+            return match s.inst.substitutions.iter().find(|(p, _)| p.name == name) {
+                Some((_, e)) => self.expr(&outer, e),
+                None => self.variable(&outer, name, span),
+            };
+        }
+        let state = if cx.primed { &cx.next } else { &cx.state };
+        let (state, i) = match (state, self.variables.iter().position(|v| v == name)) {
+            (Some(state), Some(i)) => (state, i),
+            _ => return Err(Error::Unsupported {
+                span,
+                what: format!("variable `{}` in a constant expression", name),
+            }),
+        };
+        state[i].clone().ok_or_else(|| Error::Unspecified {
+            name: if cx.primed { format!("{}'", name) } else { name.to_string() },
+            span,
+        })
+    }
+
+    // Operators that are not defined in any module.
+    fn builtin(&self, cx: &Ctx<'a>, name: &str, args: &'a [Expr], span: Span) -> Result<Value, Error> {
+        match (name, args) {
+            ("TRUE", []) => Ok(Value::Bool(true)),
+            ("FALSE", []) => Ok(Value::Bool(false)),
+            ("BOOLEAN", []) => Ok(Value::set(vec![Value::Bool(false), Value::Bool(true)])),
+            ("STRING", []) => Ok(Value::lazy(LazySet::String)),
+            ("/\\", [a, b]) => Ok(Value::Bool(self.bool(cx, a)? && self.bool(cx, b)?)),
+            ("\\/", [a, b]) => Ok(Value::Bool(self.bool(cx, a)? || self.bool(cx, b)?)),
+            ("=>", [a, b]) => Ok(Value::Bool(!self.bool(cx, a)? || self.bool(cx, b)?)),
+            ("'", [a]) if cx.next.is_some() => {
+                let mut cx = cx.clone();
+                cx.primed = true;
+                self.expr(&cx, a)
+            }
+            ("UNCHANGED", [a]) if cx.next.is_some() => Ok(Value::Bool(self.unchanged(cx, a)?)),
+            ("ENABLED", [a]) if cx.state.is_some() => Ok(Value::Bool(self.enabled(cx, a)?)),
+            ("'", _) | ("UNCHANGED", _) | ("ENABLED", _) | ("\\cdot", _) => Err(Error::Unsupported {
+                span,
+                what: format!("action operator `{}`", name),
+            }),
+            ("[]", _) | ("<>", _) | ("~>", _) | ("-+->", _) => Err(temporal(span)),
+            _ => {
+                let args = args.iter().map(|a| self.expr(cx, a)).collect::<Result<Vec<_>, _>>()?;
+                self.operator(name, &args, span)
+            }
+        }
+    }
+
+    // Operators of the standard modules.
+    fn standard(&self, cx: &Ctx<'a>, name: &str, args: &'a [Expr], span: Span) -> Result<Value, Error> {
+        if stdlib::params(name).is_none() {
+            let args = args.iter().map(|a| self.expr(cx, a)).collect::<Result<Vec<_>, _>>()?;
+            return self.operator(name, &args, span);
+        }
+        let args = self.arguments(cx, stdlib::params(name).unwrap_or_default(), args)?;
+        let call = |op: &Closure<'a>, args: Vec<Value>| {
+            self.call(cx, op, args.into_iter().map(Binding::Value).collect(), span)
+        };
+        let test = |op: &Closure<'a>, args: Vec<Value>| match call(op, args)? {
+            Value::Bool(b) => Ok(b),
+            v => Err(type_error("a Boolean", &v, span)),
+        };
+        match (name, args.as_slice()) {
+            ("SelectSeq", [Binding::Value(s), Binding::Op(op)]) => {
+                let mut res = Vec::new();
+                for x in seq(s, span)? {
+                    if test(op, vec![x.clone()])? {
+                        res.push(x.clone());
+                    }
+                }
+                Ok(Value::seq(res))
+            }
+            ("SortSeq", [Binding::Value(s), Binding::Op(op)]) => {
+                // Insertion sort, as the comparison can fail.
+                let mut res: Vec<Value> = Vec::new();
+                for x in seq(s, span)? {
+                    let mut i = res.len();
+                    while i > 0 && test(op, vec![x.clone(), res[i - 1].clone()])? {
+                        i -= 1;
+                    }
+                    res.insert(i, x.clone());
+                }
+                Ok(Value::seq(res))
+            }
+            ("BagOfAll", [Binding::Op(op), Binding::Value(b)]) => {
+                let mut res: BTreeMap<Value, Int> = BTreeMap::new();
+                for (x, n) in bag(b, span)? {
+                    let y = call(op, vec![x])?.normalize();
+                    let total = res.get(&y).map_or(n.clone(), |m| m + &n);
+                    res.insert(y, total);
+                }
+                Ok(Value::function(res.into_iter().map(|(k, n)| (k, Value::Int(n)))))
+            }
+            _ => Err(Error::Unsupported { span, what: format!("`{}` with these arguments", name) }),
+        }
+    }
+
+    // Built-in and standard operators applied to values.
+    fn operator(&self, name: &str, args: &[Value], span: Span) -> Result<Value, Error> {
+        let int = |v: &Value| match v {
+            Value::Int(i) => Ok(i.clone()),
+            v => Err(type_error("an integer", v, span)),
+        };
+        let boolean = |v: &Value| match v {
+            Value::Bool(b) => Ok(*b),
+            v => Err(type_error("a Boolean", v, span)),
+        };
+        let set = |v: &Value| match v {
+            v if v.is_set() => Ok(v.clone()),
+            v => Err(type_error("a set", v, span)),
+        };
+        let elements = |v: &Value| {
+            set(v)?.elements().ok_or(Error::Infinite { span })
+        };
+        let invalid = |message: &str| Err(Error::Invalid { span, message: message.to_string() });
+        let v = match (name, args) {
+            // Logic
+            ("/\\", [a, b]) => Value::Bool(boolean(a)? && boolean(b)?),
+            ("\\/", [a, b]) => Value::Bool(boolean(a)? || boolean(b)?),
+            ("=>", [a, b]) => Value::Bool(!boolean(a)? || boolean(b)?),
+            ("<=>", [a, b]) | ("\\equiv", [a, b]) => Value::Bool(boolean(a)? == boolean(b)?),
+            ("~", [a]) => Value::Bool(!boolean(a)?),
+            ("=", [a, b]) => Value::Bool(a.equals(b)),
+            ("/=", [a, b]) => Value::Bool(!a.equals(b)),
+            // Sets
+            ("\\in", [x, s]) => Value::Bool(set(s)?.contains(x) == Some(true)),
+            ("\\notin", [x, s]) => Value::Bool(set(s)?.contains(x) != Some(true)),
+            ("\\subseteq", [a, b]) => {
+                let b = set(b)?;
+                Value::Bool(elements(a)?.iter().all(|x| b.contains(x) == Some(true)))
+            }
+            ("\\cup", [a, b]) => {
+                let mut res = (*elements(a)?).clone();
+                res.extend(elements(b)?.iter().cloned());
+                Value::Set(Arc::new(res))
+            }
+            ("\\cap", [a, b]) => {
+                let b = set(b)?;
+                Value::set(elements(a)?.iter().filter(|x| b.contains(x) == Some(true)).cloned())
+            }
+            ("\\", [a, b]) => {
+                let b = set(b)?;
+                Value::set(elements(a)?.iter().filter(|x| b.contains(x) != Some(true)).cloned())
+            }
+            ("SUBSET", [s]) => Value::lazy(LazySet::Subset(set(s)?.normalize())),
+            ("UNION", [s]) => {
+                let mut res = std::collections::BTreeSet::new();
+                for x in elements(s)?.iter() {
+                    res.extend(elements(x)?.iter().cloned());
+                }
+                Value::Set(Arc::new(res))
+            }
+            ("\\X", sets) => {
+                let sets = sets.iter().map(|s| Ok(set(s)?.normalize())).collect::<Result<Vec<_>, Error>>()?;
+                Value::lazy(LazySet::Product(sets)).normalize()
+            }
+            ("DOMAIN", [f]) => match f.domain() {
+                Some(d) => d,
+                None => return Err(type_error("a function", f, span)),
+            },
+            // Naturals, Integers and Reals
+            ("+", [a, b]) => Value::Int(&int(a)? + &int(b)?),
+            ("-", [a, b]) => Value::Int(&int(a)? - &int(b)?),
+            ("*", [a, b]) => Value::Int(&int(a)? * &int(b)?),
+            ("-.", [a]) => Value::Int(-&int(a)?),
+            ("<", [a, b]) => Value::Bool(int(a)? < int(b)?),
+            ("<=", [a, b]) => Value::Bool(int(a)? <= int(b)?),
+            (">", [a, b]) => Value::Bool(int(a)? > int(b)?),
+            (">=", [a, b]) => Value::Bool(int(a)? >= int(b)?),
+            ("..", [a, b]) => Value::lazy(LazySet::Interval(int(a)?, int(b)?)),
+            ("\\div", [a, b]) => match int(a)?.div_floor(&int(b)?) {
+                Some(q) => Value::Int(q),
+                None => return invalid("division by zero"),
+            },
+            ("%", [a, b]) => {
+                let b = int(b)?;
+                if b <= Int::from(0) {
+                    return invalid("the second argument of % must be positive");
+                }
+                Value::Int(int(a)?.mod_floor(&b).unwrap())
+            }
+            ("^", [a, b]) => {
+                let b = int(b)?;
+                if b.is_negative() {
+                    return invalid("negative exponent");
+                }
+                match b.to_i64().and_then(|b| u32::try_from(b).ok()) {
+                    Some(b) => Value::Int(int(a)?.pow(b)),
+                    None => return invalid("exponent is too large"),
+                }
+            }
+            ("Nat", []) => Value::lazy(LazySet::Nat),
+            ("Int", []) => Value::lazy(LazySet::Int),
+            ("Real", []) => Value::lazy(LazySet::Real),
+            // Sequences
+            ("Seq", [s]) => Value::lazy(LazySet::Seqs(set(s)?.normalize())),
+            ("Len", [Value::Str(s)]) => Value::int(s.chars().count() as i64),
+            ("Len", [s]) => Value::int(seq(s, span)?.len() as i64),
+            ("Head", [s]) => match seq(s, span)?.first() {
+                Some(x) => x.clone(),
+                None => return invalid("Head of the empty sequence"),
+            },
+            ("Tail", [Value::Str(s)]) => match s.chars().next() {
+                Some(c) => Value::str(&s[c.len_utf8()..]),
+                None => return invalid("Tail of the empty sequence"),
+            },
+            ("Tail", [s]) => match seq(s, span)? {
+                [] => return invalid("Tail of the empty sequence"),
+                items => Value::seq(items[1..].to_vec()),
+            },
+            ("Append", [s, x]) => {
+                let mut items = seq(s, span)?.to_vec();
+                items.push(x.clone());
+                Value::seq(items)
+            }
+            ("\\o", [Value::Str(a), Value::Str(b)]) => Value::str(&format!("{}{}", a, b)),
+            ("\\o", [a, b]) => {
+                let mut items = seq(a, span)?.to_vec();
+                items.extend(seq(b, span)?.iter().cloned());
+                Value::seq(items)
+            }
+            ("SubSeq", [s, m, n]) => {
+                let (m, n) = (int(m)?, int(n)?);
+                if m > n {
+                    return Ok(match s {
+                        Value::Str(_) => Value::str(""),
+                        _ => Value::seq(vec![]),
+                    });
+                }
+                let chars: Vec<Value>;
+                let items = match s {
+                    Value::Str(s) => {
+                        chars = s.chars().map(|c| Value::str(&c.to_string())).collect();
+                        &chars[..]
+                    }
+                    s => seq(s, span)?,
+                };
+                let range = match (m.to_i64(), n.to_i64()) {
+                    (Some(m), Some(n)) if m >= 1 && n as usize <= items.len() => m as usize - 1..n as usize,
+                    _ => return invalid("SubSeq index is out of range"),
+                };
+                match s {
+                    Value::Str(_) => Value::str(&items[range]
+                        .iter()
+                        .map(|c| match c {
+                            Value::Str(c) => c.to_string(),
+                            _ => String::new(),
+                        })
+                        .collect::<String>()),
+                    _ => Value::seq(items[range].to_vec()),
+                }
+            }
+            // FiniteSets
+            ("Cardinality", [s]) => match set(s)?.cardinality() {
+                Some(n) => Value::Int(n),
+                None => return Err(Error::Infinite { span }),
+            },
+            ("IsFiniteSet", [s]) => Value::Bool(set(s)?.cardinality().is_some()),
+            // Bags
+            ("EmptyBag", []) => Value::function(vec![]),
+            ("SetToBag", [s]) => Value::function(elements(s)?.iter().map(|x| (x.clone(), Value::int(1)))),
+            ("BagToSet", [b]) => {
+                bag(b, span)?;
+                b.domain().unwrap()
+            }
+            ("BagIn", [x, b]) => Value::Bool(bag(b, span)?.iter().any(|(y, _)| x.equals(y))),
+            ("CopiesIn", [x, b]) => match bag(b, span)?.into_iter().find(|(y, _)| x.equals(y)) {
+                Some((_, n)) => Value::Int(n),
+                None => Value::int(0),
+            },
+            ("\\oplus", [a, b]) => bag_sum(&[bag(a, span)?, bag(b, span)?], false),
+            ("\\ominus", [a, b]) => bag_sum(&[bag(a, span)?, bag(b, span)?], true),
+            ("BagUnion", [s]) => {
+                let bags = elements(s)?.iter().map(|b| bag(b, span)).collect::<Result<Vec<_>, _>>()?;
+                bag_sum(&bags, false)
+            }
+            ("\\sqsubseteq", [a, b]) => {
+                let b = bag(b, span)?;
+                Value::Bool(bag(a, span)?.iter().all(|(x, n)| {
+                    b.iter().any(|(y, m)| x.equals(y) && n <= m)
+                }))
+            }
+            ("SubBag", [b]) => {
+                let mut res = vec![Vec::new()];
+                for (x, n) in bag(b, span)? {
+                    let n = n.to_i64().ok_or(Error::Infinite { span })?;
+                    res = res
+                        .into_iter()
+                        .flat_map(|prefix: Vec<(Value, Value)>| (0..=n).map({
+                            let x = x.clone();
+                            move |k| {
+                                let mut next = prefix.clone();
+                                if k > 0 {
+                                    next.push((x.clone(), Value::int(k)));
+                                }
+                                next
+                            }
+                        }))
+                        .collect();
+                }
+                Value::set(res.into_iter().map(Value::function))
+            }
+            ("BagCardinality", [b]) => Value::Int(bag(b, span)?
+                .iter()
+                .fold(Int::from(0), |acc, (_, n)| &acc + n)),
+            ("IsABag", [b]) => Value::Bool(bag(b, span).is_ok()),
+            // TLC
+            (":>", [x, y]) => Value::function(vec![(x.clone(), y.clone())]),
+            ("@@", [f, g]) => match (f.pairs(), g.pairs()) {
+                (Some(f), Some(g)) => {
+                    let mut map: BTreeMap<Value, Value> = g.into_iter().collect();
+                    map.extend(f);
+                    Value::function(map)
+                }
+                _ => return Err(type_error("a function", if f.is_function() { g } else { f }, span)),
+            },
+            ("Any", []) => Value::lazy(LazySet::Any),
+            ("Assert", [cond, message]) => {
+                if !boolean(cond)? {
+                    let message = match message {
+                        Value::Str(s) => s.to_string(),
+                        v => v.to_string(),
+                    };
+                    return Err(Error::Assert { span, message });
+                }
+                Value::Bool(true)
+            }
+            ("Print", [out, v]) => {
+                self.output.lock().unwrap().push(out.to_string());
+                v.clone()
+            }
+            ("PrintT", [out]) => {
+                self.output.lock().unwrap().push(out.to_string());
+                Value::Bool(true)
+            }
+            ("Permutations", [s]) => {
+                let elems: Vec<Value> = elements(s)?.iter().cloned().collect();
+                let mut res = Vec::new();
+                permutations(&mut elems.clone(), 0, &mut |p| {
+                    res.push(Value::function(elems.iter().cloned().zip(p.iter().cloned())));
+                });
+                Value::set(res)
+            }
+            // TLC picks a random element, but the result should not depend
+            // on the choice, so the first one is taken.
+            ("RandomElement", [s]) => match elements(s)?.iter().next() {
+                Some(x) => x.clone(),
+                None => return invalid("RandomElement of the empty set"),
+            },
+            ("TLCEval", [v]) => v.clone(),
+            ("ToString", [v]) => Value::str(&v.to_string()),
+            _ => return Err(Error::Unsupported { span, what: format!("operator `{}`", name) }),
+        };
+        Ok(v)
+    }
+}
+
+
+// Binding of a name in the environment.
+#[derive(Clone)]
+enum Binding<'a> {
+    Value(Value),
+    Op(Arc<Closure<'a>>),
+}
+
+// Operator that can be called with arguments.
+enum Closure<'a> {
+    Def { cx: Ctx<'a>, def: &'a Definition },
+    Lambda { cx: Ctx<'a>, params: &'a [Ident], body: &'a Expr },
+    Builtin(String),
+}
+
+impl<'a> Closure<'a> {
+    fn arities(&self) -> Vec<usize> {
+        match self {
+            Closure::Def { def, .. } => def.params.iter().map(|p| p.arity).collect(),
+            Closure::Lambda { .. } => vec![],
+            Closure::Builtin(name) => stdlib::params(name).unwrap_or_default().to_vec(),
+        }
+    }
+}
+
+// Bound names as a persistent list, declarations are identified by their
+// ids in the resolution of the file.
+type Env<'a> = Option<Arc<Node<'a>>>;
+
+struct Node<'a> {
+    decl: usize,
+    binding: Binding<'a>,
+    next: Env<'a>,
+}
+
+// Substitutions of an instance, `outer` is the context of the module that
+// instantiates.
+struct Subst<'a> {
+    inst: &'a Instance,
+    outer: Ctx<'a>,
+}
+
+impl<'a> Subst<'a> {
+    // The outer context in the states of `cx`.
+    fn outer(&self, cx: &Ctx<'a>) -> Ctx<'a> {
+        cx.moved(self.outer.file, self.outer.env.clone(), self.outer.subst.clone())
+    }
+}
+
+#[derive(Clone)]
+struct Ctx<'a> {
+    file: usize,
+    env: Env<'a>,
+    subst: Option<Arc<Subst<'a>>>,
+    // Value of `@` in EXCEPT.
+    at: Option<Value>,
+    // Values of the variables in the current and the next state, `None`
+    // for the ones that are not assigned yet.
+    state: Option<State>,
+    next: Option<State>,
+    // Variables refer to the next state, inside of `e'`.
+    primed: bool,
+}
+
+type State = Arc<Vec<Option<Value>>>;
+
+impl<'a> Ctx<'a> {
+    fn new(file: usize) -> Self {
+        Ctx { file, env: None, subst: None, at: None, state: None, next: None, primed: false }
+    }
+
+    // Context of another scope in the same states.
+    fn moved(&self, file: usize, env: Env<'a>, subst: Option<Arc<Subst<'a>>>) -> Self {
+        Ctx {
+            file,
+            env,
+            subst,
+            at: None,
+            state: self.state.clone(),
+            next: self.next.clone(),
+            primed: self.primed,
+        }
+    }
+
+    fn with(mut self, decl: usize, binding: Binding<'a>) -> Self {
+        self.env = Some(Arc::new(Node { decl, binding, next: self.env.take() }));
+        self
+    }
+
+    fn lookup(&self, decl: usize) -> Option<&Binding<'a>> {
+        let mut node = self.env.as_ref();
+        while let Some(n) = node {
+            if n.decl == decl {
+                return Some(&n.binding);
+            }
+            node = n.next.as_ref();
+        }
+        None
+    }
+}
+
+// Callback of `Evaluator::bind`.
+type Visit<'a, 'f> = dyn FnMut(&Ctx<'a>, &[Value]) -> Result<bool, Error> + 'f;
+
+// Bound variables by the components of the function argument: `x, y \in S`
+// has two components and `<<x, y>> \in S` has one.
+struct Component<'a> {
+    vars: &'a [Ident],
+    tuple: bool,
+    set: Option<&'a Expr>,
+}
+
+fn flatten(bounds: &[Bound]) -> Vec<Component<'_>> {
+    let mut res = Vec::new();
+    for b in bounds {
+        if b.tuple {
+            res.push(Component { vars: &b.vars, tuple: true, set: b.set.as_deref() });
+        } else {
+            for v in &b.vars {
+                res.push(Component {
+                    vars: std::slice::from_ref(v),
+                    tuple: false,
+                    set: b.set.as_deref(),
+                });
+            }
+        }
+    }
+    res
+}
+
+fn type_error(expected: &'static str, found: &Value, span: Span) -> Error {
+    let mut text = found.to_string();
+    if text.chars().count() > 60 {
+        text = text.chars().take(57).collect::<String>() + "...";
+    }
+    Error::Type { span, expected, found: format!("{} `{}`", found.kind(), text) }
+}
+
+fn temporal(span: Span) -> Error {
+    Error::Unsupported { span, what: "a temporal formula".to_string() }
+}
+
+fn seq(v: &Value, span: Span) -> Result<&[Value], Error> {
+    match v {
+        Value::Seq(items) => Ok(items),
+        v => Err(type_error("a sequence", v, span)),
+    }
+}
+
+// Elements of a bag with their counts.
+fn bag(v: &Value, span: Span) -> Result<Vec<(Value, Int)>, Error> {
+    v.pairs()
+        .and_then(|pairs| pairs
+            .into_iter()
+            .map(|(x, n)| match n {
+                Value::Int(n) if n > Int::from(0) => Some((x, n)),
+                _ => None,
+            })
+            .collect())
+        .ok_or_else(|| type_error("a bag", v, span))
+}
+
+// Sum of the bags, or the first bag minus the rest.
+fn bag_sum(bags: &[Vec<(Value, Int)>], subtract: bool) -> Value {
+    let mut res: BTreeMap<Value, Int> = BTreeMap::new();
+    for (i, b) in bags.iter().enumerate() {
+        for (x, n) in b {
+            let old = res.get(x).cloned().unwrap_or_else(|| Int::from(0));
+            let new = if subtract && i > 0 { &old - n } else { &old + n };
+            res.insert(x.clone(), new);
+        }
+    }
+    Value::function(res
+        .into_iter()
+        .filter(|(_, n)| *n > Int::from(0))
+        .map(|(x, n)| (x, Value::Int(n))))
+}
+
+fn permutations(items: &mut Vec<Value>, k: usize, f: &mut dyn FnMut(&[Value])) {
+    if k == items.len() {
+        return f(items);
+    }
+    for i in k..items.len() {
+        items.swap(k, i);
+        permutations(items, k + 1, f);
+        items.swap(k, i);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::resolve::resolve;
+
+    fn eval(defs: &str, name: &str) -> Result<Value, Error> {
+        let code = format!(
+            "---- MODULE M ----\nEXTENDS Naturals, Integers, Sequences, FiniteSets, TLC, Bags\n\
+             CONSTANT N\n{}\n====", defs);
+        let module = parse(&code).unwrap();
+        let res = resolve(&module);
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let mut ev = Evaluator::new(&module, &res);
+        ev.set_constant("N", Value::int(3));
+        ev.definition(name)
+    }
+
+    fn value(expr: &str) -> String {
+        match eval(&format!("E == {}", expr), "E") {
+            Ok(v) => v.to_string(),
+            Err(e) => panic!("{}: {}", expr, e),
+        }
+    }
+
+    #[test]
+    fn operators() {
+        let cases = [
+            ("2 ^ 70 - 1", "1180591620717411303423"),
+            ("(-7) \\div 2", "-4"),
+            ("(-7) % 2", "1"),
+            ("{1, 2} \\cup {2, 3} \\ {1}", "{2, 3}"),
+            ("{x \\in 1..10 : x % 3 = 0}", "{3, 6, 9}"),
+            ("{x * x : x \\in 1..N}", "{1, 4, 9}"),
+            ("\\A x \\in 1..N : \\E y \\in 1..N : x + y = 4", "TRUE"),
+            ("CHOOSE x \\in 1..N : x > 1", "2"),
+            ("UNION {{1}, {2}} = 1..2", "TRUE"),
+            ("Cardinality(SUBSET (1..10))", "1024"),
+            ("{1, 2} \\X {\"a\"}", "{<<1, \"a\">>, <<2, \"a\">>}"),
+            ("[x \\in 1..2 |-> x * 10]", "<<10, 20>>"),
+            ("[x \\in {0} |-> x]", "(0 :> 0)"),
+            ("DOMAIN [a |-> 1, b |-> 2]", "{\"a\", \"b\"}"),
+            ("[a |-> 1] = [x \\in {\"a\"} |-> 1]", "TRUE"),
+            ("<<1, 2>> = [i \\in 1..2 |-> i]", "TRUE"),
+            ("[[a |-> <<1, 2>>] EXCEPT !.a[2] = @ + 1, !.a[1] = 0]", "[a |-> <<0, 3>>]"),
+            ("[n \\in 1..9 |-> n * n][3]", "9"),
+            ("<<1, 2>> \\in [1..2 -> Nat] /\\ <<0>> \\notin Seq({1})", "TRUE"),
+            ("<<1, -2>> \\in Nat \\X Nat \\/ <<1, 2>> \\in Nat \\X Int \\X Nat", "FALSE"),
+            ("{<<1, 2>>} \\subseteq Nat \\X Nat /\\ <<1, 2, 3>> \\notin Nat \\X Nat", "TRUE"),
+            ("{[a : {1, 2}, b : {TRUE}]}", "{{[a |-> 1, b |-> TRUE], [a |-> 2, b |-> TRUE]}}"),
+            ("SubSeq(Append(<<1, 2>>, 3) \\o <<4>>, 2, 3)", "<<2, 3>>"),
+            ("Len(Tail(\"abc\")) + Head(<<5>>)", "7"),
+            ("SelectSeq(<<1, 2, 3, 4>>, LAMBDA x : x % 2 = 0)", "<<2, 4>>"),
+            ("SortSeq(<<3, 1, 2>>, LAMBDA a, b : a < b)", "<<1, 2, 3>>"),
+            ("(1 :> \"a\") @@ (2 :> \"b\")", "<<\"a\", \"b\">>"),
+            ("Permutations({1, 2})", "{<<1, 2>>, <<2, 1>>}"),
+            ("CASE N < 0 -> \"neg\" [] N > 0 -> \"pos\"", "\"pos\""),
+            ("LET F(x) == x + N IN F(1)", "4"),
+            ("SetToBag({1}) (+) SetToBag({1, 2})", "<<2, 1>>"),
+            ("BagOfAll(LAMBDA x : x % 2, SetToBag({1, 2, 3}))", "(0 :> 1 @@ 1 :> 2)"),
+        ];
+        for (expr, expected) in cases.iter() {
+            assert_eq!(value(expr), *expected, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn definitions() {
+        let defs = "\
+            RECURSIVE Sum(_)\n\
+            Sum(S) == IF S = {} THEN 0 ELSE LET y == CHOOSE x \\in S : TRUE IN y + Sum(S \\ {y})\n\
+            fact[n \\in Nat] == IF n = 0 THEN 1 ELSE n * fact[n - 1]\n\
+            Map(F(_), s) == [i \\in DOMAIN s |-> F(s[i])]\n\
+            Double(x) == 2 * x\n\
+            E == <<Sum(1..N), fact[20], Map(Double, <<1, 2>>)>>";
+        assert_eq!(eval(defs, "E").unwrap().to_string(), "<<6, 2432902008176640000, <<2, 4>>>>");
+    }
+
+    #[test]
+    fn instances() {
+        let m = parse("---- MODULE M ----\nEXTENDS Naturals\nCONSTANT C\nD == C + 1\n====").unwrap();
+        let code = "---- MODULE Main ----\nEXTENDS Naturals\nI == INSTANCE M WITH C <- 41\nE == I!D\n====";
+        let main = parse(code).unwrap();
+        let modules = vec![m];
+        let res = resolve_with(&main, &modules);
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let ev = Evaluator::with_modules(&main, &res, &modules);
+        assert_eq!(ev.definition("E"), Ok(Value::int(42)));
+    }
+
+    #[test]
+    fn errors() {
+        let message = |defs: &str| eval(defs, "E").unwrap_err().to_string();
+        assert_eq!(message("E == 1 + TRUE"), "4:6: expected an integer, found a Boolean `TRUE`");
+        assert_eq!(message("E == <<1>>[2]"), "4:6: 2 is not in the domain of the function");
+        assert_eq!(message("E == \\E x \\in Nat : x > 1"), "4:15: cannot enumerate an infinite set");
+        assert_eq!(message("E == CHOOSE x \\in {} : TRUE"), "4:6: no value satisfies the condition");
+        assert_eq!(message("E == Assert(N > 5, \"small\")"), "4:6: assertion failed: small");
+        assert_eq!(message("CONSTANT K\nE == K"), "5:6: constant `K` has no value");
+    }
+
+    #[test]
+    fn assumptions() {
+        let code = "---- MODULE M ----\nEXTENDS Naturals\nCONSTANT N\nASSUME N > 2\nASSUME N \\in {1}\n====";
+        let module = parse(code).unwrap();
+        let res = resolve(&module);
+        let mut ev = Evaluator::new(&module, &res);
+        ev.set_constant("N", Value::int(3));
+        let results: Vec<_> = ev.assumptions().into_iter().map(|(_, r)| r).collect();
+        assert_eq!(results, vec![Ok(true), Ok(false)]);
+    }
+}
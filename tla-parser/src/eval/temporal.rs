@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use crate::ast::*;
+
+use super::action::Partial;
+use super::{flatten, Ctx, Error, Evaluator, Value};
+
+
+/// Temporal formula with the definitions and the quantifiers over constant
+/// sets expanded, down to the formulas without temporal operators.
+#[derive(Clone)]
+pub enum Temporal<'a> {
+    Atom(Atom<'a>),
+    Not(Box<Temporal<'a>>),
+    And(Vec<Temporal<'a>>),
+    Or(Vec<Temporal<'a>>),
+    Always(Box<Temporal<'a>>),
+    Eventually(Box<Temporal<'a>>),
+    LeadsTo(Box<Temporal<'a>>, Box<Temporal<'a>>),
+    /// `WF_v(A)` or `SF_v(A)`: `enabled` is `ENABLED <<A>>_v` and `step`
+    /// is `<<A>>_v`.
+    Fairness { strong: bool, enabled: Atom<'a>, step: Atom<'a> },
+}
+
+/// State or action predicate of a temporal formula.
+#[derive(Clone)]
+pub struct Atom<'a> {
+    cx: Ctx<'a>,
+    kind: AtomKind<'a>,
+    span: Span,
+}
+
+#[derive(Clone)]
+enum AtomKind<'a> {
+    Expr(&'a Expr),
+    // `<<A>>_v` and `ENABLED <<A>>_v` of fairness.
+    Step(&'a Expr, &'a Expr),
+    Enabled(&'a Expr, &'a Expr),
+}
+
+impl<'a> Atom<'a> {
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl<'a> Evaluator<'a> {
+    /// Temporal formula of a definition without parameters.
+    pub fn temporal(&self, name: &str) -> Result<Temporal<'a>, Error> {
+        let (cx, _, body) = self.formula(name)?;
+        self.expand_temporal(&cx, body)
+    }
+
+    /// Value of the predicate in the step from `state` to `next`. Stuttering
+    /// steps have `next` equal to `state`.
+    pub fn holds(&self, atom: &Atom<'a>, state: &[Value], next: &[Value]) -> Result<bool, Error> {
+        let mut cx = atom.cx.clone();
+        cx.state = Some(Arc::new(state.iter().cloned().map(Some).collect()));
+        cx.next = Some(Arc::new(next.iter().cloned().map(Some).collect()));
+        match atom.kind {
+            AtomKind::Expr(e) => self.bool(&cx, e),
+            AtomKind::Step(a, sub) => Ok(self.bool(&cx, a)? && !self.unchanged(&cx, sub)?),
+            AtomKind::Enabled(a, sub) => {
+                cx.next = None;
+                let p = Partial::new(self.variables.len(), true);
+                let stopped = !self.act(&cx, a, &p, None, &mut |p| {
+                    let cx = self.assigned(&cx, p);
+                    self.unchanged(&cx, sub)
+                })?;
+                Ok(stopped)
+            }
+        }
+    }
+
+    fn expand_temporal(&self, cx: &Ctx<'a>, e: &'a Expr) -> Result<Temporal<'a>, Error> {
+        let atom = || Temporal::Atom(Atom { cx: cx.clone(), kind: AtomKind::Expr(e), span: e.span });
+        // Formulas of atoms are atoms themselves.
+        let all_atoms = |items: &[Temporal<'a>]| items.iter().all(|t| matches!(t, Temporal::Atom(_)));
+        let res = match &e.kind {
+            ExprKind::Junction { kind, items } => {
+                let items = items
+                    .iter()
+                    .map(|item| self.expand_temporal(cx, item))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if all_atoms(&items) {
+                    return Ok(atom());
+                }
+                match kind {
+                    Junction::And => Temporal::And(items),
+                    Junction::Or => Temporal::Or(items),
+                }
+            }
+            ExprKind::Quant { kind, bounds, body } if *kind == Quantifier::Forall || *kind == Quantifier::Exists => {
+                let mut items = Vec::new();
+                let expanded = self.bind(cx, &flatten(bounds), &mut vec![], &mut |cx, _| {
+                    items.push(self.expand_temporal(cx, body)?);
+                    Ok(true)
+                });
+                // Sets that depend on the state make the formula a predicate.
+                if expanded.is_err() || all_atoms(&items) {
+                    return Ok(atom());
+                }
+                match kind {
+                    Quantifier::Forall => Temporal::And(items),
+                    _ => Temporal::Or(items),
+                }
+            }
+            ExprKind::Let { body, .. } => self.expand_temporal(cx, body)?,
+            ExprKind::Fairness { strong, sub, action } => Temporal::Fairness {
+                strong: *strong,
+                enabled: Atom { cx: cx.clone(), kind: AtomKind::Enabled(action, sub), span: e.span },
+                step: Atom { cx: cx.clone(), kind: AtomKind::Step(action, sub), span: e.span },
+            },
+            ExprKind::OpApply { op, args } if self.is_builtin(cx, op) => {
+                let mut args = args
+                    .iter()
+                    .map(|a| self.expand_temporal(cx, a))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let temporal = matches!(op.name.as_str(), "[]" | "<>" | "~>");
+                if !temporal && all_atoms(&args) {
+                    return Ok(atom());
+                }
+                let not = |t: Temporal<'a>| Temporal::Not(Box::new(t));
+                match (op.name.as_str(), args.len()) {
+                    ("[]", 1) => Temporal::Always(Box::new(args.remove(0))),
+                    ("<>", 1) => Temporal::Eventually(Box::new(args.remove(0))),
+                    ("~>", 2) => {
+                        let a = args.remove(0);
+                        Temporal::LeadsTo(Box::new(a), Box::new(args.remove(0)))
+                    }
+                    ("~", 1) => not(args.remove(0)),
+                    ("/\\", _) => Temporal::And(args),
+                    ("\\/", _) => Temporal::Or(args),
+                    ("=>", 2) => {
+                        let a = args.remove(0);
+                        Temporal::Or(vec![not(a), args.remove(0)])
+                    }
+                    ("<=>", 2) | ("\\equiv", 2) => {
+                        let (a, b) = (args.remove(0), args.remove(0));
+                        Temporal::Or(vec![
+                            Temporal::And(vec![a.clone(), b.clone()]),
+                            Temporal::And(vec![not(a), not(b)]),
+                        ])
+                    }
+                    _ => return Err(Error::Unsupported {
+                        span: e.span,
+                        what: format!("`{}` in a temporal formula", op.name),
+                    }),
+                }
+            }
+            ExprKind::Apply { path, name, args } => self.expand_definition(cx, e, path, name, args)?,
+            ExprKind::OpApply { op, args } => self.expand_definition(cx, e, &[], op, args)?,
+            _ => atom(),
+        };
+        Ok(res)
+    }
+
+    fn expand_definition(
+        &self,
+        cx: &Ctx<'a>,
+        e: &'a Expr,
+        path: &'a [Ident],
+        name: &'a Ident,
+        args: &'a [Expr],
+    ) -> Result<Temporal<'a>, Error> {
+        let expanded = match self.expand(cx, path, name, args)? {
+            Some((body_cx, body)) => self.expand_temporal(&body_cx, body)?,
+            None => return Ok(Temporal::Atom(Atom { cx: cx.clone(), kind: AtomKind::Expr(e), span: e.span })),
+        };
+        match expanded {
+            Temporal::Atom(_) => Ok(Temporal::Atom(Atom { cx: cx.clone(), kind: AtomKind::Expr(e), span: e.span })),
+            t => Ok(t),
+        }
+    }
+}
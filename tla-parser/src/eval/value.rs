@@ -0,0 +1,554 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
+
+use super::int::Int;
+
+
+/// Value of a TLA+ expression.
+///
+/// Functions are kept in a canonical form, so that equal values are
+/// structurally equal: a function with the domain `1..n` is a sequence,
+/// a function with a non-empty domain of strings is a record, and the empty
+/// function is the empty sequence. Tuples are sequences as well.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Value {
+    Bool(bool),
+    Int(Int),
+    Str(Arc<str>),
+    /// Model value, which is equal only to itself.
+    Model(Arc<str>),
+    Set(Arc<BTreeSet<Value>>),
+    Seq(Arc<Vec<Value>>),
+    Record(Arc<BTreeMap<String, Value>>),
+    Fun(Arc<BTreeMap<Value, Value>>),
+    Lazy(Arc<LazySet>),
+}
+
+/// Set that is not enumerated until needed: infinite sets, and sets that
+/// are mostly used in membership tests, like `[S -> T]` in a type invariant.
+/// Elements of finite sets are enumerated when the set is compared or put
+/// into another value.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LazySet {
+    Nat,
+    Int,
+    /// `Real` contains only integers, as there are no real numbers.
+    Real,
+    String,
+    /// `Any` of the TLC module, the set of all values.
+    Any,
+    /// `a..b`
+    Interval(Int, Int),
+    /// `SUBSET S`
+    Subset(Value),
+    /// `[S -> T]`
+    Functions(Value, Value),
+    /// `[a : S, b : T]`
+    Records(BTreeMap<String, Value>),
+    /// `Seq(S)`
+    Seqs(Value),
+    /// `S \X T`, of infinite sets: the finite ones are enumerated.
+    Product(Vec<Value>),
+}
+
+impl Value {
+    pub fn int(n: i64) -> Value {
+        Value::Int(Int::from(n))
+    }
+
+    pub fn str(s: &str) -> Value {
+        Value::Str(s.into())
+    }
+
+    pub fn model(name: &str) -> Value {
+        Value::Model(name.into())
+    }
+
+    pub fn set<I: IntoIterator<Item = Value>>(items: I) -> Value {
+        Value::Set(Arc::new(items.into_iter().map(Value::normalize).collect()))
+    }
+
+    pub fn seq<I: IntoIterator<Item = Value>>(items: I) -> Value {
+        Value::Seq(Arc::new(items.into_iter().map(Value::normalize).collect()))
+    }
+
+    pub fn record<I: IntoIterator<Item = (String, Value)>>(fields: I) -> Value {
+        let fields: BTreeMap<_, _> = fields.into_iter().map(|(k, v)| (k, v.normalize())).collect();
+        if fields.is_empty() {
+            return Value::seq(vec![]);
+        }
+        Value::Record(Arc::new(fields))
+    }
+
+    /// Function with the given pairs, in the canonical form.
+    pub fn function<I: IntoIterator<Item = (Value, Value)>>(pairs: I) -> Value {
+        let map: BTreeMap<_, _> = pairs
+            .into_iter()
+            .map(|(k, v)| (k.normalize(), v.normalize()))
+            .collect();
+        let mut index = Int::from(0);
+        let is_seq = map.keys().all(|k| {
+            index = &index + &Int::from(1);
+            matches!(k, Value::Int(i) if *i == index)
+        });
+        if is_seq {
+            return Value::Seq(Arc::new(map.into_values().collect()));
+        }
+        if map.keys().all(|k| matches!(k, Value::Str(_))) {
+            return Value::Record(Arc::new(map
+                .into_iter()
+                .map(|(k, v)| match k {
+                    Value::Str(s) => (s.to_string(), v),
+                    _ => unreachable!(),
+                })
+                .collect()));
+        }
+        Value::Fun(Arc::new(map))
+    }
+
+    pub fn lazy(set: LazySet) -> Value {
+        Value::Lazy(Arc::new(set))
+    }
+
+    /// Enumerates finite lazy sets, other values are returned as is.
+    pub fn normalize(self) -> Value {
+        match &self {
+            Value::Lazy(set) => match set.elements() {
+                Some(elems) => Value::Set(Arc::new(elems)),
+                None => self,
+            },
+            _ => self,
+        }
+    }
+
+    fn normalized(&self) -> Cow<'_, Value> {
+        match self {
+            Value::Lazy(_) => Cow::Owned(self.clone().normalize()),
+            _ => Cow::Borrowed(self),
+        }
+    }
+
+    /// Equality of values, which compares the elements of lazy sets.
+    pub fn equals(&self, other: &Value) -> bool {
+        self.normalized() == other.normalized()
+    }
+
+    /// Description of the kind of the value, for error messages.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "a Boolean",
+            Value::Int(_) => "an integer",
+            Value::Str(_) => "a string",
+            Value::Model(_) => "a model value",
+            Value::Set(_) | Value::Lazy(_) => "a set",
+            Value::Seq(_) => "a sequence",
+            Value::Record(_) => "a record",
+            Value::Fun(_) => "a function",
+        }
+    }
+
+    pub fn is_set(&self) -> bool {
+        matches!(self, Value::Set(_) | Value::Lazy(_))
+    }
+
+    pub fn is_function(&self) -> bool {
+        matches!(self, Value::Seq(_) | Value::Record(_) | Value::Fun(_))
+    }
+
+    /// Elements of a finite set. `None` for infinite sets and values
+    /// that are not sets.
+    pub fn elements(&self) -> Option<Arc<BTreeSet<Value>>> {
+        match self {
+            Value::Set(elems) => Some(elems.clone()),
+            Value::Lazy(set) => set.elements().map(Arc::new),
+            _ => None,
+        }
+    }
+
+    /// Membership test, `None` if the value is not a set.
+    pub fn contains(&self, v: &Value) -> Option<bool> {
+        match self {
+            Value::Set(elems) => Some(elems.contains(&v.normalized())),
+            Value::Lazy(set) => Some(set.contains(v)),
+            _ => None,
+        }
+    }
+
+    /// Number of elements of a finite set.
+    pub fn cardinality(&self) -> Option<Int> {
+        match self {
+            Value::Set(elems) => Some(Int::from(elems.len() as i64)),
+            Value::Lazy(set) => set.cardinality(),
+            _ => None,
+        }
+    }
+
+    pub fn domain(&self) -> Option<Value> {
+        match self {
+            Value::Seq(items) => Some(Value::lazy(LazySet::Interval(
+                Int::from(1),
+                Int::from(items.len() as i64),
+            ))),
+            Value::Record(fields) => Some(Value::set(fields.keys().map(|k| Value::str(k)))),
+            Value::Fun(map) => Some(Value::Set(Arc::new(map.keys().cloned().collect()))),
+            _ => None,
+        }
+    }
+
+    /// Function application, `None` if the argument is not in the domain.
+    pub fn apply(&self, arg: &Value) -> Option<Value> {
+        match (self, arg) {
+            (Value::Seq(items), Value::Int(i)) => {
+                let i = i.to_i64()?;
+                if i < 1 {
+                    return None;
+                }
+                items.get(i as usize - 1).cloned()
+            }
+            (Value::Record(fields), Value::Str(s)) => fields.get(&**s).cloned(),
+            (Value::Fun(map), arg) => map.get(&arg.normalized()).cloned(),
+            _ => None,
+        }
+    }
+
+    /// Pairs of a function, ordered by the argument.
+    pub fn pairs(&self) -> Option<Vec<(Value, Value)>> {
+        match self {
+            Value::Seq(items) => Some(items
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (Value::int(i as i64 + 1), v.clone()))
+                .collect()),
+            Value::Record(fields) => Some(fields
+                .iter()
+                .map(|(k, v)| (Value::str(k), v.clone()))
+                .collect()),
+            Value::Fun(map) => Some(map.iter().map(|(k, v)| (k.clone(), v.clone())).collect()),
+            _ => None,
+        }
+    }
+
+    /// Function that maps `arg` to `v` and agrees with this one elsewhere.
+    /// `arg` must be in the domain.
+    pub fn except(&self, arg: &Value, v: Value) -> Value {
+        let v = v.normalize();
+        match (self, arg) {
+            (Value::Seq(items), Value::Int(i)) => {
+                let mut items = (**items).clone();
+                if let Some(item) = i.to_i64().filter(|&i| i >= 1).and_then(|i| items.get_mut(i as usize - 1)) {
+                    *item = v;
+                }
+                Value::Seq(Arc::new(items))
+            }
+            (Value::Record(fields), Value::Str(s)) => {
+                let mut fields = (**fields).clone();
+                fields.insert(s.to_string(), v);
+                Value::Record(Arc::new(fields))
+            }
+            (Value::Fun(map), arg) => {
+                let mut map = (**map).clone();
+                map.insert(arg.clone().normalize(), v);
+                Value::Fun(Arc::new(map))
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+impl LazySet {
+    pub fn is_finite(&self) -> bool {
+        match self {
+            LazySet::Nat | LazySet::Int | LazySet::Real | LazySet::String | LazySet::Any => false,
+            LazySet::Interval(..) => true,
+            LazySet::Subset(s) => is_finite(s),
+            LazySet::Functions(s, t) => is_finite(s) && is_finite(t),
+            LazySet::Records(fields) => fields.values().all(is_finite),
+            LazySet::Seqs(_) => false,
+            LazySet::Product(sets) => sets.iter().all(is_finite),
+        }
+    }
+
+    fn elements(&self) -> Option<BTreeSet<Value>> {
+        if !self.is_finite() {
+            return None;
+        }
+        match self {
+            LazySet::Interval(a, b) => {
+                let mut res = BTreeSet::new();
+                let mut i = a.clone();
+                while i <= *b {
+                    let next = &i + &Int::from(1);
+                    res.insert(Value::Int(i));
+                    i = next;
+                }
+                Some(res)
+            }
+            LazySet::Subset(s) => {
+                let elems: Vec<Value> = s.elements()?.iter().cloned().collect();
+                if elems.len() >= 64 {
+                    return None;
+                }
+                Some((0..1u64 << elems.len())
+                    .map(|mask| Value::set(elems
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| mask & (1 << i) != 0)
+                        .map(|(_, e)| e.clone())))
+                    .collect())
+            }
+            LazySet::Functions(s, t) => {
+                let domain: Vec<Value> = s.elements()?.iter().cloned().collect();
+                let range: Vec<Value> = t.elements()?.iter().cloned().collect();
+                let choices = vec![range; domain.len()];
+                Some(product(&choices)
+                    .into_iter()
+                    .map(|vs| Value::function(domain.iter().cloned().zip(vs)))
+                    .collect())
+            }
+            LazySet::Records(fields) => {
+                let choices: Vec<Vec<Value>> = fields
+                    .values()
+                    .map(|s| s.elements().map(|e| e.iter().cloned().collect()))
+                    .collect::<Option<_>>()?;
+                Some(product(&choices)
+                    .into_iter()
+                    .map(|vs| Value::record(fields.keys().cloned().zip(vs)))
+                    .collect())
+            }
+            LazySet::Product(sets) => {
+                let choices: Vec<Vec<Value>> = sets
+                    .iter()
+                    .map(|s| s.elements().map(|e| e.iter().cloned().collect()))
+                    .collect::<Option<_>>()?;
+                Some(product(&choices).into_iter().map(Value::seq).collect())
+            }
+            _ => None,
+        }
+    }
+
+    fn contains(&self, v: &Value) -> bool {
+        match (self, v) {
+            (LazySet::Any, _) => true,
+            (LazySet::Nat, Value::Int(i)) => !i.is_negative(),
+            (LazySet::Int, Value::Int(_)) | (LazySet::Real, Value::Int(_)) => true,
+            (LazySet::String, Value::Str(_)) => true,
+            (LazySet::Interval(a, b), Value::Int(i)) => a <= i && i <= b,
+            (LazySet::Subset(s), v) if v.is_set() => match v.elements() {
+                Some(elems) => elems.iter().all(|e| s.contains(e) == Some(true)),
+                None => false,
+            },
+            (LazySet::Functions(s, t), v) => match (v.domain(), v.pairs()) {
+                (Some(domain), Some(pairs)) => {
+                    domain.equals(s) && pairs.iter().all(|(_, y)| t.contains(y) == Some(true))
+                }
+                _ => false,
+            },
+            (LazySet::Records(fields), Value::Record(r)) => {
+                fields.len() == r.len()
+                    && fields.iter().all(|(k, s)| match r.get(k) {
+                        Some(v) => s.contains(v) == Some(true),
+                        None => false,
+                    })
+            }
+            (LazySet::Seqs(s), Value::Seq(items)) => {
+                items.iter().all(|v| s.contains(v) == Some(true))
+            }
+            (LazySet::Product(sets), Value::Seq(items)) => {
+                sets.len() == items.len() && sets.iter().zip(items.iter()).all(|(s, v)| s.contains(v) == Some(true))
+            }
+            _ => false,
+        }
+    }
+
+    fn cardinality(&self) -> Option<Int> {
+        if !self.is_finite() {
+            return None;
+        }
+        match self {
+            LazySet::Interval(a, b) if a > b => Some(Int::from(0)),
+            LazySet::Interval(a, b) => Some(&(b - a) + &Int::from(1)),
+            LazySet::Subset(s) => {
+                let n = s.cardinality()?.to_i64()?;
+                Some(Int::from(2).pow(u32::try_from(n).ok()?))
+            }
+            LazySet::Functions(s, t) => {
+                let n = s.cardinality()?.to_i64()?;
+                Some(t.cardinality()?.pow(u32::try_from(n).ok()?))
+            }
+            LazySet::Records(fields) => fields
+                .values()
+                .try_fold(Int::from(1), |acc, s| Some(&acc * &s.cardinality()?)),
+            LazySet::Product(sets) => sets
+                .iter()
+                .try_fold(Int::from(1), |acc, s| Some(&acc * &s.cardinality()?)),
+            _ => None,
+        }
+    }
+}
+
+fn is_finite(v: &Value) -> bool {
+    match v {
+        Value::Lazy(set) => set.is_finite(),
+        _ => true,
+    }
+}
+
+// All the ways to pick one element from each of the lists.
+fn product(choices: &[Vec<Value>]) -> Vec<Vec<Value>> {
+    let mut res = vec![vec![]];
+    for options in choices {
+        res = res
+            .into_iter()
+            .flat_map(|prefix| options.iter().map(move |o| {
+                let mut next = prefix.clone();
+                next.push(o.clone());
+                next
+            }))
+            .collect();
+    }
+    res
+}
+
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn list<T: fmt::Display>(f: &mut fmt::Formatter, items: impl Iterator<Item = T>) -> fmt::Result {
+            for (i, item) in items.enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}", item)?;
+            }
+            Ok(())
+        }
+        match self {
+            Value::Bool(true) => f.write_str("TRUE"),
+            Value::Bool(false) => f.write_str("FALSE"),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Str(s) => {
+                f.write_str("\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\t' => f.write_str("\\t")?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                f.write_str("\"")
+            }
+            Value::Model(name) => f.write_str(name),
+            Value::Set(elems) => {
+                f.write_str("{")?;
+                list(f, elems.iter())?;
+                f.write_str("}")
+            }
+            Value::Seq(items) => {
+                f.write_str("<<")?;
+                list(f, items.iter())?;
+                f.write_str(">>")
+            }
+            Value::Record(fields) => {
+                f.write_str("[")?;
+                list(f, fields.iter().map(|(k, v)| format!("{} |-> {}", k, v)))?;
+                f.write_str("]")
+            }
+            Value::Fun(map) => {
+                f.write_str("(")?;
+                for (i, (k, v)) in map.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" @@ ")?;
+                    }
+                    write!(f, "{} :> {}", k, v)?;
+                }
+                f.write_str(")")
+            }
+            Value::Lazy(set) => write!(f, "{}", set),
+        }
+    }
+}
+
+impl fmt::Display for LazySet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LazySet::Nat => f.write_str("Nat"),
+            LazySet::Int => f.write_str("Int"),
+            LazySet::Real => f.write_str("Real"),
+            LazySet::String => f.write_str("STRING"),
+            LazySet::Any => f.write_str("Any"),
+            LazySet::Interval(a, b) => write!(f, "{}..{}", a, b),
+            LazySet::Subset(s) if matches!(s, Value::Lazy(_)) => write!(f, "SUBSET ({})", s),
+            LazySet::Subset(s) => write!(f, "SUBSET {}", s),
+            LazySet::Functions(s, t) => write!(f, "[{} -> {}]", s, t),
+            LazySet::Records(fields) => {
+                f.write_str("[")?;
+                for (i, (k, s)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{} : {}", k, s)?;
+                }
+                f.write_str("]")
+            }
+            LazySet::Seqs(s) => write!(f, "Seq({})", s),
+            LazySet::Product(sets) => {
+                for (i, s) in sets.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" \\X ")?;
+                    }
+                    write!(f, "{}", s)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_functions() {
+        let seq = Value::function(vec![(Value::int(2), Value::str("b")), (Value::int(1), Value::str("a"))]);
+        assert_eq!(seq, Value::seq(vec![Value::str("a"), Value::str("b")]));
+        let rec = Value::function(vec![(Value::str("x"), Value::int(1))]);
+        assert_eq!(rec, Value::record(vec![("x".to_string(), Value::int(1))]));
+        assert_eq!(Value::function(vec![]), Value::seq(vec![]));
+        let fun = Value::function(vec![(Value::int(0), Value::Bool(true))]);
+        assert_eq!(fun.to_string(), "(0 :> TRUE)");
+        assert_eq!(rec.to_string(), "[x |-> 1]");
+    }
+
+    #[test]
+    fn lazy_sets() {
+        let bits = Value::set(vec![Value::int(0), Value::int(1)]);
+        let funs = Value::lazy(LazySet::Functions(Value::lazy(LazySet::Interval(Int::from(1), Int::from(3))), bits.clone()));
+        assert_eq!(funs.cardinality(), Some(Int::from(8)));
+        assert_eq!(funs.elements().map(|e| e.len()), Some(8));
+        let f = Value::seq(vec![Value::int(0), Value::int(1), Value::int(1)]);
+        assert_eq!(funs.contains(&f), Some(true));
+        let subsets = Value::lazy(LazySet::Subset(bits.clone()));
+        assert!(subsets.equals(&Value::set(vec![
+            Value::set(vec![]),
+            Value::set(vec![Value::int(0)]),
+            Value::set(vec![Value::int(1)]),
+            bits,
+        ])));
+        let nat = Value::lazy(LazySet::Nat);
+        assert_eq!(nat.contains(&Value::int(-1)), Some(false));
+        assert_eq!(nat.elements(), None);
+        assert_eq!(Value::lazy(LazySet::Seqs(nat.clone())).to_string(), "Seq(Nat)");
+        let pairs = Value::lazy(LazySet::Product(vec![nat.clone(), nat]));
+        assert_eq!(pairs.contains(&Value::seq(vec![Value::int(1), Value::int(2)])), Some(true));
+        assert_eq!(pairs.contains(&Value::seq(vec![Value::int(1), Value::int(-2)])), Some(false));
+        assert_eq!(pairs.contains(&Value::seq(vec![Value::int(1)])), Some(false));
+        assert_eq!(pairs.elements(), None);
+        assert_eq!(pairs.to_string(), "Nat \\X Nat");
+    }
+}
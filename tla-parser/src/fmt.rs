@@ -0,0 +1,416 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use crate::cst::{Cst, Token};
+use crate::lexer::{self, Keyword, TokenType};
+use crate::parser::{self, normalize};
+
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Use Unicode symbols for the operators instead of ASCII.
+    pub unicode: bool,
+    /// Indentation of a definition body that starts on the next line.
+    pub indent: usize,
+    /// Width of separator lines and module headers.
+    pub width: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config { unicode: false, indent: 4, width: 80 }
+    }
+}
+
+
+/// Formats the module. The source must parse, the result has the same
+/// meaning: bullets that were aligned stay aligned, and lines keep their
+/// position relative to the tokens they were written under.
+pub fn format(source: &str, config: &Config) -> Result<String, parser::Error> {
+    parser::parse(source)?;
+    let cst = Cst::new(source);
+    let mut layout = Layout::new(&cst);
+    layout.indent = Some(config.indent);
+    let range = match cst.module_range() {
+        Some(range) => range,
+        None => return Ok(source.to_string()),
+    };
+    for i in range.clone() {
+        if let Some(s) = symbol(&cst.tokens[i], config.unicode) {
+            layout.replace(i, s);
+        }
+    }
+    let lines: Vec<_> = layout.lines.iter().filter(|l| range.contains(&l.start)).cloned().collect();
+    for line in &lines {
+        let first = &cst.tokens[line.start];
+        match line.clone().map(|i| cst.tokens[i].kind).collect::<Vec<_>>()[..] {
+            [TokenType::Separator] => layout.text[line.start] = "-".repeat(config.width).into(),
+            [TokenType::ModuleEnd] => layout.text[line.start] = "=".repeat(config.width).into(),
+            [TokenType::Separator, TokenType::Keyword(Keyword::Module), TokenType::Identifier, TokenType::Separator] => {
+                let title = 7 + cst.tokens[line.start + 2].text.chars().count();
+                let left = config.width.saturating_sub(title + 2) / 2;
+                let right = config.width.saturating_sub(title + 2 + left);
+                layout.text[line.start] = "-".repeat(left.max(4)).into();
+                layout.text[line.end - 1] = "-".repeat(right.max(4)).into();
+                for i in line.start + 1..line.end {
+                    layout.gaps[i] = 1;
+                }
+            }
+            _ => {}
+        }
+        // One space after a bullet.
+        if is_junction(first) && line.len() > 1 {
+            layout.gaps[line.start + 1] = 1;
+        }
+    }
+    for i in range {
+        if cst.tokens[i].kind == TokenType::DefEq {
+            layout.gaps[i] = layout.gaps[i].max(1);
+            if i + 1 < cst.tokens.len() && !cst.tokens[i + 1].starts_line() {
+                layout.gaps[i + 1] = 1;
+            }
+        }
+    }
+    align_definitions(&mut layout, &lines);
+    Ok(layout.render())
+}
+
+/// Symbol to use for the token, if it is different.
+pub(crate) fn symbol(token: &Token, unicode: bool) -> Option<&'static str> {
+    match token.kind {
+        TokenType::String | TokenType::Comment | TokenType::Identifier | TokenType::Number => None,
+        _ if unicode => lexer::to_unicode(normalize(token.text)),
+        _ => lexer::to_ascii(token.text),
+    }
+}
+
+fn is_junction(token: &Token) -> bool {
+    let text = lexer::to_ascii(token.text).unwrap_or(token.text);
+    matches!(normalize(text), "/\\" | "\\/")
+}
+
+/// Aligns `==` in runs of adjacent top-level definitions.
+fn align_definitions(layout: &mut Layout, lines: &[Range<usize>]) {
+    let mut runs: Vec<Vec<(Range<usize>, usize)>> = Vec::new();
+    let mut previous: Option<usize> = None;
+    for (n, line) in lines.iter().enumerate() {
+        match definition_head(layout.cst, line) {
+            // Definitions with the body on the next lines are not aligned.
+            Some(eq) if eq + 1 < line.end => {
+                let adjacent = previous == Some(n - 1) && layout.cst.tokens[line.start].line_breaks() == 1;
+                if !adjacent {
+                    runs.push(Vec::new());
+                }
+                runs.last_mut().unwrap().push((line.clone(), eq));
+                previous = Some(n);
+            }
+            _ => previous = None,
+        }
+    }
+    for run in runs.iter().filter(|r| r.len() > 1) {
+        let widths: Vec<usize> = run.iter().map(|(line, eq)| layout.width(line.start..*eq)).collect();
+        let target = widths.iter().max().unwrap() + 1;
+        for ((_, eq), w) in run.iter().zip(widths) {
+            layout.gaps[*eq] = target - w;
+        }
+    }
+}
+
+/// Index of `==` if the line starts a top-level definition.
+fn definition_head(cst: &Cst, line: &Range<usize>) -> Option<usize> {
+    let first = &cst.tokens[line.start];
+    let starts = first.start.col == 1
+        && matches!(first.kind, TokenType::Identifier | TokenType::Keyword(Keyword::Local));
+    if !starts {
+        return None;
+    }
+    let mut depth = 0i32;
+    for i in line.clone() {
+        match cst.tokens[i].kind {
+            TokenType::ParenOpen | TokenType::BracketOpen => depth += 1,
+            TokenType::ParenClose | TokenType::BracketClose => depth -= 1,
+            TokenType::DefEq if depth == 0 => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+
+/// Layout of the tokens of a module. Changing the text of a token or the
+/// spaces before it moves the tokens after it on the same line; lines below
+/// are moved along with the tokens they are aligned to.
+pub(crate) struct Layout<'a> {
+    cst: &'a Cst<'a>,
+    lines: Vec<Range<usize>>,
+    /// Text of the tokens.
+    pub text: Vec<Cow<'a, str>>,
+    /// Spaces before the tokens that are not first on their line.
+    pub gaps: Vec<usize>,
+    /// Indentation of a definition body that starts on the line after
+    /// `==`. `None` keeps it relative to the definition.
+    pub indent: Option<usize>,
+}
+
+// Line that is already laid out.
+struct Placed {
+    indent: usize,
+    cols: Vec<Placement>,
+    // Line is a definition head that ends with `==`.
+    head: bool,
+}
+
+// Old and new columns of a token.
+struct Placement {
+    start: (usize, usize),
+    // `None` for multiline tokens.
+    end: Option<(usize, usize)>,
+}
+
+impl<'a> Layout<'a> {
+    pub fn new(cst: &'a Cst<'a>) -> Self {
+        let gaps = cst.tokens
+            .iter()
+            .enumerate()
+            .map(|(i, t)| match i {
+                0 => 0,
+                _ => t.start.col.saturating_sub(cst.tokens[i - 1].end.col),
+            })
+            .collect();
+        Layout {
+            cst,
+            lines: cst.lines(),
+            text: cst.tokens.iter().map(|t| Cow::Borrowed(t.text)).collect(),
+            gaps,
+            indent: None,
+        }
+    }
+
+    /// Replaces the token text, adding a space where the new text would
+    /// merge with its neighbour.
+    pub fn replace(&mut self, i: usize, text: &'a str) {
+        self.text[i] = Cow::Borrowed(text);
+        let tokens = &self.cst.tokens;
+        if i > 0 && self.gaps[i] == 0 && !tokens[i].starts_line() && merges(&self.text[i - 1], text) {
+            self.gaps[i] = 1;
+        }
+        if i + 1 < tokens.len() && self.gaps[i + 1] == 0 && !tokens[i + 1].starts_line()
+            && merges(text, &self.text[i + 1])
+        {
+            self.gaps[i + 1] = 1;
+        }
+    }
+
+    /// Width of the tokens on a line, with the spaces between them.
+    fn width(&self, tokens: Range<usize>) -> usize {
+        let start = tokens.start;
+        tokens.map(|i| {
+            let gap = if i == start { 0 } else { self.gaps[i] };
+            gap + self.text[i].chars().count()
+        }).sum()
+    }
+
+    /// New indentation of a line that was at column `col`.
+    fn indentation(&self, placed: &[Placed], col: usize) -> usize {
+        for p in placed.iter().rev() {
+            if p.indent > col {
+                continue;
+            }
+            if p.indent == col {
+                return p.cols[0].start.1;
+            }
+            if let (true, Some(indent)) = (p.head, self.indent) {
+                return p.cols[0].start.1 + indent;
+            }
+            // Keep the offset from the closest token to the left, counting
+            // from its end if the line starts after it.
+            let c = p.cols.iter().rev().find(|c| c.start.0 <= col).unwrap();
+            let (old, new) = match c.end {
+                Some(end) if end.0 <= col => end,
+                _ => c.start,
+            };
+            return new + (col - old);
+        }
+        col
+    }
+
+    fn is_head(&self, line: &Range<usize>) -> bool {
+        let tokens = &self.cst.tokens[line.clone()];
+        tokens.last().map(|t| t.kind) == Some(TokenType::DefEq)
+            && tokens.iter().all(|t| match t.kind {
+                TokenType::Keyword(k) => k == Keyword::Local,
+                _ => !is_junction(t),
+            })
+    }
+
+    /// Text of the module with the new layout. Text outside of the module
+    /// is kept as is.
+    pub fn render(&self) -> String {
+        let (cst, tokens) = (self.cst, &self.cst.tokens);
+        let range = match cst.module_range() {
+            Some(range) => range,
+            None => return cst.to_string(),
+        };
+        let mut out = cst.source[..tokens[range.start].start.byte_offset].to_string();
+        self.render_with(&mut out, &mut |_, text, out| out.push_str(text));
+        out.push_str(&cst.source[tokens[range.end - 1].end.byte_offset..]);
+        out
+    }
+
+    /// Lays out the tokens of the module, `token` writes the token with
+    /// the given index and text.
+    pub fn render_with(&self, out: &mut String, token: &mut dyn FnMut(usize, &str, &mut String)) {
+        let tokens = &self.cst.tokens;
+        let range = match self.cst.module_range() {
+            Some(range) => range,
+            None => return,
+        };
+        let mut placed: Vec<Placed> = Vec::new();
+        for line in self.lines.iter().filter(|l| range.contains(&l.start)) {
+            let line = line.start..line.end.min(range.end);
+            let first = &tokens[line.start];
+            let indent = if line.start == range.start {
+                first.start.col
+            } else {
+                out.push_str(&"\n".repeat(first.line_breaks()));
+                self.indentation(&placed, first.start.col)
+            };
+            out.push_str(&" ".repeat(indent - 1));
+            let mut col = indent;
+            let mut cols = Vec::new();
+            for i in line.clone() {
+                if i != line.start {
+                    out.push_str(&" ".repeat(self.gaps[i]));
+                    col += self.gaps[i];
+                }
+                let start = (tokens[i].start.col, col);
+                let text = &self.text[i];
+                token(i, text, out);
+                let end = match text.rfind('\n') {
+                    Some(n) => {
+                        col = text[n + 1..].chars().count() + 1;
+                        None
+                    }
+                    None => {
+                        col += text.chars().count();
+                        Some((tokens[i].end.col, col))
+                    }
+                };
+                cols.push(Placement { start, end });
+            }
+            placed.push(Placed { indent: first.start.col, cols, head: self.is_head(&line) });
+        }
+    }
+}
+
+/// Whether the texts would be read as different tokens without a space
+/// between them.
+fn merges(left: &str, right: &str) -> bool {
+    // `\inS` is lexed as `\in` and `S`, but is hard to read, and so is
+    // `A\in`.
+    let word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let backslash_word = right.strip_prefix('\\').is_some_and(|r| r.starts_with(char::is_alphabetic));
+    if word(left.chars().last()) && (word(right.chars().next()) || backslash_word) {
+        return true;
+    }
+    let text = format!("{}{}", left, right);
+    let lexemes = lexer::lex(&text);
+    match &lexemes[..] {
+        [a, b] => a.end.byte_offset != left.len() || b.end.byte_offset != text.len(),
+        _ => true,
+    }
+}
+
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn fmt(code: &str) -> String {
+        format(code, &Config { width: 30, ..Config::default() }).unwrap()
+    }
+
+    // Debug form of the module without the spans.
+    pub(crate) fn structure(code: &str) -> String {
+        let s = format!("{:?}", parse(code).unwrap());
+        let mut res = String::new();
+        let mut rest = s.as_str();
+        while let Some(i) = rest.find("span: Span {") {
+            res.push_str(&rest[..i]);
+            let mut depth = 0;
+            let end = rest[i..].char_indices().find(|&(_, c)| {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                c == '}' && depth == 0
+            }).unwrap().0;
+            rest = &rest[i + end + 1..];
+        }
+        res.push_str(rest);
+        res
+    }
+
+    const MESSY: &str = "\
+Some text
+---- MODULE M ----
+EXTENDS Naturals
+VARIABLE x, y
+------
+Init==x = 0 /\\ y = 0   \\* start
+LongName(a)   ==a
+Next ==
+      /\\   x' = x + 1
+      /\\ \\/ y' = y
+         \\/    y' = 0
+             + 1
+      (* kept *)
+Spec ==   /\\ Init
+          /\\ [][Next]_<<x, y>>
+=====
+trailing";
+
+    #[test]
+    fn layout() {
+        assert_eq!(fmt(MESSY), "\
+Some text
+---------- MODULE M ----------
+EXTENDS Naturals
+VARIABLE x, y
+------------------------------
+Init        == x = 0 /\\ y = 0   \\* start
+LongName(a) == a
+Next ==
+    /\\ x' = x + 1
+    /\\ \\/ y' = y
+       \\/ y' = 0
+           + 1
+    (* kept *)
+Spec == /\\ Init
+        /\\ [][Next]_<<x, y>>
+==============================
+trailing");
+    }
+
+    #[test]
+    fn keeps_meaning() {
+        let out = fmt(MESSY);
+        assert_eq!(structure(&out), structure(MESSY));
+        assert_eq!(fmt(&out), out);
+    }
+
+    #[test]
+    fn unicode() {
+        let cfg = Config { unicode: true, ..Config::default() };
+        let code = "---- MODULE M ----\nA == /\\ \\A x \\in {} : x # 1\n     /\\ <<1>> \\land \"/\\\\\"\n====";
+        let out = format(code, &cfg).unwrap();
+        assert!(out.contains("A ≜ ∧ ∀ x ∈ {} : x ≠ 1\n    ∧ ⟨1⟩ ∧ \"/\\\\\"\n"), "{}", out);
+
+        let code = "---- MODULE M ----\nVARIABLE v\nA == v' = v\nSpec == []<><<A>>_v /\\ [][A]_v /\\ WF_v(A)\n====";
+        let out = format(code, &cfg).unwrap();
+        assert!(out.contains("⟨A⟩_v"), "{}", out);
+        assert_eq!(structure(&out), structure(code));
+    }
+}
@@ -0,0 +1,128 @@
+use crate::lexer::{next_token, Error, Lexer, TokenType};
+
+
+/// Splits the source into tokens and the text between them, which is passed
+/// with `None`. Unrecognized text is passed as `Unknown`.
+pub fn tokens(source: &str, f: &mut dyn FnMut(Option<TokenType>, &str)) {
+    let mut lx = Lexer::new(source);
+    let mut offset = 0;
+    loop {
+        let (start, end, kind) = match next_token(&mut lx) {
+            Ok((start, end, kind)) => (start.byte_offset, end.byte_offset, kind),
+            Err(Error::EndOfString) => break,
+            Err(Error::NotRecognized) => {
+                let start = lx.pos.byte_offset;
+                if lx.next_char().is_err() || lx.pos.byte_offset == start {
+                    break;
+                }
+                (start, lx.pos.byte_offset, TokenType::Unknown)
+            }
+            // Unterminated string or comment.
+            Err(_) => break,
+        };
+        if start > offset {
+            f(None, &source[offset..start]);
+        }
+        match kind {
+            TokenType::Indent => f(None, &source[start..end]),
+            kind => f(Some(kind), &source[start..end]),
+        }
+        offset = end;
+    }
+    if offset < source.len() {
+        f(Some(TokenType::Unknown), &source[offset..]);
+    }
+}
+
+/// Stylesheet for the classes used by `html`.
+pub const CSS: &str = "\
+pre.tla { font-family: monospace; }
+.tla .Separator, .tla .ModuleEnd, .tla .DefEq { font-weight: bold; }
+.tla .Keyword { color: #0000c0; font-weight: bold; }
+.tla .Number { color: #008080; }
+.tla .String { color: #a31515; }
+.tla .Comment { color: #008000; font-style: italic; }
+.tla .PrefixOperator, .tla .InfixOperator, .tla .PostfixOperator { color: #804000; }
+.tla .Unknown { color: #ff0000; text-decoration: underline wavy; }
+";
+
+/// Renders the source as a `<pre>` element with a `<span>` for each token,
+/// of the class `TokenType::name`. Punctuation and identifiers are not
+/// wrapped, as they are not styled.
+pub fn html(source: &str) -> String {
+    let mut out = String::from("<pre class=\"tla\">");
+    tokens(source, &mut |kind, text| {
+        let text = text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        match kind {
+            Some(kind) if ansi_style(kind).is_some() => {
+                out.push_str(&format!("<span class=\"{}\">{}</span>", kind.name(), text));
+            }
+            _ => out.push_str(&text),
+        }
+    });
+    out.push_str("</pre>\n");
+    out
+}
+
+/// SGR parameters for the token in a terminal.
+pub fn ansi_style(kind: TokenType) -> Option<&'static str> {
+    match kind {
+        TokenType::Separator | TokenType::ModuleEnd | TokenType::DefEq => Some("1"),
+        TokenType::Keyword(_) => Some("1;34"),
+        TokenType::Number => Some("36"),
+        TokenType::String => Some("31"),
+        TokenType::Comment => Some("32"),
+        TokenType::PrefixOperator | TokenType::InfixOperator | TokenType::PostfixOperator => Some("33"),
+        TokenType::Unknown => Some("4;31"),
+        _ => None,
+    }
+}
+
+/// Renders the source with ANSI escape codes. Styles are reset at the end
+/// of each line, so that the output can be paged with `less -R`.
+pub fn ansi(source: &str) -> String {
+    let mut out = String::new();
+    tokens(source, &mut |kind, text| match kind.and_then(ansi_style) {
+        Some(style) => {
+            for (i, line) in text.split('\n').enumerate() {
+                if i > 0 {
+                    out.push('\n');
+                }
+                if !line.is_empty() {
+                    out.push_str(&format!("\x1b[{}m{}\x1b[0m", style, line));
+                }
+            }
+        }
+        None => out.push_str(text),
+    });
+    out
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lossless() {
+        let code = "---- MODULE M ----\r\nx == (* a\n b *) <<1, \"s\">> ? y\n====\n\"open";
+        let mut text = String::new();
+        tokens(code, &mut |_, t| text.push_str(t));
+        assert_eq!(text, code);
+    }
+
+    #[test]
+    fn html_classes() {
+        assert_eq!(
+            html("x == \\E y \\in S : y < 1 \\* c"),
+            "<pre class=\"tla\">x <span class=\"DefEq\">==</span> <span class=\"Keyword\">\\E</span> y \
+             <span class=\"InfixOperator\">\\in</span> S : y <span class=\"InfixOperator\">&lt;</span> \
+             <span class=\"Number\">1</span> <span class=\"Comment\">\\* c</span></pre>\n"
+        );
+    }
+
+    #[test]
+    fn ansi_lines() {
+        assert_eq!(ansi("(* a\nb *) x"), "\x1b[32m(* a\x1b[0m\n\x1b[32mb *)\x1b[0m x");
+    }
+}
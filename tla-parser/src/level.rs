@@ -0,0 +1,496 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ast::*;
+use crate::resolve::{resolve_with, DeclKind, Modules, Resolution};
+
+
+/// Level of an expression: whether it depends on the values of variables
+/// in the current state, in the next state, or in the whole behavior.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Constant,
+    State,
+    Action,
+    Temporal,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Level::Constant => "constant",
+            Level::State => "state",
+            Level::Action => "action",
+            Level::Temporal => "temporal",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// `e'` or `UNCHANGED e` where `e` is an action or a temporal formula.
+    Prime { span: Span, level: Level },
+    /// `ENABLED` applied to a temporal formula.
+    Enabled { span: Span },
+    /// `[]A` or `<>A` where `A` is an action, `[][A]_v` was probably meant.
+    UnsubscriptedAction { span: Span },
+    /// `[A]_v`, `<<A>>_v`, `WF_v(A)` or `SF_v(A)` where `A` is temporal
+    /// or `v` is not a state function.
+    Subscript { span: Span, level: Level },
+    /// Action and temporal formula combined with a boolean operator,
+    /// e.g. `Next /\ [][Next]_vars`.
+    Mixed { span: Span },
+    /// Initial predicate that refers to primed variables.
+    InitAction { span: Span },
+    /// ASSUME of something that is not a constant.
+    Assume { span: Span, level: Level },
+}
+
+impl Error {
+    pub fn span(&self) -> Span {
+        match self {
+            Error::Prime { span, .. }
+            | Error::Enabled { span }
+            | Error::UnsubscriptedAction { span }
+            | Error::Subscript { span, .. }
+            | Error::Mixed { span }
+            | Error::InitAction { span }
+            | Error::Assume { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pos = self.span().start;
+        write!(f, "{}:{}: ", pos.line, pos.col)?;
+        match self {
+            Error::Prime { level, .. } => write!(f, "cannot prime {} level expression", level),
+            Error::Enabled { .. } => write!(f, "ENABLED applied to a temporal formula"),
+            Error::UnsubscriptedAction { .. } => {
+                write!(f, "temporal operator applied to an action without a subscript")
+            }
+            Error::Subscript { level, .. } => {
+                write!(f, "unexpected {} level expression in an action subscript", level)
+            }
+            Error::Mixed { .. } => write!(f, "action and temporal formula in the same expression"),
+            Error::InitAction { .. } => write!(f, "initial predicate is an action"),
+            Error::Assume { level, .. } => write!(f, "ASSUME of {} level expression", level),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Levels {
+    /// Levels of the top level definitions.
+    pub definitions: HashMap<String, Level>,
+    // Levels of all expressions, by byte offsets of their spans.
+    exprs: HashMap<(usize, usize), Level>,
+    pub errors: Vec<Error>,
+}
+
+impl Levels {
+    pub fn level(&self, expr: &Expr) -> Option<Level> {
+        let key = (expr.span.start.byte_offset, expr.span.end.byte_offset);
+        self.exprs.get(&key).copied()
+    }
+}
+
+
+pub fn check(module: &Module, res: &Resolution) -> Levels {
+    check_with(module, res, &Vec::new())
+}
+
+/// Checks levels in the module. Levels of the operators defined in other
+/// modules are computed by analyzing the modules from `modules`.
+pub fn check_with(module: &Module, res: &Resolution, modules: &dyn Modules) -> Levels {
+    let mut imported = HashMap::new();
+    Checker::new(module, res, modules, &mut imported).run(module)
+}
+
+
+// Levels of the definitions exported by a module, by name.
+type Imported = HashMap<String, HashMap<String, Level>>;
+
+struct Checker<'a> {
+    res: &'a Resolution,
+    modules: &'a dyn Modules,
+    imported: &'a mut Imported,
+    // Modules declared in the same file as the module being checked.
+    local_modules: Vec<String>,
+    // Declarations by the byte offset of the declared name.
+    decl_at: HashMap<usize, usize>,
+    // Referenced declarations by the byte offset of the reference.
+    ref_at: HashMap<usize, usize>,
+    // Levels of the declarations that are not constant.
+    decl_level: HashMap<usize, Level>,
+    out: Levels,
+}
+
+impl<'a> Checker<'a> {
+    fn new(
+        module: &Module,
+        res: &'a Resolution,
+        modules: &'a dyn Modules,
+        imported: &'a mut Imported,
+    ) -> Self {
+        fn nested(m: &Module, res: &mut Vec<String>) {
+            res.push(m.name.name.clone());
+            for unit in &m.units {
+                if let Unit::Module(m) = unit {
+                    nested(m, res);
+                }
+            }
+        }
+        let mut local_modules = Vec::new();
+        nested(module, &mut local_modules);
+        let mut decl_at = HashMap::new();
+        for (i, d) in res.decls.iter().enumerate() {
+            let local = d.module.iter().all(|m| local_modules.contains(m));
+            if let (true, Some(span)) = (local, d.span) {
+                decl_at.insert(span.start.byte_offset, i);
+            }
+        }
+        let ref_at = res.references
+            .iter()
+            .map(|r| (r.span.start.byte_offset, r.decl))
+            .collect();
+        Checker {
+            res,
+            modules,
+            imported,
+            local_modules,
+            decl_at,
+            ref_at,
+            decl_level: HashMap::new(),
+            out: Levels::default(),
+        }
+    }
+
+    fn run(mut self, module: &Module) -> Levels {
+        self.units(module, true);
+        self.out
+    }
+
+    fn error(&mut self, err: Error) {
+        self.out.errors.push(err);
+    }
+
+    fn units(&mut self, module: &Module, top: bool) {
+        for unit in &module.units {
+            match unit {
+                Unit::Variables(vars) => {
+                    for v in vars {
+                        self.set_level(&v.span, Level::State);
+                    }
+                }
+                Unit::Definition(def) => {
+                    let level = self.definition(def);
+                    if top {
+                        self.out.definitions.insert(def.name.name.clone(), level);
+                    }
+                    if def.name.name == "Init" && level >= Level::Action {
+                        self.error(Error::InitAction { span: def.name.span });
+                    }
+                }
+                Unit::Assume(a) => {
+                    let level = self.expr(&a.expr);
+                    if level > Level::Constant {
+                        self.error(Error::Assume { span: a.expr.span, level });
+                    }
+                }
+                Unit::Theorem(a) => {
+                    self.expr(&a.expr);
+                }
+                Unit::Module(m) => self.units(m, false),
+                Unit::Instance(inst) => {
+                    for (_, e) in &inst.substitutions {
+                        self.expr(e);
+                    }
+                }
+                Unit::Constants(_) | Unit::Recursive(_) => {}
+            }
+        }
+    }
+
+    fn set_level(&mut self, name: &Span, level: Level) {
+        if let Some(&id) = self.decl_at.get(&name.start.byte_offset) {
+            self.decl_level.insert(id, level);
+        }
+    }
+
+    fn definition(&mut self, def: &Definition) -> Level {
+        let level = match &def.body {
+            DefBody::Expr(e) => self.expr(e),
+            DefBody::Function(bounds, e) => self.bounds(bounds).max(self.expr(e)),
+            DefBody::Instance(inst) => {
+                for (_, e) in &inst.substitutions {
+                    self.expr(e);
+                }
+                Level::Constant
+            }
+        };
+        self.set_level(&def.name.span, level);
+        level
+    }
+
+    fn bounds(&mut self, bounds: &[Bound]) -> Level {
+        let mut level = Level::Constant;
+        for b in bounds {
+            if let Some(set) = &b.set {
+                level = level.max(self.expr(set));
+            }
+        }
+        level
+    }
+
+    // Level of the declaration referenced at `span`.
+    fn reference(&mut self, span: &Span) -> Level {
+        let id = match self.ref_at.get(&span.start.byte_offset) {
+            Some(&id) => id,
+            None => return Level::Constant,
+        };
+        if let Some(&level) = self.decl_level.get(&id) {
+            return level;
+        }
+        let decl = &self.res.decls[id];
+        match decl.kind {
+            DeclKind::Variable => Level::State,
+            DeclKind::Operator | DeclKind::Function => match &decl.module {
+                Some(m) if !self.local_modules.contains(m) => {
+                    let (m, name) = (m.clone(), decl.name.clone());
+                    self.imported_level(&m, &name)
+                }
+                _ => Level::Constant,
+            },
+            _ => Level::Constant,
+        }
+    }
+
+    fn imported_level(&mut self, module: &str, name: &str) -> Level {
+        if !self.imported.contains_key(module) {
+            // Placeholder to stop the recursion on circular dependencies.
+            self.imported.insert(module.to_string(), HashMap::new());
+            let levels = match self.modules.module(module) {
+                Some(m) => {
+                    let res = resolve_with(m, self.modules);
+                    Checker::new(m, &res, self.modules, self.imported).run(m).definitions
+                }
+                None => std_levels(module),
+            };
+            self.imported.insert(module.to_string(), levels);
+        }
+        self.imported[module].get(name).copied().unwrap_or(Level::Constant)
+    }
+
+    fn expr(&mut self, e: &Expr) -> Level {
+        let level = self.expr_level(e);
+        let key = (e.span.start.byte_offset, e.span.end.byte_offset);
+        self.out.exprs.insert(key, level);
+        level
+    }
+
+    fn exprs(&mut self, es: &[Expr]) -> Vec<Level> {
+        es.iter().map(|e| self.expr(e)).collect()
+    }
+
+    fn expr_level(&mut self, e: &Expr) -> Level {
+        match &e.kind {
+            ExprKind::Apply { name, args, .. } => {
+                let level = self.reference(&name.span);
+                self.exprs(args).into_iter().fold(level, Level::max)
+            }
+            ExprKind::OpApply { op, args } => {
+                let levels = self.exprs(args);
+                self.operator(e, op, args, &levels)
+            }
+            ExprKind::Junction { items, .. } => {
+                let levels = self.exprs(items);
+                self.boolean(e, &levels)
+            }
+            ExprKind::Quant { kind, bounds, body } => {
+                if let Quantifier::TemporalForall | Quantifier::TemporalExists = kind {
+                    // Temporal quantifiers bind variables.
+                    for v in bounds.iter().flat_map(|b| &b.vars) {
+                        self.set_level(&v.span, Level::State);
+                    }
+                }
+                let level = self.bounds(bounds).max(self.expr(body));
+                match kind {
+                    Quantifier::Forall | Quantifier::Exists => level,
+                    Quantifier::TemporalForall | Quantifier::TemporalExists => Level::Temporal,
+                }
+            }
+            ExprKind::Let { defs, body } => {
+                for def in defs {
+                    self.definition(def);
+                }
+                self.expr(body)
+            }
+            ExprKind::BoxAction { action, sub } | ExprKind::AngleAction { action, sub } => {
+                self.subscript(sub);
+                let level = self.expr(action);
+                if level == Level::Temporal {
+                    self.error(Error::Subscript { span: action.span, level });
+                }
+                Level::Action
+            }
+            ExprKind::Fairness { sub, action, .. } => {
+                self.subscript(sub);
+                let level = self.expr(action);
+                if level == Level::Temporal {
+                    self.error(Error::Subscript { span: action.span, level });
+                }
+                Level::Temporal
+            }
+            _ => e.children()
+                .into_iter()
+                .map(|c| self.expr(c))
+                .fold(Level::Constant, Level::max),
+        }
+    }
+
+    fn subscript(&mut self, sub: &Expr) {
+        let level = self.expr(sub);
+        if level > Level::State {
+            self.error(Error::Subscript { span: sub.span, level });
+        }
+    }
+
+    fn boolean(&mut self, e: &Expr, levels: &[Level]) -> Level {
+        let action = levels.contains(&Level::Action);
+        let temporal = levels.contains(&Level::Temporal);
+        if action && temporal {
+            self.error(Error::Mixed { span: e.span });
+        }
+        levels.iter().copied().fold(Level::Constant, Level::max)
+    }
+
+    fn operator(&mut self, e: &Expr, op: &Ident, args: &[Expr], levels: &[Level]) -> Level {
+        let arg = levels.iter().copied().fold(Level::Constant, Level::max);
+        match op.name.as_str() {
+            "'" | "UNCHANGED" => {
+                if arg > Level::State {
+                    self.error(Error::Prime { span: e.span, level: arg });
+                }
+                Level::Action
+            }
+            "ENABLED" => {
+                if arg == Level::Temporal {
+                    self.error(Error::Enabled { span: e.span });
+                }
+                Level::State
+            }
+            "[]" | "<>" => {
+                let subscripted = args.iter().all(|a| {
+                    matches!(a.kind, ExprKind::BoxAction { .. } | ExprKind::AngleAction { .. })
+                });
+                if arg == Level::Action && !subscripted {
+                    self.error(Error::UnsubscriptedAction { span: e.span });
+                }
+                Level::Temporal
+            }
+            "~>" | "-+->" => {
+                if arg == Level::Action {
+                    self.error(Error::UnsubscriptedAction { span: e.span });
+                }
+                Level::Temporal
+            }
+            "\\cdot" => arg.max(Level::Action),
+            "/\\" | "\\/" | "~" | "=>" | "<=>" | "\\equiv" => self.boolean(e, levels),
+            _ => self.reference(&op.span).max(arg),
+        }
+    }
+}
+
+fn std_levels(module: &str) -> HashMap<String, Level> {
+    let mut res = HashMap::new();
+    if module == "RealTime" {
+        res.insert("RTnow".to_string(), Level::Action);
+        res.insert("RTBound".to_string(), Level::Temporal);
+    }
+    res
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::resolve::resolve;
+
+    fn levels(body: &str) -> Levels {
+        let code = format!("---- MODULE M ----\nCONSTANT N\nVARIABLE x, y\n{}\n====", body);
+        let m = parse(&code).unwrap();
+        check(&m, &resolve(&m))
+    }
+
+    fn errors(body: &str) -> Vec<String> {
+        levels(body).errors.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn definitions() {
+        let l = levels("
+            C == N
+            S == x = y
+            A == x' = y
+            B == UNCHANGED <<x, y>>
+            E == ENABLED A
+            F(v) == v'
+            G == F(N)
+            H == F(x) \\/ S
+            Spec == S /\\ [][A]_<<x, y>> /\\ WF_x(A)
+            Q == \\EE z : [](z = x)
+            L == LET T == x' IN T
+        ");
+        let expected = [
+            ("C", Level::Constant),
+            ("S", Level::State),
+            ("A", Level::Action),
+            ("B", Level::Action),
+            ("E", Level::State),
+            ("F", Level::Action),
+            ("G", Level::Action),
+            ("H", Level::Action),
+            ("Spec", Level::Temporal),
+            ("Q", Level::Temporal),
+            ("L", Level::Action),
+        ];
+        for (name, level) in expected.iter() {
+            assert_eq!(l.definitions[*name], *level, "{}", name);
+        }
+        assert_eq!(l.errors, vec![]);
+    }
+
+    #[test]
+    fn level_errors() {
+        assert_eq!(errors("T == []x\nA == (T)'"), vec!["5:6: cannot prime temporal level expression"]);
+        assert_eq!(errors("B == x'' = 1"), vec!["4:6: cannot prime action level expression"]);
+        assert_eq!(errors("T == ENABLED <>x"), vec!["4:6: ENABLED applied to a temporal formula"]);
+        assert_eq!(errors("Init == x' = 0"), vec!["4:1: initial predicate is an action"]);
+        assert_eq!(errors("ASSUME x > N"), vec!["4:8: ASSUME of state level expression"]);
+        assert_eq!(
+            errors("Next == x' = x\nSpec == [](Next)"),
+            vec!["5:9: temporal operator applied to an action without a subscript"]);
+        assert_eq!(
+            errors("Next == x' = x\nSpec == Next /\\ [][Next]_x"),
+            vec!["5:9: action and temporal formula in the same expression"]);
+        assert_eq!(
+            errors("Next == x' = x\nSpec == [][Next]_(x')"),
+            vec!["5:18: unexpected action level expression in an action subscript"]);
+    }
+
+    #[test]
+    fn imported() {
+        let modules = vec![
+            parse("---- MODULE Base ----\nVARIABLE v\nNext == v' = v\n====").unwrap(),
+        ];
+        let m = parse("---- MODULE M ----\nEXTENDS Base, RealTime\nSpec == []Next\nA == RTnow(v)\n====").unwrap();
+        let res = resolve_with(&m, &modules);
+        let levels = check_with(&m, &res, &modules);
+        assert_eq!(levels.errors.len(), 1);
+        assert!(levels.errors[0].to_string().contains("without a subscript"));
+        assert_eq!(levels.definitions["A"], Level::Action);
+    }
+}
@@ -58,6 +58,17 @@ impl<'a> Lexer<'a> {
     /// Advances cursor forward.
     /// Returns false if EOF, returns error if next grapheme is malformed.
     pub fn next_char(&mut self) -> Result<bool, GraphemeIncomplete> {
+        // Update line and column depending on the char we are leaving.
+        // Returns "" before the start, at the end of string, at the error.
+        match self.current_char() {
+            "\n" | "\r\n" => {
+                self.pos.line += 1;
+                self.pos.col = 1;
+            }
+            "\t" => self.pos.col += 4, // FIXME: tab_size
+            "" => {}
+            _ => self.pos.col += 1,
+        }
         // Next character starts immediately after the current one.
         self.pos.byte_offset = self.pos.byte_offset + self.pos.char_size;
         self.grc.set_cursor(self.pos.byte_offset);
@@ -66,7 +77,7 @@ impl<'a> Lexer<'a> {
         //      - position before the first char
         //      - position after the last char
         //      - position at a malformed unicode grapheme
-        let res = match self.grc.next_boundary(self.str, 0) {
+        match self.grc.next_boundary(self.str, 0) {
             Ok(Some(end)) => {
                 self.pos.char_size = end - self.pos.byte_offset;
                 Ok(true)
@@ -79,20 +90,7 @@ impl<'a> Lexer<'a> {
                 self.pos.char_size = 0;
                 Err(err)
             }
-        };
-        // Returns "" before the start, at the end of string, at the error.
-        let prev_char = self.current_char();
-        // Update line and column depending on previous char.
-        match prev_char {
-            "\n" | "\r\n" => {
-                self.pos.line += 1;
-                self.pos.col = 1;
-            }
-            "\t" => self.pos.col += 4, // FIXME: tab_size
-            "" => {}
-            _ => self.pos.col += 1,
         }
-        res
     }
 }
 
@@ -122,5 +120,13 @@ mod tests {
         assert_eq!(lx.next_char(), Ok(false));
         assert_eq!(lx.current_char(), "");
         assert_eq!(lx.next_char(), Ok(false));
+
+        let mut lx = Lexer::new("ab\ncd");
+        assert_eq!((lx.pos.line, lx.pos.col), (1, 1));
+        for _ in 0..3 {
+            let _ = lx.next_char();
+        }
+        assert_eq!(lx.current_char(), "c");
+        assert_eq!((lx.pos.line, lx.pos.col), (2, 1));
     }
 }
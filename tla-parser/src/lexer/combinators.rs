@@ -1,12 +1,13 @@
 
 use super::base::Lexer;
-use super::token_type::{TokenType, OPERATORS};
+use super::token_type::{to_ascii, TokenType, OPERATORS};
 
 
 #[derive(Debug, PartialEq)]
 pub enum Error {
     Unicode(unicode_segmentation::GraphemeIncomplete),
     UnclosedBlockComment,
+    UnclosedString,
 }
 
 
@@ -19,6 +20,8 @@ pub trait TlaCombinators {
     fn ident(&mut self) -> Result<bool, Error>;
     fn line_comment(&mut self) -> Result<bool, Error>;
     fn block_comment(&mut self) -> Result<bool, Error>;
+    fn number(&mut self) -> Result<bool, Error>;
+    fn string(&mut self) -> Result<bool, Error>;
     fn operator(&mut self) -> Result<Option<TokenType>, Error>;
 }
 
@@ -88,11 +91,8 @@ impl TlaCombinators for Lexer<'_> {
     fn ident(&mut self) -> Result<bool, Error> {
         let save_pos = self.pos;
         // FIXME: TLA+ actually allows identifiers starting with a digit.
-        if !self
-            .current_char()
-            .chars()
-            .all(|c| c.is_ascii_alphabetic() || c == '_')
-        {
+        let c = self.current_char();
+        if c.is_empty() || !c.chars().all(|c| c.is_ascii_alphabetic() || c == '_') {
             self.pos = save_pos;
             return Ok(false);
         }
@@ -100,8 +100,8 @@ impl TlaCombinators for Lexer<'_> {
             if let Err(err) = self.next_char() {
                 return Err(Error::Unicode(err));
             }
-            let valid_char = self
-                .current_char()
+            let c = self.current_char();
+            let valid_char = !c.is_empty() && c
                 .chars()
                 .all(|c| c.is_ascii_alphanumeric() || c == '_');
             if !valid_char {
@@ -144,31 +144,90 @@ impl TlaCombinators for Lexer<'_> {
         }
     }
 
+    // Decimal numbers: `42`, `3.14`. Does not consume `..` after digits.
+    fn number(&mut self) -> Result<bool, Error> {
+        let is_digit = |c: &str| c.chars().all(|c| c.is_ascii_digit()) && !c.is_empty();
+        if !is_digit(self.current_char()) {
+            return Ok(false);
+        }
+        let mut seen_dot = false;
+        loop {
+            if let Err(err) = self.next_char() {
+                return Err(Error::Unicode(err));
+            }
+            if is_digit(self.current_char()) {
+                continue;
+            }
+            let rest = &self.str[self.pos.byte_offset..];
+            let fraction = rest.starts_with('.')
+                && rest[1..].starts_with(|c: char| c.is_ascii_digit());
+            if seen_dot || !fraction {
+                return Ok(true);
+            }
+            seen_dot = true;
+        }
+    }
+
+    // String literal with backslash escapes. Strings can't span lines.
+    fn string(&mut self) -> Result<bool, Error> {
+        if self.current_char() != "\"" {
+            return Ok(false);
+        }
+        loop {
+            match self.next_char() {
+                Ok(true) => {}
+                Ok(false) => return Err(Error::UnclosedString),
+                Err(err) => return Err(Error::Unicode(err)),
+            }
+            match self.current_char() {
+                "\"" => break,
+                "\n" | "\r\n" => return Err(Error::UnclosedString),
+                "\\" => {
+                    // Skip escaped char.
+                    if let Err(err) = self.next_char() {
+                        return Err(Error::Unicode(err));
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.next_char().map_err(Error::Unicode)?;
+        Ok(true)
+    }
+
+    // Longest match against the OPERATORS table.
     fn operator(&mut self) -> Result<Option<TokenType>, Error> {
+        // Unicode symbols are single characters with an ASCII equivalent.
+        if let Some(ascii) = to_ascii(self.current_char()) {
+            let i = OPERATORS.partition_point(|t| t.0 < ascii);
+            self.next_char().map_err(Error::Unicode)?;
+            return Ok(Some(OPERATORS[i].1));
+        }
         let start = self.pos;
-        let mut prev_pos = self.pos;
-        let mut op = self.current_char();
         let mut res = None;
+        let mut res_pos = self.pos;
         loop {
-            match OPERATORS.binary_search_by_key(&op, |t| t.0) {
-                Ok(i) => {
-                    res = Some(OPERATORS[i].1);
-                    prev_pos = self.pos;
-                    match self.next_char() {
-                        Ok(true) => {
-                            op = self.substring(&start, &self.pos);
-                        }
-                        Ok(false) => return Ok(res),
-                        Err(err) => return Err(Error::Unicode(err)),
-                    }
-                }
-                _ => {
-                    // continue if is prefix
-                    self.pos = prev_pos;
-                    return Ok(res);
+            let prev_offset = self.pos.byte_offset;
+            if let Err(err) = self.next_char() {
+                return Err(Error::Unicode(err));
+            }
+            if self.pos.byte_offset == prev_offset {
+                break; // end of string
+            }
+            let op = self.substring(&start, &self.pos);
+            let i = OPERATORS.partition_point(|t| t.0 < op);
+            match OPERATORS.get(i) {
+                Some(t) if t.0 == op => {
+                    res = Some(t.1);
+                    res_pos = self.pos;
                 }
+                // Continue while `op` is a prefix of some operator.
+                Some(t) if t.0.starts_with(op) => {}
+                _ => break,
             }
         }
+        self.pos = res_pos;
+        Ok(res)
     }
 }
 
@@ -249,5 +308,34 @@ mod tests {
         assert_eq!(lx.operator(), Ok(Some(TokenType::InfixOperator)));
         let end = lx.pos;
         assert_eq!(lx.substring(&start, &end), "-+->");
+
+        let mut lx = Lexer::new("(-1)");
+        assert_eq!(lx.operator(), Ok(Some(TokenType::ParenOpen)));
+        assert_eq!(lx.current_char(), "-");
+
+        let mut lx = Lexer::new("|->");
+        assert_eq!(lx.operator(), Ok(Some(TokenType::MapsTo)));
+    }
+
+    #[test]
+    fn number() {
+        let mut lx = Lexer::new("12..3.5");
+        assert_eq!(lx.number(), Ok(true));
+        assert_eq!(lx.current_char(), ".");
+        assert_eq!(lx.operator(), Ok(Some(TokenType::InfixOperator)));
+        let start = lx.pos;
+        assert_eq!(lx.number(), Ok(true));
+        let end = lx.pos;
+        assert_eq!(lx.substring(&start, &end), "3.5");
+    }
+
+    #[test]
+    fn string() {
+        let mut lx = Lexer::new("\"a\\\"b\" c");
+        assert_eq!(lx.string(), Ok(true));
+        assert_eq!(lx.current_char(), " ");
+
+        let mut lx = Lexer::new("\"abc\nd\"");
+        assert_eq!(lx.string(), Err(Error::UnclosedString));
     }
 }
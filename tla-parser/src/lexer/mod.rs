@@ -6,7 +6,7 @@ mod token_type;
 
 pub use base::{Pos, Lexer};
 pub use combinators::TlaCombinators;
-pub use token_type::{TokenType, KEYWORDS};
+pub use token_type::{to_ascii, to_unicode, Keyword, TokenType, KEYWORDS, OPERATORS, UNICODE};
 
 
 #[derive(Debug)]
@@ -17,14 +17,39 @@ pub enum Error {
 }
 
 pub struct Lexeme {
-    start: Pos,
-    end: Pos,
-    value: Result<TokenType, Error>,
+    pub start: Pos,
+    pub end: Pos,
+    pub value: Result<TokenType, Error>,
 }
 
 
+/// Splits the whole string into lexemes.
+/// Unrecognized characters are reported as errors and skipped.
 pub fn lex(code: &str) -> Vec<Lexeme> {
-    vec![] // FIXME: not implemented
+    let mut lx = Lexer::new(code);
+    let mut res = Vec::new();
+    loop {
+        let start = lx.pos;
+        match next_token(&mut lx) {
+            Ok((start, end, token)) => res.push(Lexeme { start, end, value: Ok(token) }),
+            Err(Error::EndOfString) => return res,
+            Err(Error::NotRecognized) => {
+                // Cursor stays at the unrecognized char, skip it.
+                let start = lx.pos;
+                let skipped = lx.next_char();
+                res.push(Lexeme { start, end: lx.pos, value: Err(Error::NotRecognized) });
+                if skipped.is_err() {
+                    return res;
+                }
+            }
+            Err(err) => {
+                res.push(Lexeme { start, end: lx.pos, value: Err(err) });
+                if lx.pos.byte_offset == start.byte_offset {
+                    return res; // Malformed unicode, can't move forward.
+                }
+            }
+        }
+    }
 }
 
 
@@ -48,43 +73,186 @@ pub fn next_token(mut lx: &mut Lexer) -> Result<(Pos, Pos, TokenType), Error> {
                 .skip_many("-")
                 .map(|_| (start, lx.pos, TokenType::Separator))
                 .map_err(Error::Other),
-            Ok(false) => match lx.operator() {
-                Ok(Some(op)) => Ok((start, lx.pos, op)),
-                Ok(None) => Err(Error::NotRecognized),
-                Err(err) => Err(Error::Other(err)),
-            }
+            Ok(false) => operator(lx, start),
+            Err(err) => Err(Error::Other(err)),
+        }
+        "=" => match lx.skip("====") {
+            Ok(true) => lx
+                .skip_many("=")
+                .map(|_| (start, lx.pos, TokenType::ModuleEnd))
+                .map_err(Error::Other),
+            Ok(false) => operator(lx, start),
             Err(err) => Err(Error::Other(err)),
         }
         "\\" => match lx.line_comment() {
             Ok(true) => Ok((start, lx.pos, TokenType::Comment)),
             Err(err) => Err(Error::Other(err)),
-            Ok(false) => match lx.operator() {
-                Ok(Some(op)) => Ok((start, lx.pos, op)),
-                Ok(None) => Err(Error::NotRecognized),
-                Err(err) => Err(Error::Other(err)),
-            }
+            Ok(false) => operator(lx, start),
         }
         // "*" => match lx.skip("*)") => Unpaired comment closing
         "(" => match lx.block_comment() {
             Ok(true) => Ok((start, lx.pos, TokenType::Comment)),
-            Ok(false) => Ok((start, lx.pos, TokenType::ParenOpen)), // Move this to ops
+            Ok(false) => operator(lx, start),
             Err(err) => Err(Error::Other(err)),
         }
-        _ => match lx.ident() {
-            Ok(true) => {
+        "\"" => match lx.string() {
+            Ok(_) => Ok((start, lx.pos, TokenType::String)),
+            Err(err) => Err(Error::Other(err)),
+        }
+        _ => match lx.number() {
+            Ok(true) => Ok((start, lx.pos, TokenType::Number)),
+            Ok(false) => identifier(lx, start),
+            Err(err) => Err(Error::Other(err)),
+        }
+    }
+}
+
+fn operator(lx: &mut Lexer, start: Pos) -> Result<(Pos, Pos, TokenType), Error> {
+    match lx.operator() {
+        Ok(Some(op)) => Ok((start, lx.pos, op)),
+        Ok(None) => Err(Error::NotRecognized),
+        Err(err) => Err(Error::Other(err)),
+    }
+}
+
+fn identifier(lx: &mut Lexer, start: Pos) -> Result<(Pos, Pos, TokenType), Error> {
+    match lx.ident() {
+        Ok(true) => {
+            let end = lx.pos;
+            let name = lx.substring(&start, &end);
+            // `WF_vars` is a keyword followed by an identifier, and so is
+            // `_vars` right after the `]` of `[A]_vars` or the `>>` or `⟩`
+            // of `<<A>>_vars`. Elsewhere `_x` is an identifier.
+            let before = &lx.str[..start.byte_offset];
+            let closed = before.ends_with(']') || before.ends_with(">>") || before.ends_with('⟩');
+            let prefixes: &[&str] = if closed {
+                &["WF_", "SF_", "_"]
+            } else {
+                &["WF_", "SF_"]
+            };
+            let prefix = prefixes.iter().find(|p| name.len() > p.len() && name.starts_with(*p));
+            if let Some(prefix) = prefix {
+                lx.pos = start;
+                lx.skip(prefix).map_err(Error::Other)?;
                 let end = lx.pos;
                 let name = lx.substring(&start, &end);
-                match KEYWORDS.binary_search_by_key(&name, |t| t.0) {
-                    Ok(i) => Ok((start, end, KEYWORDS[i].1)),
-                    _ => Ok((start, end, TokenType::Identifier)),
-                }
+                return Ok((start, end, keyword(name)));
             }
-            Ok(false) => Err(Error::NotRecognized),
-            Err(err) => Err(Error::Other(err)),
+            Ok((start, end, keyword(name)))
         }
+        Ok(false) => operator(lx, start),
+        Err(err) => Err(Error::Other(err)),
     }
 }
 
+fn keyword(name: &str) -> TokenType {
+    match KEYWORDS.binary_search_by_key(&name, |t| t.0) {
+        Ok(i) => KEYWORDS[i].1,
+        _ => TokenType::Identifier,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(code: &str) -> Vec<(&str, TokenType)> {
+        lex(code)
+            .into_iter()
+            .map(|l| (&code[l.start.byte_offset..l.end.byte_offset], l.value.unwrap()))
+            .filter(|t| t.1 != TokenType::Indent)
+            .collect()
+    }
+
+    #[test]
+    fn lex_tokens() {
+        assert_eq!(
+            tokens("---- MODULE M ----\nx == [a |-> <<1, \"s\">>]_v\n===="),
+            vec![
+                ("----", TokenType::Separator),
+                ("MODULE", TokenType::Keyword(Keyword::Module)),
+                ("M", TokenType::Identifier),
+                ("----", TokenType::Separator),
+                ("x", TokenType::Identifier),
+                ("==", TokenType::DefEq),
+                ("[", TokenType::BracketOpen),
+                ("a", TokenType::Identifier),
+                ("|->", TokenType::MapsTo),
+                ("<<", TokenType::TupleOpen),
+                ("1", TokenType::Number),
+                (",", TokenType::Comma),
+                ("\"s\"", TokenType::String),
+                (">>", TokenType::TupleClose),
+                ("]", TokenType::BracketClose),
+                ("_", TokenType::Wildcard),
+                ("v", TokenType::Identifier),
+                ("====", TokenType::ModuleEnd),
+            ]
+        );
+        assert_eq!(
+            tokens("WF_vars(A) \\A x (* c *) (+)"),
+            vec![
+                ("WF_", TokenType::Keyword(Keyword::WeakFairness)),
+                ("vars", TokenType::Identifier),
+                ("(", TokenType::ParenOpen),
+                ("A", TokenType::Identifier),
+                (")", TokenType::ParenClose),
+                ("\\A", TokenType::Keyword(Keyword::Forall)),
+                ("x", TokenType::Identifier),
+                ("(* c *)", TokenType::Comment),
+                ("(+)", TokenType::InfixOperator),
+            ]
+        );
+        assert_eq!(
+            tokens("x ≜ ∀ y ∈ S : ⟨y⟩ ≠ x"),
+            vec![
+                ("x", TokenType::Identifier),
+                ("≜", TokenType::DefEq),
+                ("∀", TokenType::Keyword(Keyword::Forall)),
+                ("y", TokenType::Identifier),
+                ("∈", TokenType::InfixOperator),
+                ("S", TokenType::Identifier),
+                (":", TokenType::Colon),
+                ("⟨", TokenType::TupleOpen),
+                ("y", TokenType::Identifier),
+                ("⟩", TokenType::TupleClose),
+                ("≠", TokenType::InfixOperator),
+                ("x", TokenType::Identifier),
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_subscripts() {
+        assert_eq!(
+            tokens("_x == <<A>>_v /\\ [][B]_y_"),
+            vec![
+                ("_x", TokenType::Identifier),
+                ("==", TokenType::DefEq),
+                ("<<", TokenType::TupleOpen),
+                ("A", TokenType::Identifier),
+                (">>", TokenType::TupleClose),
+                ("_", TokenType::Wildcard),
+                ("v", TokenType::Identifier),
+                ("/\\", TokenType::InfixOperator),
+                ("[]", TokenType::PrefixOperator),
+                ("[", TokenType::BracketOpen),
+                ("B", TokenType::Identifier),
+                ("]", TokenType::BracketClose),
+                ("_", TokenType::Wildcard),
+                ("y_", TokenType::Identifier),
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_errors() {
+        let lexemes = lex("x ? y");
+        assert_eq!(lexemes.len(), 3);
+        assert!(lexemes[1].value.is_err());
+    }
+}
 // FIXME: tests
 // - next_token after EOF
 // - next_token after error should be able to skip error
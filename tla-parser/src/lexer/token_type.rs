@@ -3,18 +3,62 @@
 pub enum Keyword {
     Module,
     Extends,
+    Constant,
+    Variable,
+    Assume,
+    Theorem,
+    Proof,
+    Local,
+    Instance,
+    With,
+    Recursive,
+    Let,
+    In,
+    If,
+    Then,
+    Else,
+    Case,
+    Other,
+    Choose,
+    Except,
+    Lambda,
+    Forall,
+    Exists,
+    TemporalForall,
+    TemporalExists,
+    WeakFairness,
+    StrongFairness,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenType {
     Separator,
+    ModuleEnd,
     Indent,
     Identifier,
+    Number,
+    String,
     Comment,
     Wildcard,
     Keyword(Keyword),
     ParenOpen,
     ParenClose,
+    BracketOpen,
+    BracketClose,
+    BraceOpen,
+    BraceClose,
+    TupleOpen,
+    TupleClose,
+    Comma,
+    Colon,
+    DoubleColon,
+    Dot,
+    Bang,
+    At,
+    DefEq,
+    MapsTo,
+    Arrow,
+    LeftArrow,
     PrefixOperator,
     InfixOperator,
     PostfixOperator,
@@ -24,19 +68,51 @@ pub enum TokenType {
 // This table must be sorted.
 // Dont forget LANG=C if you use unix sort.
 pub static KEYWORDS: &'static [(&'static str, TokenType)] = &[
+    ("ASSUME", TokenType::Keyword(Keyword::Assume)),
+    ("ASSUMPTION", TokenType::Keyword(Keyword::Assume)),
+    ("AXIOM", TokenType::Keyword(Keyword::Assume)),
+    ("BY", TokenType::Keyword(Keyword::Proof)),
+    ("CASE", TokenType::Keyword(Keyword::Case)),
+    ("CHOOSE", TokenType::Keyword(Keyword::Choose)),
+    ("CONSTANT", TokenType::Keyword(Keyword::Constant)),
+    ("CONSTANTS", TokenType::Keyword(Keyword::Constant)),
+    ("COROLLARY", TokenType::Keyword(Keyword::Theorem)),
     ("DOMAIN", TokenType::PrefixOperator),
+    ("ELSE", TokenType::Keyword(Keyword::Else)),
     ("ENABLED", TokenType::PrefixOperator),
+    ("EXCEPT", TokenType::Keyword(Keyword::Except)),
     ("EXTENDS", TokenType::Keyword(Keyword::Extends)),
+    ("IF", TokenType::Keyword(Keyword::If)),
+    ("IN", TokenType::Keyword(Keyword::In)),
+    ("INSTANCE", TokenType::Keyword(Keyword::Instance)),
+    ("LAMBDA", TokenType::Keyword(Keyword::Lambda)),
+    ("LEMMA", TokenType::Keyword(Keyword::Theorem)),
+    ("LET", TokenType::Keyword(Keyword::Let)),
+    ("LOCAL", TokenType::Keyword(Keyword::Local)),
     ("MODULE", TokenType::Keyword(Keyword::Module)),
+    ("OBVIOUS", TokenType::Keyword(Keyword::Proof)),
+    ("OMITTED", TokenType::Keyword(Keyword::Proof)),
+    ("OTHER", TokenType::Keyword(Keyword::Other)),
+    ("PROOF", TokenType::Keyword(Keyword::Proof)),
+    ("PROPOSITION", TokenType::Keyword(Keyword::Theorem)),
+    ("RECURSIVE", TokenType::Keyword(Keyword::Recursive)),
+    ("SF_", TokenType::Keyword(Keyword::StrongFairness)),
     ("SUBSET", TokenType::PrefixOperator),
+    ("THEN", TokenType::Keyword(Keyword::Then)),
+    ("THEOREM", TokenType::Keyword(Keyword::Theorem)),
     ("UNCHANGED", TokenType::PrefixOperator),
     ("UNION", TokenType::PrefixOperator),
+    ("VARIABLE", TokenType::Keyword(Keyword::Variable)),
+    ("VARIABLES", TokenType::Keyword(Keyword::Variable)),
+    ("WF_", TokenType::Keyword(Keyword::WeakFairness)),
+    ("WITH", TokenType::Keyword(Keyword::With)),
     ("_", TokenType::Wildcard),
 ];
 
 // This table must be sorted.
 // Dont forget LANG=C if you use unix sort.
 pub static OPERATORS: &'static [(&'static str, TokenType)] = &[
+    ("!", TokenType::Bang),
     ("!!", TokenType::InfixOperator),
     ("#", TokenType::InfixOperator),
     ("##", TokenType::InfixOperator),
@@ -47,45 +123,62 @@ pub static OPERATORS: &'static [(&'static str, TokenType)] = &[
     ("&", TokenType::InfixOperator),
     ("&&", TokenType::InfixOperator),
     ("'", TokenType::PostfixOperator),
+    ("(", TokenType::ParenOpen),
     ("(+)", TokenType::InfixOperator),
     ("(-)", TokenType::InfixOperator),
     ("(.)", TokenType::InfixOperator),
     ("(/)", TokenType::InfixOperator),
     ("(\\X)", TokenType::InfixOperator),
+    (")", TokenType::ParenClose),
     ("*", TokenType::InfixOperator),
     ("**", TokenType::InfixOperator),
     ("+", TokenType::InfixOperator),
     ("++", TokenType::InfixOperator),
+    (",", TokenType::Comma),
     ("-", TokenType::InfixOperator),
     ("-+->", TokenType::InfixOperator),
     ("--", TokenType::InfixOperator),
     ("-.", TokenType::PrefixOperator),
+    ("->", TokenType::Arrow),
     ("-|", TokenType::InfixOperator),
+    (".", TokenType::Dot),
     ("..", TokenType::InfixOperator),
     ("...", TokenType::InfixOperator),
     ("/", TokenType::InfixOperator),
     ("//", TokenType::InfixOperator),
     ("/=", TokenType::InfixOperator),
     ("/\\", TokenType::InfixOperator),
+    (":", TokenType::Colon),
+    ("::", TokenType::DoubleColon),
     ("::=", TokenType::InfixOperator),
     (":=", TokenType::InfixOperator),
     (":>", TokenType::InfixOperator),
     ("<", TokenType::InfixOperator),
+    ("<-", TokenType::LeftArrow),
     ("<:", TokenType::InfixOperator),
+    ("<<", TokenType::TupleOpen),
     ("<=", TokenType::InfixOperator),
     ("<=>", TokenType::InfixOperator),
     ("<>", TokenType::PrefixOperator),
     ("=", TokenType::InfixOperator),
     ("=<", TokenType::InfixOperator),
+    ("==", TokenType::DefEq),
     ("=>", TokenType::InfixOperator),
     ("=|", TokenType::InfixOperator),
     (">", TokenType::InfixOperator),
     (">=", TokenType::InfixOperator),
+    (">>", TokenType::TupleClose),
     ("??", TokenType::InfixOperator),
+    ("@", TokenType::At),
     ("@@", TokenType::InfixOperator),
+    ("[", TokenType::BracketOpen),
     ("[]", TokenType::PrefixOperator),
     ("\\", TokenType::InfixOperator),
     ("\\/", TokenType::InfixOperator),
+    ("\\A", TokenType::Keyword(Keyword::Forall)),
+    ("\\AA", TokenType::Keyword(Keyword::TemporalForall)),
+    ("\\E", TokenType::Keyword(Keyword::Exists)),
+    ("\\EE", TokenType::Keyword(Keyword::TemporalExists)),
     ("\\X", TokenType::InfixOperator),
     ("\\approx", TokenType::InfixOperator),
     ("\\asymp", TokenType::InfixOperator),
@@ -138,19 +231,123 @@ pub static OPERATORS: &'static [(&'static str, TokenType)] = &[
     ("\\union", TokenType::InfixOperator),
     ("\\uplus", TokenType::InfixOperator),
     ("\\wr", TokenType::InfixOperator),
+    ("]", TokenType::BracketClose),
     ("^", TokenType::InfixOperator),
     ("^#", TokenType::PostfixOperator),
     ("^*", TokenType::PostfixOperator),
     ("^+", TokenType::PostfixOperator),
     ("^^", TokenType::InfixOperator),
+    ("{", TokenType::BraceOpen),
     ("|", TokenType::InfixOperator),
     ("|-", TokenType::InfixOperator),
+    ("|->", TokenType::MapsTo),
     ("|=", TokenType::InfixOperator),
     ("||", TokenType::InfixOperator),
+    ("}", TokenType::BraceClose),
     ("~", TokenType::PrefixOperator),
     ("~>", TokenType::InfixOperator),
 ];
 
+// Unicode forms of the operators, as listed in the TLA+ Unicode proposal.
+// Operators are in normalized form (see `parser::normalize`).
+// This table must be sorted.
+pub static UNICODE: &[(&str, &str)] = &[
+    ("-+->", "⇸"),
+    ("->", "→"),
+    ("-|", "⊣"),
+    ("...", "…"),
+    ("/=", "≠"),
+    ("/\\", "∧"),
+    ("::=", "⩴"),
+    (":=", "≔"),
+    ("<-", "←"),
+    ("<<", "⟨"),
+    ("<=", "≤"),
+    ("<=>", "⇔"),
+    ("<>", "◇"),
+    ("==", "≜"),
+    ("=>", "⇒"),
+    ("=|", "⫤"),
+    (">=", "≥"),
+    (">>", "⟩"),
+    ("[]", "□"),
+    ("\\/", "∨"),
+    ("\\A", "∀"),
+    ("\\E", "∃"),
+    ("\\X", "×"),
+    ("\\approx", "≈"),
+    ("\\asymp", "≍"),
+    ("\\bigcirc", "◯"),
+    ("\\bullet", "●"),
+    ("\\cap", "∩"),
+    ("\\cdot", "⋅"),
+    ("\\cong", "≅"),
+    ("\\cup", "∪"),
+    ("\\div", "÷"),
+    ("\\doteq", "≐"),
+    ("\\equiv", "≡"),
+    ("\\gg", "≫"),
+    ("\\in", "∈"),
+    ("\\ll", "≪"),
+    ("\\notin", "∉"),
+    ("\\o", "∘"),
+    ("\\odot", "⊙"),
+    ("\\ominus", "⊖"),
+    ("\\oplus", "⊕"),
+    ("\\oslash", "⊘"),
+    ("\\otimes", "⊗"),
+    ("\\prec", "≺"),
+    ("\\preceq", "⪯"),
+    ("\\propto", "∝"),
+    ("\\sim", "∼"),
+    ("\\simeq", "≃"),
+    ("\\sqcap", "⊓"),
+    ("\\sqcup", "⊔"),
+    ("\\sqsubset", "⊏"),
+    ("\\sqsubseteq", "⊑"),
+    ("\\sqsupset", "⊐"),
+    ("\\sqsupseteq", "⊒"),
+    ("\\star", "⋆"),
+    ("\\subset", "⊂"),
+    ("\\subseteq", "⊆"),
+    ("\\succ", "≻"),
+    ("\\succeq", "⪰"),
+    ("\\supset", "⊃"),
+    ("\\supseteq", "⊇"),
+    ("\\uplus", "⊎"),
+    ("\\wr", "≀"),
+    ("|-", "⊢"),
+    ("|->", "↦"),
+    ("|=", "⊨"),
+    ("~", "¬"),
+    ("~>", "↝"),
+];
+
+impl TokenType {
+    /// Name of the variant without its fields, e.g. `Keyword` or
+    /// `InfixOperator`.
+    pub fn name(&self) -> String {
+        let debug = format!("{:?}", self);
+        match debug.find('(') {
+            Some(i) => debug[..i].to_string(),
+            None => debug,
+        }
+    }
+}
+
+/// Unicode form of the normalized ASCII operator.
+pub fn to_unicode(ascii: &str) -> Option<&'static str> {
+    UNICODE
+        .binary_search_by_key(&ascii, |t| t.0)
+        .ok()
+        .map(|i| UNICODE[i].1)
+}
+
+/// ASCII form of the Unicode operator.
+pub fn to_ascii(unicode: &str) -> Option<&'static str> {
+    UNICODE.iter().find(|t| t.1 == unicode).map(|t| t.0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -159,5 +356,9 @@ mod tests {
     fn tables_are_sorted() {
         assert!(KEYWORDS.is_sorted_by_key(|t| t.0));
         assert!(OPERATORS.is_sorted_by_key(|t| t.0));
+        assert!(UNICODE.is_sorted_by_key(|t| t.0));
+        for (ascii, _) in UNICODE {
+            assert!(OPERATORS.iter().any(|t| t.0 == *ascii), "{}", ascii);
+        }
     }
 }
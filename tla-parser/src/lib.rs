@@ -1,3 +1,20 @@
 #![feature(is_sorted)]
 
+pub mod ast;
+pub mod cfg;
+pub mod check;
+pub mod cst;
+pub mod eval;
+pub mod fmt;
+pub mod highlight;
+pub mod level;
 pub mod lexer;
+pub mod lint;
+pub mod parser;
+pub mod resolve;
+pub mod stdlib;
+pub mod tlc;
+pub mod types;
+pub mod typeset;
+pub mod unicode;
+pub mod workspace;
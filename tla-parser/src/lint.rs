@@ -0,0 +1,774 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::ast::*;
+use crate::resolve::{DeclKind, Resolution};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Hint,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Hint => "hint",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}: {} [{}]",
+            self.span.start.line, self.span.start.col, self.severity, self.message, self.rule)
+    }
+}
+
+
+/// Check over a resolved module.
+pub trait Rule {
+    /// Name used in the configuration, e.g. `unused-declaration`.
+    fn name(&self) -> &'static str;
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+    fn check(&self, cx: &Context, report: &mut dyn FnMut(Span, String));
+}
+
+
+/// Module with the information that rules need.
+pub struct Context<'a> {
+    pub module: &'a Module,
+    pub resolution: &'a Resolution,
+    // Definitions from this file by the byte offset of their names.
+    definitions: HashMap<usize, &'a Definition>,
+    // Referenced declarations by the byte offset of the reference.
+    references: HashMap<usize, usize>,
+    local_modules: Vec<String>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(module: &'a Module, resolution: &'a Resolution) -> Self {
+        fn collect<'a>(m: &'a Module, cx: &mut Context<'a>) {
+            cx.local_modules.push(m.name.name.clone());
+            for unit in &m.units {
+                match unit {
+                    Unit::Definition(def) => {
+                        cx.definitions.insert(def.name.span.start.byte_offset, def);
+                        for d in let_definitions(def) {
+                            cx.definitions.insert(d.name.span.start.byte_offset, d);
+                        }
+                    }
+                    Unit::Module(m) => collect(m, cx),
+                    _ => {}
+                }
+            }
+        }
+        let mut cx = Context {
+            module,
+            resolution,
+            definitions: HashMap::new(),
+            references: resolution.references
+                .iter()
+                .map(|r| (r.span.start.byte_offset, r.decl))
+                .collect(),
+            local_modules: Vec::new(),
+        };
+        collect(module, &mut cx);
+        cx
+    }
+
+    /// Index of the declaration referenced by the name.
+    pub fn decl(&self, name: &Ident) -> Option<usize> {
+        self.references.get(&name.span.start.byte_offset).copied()
+    }
+
+    /// Whether the declaration is from this file.
+    fn is_local(&self, id: usize) -> bool {
+        let d = &self.resolution.decls[id];
+        d.span.is_some() && d.module.iter().all(|m| self.local_modules.contains(m))
+    }
+
+    /// Definition of the operator referenced by the name.
+    pub fn definition_of(&self, name: &Ident) -> Option<&'a Definition> {
+        let id = self.decl(name)?;
+        if !self.is_local(id) {
+            return None;
+        }
+        let span = self.resolution.decls[id].span?;
+        self.definitions.get(&span.start.byte_offset).copied()
+    }
+
+    /// Whether the name refers to a VARIABLE.
+    pub fn is_variable(&self, name: &Ident) -> bool {
+        self.decl(name)
+            .is_some_and(|id| self.resolution.decls[id].kind == DeclKind::Variable)
+    }
+
+    /// Visits the expression and the bodies of the operators it uses,
+    /// each definition once.
+    pub fn visit(&self, e: &'a Expr, f: &mut dyn FnMut(&'a Expr)) {
+        let mut seen = HashSet::new();
+        self.visit_rec(e, f, &mut seen);
+    }
+
+    fn visit_rec(&self, e: &'a Expr, f: &mut dyn FnMut(&'a Expr), seen: &mut HashSet<usize>) {
+        e.walk(&mut |e| {
+            f(e);
+        });
+        let mut used = Vec::new();
+        e.walk(&mut |e| match &e.kind {
+            ExprKind::Apply { name, .. } | ExprKind::OpApply { op: name, .. } => {
+                if let Some(def) = self.definition_of(name) {
+                    used.push(def);
+                }
+            }
+            _ => {}
+        });
+        for def in used {
+            if seen.insert(def.name.span.start.byte_offset) {
+                if let DefBody::Expr(body) | DefBody::Function(_, body) = &def.body {
+                    self.visit_rec(body, f, seen);
+                }
+            }
+        }
+    }
+
+    /// Variables used in the expression, following operator definitions.
+    pub fn variables_in(&self, e: &'a Expr) -> Vec<&'a str> {
+        let mut res = Vec::new();
+        self.visit(e, &mut |e| {
+            if let ExprKind::Apply { name, .. } = &e.kind {
+                if self.is_variable(name) && !res.contains(&name.name.as_str()) {
+                    res.push(name.name.as_str());
+                }
+            }
+        });
+        res
+    }
+
+    pub fn top_definition(&self, name: &str) -> Option<&'a Definition> {
+        self.module.definition(name)
+    }
+}
+
+/// Definitions made with LET inside of the definition.
+fn let_definitions(def: &Definition) -> Vec<&Definition> {
+    let mut res = Vec::new();
+    if let DefBody::Expr(e) | DefBody::Function(_, e) = &def.body {
+        e.walk(&mut |e| {
+            if let ExprKind::Let { defs, .. } = &e.kind {
+                for d in defs {
+                    res.push(d);
+                    res.extend(let_definitions(d));
+                }
+            }
+        });
+    }
+    res
+}
+
+fn body(def: &Definition) -> Option<&Expr> {
+    match &def.body {
+        DefBody::Expr(e) | DefBody::Function(_, e) => Some(e),
+        DefBody::Instance(_) => None,
+    }
+}
+
+
+/// Severities of the rules. Rules that are not mentioned use their
+/// default severity.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    severities: HashMap<String, Option<Severity>>,
+}
+
+impl Config {
+    /// Sets severity of the rule, `None` disables it.
+    pub fn set(&mut self, rule: &str, severity: Option<Severity>) {
+        self.severities.insert(rule.to_string(), severity);
+    }
+
+    /// Parses lines like `unused-definition = off`.
+    /// Severities are `off`, `hint`, `warning` and `error`.
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut cfg = Config::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=').map(str::trim);
+            let (rule, level) = (parts.next().unwrap(), parts.next().unwrap_or(""));
+            let severity = match level {
+                "off" => None,
+                "hint" => Some(Severity::Hint),
+                "warning" => Some(Severity::Warning),
+                "error" => Some(Severity::Error),
+                _ => return Err(format!("line {}: unknown severity `{}`", i + 1, level)),
+            };
+            cfg.set(rule, severity);
+        }
+        Ok(cfg)
+    }
+}
+
+
+pub struct Linter {
+    rules: Vec<Box<dyn Rule>>,
+    pub config: Config,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Linter::new()
+    }
+}
+
+impl Linter {
+    /// Linter with the built-in rules.
+    pub fn new() -> Self {
+        Linter {
+            rules: vec![
+                Box::new(UnusedDefinition),
+                Box::new(UnusedDeclaration),
+                Box::new(VarsTuple),
+                Box::new(NextUnchanged),
+                Box::new(PrimedInit),
+                Box::new(MissingFairness),
+            ],
+            config: Config::default(),
+        }
+    }
+
+    pub fn with_config(config: Config) -> Self {
+        Linter { config, ..Linter::new() }
+    }
+
+    pub fn add_rule(&mut self, rule: Box<dyn Rule>) {
+        self.rules.push(rule);
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|r| r.name())
+    }
+
+    pub fn lint(&self, module: &Module, resolution: &Resolution) -> Vec<Diagnostic> {
+        let cx = Context::new(module, resolution);
+        let mut res = Vec::new();
+        for rule in &self.rules {
+            let severity = match self.config.severities.get(rule.name()) {
+                Some(Some(s)) => *s,
+                Some(None) => continue,
+                None => rule.severity(),
+            };
+            rule.check(&cx, &mut |span, message| res.push(Diagnostic {
+                rule: rule.name(),
+                severity,
+                span,
+                message,
+            }));
+        }
+        res.sort_by_key(|d| d.span.start.byte_offset);
+        res
+    }
+}
+
+
+/// LOCAL and LET definitions that are not used. Other definitions can
+/// be used by the model configuration or other modules.
+pub struct UnusedDefinition;
+
+impl Rule for UnusedDefinition {
+    fn name(&self) -> &'static str {
+        "unused-definition"
+    }
+
+    fn check(&self, cx: &Context, report: &mut dyn FnMut(Span, String)) {
+        let used: HashSet<usize> = cx.resolution.references.iter().map(|r| r.decl).collect();
+        let unused = |def: &Definition| {
+            let offset = def.name.span.start.byte_offset;
+            !cx.resolution.decls.iter().enumerate().any(|(id, d)| {
+                d.span.map(|s| s.start.byte_offset) == Some(offset) && used.contains(&id)
+            })
+        };
+        for def in cx.module.definitions() {
+            let candidates = Some(def).filter(|d| d.local).into_iter().chain(let_definitions(def));
+            for d in candidates {
+                if unused(d) {
+                    report(d.name.span, format!("`{}` is never used", d.name.name));
+                }
+            }
+        }
+    }
+}
+
+
+/// CONSTANT and VARIABLE declarations that are not used.
+pub struct UnusedDeclaration;
+
+impl Rule for UnusedDeclaration {
+    fn name(&self) -> &'static str {
+        "unused-declaration"
+    }
+
+    fn check(&self, cx: &Context, report: &mut dyn FnMut(Span, String)) {
+        let used: HashSet<usize> = cx.resolution.references
+            .iter()
+            .map(|r| cx.resolution.decls[r.decl].span.map_or(0, |s| s.start.byte_offset))
+            .collect();
+        let names = cx.module.constants().map(|c| (&c.name, "constant"))
+            .chain(cx.module.variables().map(|v| (v, "variable")));
+        for (name, what) in names {
+            if !used.contains(&name.span.start.byte_offset) {
+                report(name.span, format!("{} `{}` is never used", what, name.name));
+            }
+        }
+    }
+}
+
+
+/// Variables missing from the `vars` tuple.
+pub struct VarsTuple;
+
+impl Rule for VarsTuple {
+    fn name(&self) -> &'static str {
+        "vars-tuple"
+    }
+
+    fn check(&self, cx: &Context, report: &mut dyn FnMut(Span, String)) {
+        let def = match cx.top_definition("vars") {
+            Some(def) => def,
+            None => return,
+        };
+        if let Some(body) = body(def) {
+            if let ExprKind::Tuple(_) = body.kind {
+                let listed = cx.variables_in(body);
+                for v in cx.module.variables() {
+                    if !listed.contains(&v.name.as_str()) {
+                        report(def.name.span, format!("variable `{}` is missing from `vars`", v.name));
+                    }
+                }
+            }
+        }
+    }
+}
+
+
+/// Variable that a branch of an action leaves unspecified.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unassigned {
+    pub variable: String,
+    /// Innermost operator that contains the branch, if any.
+    pub action: Option<String>,
+    pub span: Span,
+}
+
+/// Goes through the branches of the action, following operator definitions,
+/// and finds variables that are neither assigned with `x' = e` or
+/// `x' \in S` nor UNCHANGED in some branch. TLC reports these only at run
+/// time, as "successor state is not completely specified".
+pub fn unassigned<'a>(cx: &Context<'a>, action: &'a Expr) -> Vec<Unassigned> {
+    let mut a = Assignments {
+        cx,
+        variables: cx.module.variables().map(|v| v.name.as_str()).collect(),
+        stack: Vec::new(),
+        res: Vec::new(),
+    };
+    a.check(action, &HashSet::new(), None);
+    a.res
+}
+
+struct Assignments<'a, 'c> {
+    cx: &'c Context<'a>,
+    variables: Vec<&'a str>,
+    // Definitions being expanded, to stop on recursive operators.
+    stack: Vec<usize>,
+    res: Vec<Unassigned>,
+}
+
+impl<'a, 'c> Assignments<'a, 'c> {
+    fn expand(&self, name: &Ident) -> Option<(&'a Definition, &'a Expr)> {
+        let def = self.cx.definition_of(name)?;
+        if self.stack.contains(&def.name.span.start.byte_offset) {
+            return None;
+        }
+        Some((def, body(def)?))
+    }
+
+    fn recursive(&self, name: &Ident) -> bool {
+        self.cx.definition_of(name)
+            .is_some_and(|def| self.stack.contains(&def.name.span.start.byte_offset))
+    }
+
+    /// Variables that are specified in every branch of the expression.
+    fn assigned(&mut self, e: &'a Expr) -> HashSet<&'a str> {
+        let intersect = |this: &mut Self, branches: Vec<&'a Expr>| {
+            let mut sets = branches.into_iter().map(|b| this.assigned(b)).collect::<Vec<_>>();
+            let first = sets.pop().unwrap_or_default();
+            first.into_iter().filter(|v| sets.iter().all(|s| s.contains(v))).collect()
+        };
+        match &e.kind {
+            ExprKind::Junction { kind: Junction::And, items } => {
+                items.iter().flat_map(|i| self.assigned(i)).collect()
+            }
+            ExprKind::Junction { kind: Junction::Or, items } => intersect(self, items.iter().collect()),
+            ExprKind::If { then, other, .. } => intersect(self, vec![then, other]),
+            ExprKind::Case { arms, other } => {
+                intersect(self, arms.iter().map(|a| &a.1).chain(other.as_deref()).collect())
+            }
+            ExprKind::Quant { kind: Quantifier::Exists, body, .. } | ExprKind::Let { body, .. } => {
+                self.assigned(body)
+            }
+            ExprKind::OpApply { op, args } if op.name == "UNCHANGED" => {
+                self.cx.variables_in(&args[0]).into_iter().collect()
+            }
+            ExprKind::OpApply { op, args } if op.name == "=" || op.name == "\\in" => {
+                match &args[0].kind {
+                    ExprKind::OpApply { op, args } if op.name == "'" => {
+                        self.cx.variables_in(&args[0]).into_iter().collect()
+                    }
+                    _ => HashSet::new(),
+                }
+            }
+            ExprKind::Apply { name, .. } => match self.expand(name) {
+                Some((def, body)) => {
+                    self.stack.push(def.name.span.start.byte_offset);
+                    let res = self.assigned(body);
+                    self.stack.pop();
+                    res
+                }
+                None => HashSet::new(),
+            },
+            _ => HashSet::new(),
+        }
+    }
+
+    /// Whether the expression has several branches.
+    fn branching(&mut self, e: &'a Expr) -> bool {
+        match &e.kind {
+            ExprKind::Junction { kind: Junction::Or, .. }
+            | ExprKind::If { .. }
+            | ExprKind::Case { .. } => true,
+            ExprKind::Junction { kind: Junction::And, items } => items.iter().any(|i| self.branching(i)),
+            ExprKind::Quant { kind: Quantifier::Exists, body, .. } | ExprKind::Let { body, .. } => {
+                self.branching(body)
+            }
+            ExprKind::Apply { name, .. } => match self.expand(name) {
+                Some((def, body)) => {
+                    self.stack.push(def.name.span.start.byte_offset);
+                    let res = self.branching(body);
+                    self.stack.pop();
+                    res
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    // `covered` are the variables specified by the enclosing conjunctions.
+    fn check(&mut self, e: &'a Expr, covered: &HashSet<&'a str>, action: Option<&'a str>) {
+        match &e.kind {
+            ExprKind::Junction { kind: Junction::And, items } => {
+                let mut covered = covered.clone();
+                covered.extend(self.assigned(e));
+                let branches: Vec<_> = items.iter().filter(|i| self.branching(i)).collect();
+                if branches.is_empty() {
+                    self.leaf(e, &covered, action);
+                }
+                for b in branches {
+                    self.check(b, &covered, action);
+                }
+            }
+            ExprKind::Junction { kind: Junction::Or, items } => {
+                for i in items {
+                    self.check(i, covered, action);
+                }
+            }
+            ExprKind::If { then, other, .. } => {
+                self.check(then, covered, action);
+                self.check(other, covered, action);
+            }
+            ExprKind::Case { arms, other } => {
+                for (_, arm) in arms {
+                    self.check(arm, covered, action);
+                }
+                if let Some(other) = other {
+                    self.check(other, covered, action);
+                }
+            }
+            ExprKind::Quant { kind: Quantifier::Exists, body, .. } | ExprKind::Let { body, .. } => {
+                self.check(body, covered, action);
+            }
+            ExprKind::Apply { name, .. } if self.recursive(name) => {}
+            ExprKind::Apply { name, .. } if self.branching(e) => {
+                if let Some((def, body)) = self.expand(name) {
+                    self.stack.push(def.name.span.start.byte_offset);
+                    self.check(body, covered, Some(&def.name.name));
+                    self.stack.pop();
+                }
+            }
+            ExprKind::Apply { name, .. } if self.expand(name).is_some() => {
+                let mut covered = covered.clone();
+                covered.extend(self.assigned(e));
+                self.leaf(e, &covered, Some(&name.name));
+            }
+            _ => {
+                let mut covered = covered.clone();
+                covered.extend(self.assigned(e));
+                self.leaf(e, &covered, action);
+            }
+        }
+    }
+
+    fn leaf(&mut self, e: &Expr, covered: &HashSet<&str>, action: Option<&str>) {
+        for v in &self.variables {
+            if !covered.contains(v) {
+                self.res.push(Unassigned {
+                    variable: v.to_string(),
+                    action: action.map(str::to_string),
+                    span: e.span,
+                });
+            }
+        }
+    }
+}
+
+
+/// Branches of `Next` that say nothing about some variables.
+pub struct NextUnchanged;
+
+impl Rule for NextUnchanged {
+    fn name(&self) -> &'static str {
+        "next-unchanged"
+    }
+
+    fn check(&self, cx: &Context, report: &mut dyn FnMut(Span, String)) {
+        let next = match cx.top_definition("Next").and_then(body) {
+            Some(next) => next,
+            None => return,
+        };
+        for u in unassigned(cx, next) {
+            let action = match &u.action {
+                Some(a) => format!("`{}`", a),
+                None => "this action".to_string(),
+            };
+            report(u.span, format!(
+                "variable `{}` is neither primed nor UNCHANGED in {}", u.variable, action));
+        }
+    }
+}
+
+
+/// Primed variables in `Init`.
+pub struct PrimedInit;
+
+impl Rule for PrimedInit {
+    fn name(&self) -> &'static str {
+        "primed-init"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, cx: &Context, report: &mut dyn FnMut(Span, String)) {
+        let init = match cx.top_definition("Init").and_then(body) {
+            Some(init) => init,
+            None => return,
+        };
+        cx.visit(init, &mut |e| {
+            if let ExprKind::OpApply { op, .. } = &e.kind {
+                if op.name == "'" || op.name == "UNCHANGED" {
+                    report(e.span, "initial predicate refers to the next state".to_string());
+                }
+            }
+        });
+    }
+}
+
+
+/// `Spec` without fairness conditions, while there are liveness properties
+/// that can't hold without them.
+pub struct MissingFairness;
+
+impl Rule for MissingFairness {
+    fn name(&self) -> &'static str {
+        "missing-fairness"
+    }
+
+    fn check(&self, cx: &Context, report: &mut dyn FnMut(Span, String)) {
+        let spec = match cx.top_definition("Spec") {
+            Some(spec) => spec,
+            None => return,
+        };
+        let mut fair = false;
+        if let Some(body) = body(spec) {
+            cx.visit(body, &mut |e| {
+                if let ExprKind::Fairness { .. } = e.kind {
+                    fair = true;
+                }
+            });
+        }
+        if fair {
+            return;
+        }
+        for def in cx.module.definitions().filter(|d| d.name.name != "Spec" && d.params.is_empty()) {
+            if body(def).is_some_and(liveness) {
+                report(spec.name.span, format!(
+                    "`Spec` has no fairness conditions, liveness property `{}` can't hold",
+                    def.name.name));
+                return;
+            }
+        }
+    }
+}
+
+// Whether the formula is a liveness property: `<>` or `~>` under the
+// temporal and Boolean operators at its top, not inside of actions,
+// ENABLED or other operators.
+fn liveness(e: &Expr) -> bool {
+    match &e.kind {
+        ExprKind::OpApply { op, args } => match op.name.as_str() {
+            "<>" | "~>" => true,
+            "[]" | "~" | "=>" | "<=>" | "\\equiv" | "/\\" | "\\/" => args.iter().any(liveness),
+            _ => false,
+        },
+        ExprKind::Junction { items, .. } => items.iter().any(liveness),
+        ExprKind::Quant { body, .. } | ExprKind::Let { body, .. } => liveness(body),
+        _ => false,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::resolve::resolve;
+
+    fn lint_with(linter: &Linter, body: &str) -> Vec<String> {
+        let code = format!("---- MODULE M ----\nEXTENDS Naturals\n{}\n====", body);
+        let m = parse(&code).unwrap();
+        let res = resolve(&m);
+        assert_eq!(res.errors, vec![]);
+        linter.lint(&m, &res).iter().map(|d| d.to_string()).collect()
+    }
+
+    fn lint(body: &str) -> Vec<String> {
+        lint_with(&Linter::new(), body)
+    }
+
+    #[test]
+    fn clean() {
+        assert_eq!(lint("
+            CONSTANT N
+            VARIABLE x, y
+            vars == <<x, y>>
+            Init == x = 0 /\\ y = 0
+            Inc == x' = x + 1 /\\ UNCHANGED y
+            Both == LET d == 1 IN x' = x + d /\\ y' = y + d
+            Next == (Inc \\/ Both) /\\ x < N
+            Spec == Init /\\ [][Next]_vars /\\ WF_vars(Next)
+            Live == <>(x = N)
+        "), Vec::<String>::new());
+    }
+
+    #[test]
+    fn rules() {
+        assert_eq!(lint("
+            CONSTANT N
+            VARIABLE x, y, z
+            vars == <<x, y>>
+            LOCAL Helper == 1
+            Init == x = 0 /\\ y' = 0
+            Inc == LET d == 1 IN x' = x + 1
+            Next == Inc \\/ UNCHANGED vars
+            Spec == Init /\\ [][Next]_vars
+            Live == []<>(x > 1)
+        "), vec![
+            "4:22: warning: constant `N` is never used [unused-declaration]",
+            "5:28: warning: variable `z` is never used [unused-declaration]",
+            "6:13: warning: variable `z` is missing from `vars` [vars-tuple]",
+            "7:19: warning: `Helper` is never used [unused-definition]",
+            "8:30: error: initial predicate refers to the next state [primed-init]",
+            "9:24: warning: `d` is never used [unused-definition]",
+            "10:21: warning: variable `y` is neither primed nor UNCHANGED in `Inc` [next-unchanged]",
+            "10:21: warning: variable `z` is neither primed nor UNCHANGED in `Inc` [next-unchanged]",
+            "10:28: warning: variable `z` is neither primed nor UNCHANGED in this action [next-unchanged]",
+            "11:13: warning: `Spec` has no fairness conditions, liveness property `Live` can't hold [missing-fairness]",
+        ]);
+    }
+
+    #[test]
+    fn fairness() {
+        let code = "
+            VARIABLE x
+            Init == x = 0
+            Next == x' = x + 1
+            Spec == Init /\\ [][Next]_x
+            Ev(P) == <>P
+            Cond == ENABLED (<>(x > 0) /\\ x' = x)
+            Safe == [](x >= 0)
+        ";
+        assert_eq!(lint(code), Vec::<String>::new());
+        assert_eq!(lint(&format!("{}Live == \\A i \\in 1..2 : [](x = i => <>(x > i))", code)), vec![
+            "7:13: warning: `Spec` has no fairness conditions, liveness property `Live` can't hold [missing-fairness]",
+        ]);
+    }
+
+    #[test]
+    fn unassigned_branches() {
+        let code = "---- MODULE M ----
+            EXTENDS Naturals
+            VARIABLE x, y, z
+            Send(p) == x' = p /\\ y' = y
+            Recv == IF x > 0 THEN x' = x - 1 /\\ y' = y ELSE x' = 0
+            Reset == CASE x = 0 -> UNCHANGED <<x, y>> [] OTHER -> x' \\in {0}
+            RECURSIVE Loop
+            Loop == x' = 1 \\/ Loop
+            Next == /\\ \\/ \\E p \\in 1..3 : Send(p)
+                       \\/ Recv
+                       \\/ Reset
+                       \\/ Loop
+                    /\\ z' = z
+            ====";
+        let m = parse(code).unwrap();
+        let res = resolve(&m);
+        let cx = Context::new(&m, &res);
+        let next = body(m.definition("Next").unwrap()).unwrap();
+        let found: Vec<_> = unassigned(&cx, next)
+            .iter()
+            .map(|u| format!("{}:{} {} {:?}", u.span.start.line, u.span.start.col, u.variable, u.action))
+            .collect();
+        assert_eq!(found, vec![
+            "5:61 y Some(\"Recv\")",
+            "6:67 y Some(\"Reset\")",
+            "8:21 y Some(\"Loop\")",
+        ]);
+    }
+
+    #[test]
+    fn config() {
+        let cfg = Config::parse("unused-declaration = off\n# comment\nvars-tuple = error\n").unwrap();
+        let linter = Linter::with_config(cfg);
+        assert_eq!(lint_with(&linter, "VARIABLE x, y\nvars == <<x>>"), vec![
+            "4:1: error: variable `y` is missing from `vars` [vars-tuple]",
+        ]);
+        assert!(Config::parse("vars-tuple = loud").is_err());
+    }
+}
@@ -0,0 +1,616 @@
+use crate::ast::*;
+use crate::lexer::{Keyword, TokenType};
+use super::{normalize, unescape, Error, Parser, Token};
+use super::precedence;
+
+
+impl<'a> Parser<'a> {
+    pub fn expr(&mut self) -> Result<Expr, Error> {
+        self.binary(0)
+    }
+
+    // Precedence climbing. Each operator has a range of precedence levels,
+    // we use the low end to decide if it binds to the left operand
+    // and the high end for the right operand.
+    fn binary(&mut self, min: u8) -> Result<Expr, Error> {
+        let mut lhs = self.unary()?;
+        loop {
+            let t = match self.peek() {
+                Some(t) if t.ty == TokenType::InfixOperator => t,
+                _ => break,
+            };
+            let op = normalize(self.text(&t));
+            let (lo, hi, _) = match precedence::infix(op) {
+                Some(p) => p,
+                None => break,
+            };
+            if lo < min {
+                break;
+            }
+            self.next();
+            let op = self.op_ident(t);
+            if op.name == "\\X" {
+                // Cartesian product is not associative: A \X B \X C is a set
+                // of triples, so collect all the factors.
+                let mut args = vec![lhs, self.binary(hi + 1)?];
+                while self.peek_op("\\X") {
+                    self.next();
+                    args.push(self.binary(hi + 1)?);
+                }
+                let span = args[0].span.to(&args[args.len() - 1].span);
+                lhs = Expr { kind: ExprKind::OpApply { op, args }, span };
+                continue;
+            }
+            let rhs = self.binary(hi + 1)?;
+            lhs = infix(op, lhs, rhs);
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        let t = match self.peek() {
+            Some(t) => t,
+            None => return Err(self.unexpected("expression")),
+        };
+        let text = normalize(self.text(&t));
+        match t.ty {
+            TokenType::InfixOperator if text == "/\\" || text == "\\/" => self.bullets(t),
+            TokenType::InfixOperator if text == "-" => {
+                self.next();
+                let op = Ident { name: "-.".to_string(), span: Span::new(t.start, t.end) };
+                let arg = self.binary(13)?;
+                let span = op.span.to(&arg.span);
+                Ok(Expr { kind: ExprKind::OpApply { op, args: vec![arg] }, span })
+            }
+            TokenType::PrefixOperator => {
+                self.next();
+                let op = self.op_ident(t);
+                let lo = precedence::prefix(&op.name).map_or(4, |p| p.0);
+                let arg = self.binary(lo + 1)?;
+                let span = op.span.to(&arg.span);
+                Ok(Expr { kind: ExprKind::OpApply { op, args: vec![arg] }, span })
+            }
+            _ => {
+                let e = self.primary()?;
+                self.postfix(e)
+            }
+        }
+    }
+
+    // Function application, record fields and postfix operators.
+    fn postfix(&mut self, mut e: Expr) -> Result<Expr, Error> {
+        let start = e.span.start;
+        loop {
+            let t = match self.peek() {
+                Some(t) => t,
+                None => return Ok(e),
+            };
+            e = match t.ty {
+                TokenType::BracketOpen => {
+                    self.next();
+                    let args = self.expr_list()?;
+                    self.expect(TokenType::BracketClose, "]")?;
+                    let kind = ExprKind::FnApply(Box::new(e), args);
+                    Expr { kind, span: self.span_from(start) }
+                }
+                TokenType::Dot => {
+                    self.next();
+                    let field = self.ident()?;
+                    let kind = ExprKind::Field(Box::new(e), field);
+                    Expr { kind, span: self.span_from(start) }
+                }
+                TokenType::PostfixOperator => {
+                    self.next();
+                    let op = self.op_ident(t);
+                    let kind = ExprKind::OpApply { op, args: vec![e] };
+                    Expr { kind, span: self.span_from(start) }
+                }
+                _ => return Ok(e),
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let t = match self.peek() {
+            Some(t) => t,
+            None => return Err(self.unexpected("expression")),
+        };
+        let start = t.start;
+        let kind = match t.ty {
+            TokenType::Number => {
+                self.next();
+                ExprKind::Num(self.text(&t).to_string())
+            }
+            TokenType::String => {
+                self.next();
+                ExprKind::Str(unescape(self.text(&t)))
+            }
+            TokenType::At => {
+                self.next();
+                ExprKind::At
+            }
+            TokenType::Identifier => {
+                let label = self.peek_at(1).map(|t| t.ty) == Some(TokenType::DoubleColon);
+                if label {
+                    // Labels are only meaningful for proofs.
+                    self.next();
+                    self.next();
+                    return self.expr();
+                }
+                return self.apply();
+            }
+            TokenType::ParenOpen => {
+                self.next();
+                let e = self.expr()?;
+                self.expect(TokenType::ParenClose, ")")?;
+                return Ok(Expr { kind: e.kind, span: self.span_from(start) });
+            }
+            TokenType::BraceOpen => self.set()?,
+            TokenType::BracketOpen => self.bracket()?,
+            TokenType::TupleOpen => {
+                self.next();
+                let items = if self.peek_is(TokenType::TupleClose) {
+                    vec![]
+                } else {
+                    self.expr_list()?
+                };
+                self.expect(TokenType::TupleClose, ">>")?;
+                if self.peek_is(TokenType::Wildcard) && items.len() == 1 {
+                    let action = Box::new(items.into_iter().next().unwrap());
+                    let sub = Box::new(self.subscript()?);
+                    ExprKind::AngleAction { action, sub }
+                } else {
+                    ExprKind::Tuple(items)
+                }
+            }
+            TokenType::Keyword(Keyword::If) => {
+                self.next();
+                let cond = Box::new(self.expr()?);
+                self.expect(TokenType::Keyword(Keyword::Then), "THEN")?;
+                let then = Box::new(self.expr()?);
+                self.expect(TokenType::Keyword(Keyword::Else), "ELSE")?;
+                let other = Box::new(self.expr()?);
+                ExprKind::If { cond, then, other }
+            }
+            TokenType::Keyword(Keyword::Case) => self.case()?,
+            TokenType::Keyword(Keyword::Let) => {
+                self.next();
+                let mut defs = Vec::new();
+                while !self.peek_is(TokenType::Keyword(Keyword::In)) {
+                    if self.peek_is(TokenType::Keyword(Keyword::Recursive)) {
+                        // Declarations are only hints for the parser.
+                        self.next();
+                        self.op_decls()?;
+                        continue;
+                    }
+                    defs.push(self.definition(false)?);
+                }
+                self.next();
+                let body = Box::new(self.expr()?);
+                ExprKind::Let { defs, body }
+            }
+            TokenType::Keyword(Keyword::Forall) => self.quantifier(Quantifier::Forall)?,
+            TokenType::Keyword(Keyword::Exists) => self.quantifier(Quantifier::Exists)?,
+            TokenType::Keyword(Keyword::TemporalForall) => {
+                self.quantifier(Quantifier::TemporalForall)?
+            }
+            TokenType::Keyword(Keyword::TemporalExists) => {
+                self.quantifier(Quantifier::TemporalExists)?
+            }
+            TokenType::Keyword(Keyword::Choose) => {
+                self.next();
+                let (vars, tuple) = self.bound_vars()?;
+                let set = if self.peek_op("\\in") {
+                    self.next();
+                    Some(Box::new(self.expr()?))
+                } else {
+                    None
+                };
+                self.expect(TokenType::Colon, ":")?;
+                let body = Box::new(self.expr()?);
+                ExprKind::Choose { bound: Bound { vars, tuple, set }, body }
+            }
+            TokenType::Keyword(Keyword::Lambda) => {
+                self.next();
+                let params = self.ident_list()?;
+                self.expect(TokenType::Colon, ":")?;
+                let body = Box::new(self.expr()?);
+                ExprKind::Lambda { params, body }
+            }
+            TokenType::Keyword(Keyword::WeakFairness)
+            | TokenType::Keyword(Keyword::StrongFairness) => {
+                self.next();
+                let strong = t.ty == TokenType::Keyword(Keyword::StrongFairness);
+                let sub = Box::new(self.subscript_expr()?);
+                self.expect(TokenType::ParenOpen, "(")?;
+                let action = Box::new(self.expr()?);
+                self.expect(TokenType::ParenClose, ")")?;
+                ExprKind::Fairness { strong, sub, action }
+            }
+            _ => return Err(self.unexpected("expression")),
+        };
+        Ok(Expr { kind, span: self.span_from(start) })
+    }
+
+    // `x`, `F(a, b)`, `I!J!F(a)`
+    fn apply(&mut self) -> Result<Expr, Error> {
+        let mut name = self.ident()?;
+        let start = name.span.start;
+        let mut path = Vec::new();
+        while self.peek_is(TokenType::Bang) {
+            self.next();
+            path.push(name);
+            name = self.ident()?;
+        }
+        let mut args = Vec::new();
+        if self.peek_is(TokenType::ParenOpen) {
+            self.next();
+            args = self.expr_list()?;
+            self.expect(TokenType::ParenClose, ")")?;
+        }
+        Ok(Expr { kind: ExprKind::Apply { path, name, args }, span: self.span_from(start) })
+    }
+
+    fn expr_list(&mut self) -> Result<Vec<Expr>, Error> {
+        let mut res = vec![self.expr()?];
+        while self.peek_is(TokenType::Comma) {
+            self.next();
+            res.push(self.expr()?);
+        }
+        Ok(res)
+    }
+
+    // Bulleted list of conjuncts or disjuncts. Each item ends at the first
+    // token that is not to the right of the bullet.
+    fn bullets(&mut self, first: Token) -> Result<Expr, Error> {
+        let col = first.start.col;
+        let op = normalize(self.text(&first));
+        let mut items = Vec::new();
+        loop {
+            self.next();
+            let saved = self.limit;
+            self.limit = col;
+            let item = self.expr();
+            self.limit = saved;
+            items.push(item?);
+            match self.peek() {
+                Some(t) if t.start.col == col && self.peek_op(op) => {}
+                _ => break,
+            }
+        }
+        let kind = if op == "/\\" { Junction::And } else { Junction::Or };
+        let kind = ExprKind::Junction { kind, items };
+        Ok(Expr { kind, span: self.span_from(first.start) })
+    }
+
+    // `x`, `x, y` or `<<x, y>>`
+    fn bound_vars(&mut self) -> Result<(Vec<Ident>, bool), Error> {
+        if self.peek_is(TokenType::TupleOpen) {
+            self.next();
+            let vars = self.ident_list()?;
+            self.expect(TokenType::TupleClose, ">>")?;
+            Ok((vars, true))
+        } else {
+            Ok((self.ident_list()?, false))
+        }
+    }
+
+    // `x, y \in S, <<a, b>> \in T`
+    pub(super) fn bounds(&mut self) -> Result<Vec<Bound>, Error> {
+        let mut res = Vec::new();
+        loop {
+            let (vars, tuple) = self.bound_vars()?;
+            self.expect_op("\\in", "\\in")?;
+            let set = Some(Box::new(self.expr()?));
+            res.push(Bound { vars, tuple, set });
+            if !self.peek_is(TokenType::Comma) {
+                return Ok(res);
+            }
+            self.next();
+        }
+    }
+
+    fn quantifier(&mut self, kind: Quantifier) -> Result<ExprKind, Error> {
+        self.next();
+        let saved = self.save();
+        let (vars, tuple) = self.bound_vars()?;
+        let bounds = if self.peek_is(TokenType::Colon) {
+            vec![Bound { vars, tuple, set: None }]
+        } else {
+            self.restore(saved);
+            self.bounds()?
+        };
+        self.expect(TokenType::Colon, ":")?;
+        let body = Box::new(self.expr()?);
+        Ok(ExprKind::Quant { kind, bounds, body })
+    }
+
+    // Speculatively parses bounds followed by the given token.
+    fn try_bounds(&mut self, follow: TokenType) -> Option<Vec<Bound>> {
+        let saved = self.save();
+        match self.bounds() {
+            Ok(bounds) if self.peek_is(follow) => Some(bounds),
+            _ => {
+                self.restore(saved);
+                None
+            }
+        }
+    }
+
+    // `{}`, `{a, b}`, `{x \in S : P}`, `{e : x \in S}`
+    fn set(&mut self) -> Result<ExprKind, Error> {
+        self.next();
+        if self.peek_is(TokenType::BraceClose) {
+            self.next();
+            return Ok(ExprKind::SetEnum(vec![]));
+        }
+        let kind = match self.try_bounds(TokenType::Colon) {
+            Some(mut bounds) if bounds.len() == 1 => {
+                self.next();
+                let bound = bounds.pop().unwrap();
+                let pred = Box::new(self.expr()?);
+                ExprKind::SetFilter { bound, pred }
+            }
+            Some(_) => return Err(self.unexpected("}")),
+            None => {
+                let first = self.expr()?;
+                if self.peek_is(TokenType::Colon) {
+                    self.next();
+                    ExprKind::SetMap { expr: Box::new(first), bounds: self.bounds()? }
+                } else {
+                    let mut items = vec![first];
+                    while self.peek_is(TokenType::Comma) {
+                        self.next();
+                        items.push(self.expr()?);
+                    }
+                    ExprKind::SetEnum(items)
+                }
+            }
+        };
+        self.expect(TokenType::BraceClose, "}")?;
+        Ok(kind)
+    }
+
+    // Records, functions, EXCEPT and `[A]_v`.
+    fn bracket(&mut self) -> Result<ExprKind, Error> {
+        self.next();
+        let field = self.peek_is(TokenType::Identifier);
+        let next = self.peek_at(1).map(|t| t.ty);
+        if field && (next == Some(TokenType::MapsTo) || next == Some(TokenType::Colon)) {
+            let set = next == Some(TokenType::Colon);
+            let mut fields = Vec::new();
+            loop {
+                let name = self.ident()?;
+                if set {
+                    self.expect(TokenType::Colon, ":")?;
+                } else {
+                    self.expect(TokenType::MapsTo, "|->")?;
+                }
+                fields.push((name, self.expr()?));
+                if !self.peek_is(TokenType::Comma) {
+                    break;
+                }
+                self.next();
+            }
+            self.expect(TokenType::BracketClose, "]")?;
+            return Ok(if set { ExprKind::RecordSet(fields) } else { ExprKind::Record(fields) });
+        }
+        if let Some(bounds) = self.try_bounds(TokenType::MapsTo) {
+            self.next();
+            let body = Box::new(self.expr()?);
+            self.expect(TokenType::BracketClose, "]")?;
+            return Ok(ExprKind::FnCons { bounds, body });
+        }
+        let e = self.expr()?;
+        match self.peek().map(|t| t.ty) {
+            Some(TokenType::Arrow) => {
+                self.next();
+                let to = self.expr()?;
+                self.expect(TokenType::BracketClose, "]")?;
+                Ok(ExprKind::FnSet(Box::new(e), Box::new(to)))
+            }
+            Some(TokenType::Keyword(Keyword::Except)) => {
+                self.next();
+                let mut updates = Vec::new();
+                loop {
+                    updates.push(self.except_update()?);
+                    if !self.peek_is(TokenType::Comma) {
+                        break;
+                    }
+                    self.next();
+                }
+                self.expect(TokenType::BracketClose, "]")?;
+                Ok(ExprKind::Except { base: Box::new(e), updates })
+            }
+            Some(TokenType::BracketClose) => {
+                self.next();
+                let sub = Box::new(self.subscript()?);
+                Ok(ExprKind::BoxAction { action: Box::new(e), sub })
+            }
+            _ => Err(self.unexpected("]")),
+        }
+    }
+
+    // `![a].b = e`
+    fn except_update(&mut self) -> Result<ExceptUpdate, Error> {
+        self.expect(TokenType::Bang, "!")?;
+        let mut path = Vec::new();
+        loop {
+            match self.peek().map(|t| t.ty) {
+                Some(TokenType::Dot) => {
+                    self.next();
+                    path.push(ExceptKey::Field(self.ident()?));
+                }
+                Some(TokenType::BracketOpen) => {
+                    self.next();
+                    path.push(ExceptKey::Index(self.expr_list()?));
+                    self.expect(TokenType::BracketClose, "]")?;
+                }
+                _ => break,
+            }
+        }
+        if path.is_empty() {
+            return Err(self.unexpected("[ or ."));
+        }
+        self.expect_op("=", "=")?;
+        Ok(ExceptUpdate { path, value: self.expr()? })
+    }
+
+    // `_v` after `[A]` or `<<A>>`.
+    fn subscript(&mut self) -> Result<Expr, Error> {
+        self.expect(TokenType::Wildcard, "_")?;
+        self.subscript_expr()
+    }
+
+    // Subscript is an identifier, a tuple or a parenthesized expression.
+    // It does not take arguments: `WF_vars(A)` is WF applied to `vars`.
+    fn subscript_expr(&mut self) -> Result<Expr, Error> {
+        let t = match self.peek() {
+            Some(t) => t,
+            None => return Err(self.unexpected("subscript")),
+        };
+        match t.ty {
+            TokenType::Identifier => {
+                let mut name = self.ident()?;
+                let mut path = Vec::new();
+                while self.peek_is(TokenType::Bang) {
+                    self.next();
+                    path.push(name);
+                    name = self.ident()?;
+                }
+                let kind = ExprKind::Apply { path, name, args: vec![] };
+                Ok(Expr { kind, span: self.span_from(t.start) })
+            }
+            TokenType::TupleOpen | TokenType::ParenOpen => self.primary(),
+            _ => Err(self.unexpected("subscript")),
+        }
+    }
+
+    fn case(&mut self) -> Result<ExprKind, Error> {
+        self.next();
+        let mut arms = Vec::new();
+        let mut other = None;
+        loop {
+            if self.peek_is(TokenType::Keyword(Keyword::Other)) {
+                self.next();
+                self.expect(TokenType::Arrow, "->")?;
+                other = Some(Box::new(self.expr()?));
+                break;
+            }
+            let guard = self.expr()?;
+            self.expect(TokenType::Arrow, "->")?;
+            arms.push((guard, self.expr()?));
+            if !self.peek_op("[]") {
+                break;
+            }
+            self.next();
+        }
+        Ok(ExprKind::Case { arms, other })
+    }
+}
+
+fn infix(op: Ident, lhs: Expr, rhs: Expr) -> Expr {
+    let span = lhs.span.to(&rhs.span);
+    let junction = match op.name.as_str() {
+        "/\\" => Some(Junction::And),
+        "\\/" => Some(Junction::Or),
+        _ => None,
+    };
+    let kind = match junction {
+        Some(kind) => {
+            let mut items = match lhs.kind {
+                ExprKind::Junction { kind: k, items } if k == kind => items,
+                _ => vec![lhs],
+            };
+            items.push(rhs);
+            ExprKind::Junction { kind, items }
+        }
+        None => ExprKind::OpApply { op, args: vec![lhs, rhs] },
+    };
+    Expr { kind, span }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::super::parse_expr;
+    use super::*;
+
+    fn expr(code: &str) -> Expr {
+        match parse_expr(code) {
+            Ok(e) => e,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    // Compact s-expression form of the AST for tests.
+    fn sexp(e: &Expr) -> String {
+        let list = |xs: &[Expr]| xs.iter().map(sexp).collect::<Vec<_>>().join(" ");
+        match &e.kind {
+            ExprKind::Num(n) => n.clone(),
+            ExprKind::Str(s) => format!("{:?}", s),
+            ExprKind::Apply { name, args, .. } if args.is_empty() => name.name.clone(),
+            ExprKind::Apply { name, args, .. } => format!("({} {})", name.name, list(args)),
+            ExprKind::OpApply { op, args } => format!("({} {})", op.name, list(args)),
+            ExprKind::Junction { kind, items } => format!("({:?} {})", kind, list(items)),
+            ExprKind::FnApply(f, args) => format!("([] {} {})", sexp(f), list(args)),
+            ExprKind::Tuple(items) => format!("<<{}>>", list(items)),
+            ExprKind::SetEnum(items) => format!("{{{}}}", list(items)),
+            _ => format!("{:?}", std::mem::discriminant(&e.kind)),
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(sexp(&expr("a + b * c")), "(+ a (* b c))");
+        assert_eq!(sexp(&expr("a + b + c")), "(+ (+ a b) c)");
+        assert_eq!(sexp(&expr("x \\in S \\cup T")), "(\\in x (\\cup S T))");
+        assert_eq!(sexp(&expr("~ a = b /\\ c")), "(And (~ (= a b)) c)");
+        assert_eq!(sexp(&expr("-a + b")), "(+ (-. a) b)");
+        assert_eq!(sexp(&expr("x' = f[x, 1] \\land y")), "(And (= (' x) ([] f x 1)) y)");
+        assert_eq!(sexp(&expr("A \\X B \\times C")), "(\\X A B C)");
+        assert_eq!(sexp(&expr("[]P => <>Q")), "(=> ([] P) (<> Q))");
+        assert_eq!(sexp(&expr("{1, 2} \\subseteq SUBSET S")), "(\\subseteq {1 2} (SUBSET S))");
+    }
+
+    #[test]
+    fn bullets() {
+        let e = expr("
+            /\\ x = 1
+            /\\ \\/ y = 2
+               \\/ y = 3
+            /\\ z");
+        assert_eq!(sexp(&e), "(And (= x 1) (Or (= y 2) (= y 3)) z)");
+        // Item ends at the token to the left of the bullet.
+        let e = expr("
+            \\/ a
+          => b");
+        assert_eq!(sexp(&e), "(=> (Or a) b)");
+    }
+
+    #[test]
+    fn compound() {
+        let kind = |code| expr(code).kind;
+        assert!(matches!(kind("{x \\in S : x > 1}"), ExprKind::SetFilter { .. }));
+        assert!(matches!(kind("{x \\in S}"), ExprKind::SetEnum(_)));
+        assert!(matches!(kind("{f[x] : x \\in S}"), ExprKind::SetMap { .. }));
+        assert!(matches!(kind("[x, y \\in S |-> x + y]"), ExprKind::FnCons { .. }));
+        assert!(matches!(kind("[a |-> 1, b |-> 2]"), ExprKind::Record(_)));
+        assert!(matches!(kind("[a : S, b : T]"), ExprKind::RecordSet(_)));
+        assert!(matches!(kind("[S -> T]"), ExprKind::FnSet(..)));
+        assert!(matches!(kind("[f EXCEPT ![1].a = @ + 1, !.b = 2]"), ExprKind::Except { .. }));
+        assert!(matches!(kind("[Next]_vars"), ExprKind::BoxAction { .. }));
+        assert!(matches!(kind("<<A>>_<<x, y>>"), ExprKind::AngleAction { .. }));
+        assert!(matches!(kind("<<A>>"), ExprKind::Tuple(_)));
+        assert!(matches!(kind("WF_vars(Next)"), ExprKind::Fairness { strong: false, .. }));
+        assert!(matches!(kind("\\A x \\in S, y \\in T : x = y"), ExprKind::Quant { .. }));
+        assert!(matches!(kind("\\E x, y : x = y"), ExprKind::Quant { .. }));
+        assert!(matches!(kind("CHOOSE <<x, y>> \\in S : TRUE"), ExprKind::Choose { .. }));
+        assert!(matches!(kind("LET f(x) == x g == 1 IN f(g)"), ExprKind::Let { .. }));
+        assert!(matches!(kind("CASE x = 1 -> a [] x = 2 -> b [] OTHER -> c"), ExprKind::Case { .. }));
+        assert!(matches!(kind("IF a THEN b ELSE c"), ExprKind::If { .. }));
+        assert!(matches!(kind("LAMBDA x, y : x + y"), ExprKind::Lambda { .. }));
+        assert!(matches!(kind("I!J!F(1)"), ExprKind::Apply { ref path, .. } if path.len() == 2));
+        assert!(matches!(kind("r.a.b"), ExprKind::Field(..)));
+    }
+}
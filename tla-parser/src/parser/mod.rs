@@ -0,0 +1,550 @@
+mod expr;
+mod precedence;
+
+use std::fmt;
+
+use crate::ast::*;
+use crate::lexer::{self, Keyword, Pos, TokenType};
+
+pub use precedence::{infix, is_postfix, normalize, prefix, Assoc};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Lexer { span: Span },
+    Unexpected { span: Span, found: String, expected: &'static str },
+    UnexpectedEnd { expected: &'static str },
+}
+
+impl Error {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::Lexer { span } | Error::Unexpected { span, .. } => Some(*span),
+            Error::UnexpectedEnd { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Lexer { span } => write!(
+                f, "{}:{}: unrecognized token", span.start.line, span.start.col),
+            Error::Unexpected { span, found, expected } => write!(
+                f, "{}:{}: unexpected `{}`, expected {}",
+                span.start.line, span.start.col, found, expected),
+            Error::UnexpectedEnd { expected } => write!(
+                f, "unexpected end of input, expected {}", expected),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
+struct Token {
+    ty: TokenType,
+    start: Pos,
+    end: Pos,
+}
+
+/// Recursive descent parser over the token stream.
+/// Comments and line breaks are dropped, but token columns are used to
+/// delimit bulleted `/\` and `\/` lists.
+pub struct Parser<'a> {
+    src: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    // Tokens at this column or to the left of it end the current
+    // item of a bulleted list.
+    limit: usize,
+    prev_end: Pos,
+}
+
+
+/// Parses the first module found in the code.
+pub fn parse(code: &str) -> Result<Module, Error> {
+    let mut p = Parser::new(code)?;
+    p.module()
+}
+
+/// Parses standalone expression, e.g. `1 + 2`.
+pub fn parse_expr(code: &str) -> Result<Expr, Error> {
+    let mut p = Parser::new(code)?;
+    let e = p.expr()?;
+    match p.peek() {
+        None => Ok(e),
+        Some(_) => Err(p.unexpected("end of input")),
+    }
+}
+
+
+impl<'a> Parser<'a> {
+    pub fn new(src: &'a str) -> Result<Self, Error> {
+        let mut tokens = Vec::new();
+        for lexeme in lexer::lex(src) {
+            match lexeme.value {
+                Ok(TokenType::Comment) | Ok(TokenType::Indent) => {}
+                Ok(ty) => tokens.push(Token { ty, start: lexeme.start, end: lexeme.end }),
+                Err(_) => return Err(Error::Lexer { span: Span::new(lexeme.start, lexeme.end) }),
+            }
+        }
+        let start = lexer::Lexer::new(src).pos;
+        Ok(Parser { src, tokens, pos: 0, limit: 0, prev_end: start })
+    }
+
+    // Next token if it is not hidden by the column limit.
+    fn peek(&self) -> Option<Token> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, n: usize) -> Option<Token> {
+        self.tokens
+            .get(self.pos + n)
+            .filter(|t| t.start.col > self.limit)
+            .copied()
+    }
+
+    fn peek_is(&self, ty: TokenType) -> bool {
+        self.peek().map(|t| t.ty) == Some(ty)
+    }
+
+    // Checks if next token is an operator with the normalized name `op`.
+    fn peek_op(&self, op: &str) -> bool {
+        match self.peek() {
+            Some(t) => is_operator(t.ty) && normalize(self.text(&t)) == op,
+            None => false,
+        }
+    }
+
+    fn text(&self, t: &Token) -> &'a str {
+        &self.src[t.start.byte_offset..t.end.byte_offset]
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.peek()?;
+        self.pos += 1;
+        self.prev_end = t.end;
+        Some(t)
+    }
+
+    fn expect(&mut self, ty: TokenType, expected: &'static str) -> Result<Token, Error> {
+        match self.peek() {
+            Some(t) if t.ty == ty => Ok(self.next().unwrap()),
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn expect_op(&mut self, op: &str, expected: &'static str) -> Result<Token, Error> {
+        if self.peek_op(op) {
+            Ok(self.next().unwrap())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    fn unexpected(&self, expected: &'static str) -> Error {
+        match self.peek() {
+            Some(t) => Error::Unexpected {
+                span: Span::new(t.start, t.end),
+                found: self.text(&t).to_string(),
+                expected,
+            },
+            None => Error::UnexpectedEnd { expected },
+        }
+    }
+
+    fn span_from(&self, start: Pos) -> Span {
+        Span::new(start, self.prev_end)
+    }
+
+    fn ident(&mut self) -> Result<Ident, Error> {
+        let t = self.expect(TokenType::Identifier, "identifier")?;
+        Ok(Ident {
+            name: self.text(&t).to_string(),
+            span: Span::new(t.start, t.end),
+        })
+    }
+
+    fn ident_list(&mut self) -> Result<Vec<Ident>, Error> {
+        let mut res = vec![self.ident()?];
+        while self.peek_is(TokenType::Comma) {
+            self.next();
+            res.push(self.ident()?);
+        }
+        Ok(res)
+    }
+
+    // Operator symbol as an identifier, e.g. in `a (+) b == ...`.
+    fn op_ident(&mut self, t: Token) -> Ident {
+        Ident {
+            name: normalize(self.text(&t)).to_string(),
+            span: Span::new(t.start, t.end),
+        }
+    }
+
+    fn save(&self) -> (usize, Pos) {
+        (self.pos, self.prev_end)
+    }
+
+    fn restore(&mut self, saved: (usize, Pos)) {
+        self.pos = saved.0;
+        self.prev_end = saved.1;
+    }
+
+    pub fn module(&mut self) -> Result<Module, Error> {
+        // Text before the module header is ignored.
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(s), Some(m)) if s.ty == TokenType::Separator
+                    && m.ty == TokenType::Keyword(Keyword::Module) => break,
+                (Some(_), _) => { self.next(); }
+                (None, _) => return Err(Error::UnexpectedEnd { expected: "module header" }),
+            }
+        }
+        self.module_body()
+    }
+
+    fn module_body(&mut self) -> Result<Module, Error> {
+        let start = self.expect(TokenType::Separator, "----")?.start;
+        self.expect(TokenType::Keyword(Keyword::Module), "MODULE")?;
+        let name = self.ident()?;
+        self.expect(TokenType::Separator, "----")?;
+        let mut extends = Vec::new();
+        if self.peek_is(TokenType::Keyword(Keyword::Extends)) {
+            self.next();
+            extends = self.ident_list()?;
+        }
+        let mut units = Vec::new();
+        loop {
+            let t = match self.peek() {
+                Some(t) => t,
+                None => return Err(self.unexpected("====")),
+            };
+            match t.ty {
+                TokenType::ModuleEnd => {
+                    self.next();
+                    break;
+                }
+                TokenType::Separator => {
+                    let nested = self.peek_at(1)
+                        .map(|t| t.ty) == Some(TokenType::Keyword(Keyword::Module));
+                    if nested {
+                        units.push(Unit::Module(self.module_body()?));
+                    } else {
+                        self.next();
+                    }
+                }
+                TokenType::Keyword(Keyword::Constant) => {
+                    self.next();
+                    units.push(Unit::Constants(self.op_decls()?));
+                }
+                TokenType::Keyword(Keyword::Variable) => {
+                    self.next();
+                    units.push(Unit::Variables(self.ident_list()?));
+                }
+                TokenType::Keyword(Keyword::Recursive) => {
+                    self.next();
+                    units.push(Unit::Recursive(self.op_decls()?));
+                }
+                TokenType::Keyword(Keyword::Local) => {
+                    self.next();
+                    if self.peek_is(TokenType::Keyword(Keyword::Instance)) {
+                        units.push(Unit::Instance(self.instance(true)?));
+                    } else {
+                        units.push(Unit::Definition(self.definition(true)?));
+                    }
+                }
+                TokenType::Keyword(Keyword::Instance) => {
+                    units.push(Unit::Instance(self.instance(false)?));
+                }
+                TokenType::Keyword(Keyword::Assume) => {
+                    self.next();
+                    units.push(Unit::Assume(self.assumption()?));
+                }
+                TokenType::Keyword(Keyword::Theorem) => {
+                    self.next();
+                    units.push(Unit::Theorem(self.assumption()?));
+                    self.skip_proof();
+                }
+                _ => units.push(Unit::Definition(self.definition(false)?)),
+            }
+        }
+        Ok(Module { name, extends, units, span: self.span_from(start) })
+    }
+
+    fn assumption(&mut self) -> Result<Assumption, Error> {
+        let named = self.peek_is(TokenType::Identifier)
+            && self.peek_at(1).map(|t| t.ty) == Some(TokenType::DefEq);
+        let name = if named {
+            let name = self.ident()?;
+            self.next();
+            Some(name)
+        } else {
+            None
+        };
+        Ok(Assumption { name, expr: self.expr()? })
+    }
+
+    // Proofs are not supported yet, skip everything up to the next
+    // token at the start of a line.
+    fn skip_proof(&mut self) {
+        let in_proof = match self.peek() {
+            Some(t) => t.ty == TokenType::Keyword(Keyword::Proof) || self.text(&t) == "<",
+            None => false,
+        };
+        if in_proof {
+            while let Some(t) = self.peek() {
+                if t.start.col == 1 {
+                    break;
+                }
+                self.next();
+            }
+        }
+    }
+
+    fn op_decls(&mut self) -> Result<Vec<OpDecl>, Error> {
+        let mut res = vec![self.op_decl()?];
+        while self.peek_is(TokenType::Comma) {
+            self.next();
+            res.push(self.op_decl()?);
+        }
+        Ok(res)
+    }
+
+    // `F`, `F(_, _)`, `-. _`, `_ + _`, `_ ^+`
+    fn op_decl(&mut self) -> Result<OpDecl, Error> {
+        let t = match self.peek() {
+            Some(t) => t,
+            None => return Err(self.unexpected("operator declaration")),
+        };
+        match t.ty {
+            TokenType::Identifier => {
+                let name = self.ident()?;
+                let mut arity = 0;
+                if self.peek_is(TokenType::ParenOpen) {
+                    self.next();
+                    loop {
+                        self.expect(TokenType::Wildcard, "_")?;
+                        arity += 1;
+                        if !self.peek_is(TokenType::Comma) {
+                            break;
+                        }
+                        self.next();
+                    }
+                    self.expect(TokenType::ParenClose, ")")?;
+                }
+                Ok(OpDecl { name, arity, shape: Shape::Ordinary })
+            }
+            TokenType::PrefixOperator => {
+                self.next();
+                let name = self.op_ident(t);
+                self.expect(TokenType::Wildcard, "_")?;
+                Ok(OpDecl { name, arity: 1, shape: Shape::Prefix })
+            }
+            TokenType::Wildcard => {
+                self.next();
+                match self.peek() {
+                    Some(op) if op.ty == TokenType::PostfixOperator => {
+                        self.next();
+                        Ok(OpDecl { name: self.op_ident(op), arity: 1, shape: Shape::Postfix })
+                    }
+                    Some(op) if op.ty == TokenType::InfixOperator => {
+                        self.next();
+                        let name = self.op_ident(op);
+                        self.expect(TokenType::Wildcard, "_")?;
+                        Ok(OpDecl { name, arity: 2, shape: Shape::Infix })
+                    }
+                    _ => Err(self.unexpected("operator symbol")),
+                }
+            }
+            _ => Err(self.unexpected("operator declaration")),
+        }
+    }
+
+    fn instance(&mut self, local: bool) -> Result<Instance, Error> {
+        let start = self.expect(TokenType::Keyword(Keyword::Instance), "INSTANCE")?.start;
+        let module = self.ident()?;
+        let mut substitutions = Vec::new();
+        if self.peek_is(TokenType::Keyword(Keyword::With)) {
+            self.next();
+            loop {
+                let name = match self.peek() {
+                    Some(t) if t.ty == TokenType::Identifier => {
+                        self.next();
+                        Ident { name: self.text(&t).to_string(), span: Span::new(t.start, t.end) }
+                    }
+                    Some(t) if is_operator(t.ty) => {
+                        self.next();
+                        self.op_ident(t)
+                    }
+                    _ => return Err(self.unexpected("identifier")),
+                };
+                self.expect(TokenType::LeftArrow, "<-")?;
+                substitutions.push((name, self.expr()?));
+                if !self.peek_is(TokenType::Comma) {
+                    break;
+                }
+                self.next();
+            }
+        }
+        Ok(Instance { module, substitutions, local, span: self.span_from(start) })
+    }
+
+    pub(crate) fn definition(&mut self, local: bool) -> Result<Definition, Error> {
+        let t = match self.peek() {
+            Some(t) => t,
+            None => return Err(self.unexpected("definition")),
+        };
+        let start = t.start;
+        let decl = |name: Ident| OpDecl { name, arity: 0, shape: Shape::Ordinary };
+        let mut bounds = None;
+        let (name, shape, params) = match t.ty {
+            TokenType::PrefixOperator => {
+                self.next();
+                let name = self.op_ident(t);
+                let param = self.ident()?;
+                (name, Shape::Prefix, vec![decl(param)])
+            }
+            TokenType::Identifier => {
+                let first = self.ident()?;
+                match self.peek() {
+                    Some(t) if t.ty == TokenType::ParenOpen => {
+                        self.next();
+                        let params = self.op_decls()?;
+                        self.expect(TokenType::ParenClose, ")")?;
+                        (first, Shape::Ordinary, params)
+                    }
+                    Some(t) if t.ty == TokenType::BracketOpen => {
+                        self.next();
+                        bounds = Some(self.bounds()?);
+                        self.expect(TokenType::BracketClose, "]")?;
+                        (first, Shape::Ordinary, vec![])
+                    }
+                    Some(t) if t.ty == TokenType::InfixOperator => {
+                        self.next();
+                        let name = self.op_ident(t);
+                        let second = self.ident()?;
+                        (name, Shape::Infix, vec![decl(first), decl(second)])
+                    }
+                    Some(t) if t.ty == TokenType::PostfixOperator => {
+                        self.next();
+                        (self.op_ident(t), Shape::Postfix, vec![decl(first)])
+                    }
+                    _ => (first, Shape::Ordinary, vec![]),
+                }
+            }
+            _ => return Err(self.unexpected("definition")),
+        };
+        self.expect(TokenType::DefEq, "==")?;
+        let body = match bounds {
+            Some(bounds) => DefBody::Function(bounds, self.expr()?),
+            None if self.peek_is(TokenType::Keyword(Keyword::Instance)) => {
+                DefBody::Instance(self.instance(local)?)
+            }
+            None => DefBody::Expr(self.expr()?),
+        };
+        Ok(Definition { name, shape, params, local, body, span: self.span_from(start) })
+    }
+}
+
+fn is_operator(ty: TokenType) -> bool {
+    matches!(
+        ty,
+        TokenType::PrefixOperator | TokenType::InfixOperator | TokenType::PostfixOperator
+    )
+}
+
+fn unescape(s: &str) -> String {
+    let mut res = String::new();
+    let mut chars = s[1..s.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => res.push('\n'),
+            Some('t') => res.push('\t'),
+            Some('r') => res.push('\r'),
+            Some('f') => res.push('\x0c'),
+            Some(c) => res.push(c),
+            None => {}
+        }
+    }
+    res
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(code: &str) -> Module {
+        match parse(code) {
+            Ok(m) => m,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    #[test]
+    fn module_units() {
+        let m = module(r#"
+            text before the module is ignored
+            ---- MODULE Test ----
+            EXTENDS Naturals, Sequences
+            CONSTANTS N, F(_, _), _ ++ _
+            VARIABLES x, y
+            ----
+            Init == x = 0 /\ y = 0
+            Inc(v) == v' = v + 1
+            a (+) b == a
+            f[n \in Nat] == IF n = 0 THEN 1 ELSE n * f[n - 1]
+            LOCAL I == INSTANCE Other WITH z <- x
+            ASSUME N > 0
+            THEOREM T == Init => TRUE
+            ====
+            "#);
+        assert_eq!(m.name.name, "Test");
+        assert_eq!(m.extends.len(), 2);
+        assert_eq!(m.constants().map(|c| c.arity).collect::<Vec<_>>(), vec![0, 2, 2]);
+        assert_eq!(m.variables().count(), 2);
+        let names: Vec<_> = m.definitions().map(|d| d.name.name.as_str()).collect();
+        assert_eq!(names, vec!["Init", "Inc", "\\oplus", "f", "I"]);
+        assert_eq!(m.units.len(), 9);
+        match &m.definition("I").unwrap().body {
+            DefBody::Instance(inst) => assert!(inst.local),
+            _ => panic!("expected instance"),
+        }
+    }
+
+    #[test]
+    fn subscripts() {
+        let m = module("---- MODULE M ----\n_x == 1\nSpec == [][_x' = _x]_<<_x>> /\\ WF__x(TRUE)\n====");
+        let names: Vec<_> = m.definitions().map(|d| d.name.name.as_str()).collect();
+        assert_eq!(names, vec!["_x", "Spec"]);
+    }
+
+    #[test]
+    fn nested_module() {
+        let m = module("---- MODULE A ----\n---- MODULE B ----\nX == 1\n====\nY == 2\n====");
+        assert_eq!(m.units.len(), 2);
+        assert!(matches!(&m.units[0], Unit::Module(b) if b.name.name == "B"));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(parse("no module"), Err(Error::UnexpectedEnd { .. })));
+        let err = parse("---- MODULE M ----\nX == \n====").unwrap_err();
+        assert!(matches!(err, Error::Unexpected { expected: "expression", .. }));
+        assert_eq!(err.span().unwrap().start.line, 3);
+        assert!(matches!(parse("---- MODULE M ----\nX == 1 ? 2\n===="), Err(Error::Lexer { .. })));
+        let end = |code| matches!(parse(code), Err(Error::UnexpectedEnd { .. }));
+        assert!(end("---- MODULE M ----\nINSTANCE N WITH"));
+        assert!(end("---- MODULE M ----\nCONSTANT _"));
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(unescape(r#""a\"b\\c""#), "a\"b\\c");
+    }
+}
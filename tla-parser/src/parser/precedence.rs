@@ -0,0 +1,182 @@
+use crate::lexer::to_ascii;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Assoc {
+    Left,
+    None,
+}
+
+// Operator precedence ranges from "Specifying Systems", table 6.
+// Operators are listed in normalized form (see SYNONYMS).
+
+// This table must be sorted.
+// Dont forget LANG=C if you use unix sort.
+pub static INFIX: &[(&str, u8, u8, Assoc)] = &[
+    ("!!", 9, 13, Assoc::None),
+    ("##", 9, 13, Assoc::Left),
+    ("$", 9, 13, Assoc::Left),
+    ("$$", 9, 13, Assoc::Left),
+    ("%", 10, 11, Assoc::None),
+    ("%%", 10, 11, Assoc::Left),
+    ("&", 13, 13, Assoc::Left),
+    ("&&", 13, 13, Assoc::Left),
+    ("*", 13, 13, Assoc::Left),
+    ("**", 13, 13, Assoc::Left),
+    ("+", 10, 10, Assoc::Left),
+    ("++", 10, 10, Assoc::Left),
+    ("-", 11, 11, Assoc::Left),
+    ("-+->", 2, 2, Assoc::None),
+    ("--", 11, 11, Assoc::Left),
+    ("-|", 5, 5, Assoc::None),
+    ("..", 9, 9, Assoc::None),
+    ("...", 9, 9, Assoc::None),
+    ("/", 13, 13, Assoc::None),
+    ("//", 13, 13, Assoc::None),
+    ("/=", 5, 5, Assoc::None),
+    ("/\\", 3, 3, Assoc::Left),
+    ("::=", 5, 5, Assoc::None),
+    (":=", 5, 5, Assoc::None),
+    (":>", 7, 7, Assoc::None),
+    ("<", 5, 5, Assoc::None),
+    ("<:", 7, 7, Assoc::None),
+    ("<=", 5, 5, Assoc::None),
+    ("<=>", 2, 2, Assoc::None),
+    ("=", 5, 5, Assoc::None),
+    ("=>", 1, 1, Assoc::None),
+    ("=|", 5, 5, Assoc::None),
+    (">", 5, 5, Assoc::None),
+    (">=", 5, 5, Assoc::None),
+    ("??", 9, 13, Assoc::Left),
+    ("@@", 6, 6, Assoc::Left),
+    ("\\", 8, 8, Assoc::None),
+    ("\\/", 3, 3, Assoc::Left),
+    ("\\X", 10, 13, Assoc::Left),
+    ("\\approx", 5, 5, Assoc::None),
+    ("\\asymp", 5, 5, Assoc::None),
+    ("\\bigcirc", 13, 13, Assoc::Left),
+    ("\\bullet", 13, 13, Assoc::Left),
+    ("\\cap", 8, 8, Assoc::Left),
+    ("\\cdot", 5, 14, Assoc::Left),
+    ("\\cong", 5, 5, Assoc::None),
+    ("\\cup", 8, 8, Assoc::Left),
+    ("\\div", 13, 13, Assoc::None),
+    ("\\doteq", 5, 5, Assoc::None),
+    ("\\equiv", 2, 2, Assoc::None),
+    ("\\gg", 5, 5, Assoc::None),
+    ("\\in", 5, 5, Assoc::None),
+    ("\\ll", 5, 5, Assoc::None),
+    ("\\notin", 5, 5, Assoc::None),
+    ("\\o", 13, 13, Assoc::Left),
+    ("\\odot", 13, 13, Assoc::Left),
+    ("\\ominus", 11, 11, Assoc::Left),
+    ("\\oplus", 10, 10, Assoc::Left),
+    ("\\oslash", 13, 13, Assoc::None),
+    ("\\otimes", 13, 13, Assoc::Left),
+    ("\\prec", 5, 5, Assoc::None),
+    ("\\preceq", 5, 5, Assoc::None),
+    ("\\propto", 5, 5, Assoc::None),
+    ("\\sim", 5, 5, Assoc::None),
+    ("\\simeq", 5, 5, Assoc::None),
+    ("\\sqcap", 9, 13, Assoc::Left),
+    ("\\sqcup", 9, 13, Assoc::Left),
+    ("\\sqsubset", 5, 5, Assoc::None),
+    ("\\sqsubseteq", 5, 5, Assoc::None),
+    ("\\sqsupset", 5, 5, Assoc::None),
+    ("\\sqsupseteq", 5, 5, Assoc::None),
+    ("\\star", 13, 13, Assoc::Left),
+    ("\\subset", 5, 5, Assoc::None),
+    ("\\subseteq", 5, 5, Assoc::None),
+    ("\\succ", 5, 5, Assoc::None),
+    ("\\succeq", 5, 5, Assoc::None),
+    ("\\supset", 5, 5, Assoc::None),
+    ("\\supseteq", 5, 5, Assoc::None),
+    ("\\uplus", 9, 13, Assoc::Left),
+    ("\\wr", 9, 14, Assoc::None),
+    ("^", 14, 14, Assoc::None),
+    ("^^", 14, 14, Assoc::None),
+    ("|", 10, 11, Assoc::Left),
+    ("|-", 5, 5, Assoc::None),
+    ("|=", 5, 5, Assoc::None),
+    ("||", 10, 11, Assoc::Left),
+    ("~>", 2, 2, Assoc::None),
+];
+
+// This table must be sorted.
+pub static PREFIX: &[(&str, u8, u8)] = &[
+    ("-.", 12, 12),
+    ("<>", 4, 15),
+    ("DOMAIN", 9, 9),
+    ("ENABLED", 4, 15),
+    ("SUBSET", 8, 8),
+    ("UNCHANGED", 4, 15),
+    ("UNION", 8, 8),
+    ("[]", 4, 15),
+    ("~", 4, 4),
+];
+
+pub static POSTFIX: &[&str] = &["'", "^#", "^*", "^+"];
+
+// Alternative spellings of the same operator.
+// This table must be sorted.
+pub static SYNONYMS: &[(&str, &str)] = &[
+    ("#", "/="),
+    ("(+)", "\\oplus"),
+    ("(-)", "\\ominus"),
+    ("(.)", "\\odot"),
+    ("(/)", "\\oslash"),
+    ("(\\X)", "\\otimes"),
+    ("=<", "<="),
+    ("\\circ", "\\o"),
+    ("\\geq", ">="),
+    ("\\intersect", "\\cap"),
+    ("\\land", "/\\"),
+    ("\\leq", "<="),
+    ("\\lnot", "~"),
+    ("\\lor", "\\/"),
+    ("\\neg", "~"),
+    ("\\times", "\\X"),
+    ("\\union", "\\cup"),
+];
+
+
+/// Returns canonical spelling of the operator.
+/// Unicode symbols are replaced with their ASCII forms.
+pub fn normalize(op: &str) -> &str {
+    let op = to_ascii(op).unwrap_or(op);
+    match SYNONYMS.binary_search_by_key(&op, |t| t.0) {
+        Ok(i) => SYNONYMS[i].1,
+        Err(_) => op,
+    }
+}
+
+pub fn infix(op: &str) -> Option<(u8, u8, Assoc)> {
+    INFIX
+        .binary_search_by_key(&op, |t| t.0)
+        .ok()
+        .map(|i| (INFIX[i].1, INFIX[i].2, INFIX[i].3))
+}
+
+pub fn prefix(op: &str) -> Option<(u8, u8)> {
+    PREFIX
+        .binary_search_by_key(&op, |t| t.0)
+        .ok()
+        .map(|i| (PREFIX[i].1, PREFIX[i].2))
+}
+
+pub fn is_postfix(op: &str) -> bool {
+    POSTFIX.contains(&op)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_are_sorted() {
+        assert!(INFIX.is_sorted_by_key(|t| t.0));
+        assert!(PREFIX.is_sorted_by_key(|t| t.0));
+        assert!(SYNONYMS.is_sorted_by_key(|t| t.0));
+    }
+}
@@ -0,0 +1,1001 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ast::*;
+use crate::parser::is_postfix;
+use crate::stdlib;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeclKind {
+    Builtin,
+    Constant,
+    Variable,
+    Operator,
+    Function,
+    Instance,
+    Parameter,
+    Bound,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decl {
+    pub name: String,
+    pub kind: DeclKind,
+    pub arity: usize,
+    pub shape: Shape,
+    /// Module where the name is declared, `None` for built-in operators.
+    pub module: Option<String>,
+    pub span: Option<Span>,
+}
+
+impl Decl {
+    /// Constants and variables are the parameters of a module,
+    /// they can be substituted when the module is instantiated.
+    pub fn is_parameter(&self) -> bool {
+        matches!(self.kind, DeclKind::Constant | DeclKind::Variable)
+    }
+}
+
+/// Use of a name at `span` bound to `decls[decl]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub span: Span,
+    pub decl: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Undefined { name: String, span: Span },
+    /// Name is declared twice in the same scope.
+    Duplicate { name: String, span: Span, previous: Option<Span> },
+    /// Name is redeclared in a nested scope. TLA+ does not allow this.
+    Shadowing { name: String, span: Span, previous: Option<Span> },
+    UnknownModule { name: String, span: Span },
+    /// Operator applied to a wrong number of arguments.
+    Arity { name: String, span: Span, expected: usize, found: usize },
+    /// Operator used as infix, prefix or postfix, but declared otherwise.
+    Shape { name: String, span: Span, expected: Shape },
+    /// Argument of a higher-order operator is not an operator of the
+    /// expected arity.
+    OperatorArgument { span: Span, expected: usize },
+    /// Module that depends on itself through EXTENDS and INSTANCE: the
+    /// modules of the cycle, from the one with the error back to it.
+    Circular { modules: Vec<String>, span: Span },
+}
+
+impl Error {
+    pub fn span(&self) -> Span {
+        match self {
+            Error::Undefined { span, .. }
+            | Error::Duplicate { span, .. }
+            | Error::Shadowing { span, .. }
+            | Error::UnknownModule { span, .. }
+            | Error::Arity { span, .. }
+            | Error::Shape { span, .. }
+            | Error::OperatorArgument { span, .. }
+            | Error::Circular { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pos = self.span().start;
+        write!(f, "{}:{}: ", pos.line, pos.col)?;
+        match self {
+            Error::Undefined { name, .. } => write!(f, "unknown identifier `{}`", name),
+            Error::Duplicate { name, .. } => write!(f, "multiply-defined symbol `{}`", name),
+            Error::Shadowing { name, .. } => write!(f, "`{}` is already defined", name),
+            Error::UnknownModule { name, .. } => write!(f, "cannot find module `{}`", name),
+            Error::Arity { name, expected, found, .. } => write!(
+                f, "`{}` expects {} argument(s), but {} given", name, expected, found),
+            Error::Shape { name, expected, .. } => {
+                let shape = match expected {
+                    Shape::Ordinary => "an ordinary",
+                    Shape::Prefix => "a prefix",
+                    Shape::Infix => "an infix",
+                    Shape::Postfix => "a postfix",
+                };
+                write!(f, "`{}` is not {} operator", name, shape)
+            }
+            Error::OperatorArgument { expected, .. } => write!(
+                f, "expected an operator that takes {} argument(s)", expected),
+            Error::Circular { modules, .. } => write!(
+                f, "circular module dependency {}", modules.join(" -> ")),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Resolution {
+    pub decls: Vec<Decl>,
+    pub references: Vec<Reference>,
+    pub errors: Vec<Error>,
+}
+
+impl Resolution {
+    /// Declaration of the name at the given byte offset, for hover and
+    /// go-to-definition.
+    pub fn decl_at(&self, offset: usize) -> Option<&Decl> {
+        self.references
+            .iter()
+            .find(|r| r.span.contains(offset))
+            .map(|r| &self.decls[r.decl])
+    }
+
+    /// Declaration referenced by the name at `span`.
+    pub fn decl_of(&self, span: &Span) -> Option<&Decl> {
+        self.references
+            .iter()
+            .find(|r| r.span.start.byte_offset == span.start.byte_offset)
+            .map(|r| &self.decls[r.decl])
+    }
+}
+
+/// Source of the modules referenced by EXTENDS and INSTANCE.
+/// Standard modules (Naturals, Sequences, etc.) are always available
+/// unless overridden.
+pub trait Modules {
+    fn module(&self, name: &str) -> Option<&Module>;
+}
+
+impl Modules for Vec<Module> {
+    fn module(&self, name: &str) -> Option<&Module> {
+        self.iter().find(|m| m.name.name == name)
+    }
+}
+
+
+// Built-in operators of TLA+ that are not defined in any module.
+pub static BUILTINS: &[(&str, usize, Shape)] = &[
+    ("'", 1, Shape::Postfix),
+    ("-+->", 2, Shape::Infix),
+    ("/=", 2, Shape::Infix),
+    ("/\\", 2, Shape::Infix),
+    ("<=>", 2, Shape::Infix),
+    ("<>", 1, Shape::Prefix),
+    ("=", 2, Shape::Infix),
+    ("=>", 2, Shape::Infix),
+    ("BOOLEAN", 0, Shape::Ordinary),
+    ("DOMAIN", 1, Shape::Prefix),
+    ("ENABLED", 1, Shape::Prefix),
+    ("FALSE", 0, Shape::Ordinary),
+    ("STRING", 0, Shape::Ordinary),
+    ("SUBSET", 1, Shape::Prefix),
+    ("TRUE", 0, Shape::Ordinary),
+    ("UNCHANGED", 1, Shape::Prefix),
+    ("UNION", 1, Shape::Prefix),
+    ("[]", 1, Shape::Prefix),
+    ("\\", 2, Shape::Infix),
+    ("\\/", 2, Shape::Infix),
+    ("\\X", 2, Shape::Infix),
+    ("\\cap", 2, Shape::Infix),
+    ("\\cdot", 2, Shape::Infix),
+    ("\\cup", 2, Shape::Infix),
+    ("\\equiv", 2, Shape::Infix),
+    ("\\in", 2, Shape::Infix),
+    ("\\notin", 2, Shape::Infix),
+    ("\\subseteq", 2, Shape::Infix),
+    ("~", 1, Shape::Prefix),
+    ("~>", 2, Shape::Infix),
+];
+
+
+pub fn resolve(module: &Module) -> Resolution {
+    resolve_with(module, &Vec::new())
+}
+
+pub fn resolve_with(module: &Module, modules: &dyn Modules) -> Resolution {
+    let mut r = Resolver::new(modules);
+    r.module(module);
+    r.res
+}
+
+
+struct Resolver<'a> {
+    modules: &'a dyn Modules,
+    // Nested modules that are visible at this point.
+    local_modules: Vec<&'a Module>,
+    res: Resolution,
+    scopes: Vec<HashMap<String, usize>>,
+    // Names exported by a module, cached by module name.
+    exports: HashMap<String, Vec<usize>>,
+    // Names accessible with `I!name` for each named instance.
+    members: HashMap<usize, HashMap<String, usize>>,
+    // Modules whose exports are being computed, to break EXTENDS cycles.
+    visiting: Vec<String>,
+    // Arities of the parameters of higher-order operators, e.g. `[2, 0]`
+    // for `F(G(_, _), x)`.
+    params: HashMap<usize, Vec<usize>>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(modules: &'a dyn Modules) -> Self {
+        let mut r = Resolver {
+            modules,
+            local_modules: Vec::new(),
+            res: Resolution::default(),
+            scopes: vec![HashMap::new()],
+            exports: HashMap::new(),
+            members: HashMap::new(),
+            visiting: Vec::new(),
+            params: HashMap::new(),
+        };
+        for (name, arity, shape) in BUILTINS {
+            let id = r.add_decl(Decl {
+                name: name.to_string(),
+                kind: DeclKind::Builtin,
+                arity: *arity,
+                shape: *shape,
+                module: None,
+                span: None,
+            });
+            r.scopes[0].insert(name.to_string(), id);
+        }
+        r
+    }
+
+    fn add_decl(&mut self, decl: Decl) -> usize {
+        self.res.decls.push(decl);
+        self.res.decls.len() - 1
+    }
+
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.scopes.iter().rev().find_map(|s| s.get(name).copied())
+    }
+
+    fn find_module(&self, name: &str) -> Option<&'a Module> {
+        self.local_modules
+            .iter()
+            .rev()
+            .find(|m| m.name.name == name)
+            .copied()
+            .or_else(|| self.modules.module(name))
+    }
+
+    // Adds declaration to the innermost scope.
+    fn declare(&mut self, decl: Decl) -> usize {
+        let name = decl.name.clone();
+        let span = decl.span.expect("declaration in the source code");
+        if let Some(&prev) = self.scopes.last().unwrap().get(&name) {
+            let previous = self.res.decls[prev].span;
+            self.res.errors.push(Error::Duplicate { name, span, previous });
+            return prev;
+        }
+        if let Some(prev) = self.lookup(&name) {
+            let previous = self.res.decls[prev].span;
+            self.res.errors.push(Error::Shadowing { name: name.clone(), span, previous });
+        }
+        let id = self.add_decl(decl);
+        self.scopes.last_mut().unwrap().insert(name, id);
+        id
+    }
+
+    // Makes declaration from another module visible in the current scope.
+    // Importing the same declaration twice is not an error, e.g. when two
+    // extended modules both extend Naturals.
+    fn import(&mut self, id: usize, span: Span) {
+        let name = self.res.decls[id].name.clone();
+        match self.scopes.last().unwrap().get(&name) {
+            Some(&prev) if prev == id => {}
+            Some(&prev) => {
+                let previous = self.res.decls[prev].span;
+                self.res.errors.push(Error::Duplicate { name, span, previous });
+            }
+            None => {
+                self.scopes.last_mut().unwrap().insert(name, id);
+            }
+        }
+    }
+
+    fn local(&mut self, name: &Ident, kind: DeclKind, arity: usize) -> usize {
+        self.declare(Decl {
+            name: name.name.clone(),
+            kind,
+            arity,
+            shape: Shape::Ordinary,
+            module: None,
+            span: Some(name.span),
+        })
+    }
+
+    fn refer(&mut self, span: Span, decl: usize) {
+        self.res.references.push(Reference { span, decl });
+    }
+
+    fn module(&mut self, module: &'a Module) {
+        self.scopes.push(HashMap::new());
+        self.module_units(module);
+        self.scopes.pop();
+    }
+
+    fn module_units(&mut self, module: &'a Module) {
+        let name = &module.name.name;
+        for ext in &module.extends {
+            match self.module_exports(&ext.name) {
+                Some(ids) => {
+                    for id in ids {
+                        self.import(id, ext.span);
+                    }
+                }
+                None => self.res.errors.push(Error::UnknownModule {
+                    name: ext.name.clone(),
+                    span: ext.span,
+                }),
+            }
+        }
+        let saved_modules = self.local_modules.len();
+        // Operators declared RECURSIVE and not yet defined.
+        let mut recursive = HashMap::new();
+        for unit in &module.units {
+            match unit {
+                Unit::Constants(decls) => {
+                    for d in decls {
+                        self.declare(op_decl(d, DeclKind::Constant, Some(name)));
+                    }
+                }
+                Unit::Variables(vars) => {
+                    for v in vars {
+                        self.declare(Decl {
+                            name: v.name.clone(),
+                            kind: DeclKind::Variable,
+                            arity: 0,
+                            shape: Shape::Ordinary,
+                            module: Some(name.clone()),
+                            span: Some(v.span),
+                        });
+                    }
+                }
+                Unit::Recursive(decls) => {
+                    for d in decls {
+                        let id = self.declare(op_decl(d, DeclKind::Operator, Some(name)));
+                        recursive.insert(d.name.name.clone(), id);
+                    }
+                }
+                Unit::Definition(def) => match recursive.remove(&def.name.name) {
+                    Some(id) => {
+                        self.res.decls[id].span = Some(def.name.span);
+                        self.set_params(id, def);
+                        self.definition_body(def);
+                    }
+                    None => {
+                        self.definition(def, Some(name));
+                    }
+                },
+                Unit::Instance(inst) => self.instance(inst),
+                Unit::Assume(a) | Unit::Theorem(a) => {
+                    self.expr(&a.expr);
+                    if let Some(n) = &a.name {
+                        let mut decl = op_decl_ident(n, DeclKind::Operator, Some(name));
+                        decl.arity = 0;
+                        self.declare(decl);
+                    }
+                }
+                Unit::Module(m) => {
+                    self.module(m);
+                    self.local_modules.push(m);
+                }
+            }
+        }
+        self.local_modules.truncate(saved_modules);
+    }
+
+    fn definition(&mut self, def: &'a Definition, module: Option<&String>) -> usize {
+        let kind = match def.body {
+            DefBody::Expr(_) => DeclKind::Operator,
+            DefBody::Function(..) => DeclKind::Function,
+            DefBody::Instance(_) => DeclKind::Instance,
+        };
+        let decl = Decl {
+            name: def.name.name.clone(),
+            kind,
+            arity: def.arity(),
+            shape: def.shape,
+            module: module.cloned(),
+            span: Some(def.name.span),
+        };
+        // Recursive functions can refer to themselves.
+        if kind == DeclKind::Function {
+            let id = self.declare(decl);
+            self.set_params(id, def);
+            self.definition_body(def);
+            return id;
+        }
+        self.definition_body(def);
+        let id = self.declare(decl);
+        self.set_params(id, def);
+        if let DefBody::Instance(inst) = &def.body {
+            if let Some(ids) = self.module_exports(&inst.module.name) {
+                let members = self.members_of(&ids);
+                self.members.insert(id, members);
+            }
+        }
+        id
+    }
+
+    fn set_params(&mut self, id: usize, def: &Definition) {
+        if def.params.iter().any(|p| p.arity > 0) {
+            self.params.insert(id, def.params.iter().map(|p| p.arity).collect());
+        }
+    }
+
+    fn definition_body(&mut self, def: &'a Definition) {
+        self.scopes.push(HashMap::new());
+        for p in &def.params {
+            self.declare(op_decl(p, DeclKind::Parameter, None));
+        }
+        match &def.body {
+            DefBody::Expr(e) => self.expr(e),
+            DefBody::Function(bounds, e) => {
+                self.bounds_parallel(bounds);
+                self.expr(e);
+            }
+            DefBody::Instance(inst) => self.substitutions(inst),
+        }
+        self.scopes.pop();
+    }
+
+    fn members_of(&self, ids: &[usize]) -> HashMap<String, usize> {
+        ids.iter()
+            .filter(|&&id| !self.res.decls[id].is_parameter())
+            .map(|&id| (self.res.decls[id].name.clone(), id))
+            .collect()
+    }
+
+    // Unnamed `INSTANCE M` imports definitions of M into the current module.
+    fn instance(&mut self, inst: &'a Instance) {
+        self.substitutions(inst);
+        if let Some(ids) = self.module_exports(&inst.module.name) {
+            for (_, id) in self.members_of(&ids) {
+                self.import(id, inst.module.span);
+            }
+        }
+    }
+
+    // Checks that every constant and variable of the instantiated module
+    // is either substituted explicitly or has a namesake in scope.
+    fn substitutions(&mut self, inst: &'a Instance) {
+        let ids = match self.module_exports(&inst.module.name) {
+            Some(ids) => ids,
+            None => {
+                self.res.errors.push(Error::UnknownModule {
+                    name: inst.module.name.clone(),
+                    span: inst.module.span,
+                });
+                for (_, e) in &inst.substitutions {
+                    self.expr(e);
+                }
+                return;
+            }
+        };
+        let params: Vec<usize> = ids
+            .into_iter()
+            .filter(|&id| self.res.decls[id].is_parameter())
+            .collect();
+        for (name, e) in &inst.substitutions {
+            match params.iter().find(|&&id| self.res.decls[id].name == name.name) {
+                Some(&id) => {
+                    self.refer(name.span, id);
+                    // `CONSTANT F(_)` is substituted by an operator.
+                    match self.res.decls[id].arity {
+                        0 => self.expr(e),
+                        arity => self.operator_argument(e, arity),
+                    }
+                }
+                None => {
+                    self.res.errors.push(Error::Undefined {
+                        name: name.name.clone(),
+                        span: name.span,
+                    });
+                    self.expr(e);
+                }
+            }
+        }
+        for id in params {
+            let name = &self.res.decls[id].name;
+            let substituted = inst.substitutions.iter().any(|s| &s.0.name == name);
+            if !substituted && self.lookup(name).is_none() {
+                self.res.errors.push(Error::Undefined {
+                    name: name.clone(),
+                    span: inst.span,
+                });
+            }
+        }
+    }
+
+    // Declarations exported by a module: constants, variables and
+    // non-LOCAL definitions, including the ones it gets from EXTENDS.
+    fn module_exports(&mut self, name: &str) -> Option<Vec<usize>> {
+        if let Some(ids) = self.exports.get(name) {
+            return Some(ids.clone());
+        }
+        if self.visiting.iter().any(|m| m == name) {
+            return Some(vec![]); // Circular dependency.
+        }
+        let module = match self.find_module(name) {
+            Some(module) => module,
+            None => return stdlib::module(name).map(|m| self.std_exports(m)),
+        };
+        self.visiting.push(name.to_string());
+        let mut ids = Vec::new();
+        for ext in &module.extends {
+            ids.extend(self.module_exports(&ext.name).unwrap_or_default());
+        }
+        let module_name = Some(&module.name.name);
+        for unit in &module.units {
+            match unit {
+                Unit::Constants(decls) => {
+                    for d in decls {
+                        ids.push(self.add_decl(op_decl(d, DeclKind::Constant, module_name)));
+                    }
+                }
+                Unit::Variables(vars) => {
+                    for v in vars {
+                        let decl = op_decl_ident(v, DeclKind::Variable, module_name);
+                        ids.push(self.add_decl(decl));
+                    }
+                }
+                Unit::Definition(def) if !def.local => {
+                    let kind = match &def.body {
+                        DefBody::Expr(_) => DeclKind::Operator,
+                        DefBody::Function(..) => DeclKind::Function,
+                        DefBody::Instance(_) => DeclKind::Instance,
+                    };
+                    let id = self.add_decl(Decl {
+                        name: def.name.name.clone(),
+                        kind,
+                        arity: def.arity(),
+                        shape: def.shape,
+                        module: module_name.cloned(),
+                        span: Some(def.name.span),
+                    });
+                    self.set_params(id, def);
+                    if let DefBody::Instance(inst) = &def.body {
+                        if let Some(inst_ids) = self.module_exports(&inst.module.name) {
+                            let members = self.members_of(&inst_ids);
+                            self.members.insert(id, members);
+                        }
+                    }
+                    ids.push(id);
+                }
+                Unit::Instance(inst) if !inst.local => {
+                    let inst_ids = self.module_exports(&inst.module.name).unwrap_or_default();
+                    ids.extend(self.members_of(&inst_ids).values());
+                }
+                _ => {}
+            }
+        }
+        self.visiting.pop();
+        self.exports.insert(name.to_string(), ids.clone());
+        Some(ids)
+    }
+
+    // Standard modules are not parsed, their declarations come from
+    // the built-in tables.
+    fn std_exports(&mut self, module: &stdlib::StdModule) -> Vec<usize> {
+        let mut ids = Vec::new();
+        for ext in module.extends {
+            ids.extend(self.module_exports(ext).unwrap_or_default());
+        }
+        let name = Some(module.name.to_string());
+        for v in module.variables {
+            ids.push(self.add_decl(Decl {
+                name: v.to_string(),
+                kind: DeclKind::Variable,
+                arity: 0,
+                shape: Shape::Ordinary,
+                module: name.clone(),
+                span: None,
+            }));
+        }
+        for &(op, arity, shape) in module.operators {
+            let id = self.add_decl(Decl {
+                name: op.to_string(),
+                kind: DeclKind::Operator,
+                arity,
+                shape,
+                module: name.clone(),
+                span: None,
+            });
+            if let Some(params) = stdlib::params(op) {
+                self.params.insert(id, params.to_vec());
+            }
+            ids.push(id);
+        }
+        self.exports.insert(module.name.to_string(), ids.clone());
+        ids
+    }
+
+    // Bound variables of a quantifier: `\A x \in S, y \in T(x)`.
+    // Each bound is in scope for the sets of the following ones.
+    fn bounds_nested(&mut self, bounds: &'a [Bound]) {
+        for b in bounds {
+            if let Some(set) = &b.set {
+                self.expr(set);
+            }
+            for v in &b.vars {
+                self.local(v, DeclKind::Bound, 0);
+            }
+        }
+    }
+
+    // Bound variables of a function or set constructor, the sets
+    // can't refer to the variables.
+    fn bounds_parallel(&mut self, bounds: &'a [Bound]) {
+        for b in bounds {
+            if let Some(set) = &b.set {
+                self.expr(set);
+            }
+        }
+        for b in bounds {
+            for v in &b.vars {
+                self.local(v, DeclKind::Bound, 0);
+            }
+        }
+    }
+
+    fn name(&mut self, path: &[Ident], name: &Ident) -> Option<usize> {
+        let mut members: Option<&HashMap<String, usize>> = None;
+        for (i, step) in path.iter().chain(Some(name)).enumerate() {
+            let found = match members {
+                None => self.lookup(&step.name),
+                Some(m) => m.get(&step.name).copied(),
+            };
+            let id = match found {
+                Some(id) => id,
+                None => {
+                    self.res.errors.push(Error::Undefined {
+                        name: step.name.clone(),
+                        span: step.span,
+                    });
+                    return None;
+                }
+            };
+            self.refer(step.span, id);
+            if i == path.len() {
+                return Some(id);
+            }
+            members = self.members.get(&id);
+            if members.is_none() {
+                self.res.errors.push(Error::Undefined {
+                    name: path[i + 1..].iter().chain(Some(name))
+                        .map(|i| i.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join("!"),
+                    span: step.span,
+                });
+                return None;
+            }
+        }
+        None
+    }
+
+    // Arguments of the operator `decls[id]`. Operator parameters, like `G`
+    // in `F(G(_), x)`, take an operator name or a LAMBDA.
+    fn arguments(&mut self, id: Option<usize>, args: &'a [Expr]) {
+        let params = id.and_then(|id| self.params.get(&id)).cloned().unwrap_or_default();
+        for (i, a) in args.iter().enumerate() {
+            match params.get(i) {
+                Some(&arity) if arity > 0 => self.operator_argument(a, arity),
+                _ => self.expr(a),
+            }
+        }
+    }
+
+    fn operator_argument(&mut self, e: &'a Expr, expected: usize) {
+        let found = match &e.kind {
+            ExprKind::Lambda { params, .. } => {
+                self.expr(e);
+                Some(params.len())
+            }
+            ExprKind::Apply { path, name, args } if args.is_empty() => {
+                self.name(path, name).map(|id| self.res.decls[id].arity)
+            }
+            _ => {
+                self.expr(e);
+                Some(0)
+            }
+        };
+        if found.is_some_and(|n| n != expected) {
+            self.res.errors.push(Error::OperatorArgument { span: e.span, expected });
+        }
+    }
+
+    fn expr(&mut self, e: &'a Expr) {
+        match &e.kind {
+            ExprKind::Apply { path, name, args } => {
+                let id = self.name(path, name);
+                self.arguments(id, args);
+                if let Some(id) = id {
+                    let arity = self.res.decls[id].arity;
+                    if arity != args.len() {
+                        self.res.errors.push(Error::Arity {
+                            name: name.name.clone(),
+                            span: e.span,
+                            expected: arity,
+                            found: args.len(),
+                        });
+                    }
+                }
+            }
+            ExprKind::OpApply { op, args } => {
+                let id = self.name(&[], op);
+                self.arguments(id, args);
+                let expected = match args.len() {
+                    1 if is_postfix(&op.name) => Shape::Postfix,
+                    1 => Shape::Prefix,
+                    _ => Shape::Infix,
+                };
+                if let Some(id) = id {
+                    if self.res.decls[id].shape != expected {
+                        self.res.errors.push(Error::Shape {
+                            name: op.name.clone(),
+                            span: op.span,
+                            expected,
+                        });
+                    }
+                }
+            }
+            ExprKind::Quant { bounds, body, .. } => {
+                self.scopes.push(HashMap::new());
+                self.bounds_nested(bounds);
+                self.expr(body);
+                self.scopes.pop();
+            }
+            ExprKind::Choose { bound, body } => {
+                self.scopes.push(HashMap::new());
+                self.bounds_nested(std::slice::from_ref(bound));
+                self.expr(body);
+                self.scopes.pop();
+            }
+            ExprKind::SetFilter { bound, pred } => {
+                self.scopes.push(HashMap::new());
+                self.bounds_nested(std::slice::from_ref(bound));
+                self.expr(pred);
+                self.scopes.pop();
+            }
+            ExprKind::SetMap { expr, bounds } => {
+                self.scopes.push(HashMap::new());
+                self.bounds_parallel(bounds);
+                self.expr(expr);
+                self.scopes.pop();
+            }
+            ExprKind::FnCons { bounds, body } => {
+                self.scopes.push(HashMap::new());
+                self.bounds_parallel(bounds);
+                self.expr(body);
+                self.scopes.pop();
+            }
+            ExprKind::Let { defs, body } => {
+                self.scopes.push(HashMap::new());
+                for def in defs {
+                    self.definition(def, None);
+                }
+                self.expr(body);
+                self.scopes.pop();
+            }
+            ExprKind::Lambda { params, body } => {
+                self.scopes.push(HashMap::new());
+                for p in params {
+                    self.local(p, DeclKind::Parameter, 0);
+                }
+                self.expr(body);
+                self.scopes.pop();
+            }
+            _ => {
+                for c in e.children() {
+                    self.expr(c);
+                }
+            }
+        }
+    }
+}
+
+fn op_decl(d: &OpDecl, kind: DeclKind, module: Option<&String>) -> Decl {
+    Decl {
+        name: d.name.name.clone(),
+        kind,
+        arity: d.arity,
+        shape: d.shape,
+        module: module.cloned(),
+        span: Some(d.name.span),
+    }
+}
+
+fn op_decl_ident(name: &Ident, kind: DeclKind, module: Option<&String>) -> Decl {
+    Decl {
+        name: name.name.clone(),
+        kind,
+        arity: 0,
+        shape: Shape::Ordinary,
+        module: module.cloned(),
+        span: Some(name.span),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn errors(code: &str) -> Vec<String> {
+        let m = parse(code).unwrap();
+        resolve(&m).errors.iter().map(|e| match e {
+            Error::Undefined { name, .. } => format!("undefined {}", name),
+            Error::Duplicate { name, .. } => format!("duplicate {}", name),
+            Error::Shadowing { name, .. } => format!("shadowing {}", name),
+            Error::UnknownModule { name, .. } => format!("module {}", name),
+            Error::Arity { name, expected, found, .. } => {
+                format!("arity {} {} {}", name, expected, found)
+            }
+            Error::Shape { name, .. } => format!("shape {}", name),
+            Error::OperatorArgument { expected, .. } => format!("operator argument {}", expected),
+            Error::Circular { modules, .. } => format!("circular {}", modules.join(" ")),
+        }).collect()
+    }
+
+    #[test]
+    fn binds_names() {
+        let code = "---- MODULE M ----
+            CONSTANT N
+            VARIABLE x
+            Inc(v) == v + N
+            Next == x' = Inc(x)
+            ====";
+        let m = parse(code).unwrap();
+        let res = resolve(&m);
+        // `+` is not defined without EXTENDS Naturals.
+        assert_eq!(res.errors.len(), 1);
+        let at = |s: &str| code.rfind(s).unwrap();
+        assert_eq!(res.decl_at(at("Inc(x)")).unwrap().kind, DeclKind::Operator);
+        assert_eq!(res.decl_at(at("x)")).unwrap().kind, DeclKind::Variable);
+        assert_eq!(res.decl_at(at("N\n")).unwrap().kind, DeclKind::Constant);
+        assert_eq!(res.decl_at(at("v +")).unwrap().kind, DeclKind::Parameter);
+        assert_eq!(res.decl_at(at("'")).unwrap().kind, DeclKind::Builtin);
+    }
+
+    #[test]
+    fn scopes() {
+        assert_eq!(errors("---- MODULE M ----
+            A == \\A x \\in {} : \\E y \\in {x} : x = y
+            B == {x \\in {} : x} \\cup {y : y \\in {}}
+            C == [x \\in {} |-> x]
+            D == LET F(y) == y G == F(1) IN G
+            E == CHOOSE x : x
+            f[n \\in {}] == f[n]
+            ===="), Vec::<String>::new());
+        assert_eq!(errors("---- MODULE M ----
+            A == x
+            B == LET y == 1 IN y
+            C == y
+            D == E
+            E == 1
+            ===="), vec!["undefined x", "undefined y", "undefined E"]);
+    }
+
+    #[test]
+    fn duplicates_and_shadowing() {
+        assert_eq!(errors("---- MODULE M ----
+            CONSTANT A
+            VARIABLE A
+            B(A) == 1
+            C == \\E B \\in {} : TRUE
+            D == \\E x \\in {} : \\E x \\in {} : TRUE
+            TRUE == 1
+            ===="), vec![
+                "duplicate A",
+                "shadowing A",
+                "shadowing B",
+                "shadowing x",
+                "shadowing TRUE",
+            ]);
+    }
+
+    #[test]
+    fn recursive() {
+        assert_eq!(errors("---- MODULE M ----
+            RECURSIVE F(_)
+            G(n) == F(n)
+            F(n) == G(n)
+            ===="), Vec::<String>::new());
+    }
+
+    #[test]
+    fn instances() {
+        let modules = vec![
+            parse("---- MODULE Base ----\nCONSTANT C\nVARIABLE v\nOp == C\nLOCAL Hidden == 1\n====").unwrap(),
+        ];
+        let check = |code: &str| {
+            let m = parse(code).unwrap();
+            resolve_with(&m, &modules).errors.iter().map(|e| e.to_string()).collect::<Vec<_>>()
+        };
+        assert!(check("---- MODULE M ----
+            EXTENDS Base
+            A == Op + C
+            ====").iter().all(|e| e.contains("`+`")));
+        assert_eq!(check("---- MODULE M ----
+            VARIABLE w
+            I == INSTANCE Base WITH C <- 1, v <- w
+            A == I!Op
+            ====").len(), 0);
+        let errs = check("---- MODULE M ----
+            I == INSTANCE Base WITH C <- 1, X <- 2
+            A == I!Hidden
+            B == Hidden
+            ====");
+        assert_eq!(errs.len(), 4, "{:?}", errs); // X, missing v, I!Hidden, Hidden
+        let errs = check("---- MODULE M ----\nEXTENDS Nowhere\n====");
+        assert_eq!(errs, vec!["2:9: cannot find module `Nowhere`"]);
+    }
+
+    #[test]
+    fn standard_modules() {
+        let code = "---- MODULE M ----
+            EXTENDS Sequences, FiniteSets, TLC
+            LOCAL INSTANCE Integers
+            A == Len(<<1>>) + Cardinality({}) - -1
+            B == [x \\in Nat |-> x] @@ (1 :> 2)
+            C == Int \\o Print(1, 2)
+            ====";
+        let m = parse(code).unwrap();
+        let res = resolve(&m);
+        assert_eq!(res.errors, vec![]);
+        let len = res.decl_at(code.find("Len").unwrap()).unwrap();
+        assert_eq!((len.arity, len.module.as_deref()), (1, Some("Sequences")));
+        let plus = res.decl_at(code.find("+").unwrap()).unwrap();
+        assert_eq!(plus.module.as_deref(), Some("Naturals"));
+        assert_eq!(errors("---- MODULE M ----
+            EXTENDS FiniteSets
+            A == Len(<<>>) + 1
+            ===="), vec!["undefined +", "undefined Len"]);
+    }
+
+    #[test]
+    fn arity() {
+        assert_eq!(errors("---- MODULE M ----
+            EXTENDS Sequences
+            CONSTANT C(_), _ ++ _
+            VARIABLE x
+            F(G(_, _), y) == G(y, y)
+            Add(a, b) == a
+            a ** b == a ++ b
+            -. a == C(a)
+            a ^# == -a
+            A == F(Add, 1) + F(LAMBDA a, b : a ** b, x) + (x^#)
+            B == SelectSeq(<<1>>, C) \\o SelectSeq(<<1>>, LAMBDA e : TRUE)
+            ===="), Vec::<String>::new());
+        assert_eq!(errors("---- MODULE M ----
+            EXTENDS Sequences
+            F(G(_, _), y) == G(y)
+            Add(a, b) == a
+            A == F(LAMBDA a : a, 1)
+            B == F(1, 1)
+            C == F(Len, 1)
+            D == Add(1) + Add + Len(1, 2)
+            E == SelectSeq(<<>>, Add)
+            ===="), vec![
+                "arity G 2 1",
+                "operator argument 2",
+                "operator argument 2",
+                "operator argument 2",
+                "arity Add 2 1",
+                "arity Add 2 0",
+                "arity Len 1 2",
+                "operator argument 1",
+            ]);
+    }
+
+    #[test]
+    fn nested_modules() {
+        assert_eq!(errors("---- MODULE M ----
+            CONSTANT N
+            ---- MODULE Inner ----
+            A == N
+            ====
+            INSTANCE Inner
+            B == A
+            ===="), Vec::<String>::new());
+    }
+}
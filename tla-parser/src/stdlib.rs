@@ -0,0 +1,184 @@
+use crate::ast::Shape;
+use crate::ast::Shape::*;
+
+
+/// Declarations of a standard module. The tables are compiled in, so the
+/// standard modules are available without any files on disk.
+#[derive(Debug)]
+pub struct StdModule {
+    pub name: &'static str,
+    pub extends: &'static [&'static str],
+    pub variables: &'static [&'static str],
+    /// Operators with their arity and shape. Operator names are in
+    /// normalized form, e.g. `\oplus` instead of `(+)`.
+    pub operators: &'static [(&'static str, usize, Shape)],
+}
+
+impl StdModule {
+    pub fn operator(&self, name: &str) -> Option<(usize, Shape)> {
+        self.operators
+            .iter()
+            .find(|op| op.0 == name)
+            .map(|op| (op.1, op.2))
+    }
+}
+
+// Modules that are used through LOCAL INSTANCE (e.g. TLC uses Sequences)
+// do not export their names and are not listed in `extends`.
+pub static MODULES: &[StdModule] = &[
+    StdModule {
+        name: "Bags",
+        extends: &[],
+        variables: &[],
+        operators: &[
+            ("BagCardinality", 1, Ordinary),
+            ("BagIn", 2, Ordinary),
+            ("BagOfAll", 2, Ordinary),
+            ("BagToSet", 1, Ordinary),
+            ("BagUnion", 1, Ordinary),
+            ("CopiesIn", 2, Ordinary),
+            ("EmptyBag", 0, Ordinary),
+            ("IsABag", 1, Ordinary),
+            ("SetToBag", 1, Ordinary),
+            ("SubBag", 1, Ordinary),
+            ("\\ominus", 2, Infix),
+            ("\\oplus", 2, Infix),
+            ("\\sqsubseteq", 2, Infix),
+        ],
+    },
+    StdModule {
+        name: "FiniteSets",
+        extends: &[],
+        variables: &[],
+        operators: &[
+            ("Cardinality", 1, Ordinary),
+            ("IsFiniteSet", 1, Ordinary),
+        ],
+    },
+    StdModule {
+        name: "Integers",
+        extends: &["Naturals"],
+        variables: &[],
+        operators: &[
+            ("-.", 1, Prefix),
+            ("Int", 0, Ordinary),
+        ],
+    },
+    StdModule {
+        name: "Naturals",
+        extends: &[],
+        variables: &[],
+        operators: &[
+            ("%", 2, Infix),
+            ("*", 2, Infix),
+            ("+", 2, Infix),
+            ("-", 2, Infix),
+            ("..", 2, Infix),
+            ("<", 2, Infix),
+            ("<=", 2, Infix),
+            (">", 2, Infix),
+            (">=", 2, Infix),
+            ("Nat", 0, Ordinary),
+            ("\\div", 2, Infix),
+            ("^", 2, Infix),
+        ],
+    },
+    StdModule {
+        name: "RealTime",
+        extends: &["Reals"],
+        variables: &["now"],
+        operators: &[
+            ("RTBound", 4, Ordinary),
+            ("RTnow", 1, Ordinary),
+        ],
+    },
+    StdModule {
+        name: "Reals",
+        extends: &["Integers"],
+        variables: &[],
+        operators: &[
+            ("/", 2, Infix),
+            ("Infinity", 0, Ordinary),
+            ("Real", 0, Ordinary),
+        ],
+    },
+    StdModule {
+        name: "Sequences",
+        extends: &["Naturals"],
+        variables: &[],
+        operators: &[
+            ("Append", 2, Ordinary),
+            ("Head", 1, Ordinary),
+            ("Len", 1, Ordinary),
+            ("SelectSeq", 2, Ordinary),
+            ("Seq", 1, Ordinary),
+            ("SubSeq", 3, Ordinary),
+            ("Tail", 1, Ordinary),
+            ("\\o", 2, Infix),
+        ],
+    },
+    StdModule {
+        name: "TLC",
+        extends: &[],
+        variables: &[],
+        operators: &[
+            (":>", 2, Infix),
+            ("@@", 2, Infix),
+            ("Any", 0, Ordinary),
+            ("Assert", 2, Ordinary),
+            ("JavaTime", 0, Ordinary),
+            ("Permutations", 1, Ordinary),
+            ("Print", 2, Ordinary),
+            ("PrintT", 1, Ordinary),
+            ("RandomElement", 1, Ordinary),
+            ("SortSeq", 2, Ordinary),
+            ("TLCEval", 1, Ordinary),
+            ("TLCGet", 1, Ordinary),
+            ("TLCSet", 2, Ordinary),
+            ("ToString", 1, Ordinary),
+        ],
+    },
+];
+
+
+// Standard operators that take operators as arguments, with arities of
+// their parameters, e.g. `SelectSeq(s, Test(_))`.
+pub static HIGHER_ORDER: &[(&str, &[usize])] = &[
+    ("BagOfAll", &[1, 0]),
+    ("SelectSeq", &[0, 1]),
+    ("SortSeq", &[0, 2]),
+];
+
+
+pub fn module(name: &str) -> Option<&'static StdModule> {
+    MODULES.iter().find(|m| m.name == name)
+}
+
+/// Arities of the parameters of a higher-order standard operator.
+pub fn params(op: &str) -> Option<&'static [usize]> {
+    HIGHER_ORDER.iter().find(|h| h.0 == op).map(|h| h.1)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{infix, normalize, prefix};
+
+    #[test]
+    fn operators_have_precedence() {
+        for m in MODULES {
+            for ext in m.extends {
+                assert!(module(ext).is_some(), "{} extends {}", m.name, ext);
+            }
+            for &(name, _, shape) in m.operators {
+                assert_eq!(normalize(name), name);
+                match shape {
+                    Infix => assert!(infix(name).is_some(), "{}", name),
+                    Prefix => assert!(prefix(name).is_some(), "{}", name),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::fmt;
+
+use crate::eval::{self, Evaluator, Value};
+
+use super::Trace;
+
+
+/// Change of a variable between two states, at a part of its value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diff {
+    pub variable: String,
+    /// Fields of records and arguments of functions down to the part.
+    pub path: Vec<Key>,
+    pub change: Change,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Key {
+    Field(String),
+    /// Argument of a function, or index of a sequence.
+    Arg(Value),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// Value of the part replaced, when it is not of the same kind as the
+    /// new value or is a scalar.
+    Changed { from: Value, to: Value },
+    /// Argument of the function that the part is, added with its value.
+    Added(Value),
+    /// Argument of the function that the part was, with its old value.
+    Removed(Value),
+    /// Element added to the set.
+    Inserted(Value),
+    /// Element removed from the set.
+    Deleted(Value),
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.variable)?;
+        for key in &self.path {
+            match key {
+                Key::Field(name) => write!(f, ".{}", name)?,
+                Key::Arg(arg) => write!(f, "[{}]", arg)?,
+            }
+        }
+        match &self.change {
+            Change::Changed { from, to } => write!(f, ": {} -> {}", from, to),
+            Change::Added(v) => write!(f, ": added {}", v),
+            Change::Removed(v) => write!(f, ": removed {}", v),
+            Change::Inserted(v) => write!(f, ": inserted {}", v),
+            Change::Deleted(v) => write!(f, ": deleted {}", v),
+        }
+    }
+}
+
+
+impl Trace {
+    /// Changes of the variables in the step, from the state before it. The
+    /// step one past the last is the one back to the loop, if any.
+    pub fn diff(&self, step: usize) -> Vec<Diff> {
+        match self.states(step) {
+            Some((state, next)) => diff(&self.variables, state, next),
+            None => Vec::new(),
+        }
+    }
+
+    /// Subactions of `next` that produce the step from the state before it,
+    /// as `Evaluator::step_actions`. Empty for the initial state.
+    pub fn explain<'a>(
+        &self,
+        ev: &Evaluator<'a>,
+        next: &str,
+        step: usize,
+    ) -> Result<Vec<Option<&'a str>>, eval::Error> {
+        match self.states(step) {
+            Some((state, next_state)) => ev.step_actions(next, &self.variables, state, next_state),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    // States before and after the step, which is one past the last for the
+    // step back to the loop.
+    fn states(&self, step: usize) -> Option<(&[Value], &[Value])> {
+        let after = match self.back {
+            Some(back) if step == self.steps.len() => back,
+            _ if step < self.steps.len() => step,
+            _ => return None,
+        };
+        let before = self.steps.get(step.checked_sub(1)?)?;
+        Some((&before.state, &self.steps[after].state))
+    }
+}
+
+/// Changes of the variables from `state` to `next`, in the order of the
+/// variables and then of the parts of their values.
+pub fn diff(variables: &[String], state: &[Value], next: &[Value]) -> Vec<Diff> {
+    let mut res = Vec::new();
+    for ((name, from), to) in variables.iter().zip(state).zip(next) {
+        let mut changes = Vec::new();
+        diff_value(&mut Vec::new(), from, to, &mut changes);
+        res.extend(changes.into_iter().map(|(path, change)| Diff { variable: name.clone(), path, change }));
+    }
+    res
+}
+
+fn diff_value(path: &mut Vec<Key>, from: &Value, to: &Value, out: &mut Vec<(Vec<Key>, Change)>) {
+    if from == to {
+        return;
+    }
+    match (from, to) {
+        (Value::Record(a), Value::Record(b)) => {
+            for (k, v) in a.iter() {
+                path.push(Key::Field(k.clone()));
+                match b.get(k) {
+                    Some(w) => diff_value(path, v, w, out),
+                    None => out.push((path.clone(), Change::Removed(v.clone()))),
+                }
+                path.pop();
+            }
+            for (k, w) in b.iter().filter(|(k, _)| !a.contains_key(*k)) {
+                path.push(Key::Field(k.clone()));
+                out.push((path.clone(), Change::Added(w.clone())));
+                path.pop();
+            }
+        }
+        _ if from.is_function() && to.is_function() => {
+            let (a, b) = (from.pairs().unwrap_or_default(), to.pairs().unwrap_or_default());
+            for (k, v) in &a {
+                path.push(Key::Arg(k.clone()));
+                match to.apply(k) {
+                    Some(w) => diff_value(path, v, &w, out),
+                    None => out.push((path.clone(), Change::Removed(v.clone()))),
+                }
+                path.pop();
+            }
+            for (k, w) in b.into_iter().filter(|(k, _)| from.apply(k).is_none()) {
+                path.push(Key::Arg(k));
+                out.push((path.clone(), Change::Added(w)));
+                path.pop();
+            }
+        }
+        _ => match (from.elements(), to.elements()) {
+            (Some(a), Some(b)) => {
+                out.extend(a.difference(&b).map(|e| (path.clone(), Change::Deleted(e.clone()))));
+                out.extend(b.difference(&a).map(|e| (path.clone(), Change::Inserted(e.clone()))));
+            }
+            _ => out.push((path.clone(), Change::Changed { from: from.clone(), to: to.clone() })),
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::resolve::resolve;
+    use crate::tlc::Step;
+
+    fn trace(states: &[&[&str]]) -> Trace {
+        let steps = states
+            .iter()
+            .map(|s| Step {
+                action: None,
+                location: None,
+                state: s.iter().map(|v| crate::tlc::parse_value(v).unwrap()).collect(),
+            })
+            .collect();
+        Trace { variables: vec!["r".to_string(), "s".to_string()], steps, back: None }
+    }
+
+    #[test]
+    fn changes() {
+        let t = trace(&[
+            &["[a |-> 1, f |-> (p :> 1 @@ q :> 2), q |-> <<1, 2>>]", "{1, 2}"],
+            &["[a |-> 2, f |-> (p :> 1 @@ r :> 2), q |-> <<1, 3, 4>>]", "{2, 3}"],
+            &["[a |-> 2, f |-> (p :> 1 @@ r :> 2), q |-> <<1, 3, 4>>]", "\"done\""],
+        ]);
+        let lines = |step| t.diff(step).iter().map(|d| d.to_string()).collect::<Vec<_>>();
+        assert_eq!(lines(0), Vec::<String>::new());
+        assert_eq!(
+            lines(1),
+            vec![
+                "r.a: 1 -> 2",
+                "r.f[q]: removed 2",
+                "r.f[r]: added 2",
+                "r.q[2]: 2 -> 3",
+                "r.q[3]: added 4",
+                "s: deleted 1",
+                "s: inserted 3",
+            ],
+        );
+        assert_eq!(lines(2), vec!["s: {2, 3} -> \"done\""]);
+        assert_eq!(t.diff(3), vec![]);
+        assert_eq!(
+            t.diff(1)[0],
+            Diff {
+                variable: "r".to_string(),
+                path: vec![Key::Field("a".to_string())],
+                change: Change::Changed { from: Value::int(1), to: Value::int(2) },
+            },
+        );
+    }
+
+    #[test]
+    fn explain() {
+        let code = "---- MODULE M ----\nEXTENDS Naturals\nVARIABLES s, r\n\
+                    Inc == r' = r + 1 /\\ UNCHANGED s\n\
+                    Add == r' = r + 1 /\\ s' = s\n\
+                    Grow == \\E x \\in 1..3 : s' = s \\cup {x} /\\ UNCHANGED r\n\
+                    Next == Inc \\/ Add \\/ Grow \\/ (r = 5 /\\ UNCHANGED <<r, s>>)\n====";
+        let module = parse(code).unwrap();
+        let res = resolve(&module);
+        let ev = Evaluator::new(&module, &res);
+        let mut t = trace(&[&["0", "{}"], &["1", "{}"], &["1", "{2}"], &["3", "{2}"], &["5", "{2}"]]);
+        assert_eq!(t.explain(&ev, "Next", 0), Ok(vec![]));
+        assert_eq!(t.explain(&ev, "Next", 1), Ok(vec![Some("Inc"), Some("Add")]));
+        assert_eq!(t.explain(&ev, "Next", 2), Ok(vec![Some("Grow")]));
+        assert_eq!(t.explain(&ev, "Next", 3), Ok(vec![]));
+        t.back = Some(4);
+        assert_eq!(t.explain(&ev, "Next", 5), Ok(vec![Some("Grow"), None]));
+
+        t.variables[1] = "x".to_string();
+        assert!(matches!(t.explain(&ev, "Next", 1), Err(eval::Error::Unspecified { name, .. }) if name == "s"));
+    }
+}
@@ -0,0 +1,397 @@
+use std::fmt::Write;
+
+use crate::check::json_string;
+use crate::eval::{Int, Value};
+
+use super::{Error, Step, Trace};
+
+
+impl Trace {
+    /// Trace in the Informal Trace Format of Apalache. Integers beyond the
+    /// safe range of JSON are `#bigint`, sets `#set`, functions that are
+    /// not sequences or records `#map`, and model values and infinite sets
+    /// `#unserializable`. The subactions are in the `#meta` of the states.
+    pub fn to_itf(&self) -> String {
+        let vars: Vec<String> = self.variables.iter().map(|v| json_string(v)).collect();
+        let mut out = String::from("{\n  \"#meta\": {\"format\": \"ITF\"},\n");
+        writeln!(out, "  \"vars\": [{}],", vars.join(", ")).unwrap();
+        out.push_str("  \"states\": [");
+        for (i, step) in self.steps.iter().enumerate() {
+            out.push_str(if i == 0 { "\n    {" } else { ",\n    {" });
+            write!(out, "\"#meta\": {{\"index\": {}", i).unwrap();
+            if let Some(action) = &step.action {
+                write!(out, ", \"action\": {}", json_string(action)).unwrap();
+            }
+            out.push('}');
+            for (name, value) in self.variables.iter().zip(&step.state) {
+                write!(out, ", {}: ", json_string(name)).unwrap();
+                itf_value(&mut out, value);
+            }
+            out.push('}');
+        }
+        out.push_str(if self.steps.is_empty() { "]" } else { "\n  ]" });
+        if let Some(back) = self.back {
+            write!(out, ",\n  \"loop\": {}", back).unwrap();
+        }
+        out.push_str("\n}\n");
+        out
+    }
+
+    /// Reads a trace in the Informal Trace Format. Tuples become sequences,
+    /// and `#unserializable` values become model values.
+    pub fn from_itf(json: &str) -> Result<Trace, Error> {
+        let mut p = JsonParser { text: json, offset: 0 };
+        let root = p.value()?;
+        p.space();
+        if p.offset < json.len() {
+            return Err(p.error("end of the input"));
+        }
+        let variables = match root.field("vars") {
+            Some(Json::Array(vars)) => vars
+                .iter()
+                .map(|v| match v {
+                    Json::Str(s) => Ok(s.clone()),
+                    _ => Err(itf("`vars` must be strings")),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err(itf("no `vars` array")),
+        };
+        let states = match root.field("states") {
+            Some(Json::Array(states)) => states,
+            _ => return Err(itf("no `states` array")),
+        };
+        let mut steps = Vec::new();
+        for (i, s) in states.iter().enumerate() {
+            let action = match s.field("#meta").and_then(|m| m.field("action")) {
+                Some(Json::Str(a)) => Some(a.clone()),
+                _ => None,
+            };
+            let state = variables
+                .iter()
+                .map(|name| match s.field(name) {
+                    Some(v) => value(v),
+                    None => Err(itf(&format!("state {} has no value of `{}`", i, name))),
+                })
+                .collect::<Result<_, _>>()?;
+            steps.push(Step { action, location: None, state });
+        }
+        let back = match root.field("loop") {
+            Some(Json::Number(n)) => Some(n.parse().map_err(|_| itf("`loop` must be an index"))?),
+            Some(_) => return Err(itf("`loop` must be an index")),
+            None => None,
+        };
+        Ok(Trace { variables, steps, back })
+    }
+}
+
+fn itf(message: &str) -> Error {
+    Error::Itf(message.to_string())
+}
+
+// Integers in this range are exact in JSON numbers.
+const SAFE: i64 = (1 << 53) - 1;
+
+fn itf_value(out: &mut String, v: &Value) {
+    let list = |out: &mut String, items: &mut dyn Iterator<Item = &Value>| {
+        out.push('[');
+        for (i, item) in items.enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            itf_value(out, item);
+        }
+        out.push(']');
+    };
+    match v {
+        Value::Bool(b) => write!(out, "{}", b).unwrap(),
+        Value::Int(n) => match n.to_i64() {
+            Some(n) if (-SAFE..=SAFE).contains(&n) => write!(out, "{}", n).unwrap(),
+            _ => write!(out, "{{\"#bigint\": \"{}\"}}", n).unwrap(),
+        },
+        Value::Str(s) => out.push_str(&json_string(s)),
+        Value::Model(name) => write!(out, "{{\"#unserializable\": {}}}", json_string(name)).unwrap(),
+        Value::Set(elems) => {
+            out.push_str("{\"#set\": ");
+            list(out, &mut elems.iter());
+            out.push('}');
+        }
+        Value::Seq(items) => list(out, &mut items.iter()),
+        Value::Record(fields) => {
+            out.push('{');
+            for (i, (k, v)) in fields.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write!(out, "{}: ", json_string(k)).unwrap();
+                itf_value(out, v);
+            }
+            out.push('}');
+        }
+        Value::Fun(map) => {
+            out.push_str("{\"#map\": [");
+            for (i, (k, v)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                list(out, &mut [k, v].iter().copied());
+            }
+            out.push_str("]}");
+        }
+        Value::Lazy(_) => match v.clone().normalize() {
+            Value::Lazy(_) => write!(out, "{{\"#unserializable\": {}}}", json_string(&v.to_string())).unwrap(),
+            v => itf_value(out, &v),
+        },
+    }
+}
+
+fn value(json: &Json) -> Result<Value, Error> {
+    let values = |items: &[Json]| items.iter().map(value).collect::<Result<Vec<_>, _>>();
+    Ok(match json {
+        Json::Bool(b) => Value::Bool(*b),
+        Json::Number(n) => Value::Int(int(n).ok_or_else(|| itf(&format!("{} is not an integer", n)))?),
+        Json::Str(s) => Value::str(s),
+        Json::Array(items) => Value::seq(values(items)?),
+        Json::Object(fields) => match fields.as_slice() {
+            [(key, Json::Str(n))] if key == "#bigint" => {
+                Value::Int(int(n).ok_or_else(|| itf(&format!("{} is not an integer", n)))?)
+            }
+            [(key, Json::Array(items))] if key == "#set" => Value::set(values(items)?),
+            [(key, Json::Array(items))] if key == "#tup" => Value::seq(values(items)?),
+            [(key, Json::Array(pairs))] if key == "#map" => Value::function(
+                pairs
+                    .iter()
+                    .map(|pair| match pair {
+                        Json::Array(kv) if kv.len() == 2 => Ok((value(&kv[0])?, value(&kv[1])?)),
+                        _ => Err(itf("`#map` entries must be pairs")),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            [(key, Json::Str(name))] if key == "#unserializable" => Value::model(name),
+            _ => Value::record(fields.iter().map(|(k, v)| Ok((k.clone(), value(v)?))).collect::<Result<Vec<_>, _>>()?),
+        },
+        Json::Null => return Err(itf("null is not a value")),
+    })
+}
+
+fn int(s: &str) -> Option<Int> {
+    match s.strip_prefix('-') {
+        Some(digits) => Int::parse(digits, 10).map(|n| -&n),
+        None => Int::parse(s, 10),
+    }
+}
+
+
+// JSON document, with the fields of objects in their order.
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    // Text of the number, integers may be beyond 64 bits.
+    Number(String),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn field(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+struct JsonParser<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn error(&self, expected: &'static str) -> Error {
+        Error::Json { offset: self.offset, expected }
+    }
+
+    fn space(&mut self) {
+        let rest = &self.text[self.offset..];
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.space();
+        let found = self.text[self.offset..].starts_with(token);
+        if found {
+            self.offset += token.len();
+        }
+        found
+    }
+
+    fn value(&mut self) -> Result<Json, Error> {
+        self.space();
+        if self.eat("null") {
+            return Ok(Json::Null);
+        }
+        if self.eat("true") {
+            return Ok(Json::Bool(true));
+        }
+        if self.eat("false") {
+            return Ok(Json::Bool(false));
+        }
+        if self.eat("[") {
+            let mut items = Vec::new();
+            if self.eat("]") {
+                return Ok(Json::Array(items));
+            }
+            loop {
+                items.push(self.value()?);
+                if self.eat("]") {
+                    return Ok(Json::Array(items));
+                }
+                if !self.eat(",") {
+                    return Err(self.error("`,` or `]`"));
+                }
+            }
+        }
+        if self.eat("{") {
+            let mut fields = Vec::new();
+            if self.eat("}") {
+                return Ok(Json::Object(fields));
+            }
+            loop {
+                self.space();
+                let key = self.string()?;
+                if !self.eat(":") {
+                    return Err(self.error("`:`"));
+                }
+                fields.push((key, self.value()?));
+                if self.eat("}") {
+                    return Ok(Json::Object(fields));
+                }
+                if !self.eat(",") {
+                    return Err(self.error("`,` or `}`"));
+                }
+            }
+        }
+        let rest = &self.text[self.offset..];
+        if rest.starts_with('"') {
+            return Ok(Json::Str(self.string()?));
+        }
+        let len = rest.find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E')).unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error("value"));
+        }
+        self.offset += len;
+        Ok(Json::Number(rest[..len].to_string()))
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let rest = &self.text[self.offset..];
+        if !rest.starts_with('"') {
+            return Err(self.error("string"));
+        }
+        let mut s = String::new();
+        let mut chars = rest.char_indices().skip(1);
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.offset += i + 1;
+                    return Ok(s);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('r') => s.push('\r'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('u') => {
+                        let hex: String = (0..4).filter_map(|_| chars.next().map(|(_, c)| c)).collect();
+                        let c = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32);
+                        s.push(c.unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    Some(c) => s.push(c),
+                    None => break,
+                },
+                c => s.push(c),
+            }
+        }
+        self.offset = self.text.len();
+        Err(self.error("`\"`"))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let trace = Trace {
+            variables: vec!["x".to_string(), "f".to_string()],
+            steps: vec![
+                Step {
+                    action: None,
+                    location: None,
+                    state: vec![
+                        Value::int(1 << 60),
+                        Value::function(vec![(Value::model("p"), Value::set(vec![Value::str("a\"b")]))]),
+                    ],
+                },
+                Step {
+                    action: Some("Inc".to_string()),
+                    location: None,
+                    state: vec![
+                        Value::int(-3),
+                        Value::record(vec![("a".to_string(), Value::seq(vec![Value::Bool(true)]))]),
+                    ],
+                },
+            ],
+            back: Some(0),
+        };
+        let itf = trace.to_itf();
+        assert_eq!(
+            itf,
+            "{\n  \
+             \"#meta\": {\"format\": \"ITF\"},\n  \
+             \"vars\": [\"x\", \"f\"],\n  \
+             \"states\": [\n    \
+             {\"#meta\": {\"index\": 0}, \"x\": {\"#bigint\": \"1152921504606846976\"}, \
+             \"f\": {\"#map\": [[{\"#unserializable\": \"p\"}, {\"#set\": [\"a\\\"b\"]}]]}},\n    \
+             {\"#meta\": {\"index\": 1, \"action\": \"Inc\"}, \"x\": -3, \"f\": {\"a\": [true]}}\n  \
+             ],\n  \
+             \"loop\": 0\n}\n",
+        );
+        assert_eq!(Trace::from_itf(&itf), Ok(trace));
+    }
+
+    #[test]
+    fn apalache() {
+        let json = r##"{
+          "#meta": {"format": "ITF", "source": "M.tla", "varTypes": {"m": "Int -> Int"}},
+          "params": [],
+          "vars": ["m", "t"],
+          "states": [
+            {"#meta": {"index": 0}, "m": {"#map": [[1, 2], [3, {"#bigint": "-99999999999999999999"}]]},
+             "t": {"#tup": ["ué", {"#set": []}]}}
+          ]
+        }"##;
+        let trace = Trace::from_itf(json).unwrap();
+        let big = int("-99999999999999999999").unwrap();
+        assert_eq!(
+            trace.steps[0].state,
+            vec![
+                Value::function(vec![(Value::int(1), Value::int(2)), (Value::int(3), Value::Int(big))]),
+                Value::seq(vec![Value::str("u\u{e9}"), Value::set(vec![])]),
+            ],
+        );
+        assert_eq!(trace.back, None);
+
+        assert_eq!(Trace::from_itf("{\"vars\": [\"x\"], \"states\": [{}]}"), Err(itf("state 0 has no value of `x`")));
+        assert_eq!(Trace::from_itf("{\"states\": []}"), Err(itf("no `vars` array")));
+        assert_eq!(
+            Trace::from_itf("{\"vars\": [\"x\"], \"states\": [{\"x\": 1.5}]}"),
+            Err(itf("1.5 is not an integer")),
+        );
+        assert_eq!(Trace::from_itf("{\"vars\" [\"x\"]}"), Err(Error::Json { offset: 8, expected: "`:`" }));
+    }
+}
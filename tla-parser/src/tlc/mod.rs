@@ -0,0 +1,687 @@
+mod diff;
+mod itf;
+mod trace;
+
+use std::mem;
+
+pub use self::diff::{diff, Change, Diff, Key};
+pub use self::trace::{parse_value, Error, Step, Trace};
+
+
+/// Event in the output of a TLC run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// `TLC2 Version 2.18 of ...`, with the text after `TLC2 Version`.
+    Version(String),
+    /// Initial states are computed.
+    InitialStates { distinct: u64 },
+    /// Periodic report of the search, `depth` is the current level.
+    Progress(Stats),
+    Coverage(Coverage),
+    Violation(Violation),
+    /// Error that is not followed by a behavior, e.g. in a constant
+    /// expression or a parse error.
+    Error(ErrorReport),
+    /// Statistics at the end of the run.
+    Stats(Stats),
+    /// Model checking completed without finding an error.
+    Success,
+    /// `Finished in 01min 02s at (...)`, with the duration.
+    Finished(String),
+    /// Any other message, e.g. the output of `Print`.
+    Message { code: Option<u32>, text: String },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub generated: u64,
+    pub distinct: u64,
+    pub queue: u64,
+    pub depth: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Coverage {
+    /// States found by a subaction: `<Next line 5, ...>: 3:8`.
+    Action { name: String, location: Location, distinct: u64, generated: u64 },
+    /// Evaluations of an expression of a subaction.
+    Expression { location: Location, count: u64 },
+}
+
+/// Behavior that violates the model, with the states numbered from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub trace: Vec<State>,
+    /// State that the last one loops back to, in a violation of a liveness
+    /// property.
+    pub back: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViolationKind {
+    Invariant(String),
+    /// Temporal or action property.
+    Property,
+    Deadlock,
+    /// Error in the evaluation of the last state, like a failed `Assert`.
+    Error(ErrorReport),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorReport {
+    /// Code of the message in the `-tool` mode.
+    pub code: Option<u32>,
+    pub message: String,
+    /// Locations in the spec that the message refers to.
+    pub locations: Vec<Location>,
+}
+
+/// State of a behavior as TLC prints it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct State {
+    pub number: usize,
+    /// Text after the number, like `<Initial predicate>`,
+    /// `<Next line 8, col 9 to line 9, col 20 of module M>` or
+    /// `Stuttering`.
+    pub label: String,
+    /// Subaction that produced the state, `None` for initial states.
+    pub action: Option<String>,
+    pub location: Option<Location>,
+    /// Variables with the text of their values.
+    pub assignments: Vec<(String, String)>,
+}
+
+/// Region of a module, as TLC reports it: lines and columns start at 1 and
+/// the end column is inclusive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub module: String,
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+
+/// Parses the whole output of a TLC run.
+pub fn parse(output: &str) -> Vec<Event> {
+    let mut reader = Reader::default();
+    let mut events = Vec::new();
+    for line in output.lines() {
+        events.extend(reader.line(line));
+    }
+    events.extend(reader.finish());
+    events
+}
+
+/// Reader of TLC output line by line, for runs that are in progress. The
+/// output may be in the `-tool` mode, where the messages are framed by
+/// `@!@!@STARTMSG code:class @!@!@` and `@!@!@ENDMSG code @!@!@`, or as
+/// on the console.
+#[derive(Debug, Default)]
+pub struct Reader {
+    // Code, class and lines of the framed message being read.
+    framed: Option<(u32, u32, Vec<String>)>,
+    // Lines of a console message that may continue.
+    console: Vec<String>,
+    // Violation that may get more states, or statistics that may get the
+    // depth.
+    pending: Option<Pending>,
+    events: Vec<Event>,
+}
+
+#[derive(Debug)]
+enum Pending {
+    Violation(Violation),
+    Error(ErrorReport),
+    Stats(Stats),
+}
+
+// Part of an event in a single message.
+enum Item {
+    Event(Event),
+    Start(ViolationKind),
+    Behavior(ErrorReport),
+    Nested(String, Vec<Location>),
+    State(State),
+    Back(usize),
+    Error(ErrorReport),
+    Stats(Stats),
+    Depth(u64),
+}
+
+impl Reader {
+    /// Reads the next line, the result has the events that it completes.
+    pub fn line(&mut self, line: &str) -> Vec<Event> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        if let Some(header) = line.strip_prefix("@!@!@STARTMSG ") {
+            self.end_console();
+            let header = header.trim_end_matches("@!@!@").trim();
+            let (code, class) = header.split_once(':').unwrap_or((header, "0"));
+            self.framed = Some((code.parse().unwrap_or(0), class.parse().unwrap_or(0), Vec::new()));
+        } else if line.starts_with("@!@!@ENDMSG") {
+            if let Some((code, class, lines)) = self.framed.take() {
+                let item = classify(&lines.join("\n"), Some(code), class == 1);
+                self.item(item);
+            }
+        } else if let Some((_, _, lines)) = &mut self.framed {
+            lines.push(line.to_string());
+        } else if line.trim().is_empty() {
+            self.end_console();
+        } else {
+            if starts_message(line) {
+                self.end_console();
+            }
+            self.console.push(line.to_string());
+            // Only errors, warnings and states continue on the next lines.
+            let first = &self.console[0];
+            if !first.starts_with("Error:") && !first.starts_with("Warning:") && header(first).is_none() {
+                self.end_console();
+            }
+        }
+        mem::take(&mut self.events)
+    }
+
+    /// Completes the events at the end of the output.
+    pub fn finish(mut self) -> Vec<Event> {
+        if let Some((code, class, lines)) = self.framed.take() {
+            let item = classify(&lines.join("\n"), Some(code), class == 1);
+            self.item(item);
+        }
+        self.end_console();
+        self.flush();
+        self.events
+    }
+
+    fn end_console(&mut self) {
+        if !self.console.is_empty() {
+            let text = mem::take(&mut self.console).join("\n");
+            let (text, error) = match text.strip_prefix("Error: ") {
+                Some(text) => (text, true),
+                None => (text.as_str(), false),
+            };
+            let item = classify(text, None, error);
+            self.item(item);
+        }
+    }
+
+    fn item(&mut self, item: Item) {
+        match item {
+            Item::Start(kind) => self.start(Pending::Violation(Violation { kind, trace: Vec::new(), back: None })),
+            Item::Behavior(report) => {
+                let kind = match self.pending.take() {
+                    Some(Pending::Violation(v)) => {
+                        self.pending = Some(Pending::Violation(v));
+                        return;
+                    }
+                    Some(Pending::Error(e)) => ViolationKind::Error(e),
+                    pending => {
+                        self.pending = pending;
+                        ViolationKind::Error(report)
+                    }
+                };
+                self.start(Pending::Violation(Violation { kind, trace: Vec::new(), back: None }));
+            }
+            Item::Nested(text, locations) => match &mut self.pending {
+                Some(Pending::Error(e)) => {
+                    e.message.push('\n');
+                    e.message.push_str(&text);
+                    e.locations.extend(locations);
+                }
+                _ => self.start(Pending::Error(ErrorReport { code: None, message: text, locations })),
+            },
+            Item::State(state) => match &mut self.pending {
+                Some(Pending::Violation(v)) => v.trace.push(state),
+                _ => {
+                    let text = state_text(&state);
+                    self.event(Event::Message { code: None, text });
+                }
+            },
+            Item::Back(n) => match &mut self.pending {
+                Some(Pending::Violation(v)) => v.back = Some(n),
+                _ => self.event(Event::Message { code: None, text: format!("Back to state {}", n) }),
+            },
+            Item::Error(e) => self.start(Pending::Error(e)),
+            Item::Stats(stats) => self.start(Pending::Stats(stats)),
+            Item::Depth(depth) => match &mut self.pending {
+                Some(Pending::Stats(stats)) => {
+                    stats.depth = Some(depth);
+                    self.flush();
+                }
+                _ => {
+                    let text = format!("The depth of the complete state graph search is {}.", depth);
+                    self.event(Event::Message { code: None, text });
+                }
+            },
+            Item::Event(e) => self.event(e),
+        }
+    }
+
+    fn start(&mut self, pending: Pending) {
+        self.flush();
+        self.pending = Some(pending);
+    }
+
+    fn event(&mut self, event: Event) {
+        self.flush();
+        self.events.push(event);
+    }
+
+    fn flush(&mut self) {
+        match self.pending.take() {
+            Some(Pending::Violation(v)) => self.events.push(Event::Violation(v)),
+            Some(Pending::Error(e)) => self.events.push(Event::Error(e)),
+            Some(Pending::Stats(s)) => self.events.push(Event::Stats(s)),
+            None => {}
+        }
+    }
+}
+
+// Whether a console line starts a message, rather than continuing an
+// error message or a state.
+fn starts_message(line: &str) -> bool {
+    const STARTS: &[&str] = &[
+        "Error:",
+        "Warning:",
+        "State ",
+        "Back to state",
+        "Progress(",
+        "TLC2 Version",
+        "Starting...",
+        "Computing initial states",
+        "Finished",
+        "Model checking completed",
+        "The depth of",
+        "The average outdegree",
+        "The coverage statistics",
+        "End of statistics",
+        "Checking temporal properties",
+        "Implied-temporal checking",
+    ];
+    STARTS.iter().any(|s| line.starts_with(s)) || line.contains(" states generated")
+}
+
+fn classify(text: &str, code: Option<u32>, error: bool) -> Item {
+    let first = text.lines().next().unwrap_or("");
+    let report = || ErrorReport { code, message: text.to_string(), locations: locations(text) };
+    if let Some(name) = first.strip_prefix("Invariant ").and_then(|s| Some(&s[..s.find(" is violated")?])) {
+        return Item::Start(ViolationKind::Invariant(name.to_string()));
+    }
+    if first.starts_with("Deadlock reached") {
+        return Item::Start(ViolationKind::Deadlock);
+    }
+    if first.starts_with("Temporal properties were violated")
+        || first.starts_with("Action property") && first.contains("is violated")
+    {
+        return Item::Start(ViolationKind::Property);
+    }
+    if first.starts_with("The behavior up to this point is")
+        || first.starts_with("The following behavior constitutes a counter-example")
+    {
+        return Item::Behavior(report());
+    }
+    if first.starts_with("The error occurred when TLC was evaluating the nested") {
+        return Item::Nested(text.to_string(), locations(text));
+    }
+    if let Some(rest) = first.find("Back to state").map(|i| &first[i + "Back to state".len()..]) {
+        if let Some((n, _)) = number(rest.trim_start_matches([':', ' '])) {
+            return Item::Back(n);
+        }
+    }
+    if let Some(state) = state(text) {
+        return Item::State(state);
+    }
+    if error {
+        return Item::Error(report());
+    }
+    if let Some(rest) = first.strip_prefix("Progress(") {
+        let depth = number(rest).map(|(n, _)| n as u64);
+        return Item::Event(Event::Progress(Stats { depth, ..stats(first) }));
+    }
+    if first.starts_with("Finished computing initial states") {
+        // `12 states generated, with 4 of them distinct` or
+        // `4 distinct states generated`.
+        let distinct = count(first, "of them distinct").or_else(|| count(first, "distinct"));
+        return Item::Event(Event::InitialStates { distinct: distinct.unwrap_or(0) });
+    }
+    if first.contains(" generated") && first.contains("distinct states found") {
+        return Item::Stats(stats(first));
+    }
+    if let Some(rest) = first.strip_prefix("The depth of the complete state graph search is ") {
+        if let Some((depth, _)) = number(rest) {
+            return Item::Depth(depth as u64);
+        }
+    }
+    if first.starts_with("Model checking completed. No error has been found") {
+        return Item::Event(Event::Success);
+    }
+    if let Some(rest) = first.strip_prefix("Finished in ") {
+        let time = rest.split(" at ").next().unwrap_or(rest);
+        return Item::Event(Event::Finished(time.to_string()));
+    }
+    if let Some(version) = first.strip_prefix("TLC2 Version ") {
+        return Item::Event(Event::Version(version.to_string()));
+    }
+    if let Some(coverage) = coverage(first) {
+        return Item::Event(Event::Coverage(coverage));
+    }
+    Item::Event(Event::Message { code, text: text.to_string() })
+}
+
+// Progress and final statistics:
+// `1,234 states generated, 567 distinct states found, 12 states left on queue.`
+fn stats(line: &str) -> Stats {
+    Stats {
+        generated: count(line, "generated").unwrap_or(0),
+        distinct: count(line, "distinct").unwrap_or(0),
+        queue: count(line, "left on queue").unwrap_or(0),
+        depth: None,
+    }
+}
+
+// Number before the phrase, which may be separated by `states`. Numbers
+// may have thousands separators.
+fn count(line: &str, phrase: &str) -> Option<u64> {
+    let before = &line[..line.find(phrase)?];
+    let mut words = before.split_whitespace().rev().skip_while(|w| *w == "states" || *w == "state");
+    words.next()?.replace(',', "").parse().ok()
+}
+
+// `N: <label>` in the `-tool` mode or `State N: <label>` on the console,
+// followed by the values of the variables.
+fn header(line: &str) -> Option<(usize, &str)> {
+    let line = line.strip_prefix("State ").unwrap_or(line);
+    let (n, rest) = number(line)?;
+    Some((n, rest.strip_prefix(": ")?))
+}
+
+fn state(text: &str) -> Option<State> {
+    let mut lines = text.lines();
+    let (number, label) = header(lines.next()?)?;
+    let (action, location) = match label.strip_prefix('<').and_then(|l| l.strip_suffix('>')) {
+        Some("Initial predicate") | None => (None, None),
+        Some(inner) => match inner.find(" line ") {
+            Some(i) => (Some(inner[..i].to_string()), location(&inner[i + 1..]).map(|(l, _)| l)),
+            None => (Some(inner.to_string()), None),
+        },
+    };
+    let mut assignments: Vec<(String, String)> = Vec::new();
+    for line in lines {
+        // A single variable is printed without `/\`.
+        let assignment = line.strip_prefix("/\\ ").or(if assignments.is_empty() { Some(line) } else { None });
+        match assignment.and_then(|a| a.split_once(" = ")) {
+            Some((name, value)) => assignments.push((name.trim().to_string(), value.to_string())),
+            None => {
+                let (_, value) = assignments.last_mut()?;
+                value.push('\n');
+                value.push_str(line);
+            }
+        }
+    }
+    Some(State { number, label: label.to_string(), action, location, assignments })
+}
+
+fn state_text(state: &State) -> String {
+    let mut text = format!("{}: {}", state.number, state.label);
+    for (name, value) in &state.assignments {
+        text.push_str(&format!("\n/\\ {} = {}", name, value));
+    }
+    text
+}
+
+// `<Next line 5, col 1 to line 5, col 4 of module M>: 3:8`, or
+// `line 6, col 5 to line 6, col 10 of module M: 2` for an expression,
+// which may be indented with `|`.
+fn coverage(line: &str) -> Option<Coverage> {
+    if let Some(rest) = line.strip_prefix('<') {
+        let (inner, counts) = rest.split_once(">: ")?;
+        let i = inner.find(" line ")?;
+        let (location, _) = location(&inner[i + 1..])?;
+        let (distinct, generated) = counts.split_once(':')?;
+        return Some(Coverage::Action {
+            name: inner[..i].to_string(),
+            location,
+            distinct: distinct.trim().parse().ok()?,
+            generated: generated.trim().parse().ok()?,
+        });
+    }
+    let (location, rest) = location(line.trim_start_matches([' ', '|']))?;
+    let count = rest.strip_prefix(": ")?.split(':').next()?.trim().parse().ok()?;
+    Some(Coverage::Expression { location, count })
+}
+
+// All locations in the text.
+fn locations(text: &str) -> Vec<Location> {
+    text.char_indices().filter_map(|(i, _)| location(&text[i..]).map(|(l, _)| l)).collect()
+}
+
+// `line 1, col 2 to line 3, col 4 of module M`, or the form of the nested
+// expressions `Line 1, column 2 to line 3, column 4 in M`. The result has
+// the text after it.
+fn location(s: &str) -> Option<(Location, &str)> {
+    let s = s.strip_prefix("line ").or_else(|| s.strip_prefix("Line "))?;
+    let (start, s) = line_col(s)?;
+    let (end, s) = line_col(s.strip_prefix(" to line ")?)?;
+    let s = s.strip_prefix(" of module ").or_else(|| s.strip_prefix(" in "))?;
+    let len = s.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(s.len());
+    if len == 0 {
+        return None;
+    }
+    Some((Location { module: s[..len].to_string(), start, end }, &s[len..]))
+}
+
+// `1, col 2` or `1, column 2`.
+fn line_col(s: &str) -> Option<((usize, usize), &str)> {
+    let (line, s) = number(s)?;
+    let s = s.strip_prefix(", col")?;
+    let s = s.strip_prefix("umn").unwrap_or(s);
+    let (col, s) = number(s.strip_prefix(' ')?)?;
+    Some(((line, col), s))
+}
+
+fn number(s: &str) -> Option<(usize, &str)> {
+    let len = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    Some((s[..len].parse().ok()?, &s[len..]))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(module: &str, start: (usize, usize), end: (usize, usize)) -> Location {
+        Location { module: module.to_string(), start, end }
+    }
+
+    #[test]
+    fn tool_mode() {
+        let output = "\
+@!@!@STARTMSG 2262:0 @!@!@
+TLC2 Version 2.18 of 20 March 2023 (rev: 3ea3222)
+@!@!@ENDMSG 2262 @!@!@
+@!@!@STARTMSG 2190:0 @!@!@
+Finished computing initial states: 1 distinct state generated at 2024-05-01 10:00:00.
+@!@!@ENDMSG 2190 @!@!@
+@!@!@STARTMSG 2110:1 @!@!@
+Invariant Small is violated.
+@!@!@ENDMSG 2110 @!@!@
+@!@!@STARTMSG 2121:1 @!@!@
+The behavior up to this point is:
+@!@!@ENDMSG 2121 @!@!@
+@!@!@STARTMSG 2217:4 @!@!@
+1: <Initial predicate>
+/\\ x = 0
+/\\ y = [a |-> 1]
+@!@!@ENDMSG 2217 @!@!@
+@!@!@STARTMSG 2217:4 @!@!@
+2: <Next line 8, col 9 to line 9, col 20 of module M>
+/\\ x = 1
+/\\ y = [a |-> 1]
+@!@!@ENDMSG 2217 @!@!@
+@!@!@STARTMSG 2201:0 @!@!@
+The coverage statistics at 2024-05-01 10:00:01
+@!@!@ENDMSG 2201 @!@!@
+@!@!@STARTMSG 2772:0 @!@!@
+<Init line 5, col 1 to line 5, col 4 of module M>: 1:1
+@!@!@ENDMSG 2772 @!@!@
+@!@!@STARTMSG 2221:0 @!@!@
+  |line 6, col 5 to line 6, col 10 of module M: 1
+@!@!@ENDMSG 2221 @!@!@
+@!@!@STARTMSG 2200:0 @!@!@
+Progress(2) at 2024-05-01 10:00:01: 3 states generated (180 s/min), 2 distinct states found (120 ds/min), 1 states left on queue.
+@!@!@ENDMSG 2200 @!@!@
+@!@!@STARTMSG 2199:0 @!@!@
+3 states generated, 2 distinct states found, 1 states left on queue.
+@!@!@ENDMSG 2199 @!@!@
+@!@!@STARTMSG 2194:0 @!@!@
+The depth of the complete state graph search is 2.
+@!@!@ENDMSG 2194 @!@!@
+@!@!@STARTMSG 2186:0 @!@!@
+Finished in 01s at (2024-05-01 10:00:01)
+@!@!@ENDMSG 2186 @!@!@
+";
+        let state = |number, label: &str, action: Option<&str>, location, x: &str| State {
+            number,
+            label: label.to_string(),
+            action: action.map(str::to_string),
+            location,
+            assignments: vec![("x".to_string(), x.to_string()), ("y".to_string(), "[a |-> 1]".to_string())],
+        };
+        assert_eq!(
+            parse(output),
+            vec![
+                Event::Version("2.18 of 20 March 2023 (rev: 3ea3222)".to_string()),
+                Event::InitialStates { distinct: 1 },
+                Event::Violation(Violation {
+                    kind: ViolationKind::Invariant("Small".to_string()),
+                    trace: vec![
+                        state(1, "<Initial predicate>", None, None, "0"),
+                        state(
+                            2,
+                            "<Next line 8, col 9 to line 9, col 20 of module M>",
+                            Some("Next"),
+                            Some(at("M", (8, 9), (9, 20))),
+                            "1",
+                        ),
+                    ],
+                    back: None,
+                }),
+                Event::Message { code: Some(2201), text: "The coverage statistics at 2024-05-01 10:00:01".to_string() },
+                Event::Coverage(Coverage::Action {
+                    name: "Init".to_string(),
+                    location: at("M", (5, 1), (5, 4)),
+                    distinct: 1,
+                    generated: 1,
+                }),
+                Event::Coverage(Coverage::Expression { location: at("M", (6, 5), (6, 10)), count: 1 }),
+                Event::Progress(Stats { generated: 3, distinct: 2, queue: 1, depth: Some(2) }),
+                Event::Stats(Stats { generated: 3, distinct: 2, queue: 1, depth: Some(2) }),
+                Event::Finished("01s".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn console() {
+        let output = "\
+Starting... (2024-05-01 10:00:00)
+Error: The first argument of Assert evaluated to FALSE; the second argument was:
+\"x too big\"
+Error: The error occurred when TLC was evaluating the nested
+expressions at the following positions:
+0. Line 7, column 5 to line 7, column 30 in M
+1. Line 7, column 12 to line 7, column 17 in M
+
+Error: The behavior up to this point is:
+State 1: <Initial predicate>
+x = 0
+
+State 2: <Inc line 6, col 8 to line 7, col 30 of module M>
+x = <<1,
+  2>>
+
+Back to state 1: <Inc line 6, col 8 to line 7, col 30 of module M>
+
+Error: Deadlock reached.
+Error: The behavior up to this point is:
+State 1: <Initial predicate>
+/\\ x = 0
+/\\ y = 1
+
+1,234 states generated, 1,000 distinct states found, 0 states left on queue.
+Error: In evaluation, the identifier y is either undefined or not an operator.
+line 3, col 9 to line 3, col 9 of module N
+Model checking completed. No error has been found.
+";
+        let events = parse(output);
+        assert_eq!(events.len(), 6, "{:#?}", events);
+        assert_eq!(events[0], Event::Message { code: None, text: "Starting... (2024-05-01 10:00:00)".to_string() });
+        match &events[1] {
+            Event::Violation(Violation { kind: ViolationKind::Error(e), trace, back }) => {
+                assert!(e.message.starts_with("The first argument of Assert"), "{}", e.message);
+                assert_eq!(e.locations, vec![at("M", (7, 5), (7, 30)), at("M", (7, 12), (7, 17))]);
+                assert_eq!(trace.len(), 2);
+                assert_eq!(trace[0].assignments, vec![("x".to_string(), "0".to_string())]);
+                assert_eq!(trace[1].action.as_deref(), Some("Inc"));
+                assert_eq!(trace[1].assignments, vec![("x".to_string(), "<<1,\n  2>>".to_string())]);
+                assert_eq!(*back, Some(1));
+            }
+            e => panic!("{:?}", e),
+        }
+        match &events[2] {
+            Event::Violation(Violation { kind: ViolationKind::Deadlock, trace, back: None }) => {
+                assert_eq!(trace[0].assignments.len(), 2);
+            }
+            e => panic!("{:?}", e),
+        }
+        assert_eq!(events[3], Event::Stats(Stats { generated: 1234, distinct: 1000, queue: 0, depth: None }));
+        match &events[4] {
+            Event::Error(e) => assert_eq!(e.locations, vec![at("N", (3, 9), (3, 9))]),
+            e => panic!("{:?}", e),
+        }
+        assert_eq!(events[5], Event::Success);
+    }
+
+    #[test]
+    fn console_messages() {
+        let output = "\
+Computing initial states...
+Finished computing initial states: 12 states generated, with 4 of them distinct at 2024-05-01 10:00:00.
+Finished computing initial states: 1,024 distinct states generated.
+Warning: Please run the Java VM which executes TLC with a throughput optimized garbage collector
+by passing the \"-XX:+UseParallelGC\" property.
+Model checking completed. No error has been found.
+";
+        assert_eq!(
+            parse(output),
+            vec![
+                Event::Message { code: None, text: "Computing initial states...".to_string() },
+                Event::InitialStates { distinct: 4 },
+                Event::InitialStates { distinct: 1024 },
+                Event::Message {
+                    code: None,
+                    text: "Warning: Please run the Java VM which executes TLC with a throughput optimized garbage collector\n\
+                           by passing the \"-XX:+UseParallelGC\" property."
+                        .to_string(),
+                },
+                Event::Success,
+            ],
+        );
+    }
+
+    #[test]
+    fn incremental() {
+        let mut reader = Reader::default();
+        assert_eq!(reader.line("Error: Invariant Inv is violated by the initial state:"), vec![]);
+        assert_eq!(reader.line("State 1: <Initial predicate>"), vec![]);
+        assert_eq!(reader.line("x = 5"), vec![]);
+        assert_eq!(reader.line(""), vec![]);
+        match reader.line("Finished in 00s at (2024-05-01 10:00:01)").as_slice() {
+            [Event::Violation(v), Event::Finished(time)] => {
+                assert_eq!(v.kind, ViolationKind::Invariant("Inv".to_string()));
+                assert_eq!(time, "00s");
+            }
+            events => panic!("{:?}", events),
+        }
+        assert_eq!(reader.finish(), vec![]);
+    }
+}
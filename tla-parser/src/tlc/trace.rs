@@ -0,0 +1,372 @@
+use std::fmt;
+
+use crate::ast::Span;
+use crate::eval::{Int, LazySet, Value};
+use crate::lexer::Pos;
+
+use super::{Location, State, Violation};
+
+
+/// Behavior of a TLC error trace with the values of the variables.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub variables: Vec<String>,
+    pub steps: Vec<Step>,
+    /// Index of the step that the last state loops back to, for the
+    /// behaviors that violate liveness properties. The last index if the
+    /// behavior stutters forever in the last state.
+    pub back: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    /// Subaction that produced the state, `None` for the initial state.
+    pub action: Option<String>,
+    /// Location of the subaction, `Location::span` maps it into the
+    /// source of the module.
+    pub location: Option<Location>,
+    /// Values of the variables.
+    pub state: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// Text that is not a value printed by TLC, at the byte offset.
+    Value { offset: usize, expected: &'static str },
+    /// Value of a variable in a state, numbered from 1, that is not a value
+    /// printed by TLC.
+    State { state: usize, variable: String, offset: usize, expected: &'static str },
+    /// State that does not assign the variables of the first one.
+    Variables { state: usize },
+    /// Text that is not JSON, at the byte offset.
+    Json { offset: usize, expected: &'static str },
+    /// JSON document that is not a trace in the Informal Trace Format.
+    Itf(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Value { offset, expected } => write!(f, "offset {}: expected {}", offset, expected),
+            Error::State { state, variable, offset, expected } => write!(
+                f, "state {}, variable `{}`, offset {}: expected {}", state, variable, offset, expected),
+            Error::Variables { state } => write!(
+                f, "state {} does not assign the variables of the first state", state),
+            Error::Json { offset, expected } => write!(f, "offset {}: expected {}", offset, expected),
+            Error::Itf(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+
+impl Violation {
+    /// Values of the variables in the states of the trace.
+    pub fn trace(&self) -> Result<Trace, Error> {
+        Trace::new(&self.trace, self.back)
+    }
+}
+
+impl Trace {
+    /// Trace of the states as TLC prints them, with `back` the number of the
+    /// state that the last one loops back to.
+    pub fn new(states: &[State], back: Option<usize>) -> Result<Trace, Error> {
+        let variables: Vec<String> = states.first().map_or(Vec::new(), |s| {
+            s.assignments.iter().map(|(name, _)| name.clone()).collect()
+        });
+        let mut trace = Trace { variables, steps: Vec::new(), back: back.map(|n| n.saturating_sub(1)) };
+        for s in states {
+            if s.label == "Stuttering" {
+                trace.back = trace.steps.len().checked_sub(1);
+                continue;
+            }
+            let names = s.assignments.iter().map(|(name, _)| name);
+            if s.assignments.len() != trace.variables.len() || !names.eq(trace.variables.iter()) {
+                return Err(Error::Variables { state: s.number });
+            }
+            let state = s
+                .assignments
+                .iter()
+                .map(|(name, text)| {
+                    parse_value(text).map_err(|e| match e {
+                        Error::Value { offset, expected } => {
+                            Error::State { state: s.number, variable: name.clone(), offset, expected }
+                        }
+                        e => e,
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+            trace.steps.push(Step { action: s.action.clone(), location: s.location.clone(), state });
+        }
+        Ok(trace)
+    }
+}
+
+impl Location {
+    /// Region of the source of the module. `None` if the location is
+    /// outside of it.
+    pub fn span(&self, source: &str) -> Option<Span> {
+        let start = pos(source, self.start)?;
+        let end = pos(source, self.end)?;
+        // The end column is inclusive.
+        let end = Pos {
+            col: end.col + 1,
+            byte_offset: end.byte_offset + end.char_size,
+            char_size: source[end.byte_offset + end.char_size..].chars().next().map_or(0, char::len_utf8),
+            ..end
+        };
+        Some(Span::new(start, end))
+    }
+}
+
+// Position of the character at the line and column of TLC, where a tab is
+// a single column, with the column as the lexer counts it.
+fn pos(source: &str, (line, col): (usize, usize)) -> Option<Pos> {
+    let start = if line == 1 {
+        0
+    } else {
+        source.match_indices('\n').nth(line.checked_sub(2)?)?.0 + 1
+    };
+    let text = source[start..].split('\n').next()?;
+    let (offset, c) = text.char_indices().nth(col.checked_sub(1)?)?;
+    let lexer_col = 1 + text[..offset].chars().map(|c| if c == '\t' { 4 } else { 1 }).sum::<usize>();
+    Some(Pos { line, col: lexer_col, byte_offset: start + offset, char_size: c.len_utf8() })
+}
+
+
+/// Parses a value as TLC prints it: integers, strings, booleans, model
+/// values, sets and intervals `a..b`, sequences `<<a, b>>`, records
+/// `[a |-> 1]` and functions `(1 :> 2 @@ 3 :> 4)`.
+pub fn parse_value(text: &str) -> Result<Value, Error> {
+    let mut p = ValueParser { text, offset: 0 };
+    let value = p.value()?;
+    p.space();
+    if p.offset < text.len() {
+        return Err(p.error("end of the value"));
+    }
+    Ok(value)
+}
+
+struct ValueParser<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl<'a> ValueParser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.offset..]
+    }
+
+    fn error(&self, expected: &'static str) -> Error {
+        Error::Value { offset: self.offset, expected }
+    }
+
+    fn space(&mut self) {
+        let rest = self.rest();
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+
+    // Skips the token if it is next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.space();
+        let found = self.rest().starts_with(token);
+        if found {
+            self.offset += token.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &'static str) -> Result<(), Error> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(token))
+        }
+    }
+
+    // Values separated by `sep` until `close`.
+    fn list<T>(&mut self, sep: &str, close: &'static str, item: fn(&mut Self) -> Result<T, Error>) -> Result<Vec<T>, Error> {
+        let mut items = Vec::new();
+        if self.eat(close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.eat(close) {
+                return Ok(items);
+            }
+            if !self.eat(sep) {
+                return Err(self.error(close));
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        self.space();
+        if self.eat("{") {
+            return Ok(Value::set(self.list(",", "}", Self::value)?));
+        }
+        if self.eat("<<") {
+            return Ok(Value::seq(self.list(",", ">>", Self::value)?));
+        }
+        if self.eat("[") {
+            let fields = self.list(",", "]", |p| {
+                let name = p.word().ok_or_else(|| p.error("field name"))?;
+                p.expect("|->")?;
+                Ok((name.to_string(), p.value()?))
+            })?;
+            return Ok(Value::record(fields));
+        }
+        if self.eat("(") {
+            let pairs = self.list("@@", ")", |p| {
+                let key = p.value()?;
+                p.expect(":>")?;
+                Ok((key, p.value()?))
+            })?;
+            return Ok(Value::function(pairs));
+        }
+        if self.rest().starts_with('"') {
+            return self.string();
+        }
+        if let Some(n) = self.int() {
+            if self.eat("..") {
+                let m = self.int().ok_or_else(|| self.error("integer"))?;
+                return Ok(Value::lazy(LazySet::Interval(n, m)).normalize());
+            }
+            return Ok(Value::Int(n));
+        }
+        match self.word() {
+            Some("TRUE") => Ok(Value::Bool(true)),
+            Some("FALSE") => Ok(Value::Bool(false)),
+            Some(name) => Ok(Value::model(name)),
+            None => Err(self.error("value")),
+        }
+    }
+
+    fn int(&mut self) -> Option<Int> {
+        self.space();
+        let rest = self.rest();
+        let digits = rest.strip_prefix('-').unwrap_or(rest);
+        let len = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+        // Names of model values may start with digits.
+        if len == 0 || digits[len..].starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+            return None;
+        }
+        let n = Int::parse(&digits[..len], 10)?;
+        self.offset += rest.len() - digits.len() + len;
+        Some(if rest.starts_with('-') { -&n } else { n })
+    }
+
+    fn word(&mut self) -> Option<&'a str> {
+        self.space();
+        let rest = self.rest();
+        let len = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+        if len == 0 {
+            return None;
+        }
+        self.offset += len;
+        Some(&rest[..len])
+    }
+
+    fn string(&mut self) -> Result<Value, Error> {
+        let mut s = String::new();
+        let mut chars = self.rest().char_indices().skip(1);
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.offset += i + 1;
+                    return Ok(Value::str(&s));
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => s.push('\n'),
+                    Some((_, 't')) => s.push('\t'),
+                    Some((_, 'r')) => s.push('\r'),
+                    Some((_, c)) => s.push(c),
+                    None => break,
+                },
+                c => s.push(c),
+            }
+        }
+        self.offset = self.text.len();
+        Err(self.error("`\"`"))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values() {
+        let values = vec![
+            Value::int(-12),
+            Value::str("a \"quoted\"\nline"),
+            Value::Bool(false),
+            Value::model("p1"),
+            Value::set(vec![Value::int(1), Value::model("2a")]),
+            Value::seq(vec![]),
+            Value::seq(vec![Value::int(1), Value::seq(vec![Value::str("x")])]),
+            Value::record(vec![("a".to_string(), Value::int(1)), ("b".to_string(), Value::set(vec![]))]),
+            Value::function(vec![(Value::model("p"), Value::int(2)), (Value::int(0), Value::Bool(true))]),
+        ];
+        for v in values {
+            assert_eq!(parse_value(&v.to_string()), Ok(v.clone()), "{}", v);
+        }
+        assert_eq!(parse_value("1..3"), Ok(Value::set(vec![Value::int(1), Value::int(2), Value::int(3)])));
+        assert_eq!(parse_value("(1 :> \"a\" @@ 2 :> \"b\")"), Ok(Value::seq(vec![Value::str("a"), Value::str("b")])));
+        assert_eq!(parse_value("[a |-> 1,\n   b |-> <<>>]").unwrap().to_string(), "[a |-> 1, b |-> <<>>]");
+        assert_eq!(parse_value("{1, 2"), Err(Error::Value { offset: 5, expected: "}" }));
+        assert_eq!(parse_value("[a = 1]"), Err(Error::Value { offset: 3, expected: "|->" }));
+        assert_eq!(parse_value("1 2"), Err(Error::Value { offset: 2, expected: "end of the value" }));
+        assert_eq!(parse_value("\"abc"), Err(Error::Value { offset: 4, expected: "`\"`" }));
+    }
+
+    #[test]
+    fn trace() {
+        let output = "\
+Error: Temporal properties were violated.
+Error: The following behavior constitutes a counter-example:
+State 1: <Initial predicate>
+/\\ x = 0
+/\\ f = (a :> {} @@ b :> {1})
+
+State 2: <Inc line 2, col 2 to line 3, col 16 of module M>
+/\\ x = 1
+/\\ f = (a :> {1} @@ b :> {1})
+
+State 3: Stuttering
+";
+        let v = match super::super::parse(output).as_slice() {
+            [super::super::Event::Violation(v)] => v.clone(),
+            events => panic!("{:?}", events),
+        };
+        let trace = v.trace().unwrap();
+        assert_eq!(trace.variables, vec!["x", "f"]);
+        assert_eq!(trace.steps.len(), 2);
+        assert_eq!(trace.back, Some(1));
+        let f = |a: Vec<i64>| {
+            Value::function(vec![
+                (Value::model("a"), Value::set(a.into_iter().map(Value::int))),
+                (Value::model("b"), Value::set(vec![Value::int(1)])),
+            ])
+        };
+        assert_eq!(trace.steps[0], Step { action: None, location: None, state: vec![Value::int(0), f(vec![])] });
+        assert_eq!(trace.steps[1].state, vec![Value::int(1), f(vec![1])]);
+        assert_eq!(trace.steps[1].action.as_deref(), Some("Inc"));
+
+        let source = "---- MODULE M ----\n\tInc == x' = x + 1\n  /\\ UNCHANGED f\n====";
+        let span = trace.steps[1].location.as_ref().unwrap().span(source).unwrap();
+        assert_eq!(&source[span.start.byte_offset..span.end.byte_offset], "Inc == x' = x + 1\n  /\\ UNCHANGED f");
+        assert_eq!((span.start.line, span.start.col, span.end.line, span.end.col), (2, 5, 3, 17));
+        assert_eq!(Location { module: "M".to_string(), start: (9, 1), end: (9, 2) }.span(source), None);
+
+        let mut bad = v.clone();
+        bad.trace[1].assignments[1].1 = "(a :> {1}".to_string();
+        assert_eq!(
+            bad.trace().unwrap_err().to_string(),
+            "state 2, variable `f`, offset 9: expected )",
+        );
+        bad.trace[1].assignments.pop();
+        assert_eq!(bad.trace(), Err(Error::Variables { state: 2 }));
+    }
+}
@@ -0,0 +1,1383 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use crate::ast::*;
+use crate::lexer::{self, Keyword, TokenType};
+use crate::resolve::{DeclKind, Resolution};
+
+
+/// Types in the notation of Apalache type annotations.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Int,
+    Bool,
+    Str,
+    /// Uninterpreted type, e.g. `PROC`. Its values are model values.
+    Const(String),
+    Var(usize),
+    Set(Box<Type>),
+    Seq(Box<Type>),
+    Fun(Box<Type>, Box<Type>),
+    Tuple(Vec<Type>),
+    /// Record with the known fields. If the second component is a type
+    /// variable, the record may have more fields.
+    Record(BTreeMap<String, Type>, Option<usize>),
+    Oper(Vec<Type>, Box<Type>),
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn var(f: &mut fmt::Formatter, v: usize) -> fmt::Result {
+            let letter = (b'a' + (v % 26) as u8) as char;
+            match v / 26 {
+                0 => write!(f, "{}", letter),
+                n => write!(f, "{}{}", letter, n),
+            }
+        }
+        fn list(f: &mut fmt::Formatter, ts: &[Type]) -> fmt::Result {
+            for (i, t) in ts.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}", t)?;
+            }
+            Ok(())
+        }
+        match self {
+            Type::Int => f.write_str("Int"),
+            Type::Bool => f.write_str("Bool"),
+            Type::Str => f.write_str("Str"),
+            Type::Const(name) => f.write_str(name),
+            Type::Var(v) => var(f, *v),
+            Type::Set(t) => write!(f, "Set({})", t),
+            Type::Seq(t) => write!(f, "Seq({})", t),
+            Type::Fun(a, b) => match **a {
+                Type::Fun(..) | Type::Oper(..) => write!(f, "({}) -> {}", a, b),
+                _ => write!(f, "{} -> {}", a, b),
+            },
+            Type::Tuple(ts) => {
+                f.write_str("<<")?;
+                list(f, ts)?;
+                f.write_str(">>")
+            }
+            Type::Record(fields, row) => {
+                f.write_str("[")?;
+                for (i, (name, t)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}: {}", name, t)?;
+                }
+                if row.is_some() {
+                    f.write_str(if fields.is_empty() { "..." } else { ", ..." })?;
+                }
+                f.write_str("]")
+            }
+            Type::Oper(params, res) => {
+                f.write_str("(")?;
+                list(f, params)?;
+                write!(f, ") => {}", res)
+            }
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Mismatch { span: Span, expected: Type, found: Type },
+    /// Access to a field that the record does not have.
+    NoField { span: Span, field: String, record: Type },
+    /// Malformed `@type:` annotation.
+    Annotation { span: Span, message: String },
+}
+
+impl Error {
+    pub fn span(&self) -> Span {
+        match self {
+            Error::Mismatch { span, .. }
+            | Error::NoField { span, .. }
+            | Error::Annotation { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pos = self.span().start;
+        write!(f, "{}:{}: ", pos.line, pos.col)?;
+        match self {
+            Error::Mismatch { expected, found, .. } => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+            Error::NoField { field, record, .. } => {
+                write!(f, "record {} has no field `{}`", record, field)
+            }
+            Error::Annotation { message, .. } => write!(f, "bad type annotation: {}", message),
+        }
+    }
+}
+
+
+#[derive(Debug, Default)]
+pub struct Types {
+    // Types of the declarations, by index in `Resolution::decls`.
+    decls: HashMap<usize, Type>,
+    /// Types of the top level constants, variables and definitions.
+    pub definitions: HashMap<String, Type>,
+    pub errors: Vec<Error>,
+}
+
+impl Types {
+    pub fn decl(&self, id: usize) -> Option<&Type> {
+        self.decls.get(&id)
+    }
+}
+
+
+// Signatures of the built-in and standard operators. The operators of
+// the Reals module are left untyped, as there is no type of reals.
+// This table must be sorted.
+pub static SIGNATURES: &[(&str, &str)] = &[
+    ("%", "(Int, Int) => Int"),
+    ("'", "(a) => a"),
+    ("*", "(Int, Int) => Int"),
+    ("+", "(Int, Int) => Int"),
+    ("-", "(Int, Int) => Int"),
+    ("-+->", "(Bool, Bool) => Bool"),
+    ("-.", "(Int) => Int"),
+    ("..", "(Int, Int) => Set(Int)"),
+    ("/=", "(a, a) => Bool"),
+    ("/\\", "(Bool, Bool) => Bool"),
+    (":>", "(a, b) => a -> b"),
+    ("<", "(Int, Int) => Bool"),
+    ("<=", "(Int, Int) => Bool"),
+    ("<=>", "(Bool, Bool) => Bool"),
+    ("<>", "(Bool) => Bool"),
+    ("=", "(a, a) => Bool"),
+    ("=>", "(Bool, Bool) => Bool"),
+    (">", "(Int, Int) => Bool"),
+    (">=", "(Int, Int) => Bool"),
+    ("@@", "(a -> b, a -> b) => a -> b"),
+    ("Any", "a"),
+    ("Append", "(Seq(a), a) => Seq(a)"),
+    ("Assert", "(Bool, Str) => Bool"),
+    ("BOOLEAN", "Set(Bool)"),
+    ("BagCardinality", "(a -> Int) => Int"),
+    ("BagIn", "(a, a -> Int) => Bool"),
+    ("BagOfAll", "((a) => b, a -> Int) => b -> Int"),
+    ("BagToSet", "(a -> Int) => Set(a)"),
+    ("BagUnion", "(Set(a -> Int)) => a -> Int"),
+    ("Cardinality", "(Set(a)) => Int"),
+    ("CopiesIn", "(a, a -> Int) => Int"),
+    ("DOMAIN", "(a -> b) => Set(a)"),
+    ("ENABLED", "(Bool) => Bool"),
+    ("EmptyBag", "a -> Int"),
+    ("FALSE", "Bool"),
+    ("Head", "(Seq(a)) => a"),
+    ("Int", "Set(Int)"),
+    ("IsABag", "(a) => Bool"),
+    ("IsFiniteSet", "(Set(a)) => Bool"),
+    ("JavaTime", "Int"),
+    ("Len", "(Seq(a)) => Int"),
+    ("Nat", "Set(Int)"),
+    ("Permutations", "(Set(a)) => Set(a -> a)"),
+    ("Print", "(a, b) => b"),
+    ("PrintT", "(a) => Bool"),
+    ("RTBound", "(Bool, a, Int, Int) => Bool"),
+    ("RTnow", "(a) => Bool"),
+    ("RandomElement", "(Set(a)) => a"),
+    ("STRING", "Set(Str)"),
+    ("SUBSET", "(Set(a)) => Set(Set(a))"),
+    ("SelectSeq", "(Seq(a), (a) => Bool) => Seq(a)"),
+    ("Seq", "(Set(a)) => Set(Seq(a))"),
+    ("SetToBag", "(Set(a)) => a -> Int"),
+    ("SortSeq", "(Seq(a), (a, a) => Bool) => Seq(a)"),
+    ("SubBag", "(a -> Int) => Set(a -> Int)"),
+    ("SubSeq", "(Seq(a), Int, Int) => Seq(a)"),
+    ("TLCEval", "(a) => a"),
+    ("TLCGet", "(a) => b"),
+    ("TLCSet", "(a, b) => Bool"),
+    ("TRUE", "Bool"),
+    ("Tail", "(Seq(a)) => Seq(a)"),
+    ("ToString", "(a) => Str"),
+    ("UNCHANGED", "(a) => Bool"),
+    ("UNION", "(Set(Set(a))) => Set(a)"),
+    ("[]", "(Bool) => Bool"),
+    ("\\", "(Set(a), Set(a)) => Set(a)"),
+    ("\\/", "(Bool, Bool) => Bool"),
+    ("\\X", "(Set(a), Set(b)) => Set(<<a, b>>)"),
+    ("\\cap", "(Set(a), Set(a)) => Set(a)"),
+    ("\\cdot", "(Bool, Bool) => Bool"),
+    ("\\cup", "(Set(a), Set(a)) => Set(a)"),
+    ("\\div", "(Int, Int) => Int"),
+    ("\\equiv", "(Bool, Bool) => Bool"),
+    ("\\in", "(a, Set(a)) => Bool"),
+    ("\\notin", "(a, Set(a)) => Bool"),
+    ("\\o", "(Seq(a), Seq(a)) => Seq(a)"),
+    ("\\ominus", "(a -> Int, a -> Int) => a -> Int"),
+    ("\\oplus", "(a -> Int, a -> Int) => a -> Int"),
+    ("\\sqsubseteq", "(a -> Int, a -> Int) => Bool"),
+    ("\\subseteq", "(Set(a), Set(a)) => Bool"),
+    ("^", "(Int, Int) => Int"),
+    ("now", "Int"),
+    ("~", "(Bool) => Bool"),
+    ("~>", "(Bool, Bool) => Bool"),
+];
+
+
+/// Infers types of the declarations in a resolved module.
+/// Declarations can be annotated with `\* @type: T;` comments in the
+/// source code, where `T` uses the Apalache notation, e.g.
+/// `Set(Int)`, `Seq(Str)`, `Int -> Bool`, `<<Int, Str>>`,
+/// `[name: Str, age: Int]`, `(Int, a) => Bool` or `PROC`.
+pub fn infer(module: &Module, res: &Resolution, source: &str) -> Types {
+    let mut inf = Infer::new(module, res, annotations(source));
+    inf.units(module, true);
+    inf.finish()
+}
+
+
+/// `@type:` annotations by the byte offset of the declaration they
+/// precede. Only comments, `LOCAL`, `CONSTANT`, `VARIABLE` and `RECURSIVE`
+/// may come between an annotation and its declaration.
+fn annotations(source: &str) -> Vec<(usize, Span, String)> {
+    let mut res = Vec::new();
+    let mut pending = None;
+    for lx in lexer::lex(source) {
+        match lx.value {
+            Ok(TokenType::Comment) => {
+                let text = &source[lx.start.byte_offset..lx.end.byte_offset];
+                if let Some(i) = text.find("@type:") {
+                    let rest = &text[i + "@type:".len()..];
+                    let ty = rest.split(';').next().unwrap_or("");
+                    let ty = ty.trim_end_matches("*)").trim();
+                    pending = Some((Span::new(lx.start, lx.end), ty.to_string()));
+                }
+            }
+            Ok(TokenType::Indent)
+            | Ok(TokenType::Keyword(Keyword::Local))
+            | Ok(TokenType::Keyword(Keyword::Constant))
+            | Ok(TokenType::Keyword(Keyword::Variable))
+            | Ok(TokenType::Keyword(Keyword::Recursive)) => {}
+            _ => {
+                if let Some((span, ty)) = pending.take() {
+                    res.push((lx.start.byte_offset, span, ty));
+                }
+            }
+        }
+    }
+    res
+}
+
+
+enum Failure {
+    Mismatch,
+    Field(String),
+}
+
+// Type with quantified variables.
+#[derive(Clone)]
+struct Scheme {
+    vars: Vec<usize>,
+    ty: Type,
+}
+
+struct Infer<'a> {
+    res: &'a Resolution,
+    // Annotations that are not yet attached to a declaration.
+    annotations: Vec<(usize, Span, String)>,
+    ref_at: HashMap<usize, usize>,
+    decl_at: HashMap<usize, usize>,
+    // Bindings of type variables.
+    subst: Vec<Option<Type>>,
+    schemes: HashMap<usize, Scheme>,
+    // Declarations in scope that are not generalized.
+    mono: Vec<usize>,
+    // Types of `@` in EXCEPT.
+    at: Vec<Type>,
+    signatures: HashMap<&'static str, Scheme>,
+    out: Types,
+}
+
+impl<'a> Infer<'a> {
+    fn new(module: &Module, res: &'a Resolution, annotations: Vec<(usize, Span, String)>) -> Self {
+        fn nested(m: &Module, res: &mut Vec<String>) {
+            res.push(m.name.name.clone());
+            for unit in &m.units {
+                if let Unit::Module(m) = unit {
+                    nested(m, res);
+                }
+            }
+        }
+        // Declarations from this file, other modules have their own spans.
+        let mut local_modules = Vec::new();
+        nested(module, &mut local_modules);
+        let mut decl_at = HashMap::new();
+        for (i, d) in res.decls.iter().enumerate() {
+            let local = d.module.iter().all(|m| local_modules.contains(m));
+            if let (true, Some(span)) = (local, d.span) {
+                decl_at.insert(span.start.byte_offset, i);
+            }
+        }
+        Infer {
+            res,
+            annotations,
+            ref_at: res.references.iter().map(|r| (r.span.start.byte_offset, r.decl)).collect(),
+            decl_at,
+            subst: Vec::new(),
+            schemes: HashMap::new(),
+            mono: Vec::new(),
+            at: Vec::new(),
+            signatures: HashMap::new(),
+            out: Types::default(),
+        }
+    }
+
+    fn finish(mut self) -> Types {
+        let ids: Vec<usize> = self.schemes.keys().copied().collect();
+        for id in ids {
+            let ty = self.schemes[&id].ty.clone();
+            let ty = self.resolve(&ty);
+            self.out.decls.insert(id, normalize_vars(&ty));
+        }
+        let defs: Vec<(String, Type)> = self.out.definitions.drain().collect();
+        for (name, ty) in defs {
+            let ty = normalize_vars(&self.resolve(&ty));
+            self.out.definitions.insert(name, ty);
+        }
+        self.out
+    }
+
+    fn fresh(&mut self) -> Type {
+        self.subst.push(None);
+        Type::Var(self.subst.len() - 1)
+    }
+
+    fn fresh_row(&mut self) -> usize {
+        self.subst.push(None);
+        self.subst.len() - 1
+    }
+
+    // Follows bindings of the type variables at the top of the type.
+    fn prune(&self, t: &Type) -> Type {
+        let mut t = t.clone();
+        loop {
+            match t {
+                Type::Var(v) => match &self.subst[v] {
+                    Some(b) => t = b.clone(),
+                    None => return t,
+                },
+                Type::Record(fields, Some(row)) => {
+                    let (fields, row) = self.record(fields, row);
+                    return Type::Record(fields, row);
+                }
+                _ => return t,
+            }
+        }
+    }
+
+    // Collects fields of the record and its bound row variables.
+    fn record(&self, mut fields: BTreeMap<String, Type>, mut row: usize)
+        -> (BTreeMap<String, Type>, Option<usize>)
+    {
+        loop {
+            match &self.subst[row] {
+                Some(Type::Record(more, next)) => {
+                    for (k, v) in more {
+                        fields.entry(k.clone()).or_insert_with(|| v.clone());
+                    }
+                    match next {
+                        Some(next) => row = *next,
+                        None => return (fields, None),
+                    }
+                }
+                _ => return (fields, Some(row)),
+            }
+        }
+    }
+
+    // Substitutes all bound type variables.
+    fn resolve(&self, t: &Type) -> Type {
+        match self.prune(t) {
+            Type::Set(t) => Type::Set(Box::new(self.resolve(&t))),
+            Type::Seq(t) => Type::Seq(Box::new(self.resolve(&t))),
+            Type::Fun(a, b) => Type::Fun(Box::new(self.resolve(&a)), Box::new(self.resolve(&b))),
+            Type::Tuple(ts) => Type::Tuple(ts.iter().map(|t| self.resolve(t)).collect()),
+            Type::Record(fields, row) => Type::Record(
+                fields.iter().map(|(k, v)| (k.clone(), self.resolve(v))).collect(),
+                row,
+            ),
+            Type::Oper(ps, r) => Type::Oper(
+                ps.iter().map(|t| self.resolve(t)).collect(),
+                Box::new(self.resolve(&r)),
+            ),
+            t => t,
+        }
+    }
+
+    fn occurs(&self, v: usize, t: &Type) -> bool {
+        let mut vars = BTreeSet::new();
+        free_vars(&self.resolve(t), &mut vars);
+        vars.contains(&v)
+    }
+
+    fn bind(&mut self, v: usize, t: Type) -> Result<(), Failure> {
+        if self.occurs(v, &t) {
+            return Err(Failure::Mismatch);
+        }
+        self.subst[v] = Some(t);
+        Ok(())
+    }
+
+    fn unify(&mut self, a: &Type, b: &Type) -> Result<(), Failure> {
+        use Type::*;
+        let (a, b) = (self.prune(a), self.prune(b));
+        match (&a, &b) {
+            (Var(x), Var(y)) if x == y => Ok(()),
+            (Var(x), t) | (t, Var(x)) => self.bind(*x, t.clone()),
+            (Int, Int) | (Bool, Bool) | (Str, Str) => Ok(()),
+            (Const(x), Const(y)) if x == y => Ok(()),
+            (Set(x), Set(y)) | (Seq(x), Seq(y)) => self.unify(x, y),
+            (Fun(a1, b1), Fun(a2, b2)) => {
+                self.unify(a1, a2)?;
+                self.unify(b1, b2)
+            }
+            // Sequences are functions with domain `1..n`.
+            (Seq(t), Fun(d, r)) | (Fun(d, r), Seq(t)) => {
+                self.unify(d, &Int)?;
+                self.unify(r, t)
+            }
+            // Tuple literals are also sequence literals, and so functions
+            // with domain `1..n`.
+            (Tuple(ts), Fun(d, r)) | (Fun(d, r), Tuple(ts)) => {
+                self.unify(d, &Int)?;
+                for x in ts {
+                    self.unify(x, r)?;
+                }
+                Ok(())
+            }
+            (Tuple(ts), Seq(t)) | (Seq(t), Tuple(ts)) => {
+                for x in ts {
+                    self.unify(x, t)?;
+                }
+                Ok(())
+            }
+            (Tuple(xs), Tuple(ys)) if xs.len() == ys.len() => {
+                for (x, y) in xs.iter().zip(ys) {
+                    self.unify(x, y)?;
+                }
+                Ok(())
+            }
+            (Record(f1, r1), Record(f2, r2)) => self.unify_records(f1, *r1, f2, *r2),
+            (Oper(p1, r1), Oper(p2, r2)) if p1.len() == p2.len() => {
+                for (x, y) in p1.iter().zip(p2) {
+                    self.unify(x, y)?;
+                }
+                self.unify(r1, r2)
+            }
+            _ => Err(Failure::Mismatch),
+        }
+    }
+
+    fn unify_records(
+        &mut self,
+        f1: &BTreeMap<String, Type>,
+        r1: Option<usize>,
+        f2: &BTreeMap<String, Type>,
+        r2: Option<usize>,
+    ) -> Result<(), Failure> {
+        for (k, t) in f1 {
+            if let Some(u) = f2.get(k) {
+                self.unify(t, u)?;
+            }
+        }
+        let only = |a: &BTreeMap<String, Type>, b: &BTreeMap<String, Type>| {
+            a.iter()
+                .filter(|(k, _)| !b.contains_key(*k))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let (only1, only2) = (only(f1, f2), only(f2, f1));
+        let missing = |fields: &BTreeMap<String, Type>| match fields.keys().next() {
+            Some(k) => Err(Failure::Field(k.clone())),
+            None => Ok(()),
+        };
+        match (r1, r2) {
+            (None, None) if only1.is_empty() && only2.is_empty() => Ok(()),
+            (None, None) => Err(Failure::Mismatch),
+            (Some(a), None) => {
+                missing(&only1)?;
+                self.bind(a, Type::Record(only2, None))
+            }
+            (None, Some(b)) => {
+                missing(&only2)?;
+                self.bind(b, Type::Record(only1, None))
+            }
+            (Some(a), Some(b)) if a == b => {
+                missing(&only1)?;
+                missing(&only2)
+            }
+            (Some(a), Some(b)) => {
+                let rest = self.fresh_row();
+                self.bind(a, Type::Record(only2, Some(rest)))?;
+                self.bind(b, Type::Record(only1, Some(rest)))
+            }
+        }
+    }
+
+    // Checks that the expression at `span` of type `found` has type
+    // `expected`.
+    fn expect(&mut self, span: Span, expected: &Type, found: &Type) {
+        match self.unify(expected, found) {
+            Ok(()) => {}
+            Err(Failure::Mismatch) => {
+                let pair = Type::Tuple(vec![self.resolve(expected), self.resolve(found)]);
+                if let Type::Tuple(mut pair) = normalize_vars(&pair) {
+                    let found = pair.pop().unwrap();
+                    let expected = pair.pop().unwrap();
+                    self.out.errors.push(Error::Mismatch { span, expected, found });
+                }
+            }
+            Err(Failure::Field(field)) => {
+                let record = normalize_vars(&self.resolve(found));
+                self.out.errors.push(Error::NoField { span, field, record });
+            }
+        }
+    }
+
+    fn generalize(&self, t: &Type) -> Scheme {
+        let ty = self.resolve(t);
+        let mut vars = BTreeSet::new();
+        free_vars(&ty, &mut vars);
+        let mut bound = BTreeSet::new();
+        for id in &self.mono {
+            if let Some(s) = self.schemes.get(id) {
+                free_vars(&self.resolve(&s.ty), &mut bound);
+            }
+        }
+        Scheme { vars: vars.difference(&bound).copied().collect(), ty }
+    }
+
+    fn instantiate(&mut self, s: &Scheme) -> Type {
+        let map: HashMap<usize, usize> = s.vars
+            .iter()
+            .map(|&v| (v, self.fresh_row()))
+            .collect();
+        substitute(&s.ty, &map)
+    }
+
+    fn set_mono(&mut self, id: Option<usize>, ty: Type) {
+        if let Some(id) = id {
+            self.schemes.insert(id, Scheme { vars: vec![], ty });
+            self.mono.push(id);
+        }
+    }
+
+    // Annotation written right before the declaration at `name`, if any.
+    fn annotation(&mut self, name: &Span) -> Option<Type> {
+        let offset = name.start.byte_offset;
+        let i = self.annotations.iter().position(|a| a.0 == offset)?;
+        let (_, span, text) = self.annotations.remove(i);
+        match parse_type(&text) {
+            Ok((ty, vars)) => {
+                let map = (0..vars).map(|v| (v, self.fresh_row())).collect();
+                Some(substitute(&ty, &map))
+            }
+            Err(message) => {
+                self.out.errors.push(Error::Annotation { span, message });
+                None
+            }
+        }
+    }
+
+    fn declared(&self, name: &Ident) -> Option<usize> {
+        self.decl_at.get(&name.span.start.byte_offset).copied()
+    }
+
+    fn units(&mut self, module: &Module, top: bool) {
+        for unit in &module.units {
+            match unit {
+                Unit::Constants(decls) => {
+                    for d in decls {
+                        self.declaration(&d.name, top);
+                    }
+                }
+                Unit::Variables(vars) => {
+                    for v in vars {
+                        self.declaration(v, top);
+                    }
+                }
+                Unit::Recursive(decls) => {
+                    for d in decls {
+                        let ty = self.fresh();
+                        let id = self.declared(&d.name);
+                        self.set_mono(id, ty);
+                    }
+                }
+                Unit::Definition(def) => {
+                    let ty = self.definition(def);
+                    if top {
+                        self.out.definitions.insert(def.name.name.clone(), ty);
+                    }
+                }
+                Unit::Assume(a) | Unit::Theorem(a) => {
+                    let t = self.expr(&a.expr);
+                    self.expect(a.expr.span, &Type::Bool, &t);
+                }
+                Unit::Instance(inst) => {
+                    for (_, e) in &inst.substitutions {
+                        self.expr(e);
+                    }
+                }
+                Unit::Module(m) => self.units(m, false),
+            }
+        }
+    }
+
+    // Constant or variable.
+    fn declaration(&mut self, name: &Ident, top: bool) {
+        let ty = match self.annotation(&name.span) {
+            Some(ty) => ty,
+            None => self.fresh(),
+        };
+        if top {
+            self.out.definitions.insert(name.name.clone(), ty.clone());
+        }
+        let id = self.declared(name);
+        self.set_mono(id, ty);
+    }
+
+    fn definition(&mut self, def: &Definition) -> Type {
+        let annotation = self.annotation(&def.name.span);
+        let id = self.declared(&def.name);
+        let mono = self.mono.len();
+        let ty = match &def.body {
+            DefBody::Expr(e) => {
+                let mut params = Vec::new();
+                for p in &def.params {
+                    let t = self.fresh();
+                    params.push(t.clone());
+                    let pid = self.declared(&p.name);
+                    self.set_mono(pid, t);
+                }
+                let body = self.expr(e);
+                if params.is_empty() {
+                    body
+                } else {
+                    Type::Oper(params, Box::new(body))
+                }
+            }
+            DefBody::Function(bounds, e) => {
+                let dom = self.bounds(bounds);
+                let dom = match dom.len() {
+                    1 => dom.into_iter().next().unwrap(),
+                    _ => Type::Tuple(dom),
+                };
+                let res = self.fresh();
+                let ty = Type::Fun(Box::new(dom), Box::new(res.clone()));
+                // Recursive functions refer to themselves.
+                self.set_mono(id, ty.clone());
+                let body = self.expr(e);
+                self.expect(e.span, &res, &body);
+                ty
+            }
+            DefBody::Instance(inst) => {
+                for (_, e) in &inst.substitutions {
+                    self.expr(e);
+                }
+                self.fresh()
+            }
+        };
+        self.mono.truncate(mono);
+        if let Some(ann) = annotation {
+            let ann = match ann {
+                Type::Oper(ref ps, ref r) if ps.is_empty() => (**r).clone(),
+                ann => ann,
+            };
+            self.expect(def.name.span, &ann, &ty);
+        }
+        match id.map(|id| (id, self.schemes.get(&id).cloned())) {
+            // Operators declared RECURSIVE and recursive functions already
+            // have a type, which is not generalized.
+            Some((_, Some(prev))) => {
+                if let DefBody::Expr(_) = def.body {
+                    self.expect(def.name.span, &prev.ty, &ty);
+                }
+            }
+            Some((id, None)) => {
+                let scheme = self.generalize(&ty);
+                self.schemes.insert(id, scheme);
+            }
+            None => {}
+        }
+        ty
+    }
+
+    // Declares bound variables, returns their types.
+    fn bounds(&mut self, bounds: &[Bound]) -> Vec<Type> {
+        let mut res = Vec::new();
+        for b in bounds {
+            let vars: Vec<Type> = b.vars.iter().map(|_| self.fresh()).collect();
+            if let Some(set) = &b.set {
+                let st = self.expr(set);
+                if b.tuple {
+                    let t = Type::Set(Box::new(Type::Tuple(vars.clone())));
+                    self.expect(set.span, &t, &st);
+                } else {
+                    for v in &vars {
+                        self.expect(set.span, &Type::Set(Box::new(v.clone())), &st);
+                    }
+                }
+            }
+            for (v, t) in b.vars.iter().zip(&vars) {
+                let id = self.declared(v);
+                self.set_mono(id, t.clone());
+            }
+            if b.tuple {
+                res.push(Type::Tuple(vars));
+            } else {
+                res.extend(vars);
+            }
+        }
+        res
+    }
+
+    fn signature(&mut self, name: &str) -> Option<Type> {
+        if !self.signatures.contains_key(name) {
+            let i = SIGNATURES.binary_search_by_key(&name, |s| s.0).ok()?;
+            let (ty, vars) = parse_type(SIGNATURES[i].1).ok()?;
+            self.signatures.insert(SIGNATURES[i].0, Scheme { vars: (0..vars).collect(), ty });
+        }
+        let s = self.signatures[name].clone();
+        Some(self.instantiate(&s))
+    }
+
+    // Type of the declaration referenced at `name`.
+    fn reference(&mut self, name: &Ident) -> Type {
+        let id = match self.ref_at.get(&name.span.start.byte_offset) {
+            Some(&id) => id,
+            None => return self.fresh(),
+        };
+        if let Some(s) = self.schemes.get(&id).cloned() {
+            return self.instantiate(&s);
+        }
+        let decl = &self.res.decls[id];
+        let builtin = decl.kind == DeclKind::Builtin || decl.span.is_none();
+        if builtin {
+            if let Some(t) = self.signature(&decl.name.clone()) {
+                return t;
+            }
+        }
+        self.fresh()
+    }
+
+    fn apply(&mut self, e: &Expr, op: Type, args: &[Expr]) -> Type {
+        if args.is_empty() {
+            return match self.prune(&op) {
+                Type::Oper(ref ps, ref r) if ps.is_empty() => (**r).clone(),
+                t => t,
+            };
+        }
+        let types: Vec<Type> = args.iter().map(|a| self.expr(a)).collect();
+        match self.prune(&op) {
+            Type::Oper(ps, r) if ps.len() == args.len() => {
+                for ((p, t), a) in ps.iter().zip(&types).zip(args) {
+                    self.expect(a.span, p, t);
+                }
+                *r
+            }
+            op => {
+                let r = self.fresh();
+                let expected = Type::Oper(types, Box::new(r.clone()));
+                self.expect(e.span, &op, &expected);
+                r
+            }
+        }
+    }
+
+    fn expr(&mut self, e: &Expr) -> Type {
+        use Type::*;
+        match &e.kind {
+            ExprKind::Num(_) => Int,
+            ExprKind::Str(_) => Str,
+            ExprKind::Apply { name, args, .. } => {
+                let op = self.reference(name);
+                self.apply(e, op, args)
+            }
+            // `A \X B \X C` is a single product of all the sets.
+            ExprKind::OpApply { op, args } if op.name == "\\X" => {
+                let mut ts = Vec::new();
+                for a in args {
+                    let t = self.expr(a);
+                    let elem = self.fresh();
+                    self.expect(a.span, &Set(Box::new(elem.clone())), &t);
+                    ts.push(elem);
+                }
+                Set(Box::new(Tuple(ts)))
+            }
+            ExprKind::OpApply { op, args } => {
+                let t = self.reference(op);
+                self.apply(e, t, args)
+            }
+            ExprKind::Junction { items, .. } => {
+                for i in items {
+                    let t = self.expr(i);
+                    self.expect(i.span, &Bool, &t);
+                }
+                Bool
+            }
+            ExprKind::FnApply(f, args) => {
+                let ft = self.expr(f);
+                let types: Vec<Type> = args.iter().map(|a| self.expr(a)).collect();
+                // Tuple components: `t[2]`.
+                if let (Tuple(ts), [arg]) = (self.prune(&ft), args.as_slice()) {
+                    if let ExprKind::Num(n) = &arg.kind {
+                        if let Some(t) = n.parse::<usize>().ok().and_then(|i| ts.get(i.wrapping_sub(1))) {
+                            return t.clone();
+                        }
+                    }
+                }
+                let arg = match types.len() {
+                    1 => types.into_iter().next().unwrap(),
+                    _ => Tuple(types),
+                };
+                let r = self.fresh();
+                let expected = Fun(Box::new(arg), Box::new(r.clone()));
+                self.expect(f.span, &expected, &ft);
+                r
+            }
+            ExprKind::Field(rec, field) => {
+                let rt = self.expr(rec);
+                let r = self.fresh();
+                let mut fields = BTreeMap::new();
+                fields.insert(field.name.clone(), r.clone());
+                let row = self.fresh_row();
+                self.expect(field.span, &Record(fields, Some(row)), &rt);
+                r
+            }
+            ExprKind::Quant { bounds, body, .. } => {
+                let mono = self.mono.len();
+                self.bounds(bounds);
+                let t = self.expr(body);
+                self.expect(body.span, &Bool, &t);
+                self.mono.truncate(mono);
+                Bool
+            }
+            ExprKind::Choose { bound, body } => {
+                let mono = self.mono.len();
+                let vars = self.bounds(std::slice::from_ref(bound));
+                let t = self.expr(body);
+                self.expect(body.span, &Bool, &t);
+                self.mono.truncate(mono);
+                vars.into_iter().next().unwrap_or_else(|| self.fresh())
+            }
+            ExprKind::SetEnum(items) => {
+                let elem = self.fresh();
+                for i in items {
+                    let t = self.expr(i);
+                    self.expect(i.span, &elem, &t);
+                }
+                Set(Box::new(elem))
+            }
+            ExprKind::SetFilter { bound, pred } => {
+                let mono = self.mono.len();
+                let vars = self.bounds(std::slice::from_ref(bound));
+                let t = self.expr(pred);
+                self.expect(pred.span, &Bool, &t);
+                self.mono.truncate(mono);
+                let elem = vars.into_iter().next().unwrap_or_else(|| self.fresh());
+                Set(Box::new(elem))
+            }
+            ExprKind::SetMap { expr, bounds } => {
+                let mono = self.mono.len();
+                self.bounds(bounds);
+                let t = self.expr(expr);
+                self.mono.truncate(mono);
+                Set(Box::new(t))
+            }
+            ExprKind::FnCons { bounds, body } => {
+                let mono = self.mono.len();
+                let dom = self.bounds(bounds);
+                let t = self.expr(body);
+                self.mono.truncate(mono);
+                let dom = match dom.len() {
+                    1 => dom.into_iter().next().unwrap(),
+                    _ => Tuple(dom),
+                };
+                Fun(Box::new(dom), Box::new(t))
+            }
+            ExprKind::FnSet(a, b) => {
+                let (x, y) = (self.fresh(), self.fresh());
+                let ta = self.expr(a);
+                self.expect(a.span, &Set(Box::new(x.clone())), &ta);
+                let tb = self.expr(b);
+                self.expect(b.span, &Set(Box::new(y.clone())), &tb);
+                Set(Box::new(Fun(Box::new(x), Box::new(y))))
+            }
+            ExprKind::Record(fields) => {
+                let fields = fields.iter().map(|(k, v)| (k.name.clone(), self.expr(v))).collect();
+                Record(fields, None)
+            }
+            ExprKind::RecordSet(fields) => {
+                let mut types = BTreeMap::new();
+                for (k, v) in fields {
+                    let t = self.fresh();
+                    let vt = self.expr(v);
+                    self.expect(v.span, &Set(Box::new(t.clone())), &vt);
+                    types.insert(k.name.clone(), t);
+                }
+                Set(Box::new(Record(types, None)))
+            }
+            ExprKind::Except { base, updates } => {
+                let bt = self.expr(base);
+                for u in updates {
+                    let mut target = bt.clone();
+                    for key in &u.path {
+                        let r = self.fresh();
+                        match key {
+                            ExceptKey::Field(f) => {
+                                let mut fields = BTreeMap::new();
+                                fields.insert(f.name.clone(), r.clone());
+                                let row = self.fresh_row();
+                                self.expect(f.span, &Record(fields, Some(row)), &target);
+                            }
+                            ExceptKey::Index(ix) => {
+                                let types: Vec<Type> = ix.iter().map(|i| self.expr(i)).collect();
+                                let arg = match types.len() {
+                                    1 => types.into_iter().next().unwrap(),
+                                    _ => Tuple(types),
+                                };
+                                let f = Fun(Box::new(arg), Box::new(r.clone()));
+                                self.expect(base.span, &f, &target);
+                            }
+                        }
+                        target = r;
+                    }
+                    self.at.push(target.clone());
+                    let vt = self.expr(&u.value);
+                    self.at.pop();
+                    self.expect(u.value.span, &target, &vt);
+                }
+                bt
+            }
+            ExprKind::At => match self.at.last() {
+                Some(t) => t.clone(),
+                None => self.fresh(),
+            },
+            ExprKind::Tuple(items) if items.is_empty() => Seq(Box::new(self.fresh())),
+            ExprKind::Tuple(items) => Tuple(items.iter().map(|i| self.expr(i)).collect()),
+            ExprKind::If { cond, then, other } => {
+                let c = self.expr(cond);
+                self.expect(cond.span, &Bool, &c);
+                let t = self.expr(then);
+                let o = self.expr(other);
+                self.expect(other.span, &t, &o);
+                t
+            }
+            ExprKind::Case { arms, other } => {
+                let res = self.fresh();
+                for (guard, arm) in arms {
+                    let g = self.expr(guard);
+                    self.expect(guard.span, &Bool, &g);
+                    let t = self.expr(arm);
+                    self.expect(arm.span, &res, &t);
+                }
+                if let Some(o) = other {
+                    let t = self.expr(o);
+                    self.expect(o.span, &res, &t);
+                }
+                res
+            }
+            ExprKind::Let { defs, body } => {
+                for def in defs {
+                    self.definition(def);
+                }
+                self.expr(body)
+            }
+            ExprKind::Lambda { params, body } => {
+                let mono = self.mono.len();
+                let mut types = Vec::new();
+                for p in params {
+                    let t = self.fresh();
+                    types.push(t.clone());
+                    let id = self.declared(p);
+                    self.set_mono(id, t);
+                }
+                let t = self.expr(body);
+                self.mono.truncate(mono);
+                Oper(types, Box::new(t))
+            }
+            ExprKind::BoxAction { action, sub } | ExprKind::AngleAction { action, sub } => {
+                let t = self.expr(action);
+                self.expect(action.span, &Bool, &t);
+                self.expr(sub);
+                Bool
+            }
+            ExprKind::Fairness { sub, action, .. } => {
+                self.expr(sub);
+                let t = self.expr(action);
+                self.expect(action.span, &Bool, &t);
+                Bool
+            }
+        }
+    }
+}
+
+
+fn free_vars(t: &Type, vars: &mut BTreeSet<usize>) {
+    match t {
+        Type::Var(v) => {
+            vars.insert(*v);
+        }
+        Type::Set(t) | Type::Seq(t) => free_vars(t, vars),
+        Type::Fun(a, b) => {
+            free_vars(a, vars);
+            free_vars(b, vars);
+        }
+        Type::Tuple(ts) => ts.iter().for_each(|t| free_vars(t, vars)),
+        Type::Record(fields, row) => {
+            fields.values().for_each(|t| free_vars(t, vars));
+            vars.extend(row);
+        }
+        Type::Oper(ps, r) => {
+            ps.iter().for_each(|t| free_vars(t, vars));
+            free_vars(r, vars);
+        }
+        Type::Int | Type::Bool | Type::Str | Type::Const(_) => {}
+    }
+}
+
+// Renames type variables.
+fn substitute(t: &Type, map: &HashMap<usize, usize>) -> Type {
+    let rename = |v: &usize| *map.get(v).unwrap_or(v);
+    match t {
+        Type::Var(v) => Type::Var(rename(v)),
+        Type::Set(t) => Type::Set(Box::new(substitute(t, map))),
+        Type::Seq(t) => Type::Seq(Box::new(substitute(t, map))),
+        Type::Fun(a, b) => Type::Fun(Box::new(substitute(a, map)), Box::new(substitute(b, map))),
+        Type::Tuple(ts) => Type::Tuple(ts.iter().map(|t| substitute(t, map)).collect()),
+        Type::Record(fields, row) => Type::Record(
+            fields.iter().map(|(k, v)| (k.clone(), substitute(v, map))).collect(),
+            row.as_ref().map(rename),
+        ),
+        Type::Oper(ps, r) => Type::Oper(
+            ps.iter().map(|t| substitute(t, map)).collect(),
+            Box::new(substitute(r, map)),
+        ),
+        t => t.clone(),
+    }
+}
+
+// Renames type variables to `a`, `b`, ... in the order of appearance.
+fn normalize_vars(t: &Type) -> Type {
+    fn order(t: &Type, res: &mut Vec<usize>) {
+        let mut push = |v: usize| {
+            if !res.contains(&v) {
+                res.push(v)
+            }
+        };
+        match t {
+            Type::Var(v) => push(*v),
+            Type::Set(t) | Type::Seq(t) => order(t, res),
+            Type::Fun(a, b) => {
+                order(a, res);
+                order(b, res);
+            }
+            Type::Tuple(ts) => ts.iter().for_each(|t| order(t, res)),
+            Type::Record(fields, row) => {
+                fields.values().for_each(|t| order(t, res));
+                if let Some(r) = row {
+                    if !res.contains(r) {
+                        res.push(*r);
+                    }
+                }
+            }
+            Type::Oper(ps, r) => {
+                ps.iter().for_each(|t| order(t, res));
+                order(r, res);
+            }
+            _ => {}
+        }
+    }
+    let mut vars = Vec::new();
+    order(t, &mut vars);
+    let map = vars.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+    substitute(t, &map)
+}
+
+
+/// Parses type in the Apalache notation. Returns the type and the number
+/// of type variables, which are numbered from zero.
+pub fn parse_type(s: &str) -> Result<(Type, usize), String> {
+    let mut p = TypeParser { tokens: type_tokens(s)?, pos: 0, vars: Vec::new() };
+    let t = p.ty()?;
+    match p.tokens.get(p.pos) {
+        None => Ok((t, p.vars.len())),
+        Some(tok) => Err(format!("unexpected `{}`", tok)),
+    }
+}
+
+fn type_tokens(s: &str) -> Result<Vec<String>, String> {
+    let mut res = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut id = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                id.push(c);
+                chars.next();
+            }
+            res.push(id);
+        } else {
+            chars.next();
+            let two = chars.peek().map(|&d| format!("{}{}", c, d));
+            match two.as_deref() {
+                Some("->") | Some("=>") | Some("<<") | Some(">>") => {
+                    chars.next();
+                    res.push(two.unwrap());
+                }
+                _ if "()[]{},:".contains(c) => res.push(c.to_string()),
+                _ => return Err(format!("unexpected `{}`", c)),
+            }
+        }
+    }
+    Ok(res)
+}
+
+struct TypeParser {
+    tokens: Vec<String>,
+    pos: usize,
+    vars: Vec<String>,
+}
+
+impl TypeParser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|s| s.as_str())
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        let t = self.tokens.get(self.pos).cloned().ok_or("unexpected end of type")?;
+        self.pos += 1;
+        Ok(t)
+    }
+
+    fn expect(&mut self, tok: &str) -> Result<(), String> {
+        match self.next()? {
+            ref t if t == tok => Ok(()),
+            t => Err(format!("expected `{}`, found `{}`", tok, t)),
+        }
+    }
+
+    // type := '(' types ')' '=>' type | atom ['->' type]
+    fn ty(&mut self) -> Result<Type, String> {
+        if self.peek() == Some("(") {
+            self.next()?;
+            let ts = self.list(")")?;
+            if self.peek() == Some("=>") {
+                self.next()?;
+                return Ok(Type::Oper(ts, Box::new(self.ty()?)));
+            }
+            let t = match ts.len() {
+                1 => ts.into_iter().next().unwrap(),
+                _ => return Err("expected `=>` after operator parameters".to_string()),
+            };
+            return self.arrow(t);
+        }
+        let t = self.atom()?;
+        self.arrow(t)
+    }
+
+    fn arrow(&mut self, t: Type) -> Result<Type, String> {
+        if self.peek() == Some("->") {
+            self.next()?;
+            return Ok(Type::Fun(Box::new(t), Box::new(self.ty()?)));
+        }
+        Ok(t)
+    }
+
+    fn list(&mut self, close: &str) -> Result<Vec<Type>, String> {
+        let mut res = Vec::new();
+        if self.peek() == Some(close) {
+            self.next()?;
+            return Ok(res);
+        }
+        loop {
+            res.push(self.ty()?);
+            match self.next()? {
+                ref t if t == close => return Ok(res),
+                ref t if t == "," => {}
+                t => return Err(format!("expected `{}`, found `{}`", close, t)),
+            }
+        }
+    }
+
+    fn atom(&mut self) -> Result<Type, String> {
+        let tok = self.next()?;
+        match tok.as_str() {
+            "Int" | "Nat" => Ok(Type::Int),
+            "Bool" => Ok(Type::Bool),
+            "Str" => Ok(Type::Str),
+            "Set" | "Seq" => {
+                self.expect("(")?;
+                let t = Box::new(self.ty()?);
+                self.expect(")")?;
+                Ok(if tok == "Set" { Type::Set(t) } else { Type::Seq(t) })
+            }
+            "<<" => Ok(Type::Tuple(self.list(">>")?)),
+            "[" | "{" => {
+                let close = if tok == "[" { "]" } else { "}" };
+                let mut fields = BTreeMap::new();
+                while self.peek() != Some(close) {
+                    let name = self.next()?;
+                    self.expect(":")?;
+                    fields.insert(name, self.ty()?);
+                    if self.peek() == Some(",") {
+                        self.next()?;
+                    }
+                }
+                self.next()?;
+                Ok(Type::Record(fields, None))
+            }
+            _ if tok.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_') => {
+                Ok(Type::Const(tok))
+            }
+            _ if tok.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()) => {
+                let v = match self.vars.iter().position(|v| *v == tok) {
+                    Some(v) => v,
+                    None => {
+                        self.vars.push(tok);
+                        self.vars.len() - 1
+                    }
+                };
+                Ok(Type::Var(v))
+            }
+            _ => Err(format!("unexpected `{}`", tok)),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::resolve::resolve;
+
+    fn infer_code(body: &str) -> Types {
+        let code = format!("---- MODULE M ----\nEXTENDS Naturals, Sequences, FiniteSets, Reals\n{}\n====", body);
+        let m = parse(&code).unwrap();
+        let res = resolve(&m);
+        assert_eq!(res.errors, vec![]);
+        infer(&m, &res, &code)
+    }
+
+    fn types(body: &str) -> Vec<String> {
+        let t = infer_code(body);
+        assert_eq!(t.errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(), Vec::<String>::new());
+        let mut defs: Vec<String> = t.definitions
+            .iter()
+            .map(|(k, v)| format!("{}: {}", k, normalize_vars(v)))
+            .collect();
+        defs.sort();
+        defs
+    }
+
+    fn errors(body: &str) -> Vec<String> {
+        infer_code(body).errors.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn signatures() {
+        assert!(SIGNATURES.is_sorted_by_key(|s| s.0));
+        for (name, sig) in SIGNATURES {
+            assert!(parse_type(sig).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn annotations() {
+        assert_eq!(parse_type("(Set(a), <<Int, PROC>>) => a -> Seq(Str)").unwrap().0.to_string(),
+            "(Set(a), <<Int, PROC>>) => a -> Seq(Str)");
+        assert_eq!(parse_type("[name: Str, age: Int]").unwrap().0.to_string(), "[age: Int, name: Str]");
+        assert!(parse_type("Set(Int").is_err());
+    }
+
+    #[test]
+    fn inference() {
+        assert_eq!(types("
+            VARIABLE
+              \\* @type: Seq(PROC);
+              queue,
+              x
+            CONSTANT N
+            Inc(a) == a + 1
+            Id(a) == a
+            Init == x = {Inc(1)} /\\ queue = <<>> /\\ N \\in Nat
+            Pairs == {<<i, Id(\"a\")>> : i \\in 1..N}
+            People == [name : STRING, age : Nat]
+            Ages(p) == p.age
+            f[n \\in Nat] == IF n = 0 THEN 1 ELSE n * f[n - 1]
+            Head2 == Head(queue)
+            Upd == [[a |-> 1, b |-> TRUE] EXCEPT !.a = @ + 1]
+            Msgs == [type : {\"a\"}, x : Nat] \\cup [type : {\"b\"}, x : {0}]
+            Half == 3 / 2
+            \\* @type: Int;
+
+            y == 1
+            Seq3 == <<1, 2, 3>>
+            Third(i) == Seq3[i]
+            Dom == DOMAIN Seq3
+            Same == [i \\in 1..2 |-> i] = <<1, 2>>
+            Triples == Nat \\X BOOLEAN \\X STRING
+        "), vec![
+            "Ages: ([age: a, ...]) => a",
+            "Dom: Set(Int)",
+            "Half: a",
+            "Head2: PROC",
+            "Id: (a) => a",
+            "Inc: (Int) => Int",
+            "Init: Bool",
+            "Msgs: Set([type: Str, x: Int])",
+            "N: Int",
+            "Pairs: Set(<<Int, Str>>)",
+            "People: Set([age: Int, name: Str])",
+            "Same: Bool",
+            "Seq3: <<Int, Int, Int>>",
+            "Third: (Int) => Int",
+            "Triples: Set(<<Int, Bool, Str>>)",
+            "Upd: [a: Int, b: Bool]",
+            "f: Int -> Int",
+            "queue: Seq(PROC)",
+            "x: Set(Int)",
+            "y: Int",
+        ]);
+    }
+
+    #[test]
+    fn mismatches() {
+        assert_eq!(errors("A == {1} + 2"), vec!["3:6: expected Int, found Set(Int)"]);
+        assert_eq!(errors("R == [a |-> 1]\nB == R.b"), vec!["4:8: record [a: Int] has no field `b`"]);
+        assert_eq!(errors("B == IF 1 THEN 2 ELSE \"s\""), vec![
+            "3:9: expected Bool, found Int",
+            "3:23: expected Int, found Str",
+        ]);
+        assert_eq!(errors("\\* @type: Int;\nCONSTANT N\nA == N \\cup {}"), vec![
+            "5:6: expected Set(a), found Int",
+        ]);
+        assert_eq!(errors("\\* @type: (Int) => Bool;\nF(x) == x"), vec![
+            "4:1: expected (Int) => Bool, found (Int) => Int",
+        ]);
+        assert_eq!(errors("R == [a |-> 1] = [b |-> 2]"), vec!["3:18: expected [a: Int], found [b: Int]"]);
+        assert_eq!(errors("\\* @type: Str;\nA == 1\nB == 2"), vec!["4:1: expected Str, found Int"]);
+        assert_eq!(errors("\\* @type: Str;\nA == 1 B == 2"), vec!["4:1: expected Str, found Int"]);
+        assert_eq!(errors("\\* @type: Set(;\nCONSTANT N"), vec![
+            "3:1: bad type annotation: unexpected end of type",
+        ]);
+    }
+}
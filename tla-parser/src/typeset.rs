@@ -0,0 +1,375 @@
+use std::ops::Range;
+
+use crate::cst::{Cst, Token};
+use crate::fmt::{symbol, Layout};
+use crate::lexer::{Keyword, TokenType};
+use crate::parser::normalize;
+
+
+// LaTeX forms of the operators, as in tla2tex. Operators that are not
+// listed are typeset as is. Operators are in normalized form.
+// This table must be sorted.
+pub static TEX: &[(&str, &str)] = &[
+    ("#", "\\#"),
+    ("$", "\\$"),
+    ("$$", "\\$\\$"),
+    ("%", "\\%"),
+    ("%%", "\\%\\%"),
+    ("&", "\\&"),
+    ("&&", "\\&\\&"),
+    ("-+->", "\\stackrel{+}{\\rightarrow}"),
+    ("-.", "-"),
+    ("->", "\\rightarrow"),
+    ("-|", "\\dashv"),
+    ("..", "\\mathrel{.\\,.}"),
+    ("...", "\\dots"),
+    ("/=", "\\neq"),
+    ("/\\", "\\land"),
+    ("::=", "::="),
+    (":=", "\\mathrel{:=}"),
+    (":>", "\\mathrel{:>}"),
+    ("<-", "\\leftarrow"),
+    ("<<", "\\langle"),
+    ("<=", "\\leq"),
+    ("<=>", "\\equiv"),
+    ("<>", "\\Diamond"),
+    ("==", "\\triangleq"),
+    ("=>", "\\implies"),
+    ("=|", "\\models"),
+    (">=", "\\geq"),
+    (">>", "\\rangle"),
+    ("@@", "\\mathbin{@@}"),
+    ("[]", "\\Box"),
+    ("\\", "\\setminus"),
+    ("\\/", "\\lor"),
+    ("\\A", "\\forall"),
+    ("\\AA", "\\mathbf{\\forall}"),
+    ("\\E", "\\exists"),
+    ("\\EE", "\\mathbf{\\exists}"),
+    ("\\X", "\\times"),
+    ("\\o", "\\circ"),
+    ("^", "\\hat{\\ }"),
+    ("^#", "^{\\#}"),
+    ("^*", "^{*}"),
+    ("^+", "^{+}"),
+    ("{", "\\{"),
+    ("|", "\\mid"),
+    ("|-", "\\vdash"),
+    ("|->", "\\mapsto"),
+    ("|=", "\\models"),
+    ("}", "\\}"),
+    ("~", "\\lnot"),
+    ("~>", "\\leadsto"),
+];
+
+fn tex_symbol(op: &str) -> &str {
+    let op = normalize(op);
+    match TEX.binary_search_by_key(&op, |t| t.0) {
+        Ok(i) => TEX[i].1,
+        // Backslash operators like `\in` and `\cup` have the same name.
+        Err(_) => op,
+    }
+}
+
+/// Escapes text for LaTeX text mode.
+fn tex_text(s: &str) -> String {
+    let mut res = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' => res.push_str("\\textbackslash{}"),
+            '^' => res.push_str("\\textasciicircum{}"),
+            '~' => res.push_str("\\textasciitilde{}"),
+            '{' | '}' | '$' | '&' | '%' | '#' | '_' => {
+                res.push('\\');
+                res.push(c);
+            }
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+/// Text of a comment without the comment markers.
+fn comment_text(text: &str) -> String {
+    let text = match text.strip_prefix("\\*") {
+        Some(rest) => rest,
+        None => text.trim_start_matches("(*").trim_end_matches("*)"),
+    };
+    // Lines of stars are decoration.
+    text.lines()
+        .map(|l| l.trim().trim_matches('*').trim())
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_word(token: &Token) -> bool {
+    token.text.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+
+/// Renders the module to LaTeX, in the style of tla2tex. Lines aligned to
+/// a token of a previous line are indented by the width of the text before
+/// that token. The result needs the `amsmath`, `amssymb` and `calc`
+/// packages; with `standalone` it is a complete document.
+pub fn latex(source: &str, standalone: bool) -> String {
+    let cst = Cst::new(source);
+    let mut out = String::new();
+    if standalone {
+        out.push_str("\\documentclass{article}\n\\usepackage{amsmath,amssymb,calc}\n\\begin{document}\n");
+    }
+    if let Some(range) = cst.module_range() {
+        let lines: Vec<_> = cst.lines().into_iter().filter(|l| range.contains(&l.start)).collect();
+        let mut tex = Tex { cst: &cst, lines: Vec::new() };
+        for line in lines {
+            let line = line.start..line.end.min(range.end);
+            let first = &cst.tokens[line.start];
+            if first.line_breaks() > 1 {
+                out.push_str("\\medskip\n");
+            }
+            out.push_str(&tex.line(line));
+            out.push('\n');
+        }
+    }
+    if standalone {
+        out.push_str("\\end{document}\n");
+    }
+    out
+}
+
+struct Tex<'a> {
+    cst: &'a Cst<'a>,
+    // Tokens and their rendering for the lines typeset so far,
+    // with the indentation of each line.
+    lines: Vec<(Range<usize>, String, Vec<String>)>,
+}
+
+impl<'a> Tex<'a> {
+    fn line(&mut self, line: Range<usize>) -> String {
+        let tokens = &self.cst.tokens;
+        let kinds: Vec<_> = line.clone().map(|i| tokens[i].kind).collect();
+        match kinds[..] {
+            [TokenType::Separator] => return "\\par\\noindent\\rule{\\linewidth}{0.4pt}\\par".to_string(),
+            [TokenType::ModuleEnd] => return "\\par\\noindent\\rule{\\linewidth}{1.2pt}\\par".to_string(),
+            [TokenType::Separator, TokenType::Keyword(Keyword::Module), TokenType::Identifier, TokenType::Separator] => {
+                return format!(
+                    "\\par\\noindent\\hrulefill\\ \\textsc{{module}} ${}$\\ \\hrulefill\\par",
+                    ident(tokens[line.start + 2].text));
+            }
+            [TokenType::Comment] => {
+                return format!("\\par\\noindent{{\\small {}}}\\par", tex_text(&comment_text(tokens[line.start].text)));
+            }
+            _ => {}
+        }
+        let indent = self.indentation(tokens[line.start].start.col);
+        // Rendering of each token, with a space before it if there was one.
+        let mut parts = Vec::new();
+        let mut i = line.start;
+        while i < line.end {
+            let t = &tokens[i];
+            let gap = i > line.start && t.start.col > tokens[i - 1].end.col;
+            // `WF_vars` and `]_vars`
+            let sub = line.contains(&(i + 1))
+                && tokens[i + 1].start.byte_offset == t.end.byte_offset
+                && matches!(t.kind, TokenType::Keyword(Keyword::WeakFairness)
+                    | TokenType::Keyword(Keyword::StrongFairness)
+                    | TokenType::Wildcard);
+            let part = match t.kind {
+                _ if sub => {
+                    let base = match t.kind {
+                        TokenType::Wildcard => "{}".to_string(),
+                        _ => format!("\\mathrm{{{}}}", &t.text[..2]),
+                    };
+                    format!("${}_{{{}}}$", base, math(&tokens[i + 1]))
+                }
+                TokenType::Comment => format!("\\quad{{\\small {}}}", tex_text(&comment_text(t.text))),
+                TokenType::String => format!("\\textsf{{``{}''}}", tex_text(&t.text[1..t.text.len() - 1])),
+                TokenType::Unknown => format!("\\texttt{{{}}}", tex_text(t.text)),
+                _ => format!("${}$", math(t)),
+            };
+            parts.push(if gap { format!("\\ {}", part) } else { part });
+            if sub {
+                // The subscript is rendered with the previous token.
+                parts.push(String::new());
+                i += 1;
+            }
+            i += 1;
+        }
+        let res = format!("\\par\\noindent{}{}\\par", indent, parts.concat());
+        self.lines.push((line, indent, parts));
+        res
+    }
+
+    /// Horizontal space for a line that starts at `col`: the width of the
+    /// text before the token it is aligned to.
+    fn indentation(&self, col: usize) -> String {
+        let tokens = &self.cst.tokens;
+        for (line, indent, parts) in self.lines.iter().rev() {
+            if tokens[line.start].start.col > col {
+                continue;
+            }
+            // Keep the offset from the closest token to the left, counting
+            // from its end if the line starts after it.
+            let k = line.clone().rev().find(|&i| tokens[i].start.col <= col).unwrap() - line.start;
+            let t = &tokens[line.start + k];
+            let mut prefix = parts[..k].concat();
+            let spaces = if t.end.line == t.start.line && t.end.col <= col {
+                prefix.push_str(&parts[k]);
+                col - t.end.col
+            } else {
+                if k > 0 && parts[k].starts_with("\\ ") {
+                    prefix.push_str("\\ ");
+                }
+                col - t.start.col
+            };
+            let mut res = indent.clone();
+            if !prefix.is_empty() {
+                res = format!("\\hspace*{{\\widthof{{{}{}}}}}", indent, prefix);
+            }
+            if spaces > 0 {
+                res.push_str(&format!("\\hspace*{{{}em}}", spaces as f32 / 2.0));
+            }
+            return res;
+        }
+        match col {
+            1 => String::new(),
+            _ => format!("\\hspace*{{{}em}}", (col - 1) as f32 / 2.0),
+        }
+    }
+}
+
+fn ident(name: &str) -> String {
+    format!("\\mathit{{{}}}", name.replace('_', "\\_"))
+}
+
+/// Math mode form of a token.
+fn math(t: &Token) -> String {
+    match t.kind {
+        TokenType::Identifier => ident(t.text),
+        TokenType::Number => t.text.to_string(),
+        TokenType::Wildcard => "\\_".to_string(),
+        TokenType::String | TokenType::Comment | TokenType::Unknown => format!("\\mbox{{{}}}", tex_text(t.text)),
+        _ if is_word(t) => format!("\\textsc{{{}}}", t.text.to_lowercase()),
+        _ => tex_symbol(t.text).to_string(),
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symbols {
+    Unicode,
+    MathMl,
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+const STYLE: &str = "\
+.tla { font-family: monospace; line-height: 1.4; }
+.tla .kw { font-variant: small-caps; font-weight: bold; }
+.tla .str { color: #a31515; }
+.tla .comment { font-style: italic; white-space: pre-wrap; }
+.tla math { display: inline-block; width: 1ch; text-align: center; }
+";
+
+/// Renders the module as a standalone HTML page. Operators are shown with
+/// Unicode symbols or MathML, and the text is laid out in a monospace font
+/// so that bullets stay aligned.
+pub fn html(source: &str, symbols: Symbols) -> String {
+    let cst = Cst::new(source);
+    let mut layout = Layout::new(&cst);
+    let range = cst.module_range();
+    let title = range.as_ref().map_or("", |r| cst.tokens[r.start + 2].text);
+    for i in range.clone().unwrap_or(0..0) {
+        if let Some(s) = symbol(&cst.tokens[i], true) {
+            layout.replace(i, s);
+        }
+    }
+    let mut body = String::new();
+    layout.render_with(&mut body, &mut |i, text, out| {
+        let t = &cst.tokens[i];
+        match t.kind {
+            TokenType::Separator | TokenType::ModuleEnd => out.push_str(&html_escape(text)),
+            TokenType::Comment => {
+                // The markers are blanked rather than removed, so that the
+                // tokens after the comment stay in their columns.
+                let blank = |n: usize| " ".repeat(n);
+                let (open, close) = match text.strip_prefix("(*").and_then(|t| t.strip_suffix("*)")) {
+                    Some(inner) => (2, text.len() - 2 - inner.len()),
+                    None => (2, 0),
+                };
+                let inner = &text[open..text.len() - close];
+                let trimmed = inner.trim();
+                if trimmed.is_empty() {
+                    out.push_str(&blank(open + inner.chars().count() + close));
+                } else {
+                    let before = inner.len() - inner.trim_start().len();
+                    let after = inner.len() - inner.trim_end().len();
+                    out.push_str(&blank(open + before));
+                    out.push_str(&format!("<span class=\"comment\">{}</span>", html_escape(trimmed)));
+                    out.push_str(&blank(after + close));
+                }
+            }
+            TokenType::String => out.push_str(&format!("<span class=\"str\">{}</span>", html_escape(text))),
+            TokenType::Identifier | TokenType::Number | TokenType::Unknown => out.push_str(&html_escape(text)),
+            _ if is_word(t) => out.push_str(&format!("<span class=\"kw\">{}</span>", text)),
+            _ if symbols == Symbols::MathMl && text != t.text => {
+                // Symbols are one character wide, as in the layout.
+                out.push_str(&format!("<math><mo>{}</mo></math>", html_escape(text)));
+            }
+            _ => out.push_str(&html_escape(text)),
+        }
+    });
+    format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             <style>\n{}</style>\n</head>\n<body>\n<pre class=\"tla\">\n{}\n</pre>\n</body>\n</html>\n",
+            html_escape(title), STYLE, body)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "\
+---- MODULE Counter ----
+EXTENDS Naturals
+VARIABLE x
+\\* The counter starts at zero.
+Init == x = 0
+Next == /\\ x < 10
+        /\\ x' = x + 1
+Spec == Init /\\ [][Next]_x /\\ WF_x(Next)
+====";
+
+    #[test]
+    fn tables_are_sorted() {
+        assert!(TEX.is_sorted_by_key(|t| t.0));
+    }
+
+    #[test]
+    fn latex_alignment() {
+        let out = latex(CODE, false);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "\\par\\noindent\\hrulefill\\ \\textsc{module} $\\mathit{Counter}$\\ \\hrulefill\\par");
+        assert_eq!(lines[3], "\\par\\noindent{\\small The counter starts at zero.}\\par");
+        assert_eq!(lines[5], "\\par\\noindent$\\mathit{Next}$\\ $\\triangleq$\\ $\\land$\\ $\\mathit{x}$\\ $<$\\ $10$\\par");
+        assert_eq!(lines[6], "\\par\\noindent\\hspace*{\\widthof{$\\mathit{Next}$\\ $\\triangleq$\\ }}\
+            $\\land$\\ $\\mathit{x}$$'$\\ $=$\\ $\\mathit{x}$\\ $+$\\ $1$\\par");
+        assert!(lines[7].contains("$[$$\\mathit{Next}$$]$${}_{\\mathit{x}}$"), "{}", lines[7]);
+        assert!(lines[7].contains("$\\mathrm{WF}_{\\mathit{x}}$$($"), "{}", lines[7]);
+        assert!(latex(CODE, true).starts_with("\\documentclass"));
+    }
+
+    #[test]
+    fn html_symbols() {
+        let out = html(CODE, Symbols::Unicode);
+        assert!(out.contains("<title>Counter</title>"));
+        assert!(out.contains("Next ≜ ∧ x &lt; 10\n       ∧ x' = x + 1\n"), "{}", out);
+        assert!(out.contains("\n   <span class=\"comment\">The counter starts at zero.</span>\n"));
+        let out = html("---- MODULE M ----\nA == (* one *) 1 +\n            2\n====", Symbols::Unicode);
+        assert!(out.contains("A ≜    <span class=\"comment\">one</span>    1 +\n           2"), "{}", out);
+        let out = html(CODE, Symbols::MathMl);
+        assert!(out.contains("Next <math><mo>≜</mo></math> <math><mo>∧</mo></math> x"), "{}", out);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::cst::Cst;
+use crate::fmt::{symbol, Layout};
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Form {
+    /// `/\`, `\in`, `<<`
+    Ascii,
+    /// `∧`, `∈`, `⟨`
+    Unicode,
+}
+
+/// Rewrites operators of the module in the given form. Synonyms such as
+/// `\land` are converted to the canonical symbol. Strings, comments and
+/// text outside of the module are left as is. Lines aligned to a token are
+/// moved along with it, so that bullet lists keep their meaning.
+pub fn convert(source: &str, to: Form) -> String {
+    let cst = Cst::new(source);
+    let range = match cst.module_range() {
+        Some(range) => range,
+        None => return source.to_string(),
+    };
+    let mut layout = Layout::new(&cst);
+    for i in range {
+        if let Some(s) = symbol(&cst.tokens[i], to == Form::Unicode) {
+            layout.replace(i, s);
+        }
+    }
+    layout.render()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fmt::tests::structure;
+    use crate::parser::parse;
+
+    const ASCII: &str = "\
+---- MODULE M ----
+EXTENDS Naturals
+Next == /\\ \\E x \\in {1, 2} : x /= 0   \\* x /= 0
+        /\\ \\/ <<1>> = <<1>>
+           \\/ \"/\\\\\" = \"<<\"
+(* [] <> *)
+Prop == []<>(1 >= 0)
+====
+text /\\ outside";
+
+    const UNICODE: &str = "\
+---- MODULE M ----
+EXTENDS Naturals
+Next ≜ ∧ ∃ x ∈ {1, 2} : x ≠ 0   \\* x /= 0
+       ∧ ∨ ⟨1⟩ = ⟨1⟩
+         ∨ \"/\\\\\" = \"<<\"
+(* [] <> *)
+Prop ≜ □◇(1 ≥ 0)
+====
+text /\\ outside";
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(convert(ASCII, Form::Unicode), UNICODE);
+        assert_eq!(convert(UNICODE, Form::Ascii), ASCII);
+        assert_eq!(convert(UNICODE, Form::Unicode), UNICODE);
+        assert!(parse(UNICODE).is_ok());
+    }
+
+    #[test]
+    fn subscripts() {
+        let code = "---- MODULE M ----\nVARIABLE v\nA == v' = v\n\
+                    Spec == []<><<A>>_v /\\ [][A]_v /\\ WF_v(A) /\\ SF_<<v>>(A)\n====";
+        let unicode = convert(code, Form::Unicode);
+        assert!(unicode.contains("□◇⟨A⟩_v ∧ □[A]_v ∧ WF_v(A) ∧ SF_⟨v⟩(A)"), "{}", unicode);
+        assert_eq!(structure(&unicode), structure(code));
+    }
+
+    #[test]
+    fn synonyms_and_spacing() {
+        let code = "---- MODULE M ----\nA == x \\land y\n====";
+        assert_eq!(convert(code, Form::Unicode), "---- MODULE M ----\nA ≜ x ∧ y\n====");
+        let code = "---- MODULE M ----\nB ≜ ∀A∈S : A\n====";
+        assert_eq!(convert(code, Form::Ascii), "---- MODULE M ----\nB == \\A A \\in S : A\n====");
+    }
+}
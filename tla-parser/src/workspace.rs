@@ -0,0 +1,315 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::{fs, io};
+
+use crate::ast::*;
+use crate::parser::{self, parse};
+use crate::resolve::{self, resolve_with, Modules, Resolution};
+
+
+struct Entry {
+    source: String,
+    module: Result<Module, parser::Error>,
+    // Modules referenced by EXTENDS and INSTANCE.
+    dependencies: BTreeSet<String>,
+    // `None` if the module or one of its dependencies changed since the
+    // last analysis.
+    resolution: Option<Resolution>,
+}
+
+/// Set of modules that refer to each other by name.
+/// Modules are keyed by name, as TLA+ tools expect `M.tla` to contain
+/// module `M`.
+#[derive(Default)]
+pub struct Workspace {
+    entries: BTreeMap<String, Entry>,
+}
+
+impl Modules for Workspace {
+    fn module(&self, name: &str) -> Option<&Module> {
+        self.entries.get(name)?.module.as_ref().ok()
+    }
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        Workspace::default()
+    }
+
+    /// Loads all `*.tla` files from the directory.
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        let mut ws = Workspace::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("tla") {
+                continue;
+            }
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                ws.update(name, fs::read_to_string(&path)?);
+            }
+        }
+        Ok(ws)
+    }
+
+    /// Adds or replaces module source. Returns names of the modules that
+    /// need to be analyzed again: the module itself and its dependents.
+    pub fn update(&mut self, name: &str, source: String) -> Vec<String> {
+        if self.entries.get(name).map(|e| &e.source) == Some(&source) {
+            return vec![];
+        }
+        let module = parse(&source);
+        let dependencies = module.as_ref().map(dependencies).unwrap_or_default();
+        self.entries.insert(name.to_string(), Entry {
+            source,
+            module,
+            dependencies,
+            resolution: None,
+        });
+        self.invalidate(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Vec<String> {
+        if self.entries.remove(name).is_none() {
+            return vec![];
+        }
+        self.invalidate(name)
+    }
+
+    fn invalidate(&mut self, name: &str) -> Vec<String> {
+        let mut names = self.dependents(name);
+        names.insert(0, name.to_string());
+        for n in &names {
+            if let Some(e) = self.entries.get_mut(n) {
+                e.resolution = None;
+            }
+        }
+        names.retain(|n| self.entries.contains_key(n));
+        names
+    }
+
+    /// Resolves names in the modules that changed since the last call.
+    /// Returns names of the analyzed modules.
+    pub fn analyze(&mut self) -> Vec<String> {
+        let stale: Vec<String> = self.entries
+            .iter()
+            .filter(|(_, e)| e.resolution.is_none() && e.module.is_ok())
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in &stale {
+            let mut res = match &self.entries[name].module {
+                Ok(m) => resolve_with(m, self),
+                Err(_) => continue,
+            };
+            res.errors.splice(0..0, self.cycle_errors(name));
+            self.entries.get_mut(name).unwrap().resolution = Some(res);
+        }
+        stale
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|k| k.as_str())
+    }
+
+    pub fn source(&self, name: &str) -> Option<&str> {
+        self.entries.get(name).map(|e| e.source.as_str())
+    }
+
+    pub fn parse_error(&self, name: &str) -> Option<&parser::Error> {
+        self.entries.get(name)?.module.as_ref().err()
+    }
+
+    /// Result of the last analysis, `None` if the module is not analyzed yet.
+    pub fn resolution(&self, name: &str) -> Option<&Resolution> {
+        self.entries.get(name)?.resolution.as_ref()
+    }
+
+    /// Modules directly referenced by EXTENDS and INSTANCE, including
+    /// standard modules and the ones missing from the workspace.
+    pub fn dependencies(&self, name: &str) -> Vec<String> {
+        self.entries
+            .get(name)
+            .map(|e| e.dependencies.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Modules that depend on the given one, directly or transitively.
+    pub fn dependents(&self, name: &str) -> Vec<String> {
+        let mut res: Vec<String> = Vec::new();
+        let mut queue = vec![name.to_string()];
+        while let Some(m) = queue.pop() {
+            for (n, e) in &self.entries {
+                if e.dependencies.contains(&m) && n != name && !res.contains(n) {
+                    res.push(n.clone());
+                    queue.push(n.clone());
+                }
+            }
+        }
+        res.sort();
+        res
+    }
+
+    /// Errors for the circular dependencies that go through the module,
+    /// at its reference to the next module of each cycle. Name resolution
+    /// ignores the modules that are already being resolved, so these
+    /// explain the names that are missing because of a cycle.
+    pub fn cycle_errors(&self, name: &str) -> Vec<resolve::Error> {
+        let module = match self.module(name) {
+            Some(module) => module,
+            None => return vec![],
+        };
+        let mut errors = Vec::new();
+        for mut cycle in self.cycles() {
+            let i = match cycle.iter().position(|m| m == name) {
+                Some(i) => i,
+                None => continue,
+            };
+            cycle.rotate_left(i);
+            cycle.push(name.to_string());
+            if let Some(next) = references(module).into_iter().find(|r| r.name == cycle[1]) {
+                errors.push(resolve::Error::Circular { modules: cycle, span: next.span });
+            }
+        }
+        errors
+    }
+
+    /// Circular dependencies between modules. Each cycle is listed once,
+    /// starting from its smallest module name.
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        let mut cycles = Vec::new();
+        for start in self.entries.keys() {
+            // Depth-first search for paths leading back to `start` through
+            // modules that are greater than `start`, so that each cycle is
+            // found only from its smallest member.
+            let mut path = vec![start.clone()];
+            let mut stack = vec![self.dependencies(start).into_iter()];
+            while let Some(deps) = stack.last_mut() {
+                match deps.next() {
+                    Some(d) if &d == start => cycles.push(path.clone()),
+                    Some(d) if &d > start && !path.contains(&d) => {
+                        stack.push(self.dependencies(&d).into_iter());
+                        path.push(d);
+                    }
+                    Some(_) => {}
+                    None => {
+                        stack.pop();
+                        path.pop();
+                    }
+                }
+            }
+        }
+        cycles
+    }
+}
+
+
+/// Names of the modules used by EXTENDS and INSTANCE in the module,
+/// excluding its own nested modules.
+pub fn dependencies(module: &Module) -> BTreeSet<String> {
+    references(module).into_iter().map(|i| i.name.clone()).collect()
+}
+
+// Module names of EXTENDS and INSTANCE in the module, excluding its own
+// nested modules.
+fn references(module: &Module) -> Vec<&Ident> {
+    fn instances<'a>(def: &'a Definition, res: &mut Vec<&'a Ident>) {
+        let mut expr = |e: &'a Expr| e.walk(&mut |e| {
+            if let ExprKind::Let { defs, .. } = &e.kind {
+                for d in defs {
+                    if let DefBody::Instance(inst) = &d.body {
+                        res.push(&inst.module);
+                    }
+                }
+            }
+        });
+        match &def.body {
+            DefBody::Expr(e) | DefBody::Function(_, e) => expr(e),
+            DefBody::Instance(inst) => res.push(&inst.module),
+        }
+    }
+
+    fn collect<'a>(module: &'a Module, res: &mut Vec<&'a Ident>, nested: &mut Vec<&'a str>) {
+        res.extend(module.extends.iter());
+        for unit in &module.units {
+            match unit {
+                Unit::Instance(inst) => res.push(&inst.module),
+                Unit::Definition(def) => instances(def, res),
+                Unit::Module(m) => {
+                    nested.push(&m.name.name);
+                    collect(m, res, nested);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut res = Vec::new();
+    let mut nested = Vec::new();
+    collect(module, &mut res, &mut nested);
+    res.retain(|i| !nested.contains(&i.name.as_str()));
+    res
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(name: &str, body: &str) -> String {
+        format!("---- MODULE {} ----\n{}\n====", name, body)
+    }
+
+    #[test]
+    fn incremental() {
+        let mut ws = Workspace::new();
+        ws.update("A", module("A", "EXTENDS B, Naturals\nX == Y + 1"));
+        ws.update("B", module("B", "C == INSTANCE C\nY == C!Z"));
+        ws.update("C", module("C", "Z == 1"));
+        ws.update("D", module("D", "W == 1"));
+        assert_eq!(ws.dependencies("A"), vec!["B", "Naturals"]);
+        assert_eq!(ws.analyze(), vec!["A", "B", "C", "D"]);
+        assert_eq!(ws.resolution("A").unwrap().errors, vec![]);
+        assert_eq!(ws.resolution("B").unwrap().errors, vec![]);
+        assert_eq!(ws.analyze(), Vec::<String>::new());
+
+        assert_eq!(ws.update("C", module("C", "Z == 2")), vec!["C", "A", "B"]);
+        assert!(ws.resolution("A").is_none());
+        assert_eq!(ws.analyze(), vec!["A", "B", "C"]);
+
+        ws.update("C", module("C", "Q == 2"));
+        ws.analyze();
+        assert_eq!(ws.resolution("B").unwrap().errors.len(), 1);
+        assert_eq!(ws.update("D", module("D", "W == 1")), Vec::<String>::new());
+    }
+
+    #[test]
+    fn nested_and_let_instances() {
+        let m = parse(&module("M", "
+            ---- MODULE Inner ----
+            EXTENDS Sequences
+            ====
+            INSTANCE Inner
+            A == LET I == INSTANCE Other IN 1
+        ")).unwrap();
+        let deps: Vec<String> = dependencies(&m).into_iter().collect();
+        assert_eq!(deps, vec!["Other", "Sequences"]);
+    }
+
+    #[test]
+    fn cycles() {
+        let mut ws = Workspace::new();
+        ws.update("A", module("A", "EXTENDS B"));
+        ws.update("B", module("B", "EXTENDS C"));
+        ws.update("C", module("C", "INSTANCE A"));
+        ws.update("D", module("D", "EXTENDS D"));
+        ws.update("E", module("E", "EXTENDS A"));
+        assert_eq!(ws.cycles(), vec![vec!["A", "B", "C"], vec!["D"]]);
+        ws.analyze();
+        assert_eq!(ws.resolution("E").unwrap().errors, vec![]);
+        let errors: Vec<String> = ws.resolution("B").unwrap().errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors, vec!["2:9: circular module dependency B -> C -> A -> B"]);
+        assert_eq!(ws.cycle_errors("D")[0].to_string(), "2:9: circular module dependency D -> D");
+        ws.update("A", "---- MODULE A".to_string());
+        assert!(ws.parse_error("A").is_some());
+        assert_eq!(ws.cycles(), vec![vec!["D"]]);
+    }
+}
@@ -0,0 +1,63 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{env, fs, process};
+
+const PROCS: &str = "\
+---- MODULE Procs ----
+EXTENDS FiniteSets, TLC
+CONSTANT Procs
+VARIABLE done
+Init == done = {}
+Next == \\E p \\in Procs \\ done : done' = done \\cup {p}
+Sym == Permutations(Procs)
+Spec == Init /\\ [][Next]_done
+====
+";
+
+// Directory with the module and a config for it.
+fn model(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("tla-check-{}-{}", name, process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("Procs.tla"), PROCS).unwrap();
+    fs::write(dir.join("Procs.cfg"), "CONSTANT Procs = {p1, p2, p3}\nSYMMETRY Sym\n").unwrap();
+    fs::write(dir.join("Spec.cfg"), "CONSTANT Procs = {p1, p2, p3}\nSPECIFICATION Spec\n").unwrap();
+    dir
+}
+
+// Exit code and standard output of the checker.
+fn run(dir: &Path, args: &[&str]) -> (Option<i32>, String) {
+    let out = Command::new(env!("CARGO_BIN_EXE_tla-check"))
+        .args(args)
+        .arg(dir.join("Procs.tla"))
+        .output()
+        .unwrap();
+    (out.status.code(), String::from_utf8(out.stdout).unwrap())
+}
+
+#[test]
+fn symmetry() {
+    let dir = model("symmetry");
+    let procs = ["--no-deadlock", "--constant", "Procs = {p1, p2, p3}"];
+    let (code, out) = run(&dir, &procs);
+    assert_eq!(code, Some(0), "{}", out);
+    assert!(out.contains("8 distinct states found"), "{}", out);
+
+    let (code, out) = run(&dir, &[&procs[..], &["--symmetry", "Sym"]].concat());
+    assert_eq!(code, Some(0), "{}", out);
+    assert!(out.contains("4 distinct states found"), "{}", out);
+
+    let config = dir.join("Procs.cfg");
+    let (code, out) = run(&dir, &["--no-deadlock", "--config", config.to_str().unwrap()]);
+    assert_eq!(code, Some(0), "{}", out);
+    assert!(out.contains("4 distinct states found"), "{}", out);
+
+    let config = dir.join("Spec.cfg");
+    let (code, out) = run(&dir, &["--no-deadlock", "--config", config.to_str().unwrap()]);
+    assert_eq!(code, Some(0), "{}", out);
+    assert!(out.contains("8 distinct states found"), "{}", out);
+
+    let (code, out) = run(&dir, &["--no-deadlock", "--symmetry", "Sym"]);
+    assert_eq!(code, Some(1), "{}", out);
+    assert!(out.contains("constant `Procs` has no value"), "{}", out);
+    fs::remove_dir_all(&dir).unwrap();
+}
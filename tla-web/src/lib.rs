@@ -4,7 +4,11 @@ use wee_alloc::WeeAlloc;
 static ALLOC: WeeAlloc = WeeAlloc::INIT;
 
 use wasm_bindgen::prelude::*;
+use tla_parser::cfg;
+use tla_parser::check::{self, Model};
+use tla_parser::eval::Evaluator;
 use tla_parser::lexer::Pos;
+use tla_parser::resolve::resolve;
 
 #[wasm_bindgen]
 pub struct ParseTree;
@@ -25,3 +29,102 @@ pub fn parse(_code: &str) -> Result<ParseTree, JsValue> {
     Ok(ParseTree {})
 }
 
+
+/// Checks the TLC model configuration in `config` against the module in
+/// `code`. The result has a diagnostic per line, with its position in the
+/// config.
+#[wasm_bindgen]
+pub fn check_config(code: &str, config: &str) -> Result<String, JsValue> {
+    let module = tla_parser::parser::parse(code).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let config = cfg::parse(config).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let diagnostics: Vec<String> = cfg::validate(&config, &module, &Vec::new()).iter().map(|d| d.to_string()).collect();
+    Ok(diagnostics.join("\n"))
+}
+
+
+/// Random simulation of a module, as TLC does with `-simulate`.
+#[wasm_bindgen]
+pub struct Simulation {
+    model: Model,
+    options: check::Simulation,
+    constants: Vec<cfg::Constant>,
+}
+
+#[wasm_bindgen]
+impl Simulation {
+    #[wasm_bindgen(constructor)]
+    pub fn new(traces: u32, depth: u32, seed: u32) -> Simulation {
+        let options = check::Simulation {
+            traces: traces as usize,
+            depth: depth as usize,
+            seed: u64::from(seed),
+            weights: Vec::new(),
+        };
+        Simulation { model: Model::default(), options, constants: Vec::new() }
+    }
+
+    pub fn set_init(&mut self, name: &str) {
+        self.model.init = name.to_string();
+    }
+
+    pub fn set_next(&mut self, name: &str) {
+        self.model.next = name.to_string();
+    }
+
+    pub fn set_deadlock(&mut self, check: bool) {
+        self.model.deadlock = check;
+    }
+
+    pub fn add_invariant(&mut self, name: &str) {
+        self.model.invariants.push(name.to_string());
+    }
+
+    pub fn add_property(&mut self, name: &str) {
+        self.model.properties.push(name.to_string());
+    }
+
+    pub fn add_constraint(&mut self, name: &str) {
+        self.model.constraints.push(name.to_string());
+    }
+
+    /// Fairness condition that the behaviors are assumed to meet when the
+    /// properties are checked.
+    pub fn add_fairness(&mut self, name: &str) {
+        self.model.fairness.push(name.to_string());
+    }
+
+    /// Value of a constant, written as in a TLC config, e.g. `{p1, p2}`.
+    pub fn set_constant(&mut self, name: &str, value: &str) -> Result<(), JsValue> {
+        let config = cfg::parse(&format!("CONSTANT {} = {}", name, value)).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        match config.constants().collect::<Vec<_>>().as_slice() {
+            [c] if config.sections.len() == 1 && c.name().text == name => {
+                self.constants.retain(|c| c.name().text != name);
+                self.constants.push((*c).clone());
+                Ok(())
+            }
+            _ => Err(JsValue::from_str(&format!("invalid value of constant `{}`", name))),
+        }
+    }
+
+    /// Relative chance of the subaction, 1 by default.
+    pub fn set_weight(&mut self, action: &str, weight: u32) {
+        self.options.weights.retain(|(a, _)| a != action);
+        self.options.weights.push((action.to_string(), weight));
+    }
+
+    /// Simulates the module in `code`. The result is the violation with its
+    /// trace, or an empty string if none is found.
+    pub fn run(&self, code: &str) -> Result<String, JsValue> {
+        let module = tla_parser::parser::parse(code).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let res = resolve(&module);
+        if let Some(err) = res.errors.first() {
+            return Err(JsValue::from_str(&err.to_string()));
+        }
+        let mut ev = Evaluator::new(&module, &res);
+        for c in &self.constants {
+            c.assign(&mut ev).map_err(|e| JsValue::from_str(&e))?;
+        }
+        let outcome = check::simulate(&ev, &self.model, &self.options);
+        Ok(outcome.violation.map_or_else(String::new, |v| v.to_string()))
+    }
+}